@@ -36,49 +36,49 @@ async fn main() -> Result<(), Box<dyn Error>> {
     // Example 3: Custom TLS with specific root certificate
     println!("\n3. Creating a client with custom TLS (specific root certificate)");
     let _client_transport = Http2Builder::new()
-        .with_custom_tls("localhost.example.crt".to_string(), true)
+        .with_custom_tls("certs/localhost.example.crt".to_string(), true)
         .with_host("localhost".to_string())
         .with_port(8443)
         .build();
 
     println!("   Client created with URL: https://localhost:8443");
     println!("   TLS: Enabled with custom root certificate");
-    println!("   Certificate: localhost.example.crt");
+    println!("   Certificate: certs/localhost.example.crt");
     println!("   Verify Server: Yes");
 
     // Example 4: Custom TLS with verification disabled
     println!("\n4. Creating a client with custom TLS (verification disabled)");
     let _client_transport = Http2Builder::new()
-        .with_custom_tls("localhost.example.crt".to_string(), false)
+        .with_custom_tls("certs/localhost.example.crt".to_string(), false)
         .with_host("localhost".to_string())
         .with_port(8443)
         .build();
 
     println!("   Client created with URL: https://localhost:8443");
     println!("   TLS: Enabled with custom root certificate");
-    println!("   Certificate: localhost.example.crt");
+    println!("   Certificate: certs/localhost.example.crt");
     println!("   Verify Server: No");
 
     // Example 5: Mutual TLS with client certificate
     println!("\n5. Creating a client with mutual TLS (client certificate)");
     let _client_transport = Http2Builder::new()
-        .with_custom_tls("localhost.example.crt".to_string(), true)
-        .with_client_cert("client.crt".to_string(), "client.key".to_string())
+        .with_custom_tls("certs/localhost.example.crt".to_string(), true)
+        .with_client_cert("certs/localhost.example.crt".to_string(), "certs/localhost.example.key".to_string())
         .with_host("localhost".to_string())
         .with_port(8443)
         .build();
 
     println!("   Client created with URL: https://localhost:8443");
     println!("   TLS: Enabled with custom root certificate");
-    println!("   Certificate: localhost.example.crt");
-    println!("   Client Certificate: client.crt");
-    println!("   Client Key: client.key");
+    println!("   Certificate: certs/localhost.example.crt");
+    println!("   Client Certificate: certs/localhost.example.crt");
+    println!("   Client Key: certs/localhost.example.key");
     println!("   Verify Server: Yes");
 
     // Example 6: TLS with SNI
     println!("\n6. Creating a client with SNI (Server Name Indication)");
     let _client_transport = Http2Builder::new()
-        .with_custom_tls("localhost.example.crt".to_string(), true)
+        .with_custom_tls("certs/localhost.example.crt".to_string(), true)
         .with_sni("example.com".to_string())
         .with_host("localhost".to_string())
         .with_port(8443)
@@ -86,7 +86,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     println!("   Client created with URL: https://localhost:8443");
     println!("   TLS: Enabled with custom root certificate");
-    println!("   Certificate: localhost.example.crt");
+    println!("   Certificate: certs/localhost.example.crt");
     println!("   SNI: example.com");
     println!("   Verify Server: Yes");
 
@@ -94,7 +94,8 @@ async fn main() -> Result<(), Box<dyn Error>> {
     println!("✅ Added hyper-rustls dependency for proper TLS support");
     println!("✅ Implemented client certificate support for mutual TLS");
     println!("✅ Added explicit SNI support for multi-domain servers");
-    println!("⏳ Implement connection pooling and timeouts (coming soon)");
+    println!("✅ Requests and the event stream share one pooled HTTP/2 connection");
+    println!("⏳ Implement request timeouts (coming soon)");
 
     Ok(())
 }
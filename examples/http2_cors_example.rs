@@ -1,4 +1,7 @@
-use mcp_daemon::transport::{CorsConfig, Http2ServerConfig, TlsConfig, start_http2_server};
+use mcp_daemon::transport::{
+    CorsConfig, Http2ServerConfig, JsonRpcMessage, JsonRpcResponse, JsonRpcVersion, TlsConfig,
+    start_http2_server,
+};
 use std::net::SocketAddr;
 use tokio::signal;
 
@@ -12,9 +15,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         allowed_origins: "*".to_string(), // Allow all origins for testing
         allowed_methods: "GET, POST, OPTIONS".to_string(),
         allowed_headers: "Content-Type, Authorization, Access-Control-Request-Method, Access-Control-Request-Headers".to_string(),
-        allow_credentials: true,
+        allow_credentials: false, // Browsers refuse credentials with `*`
         max_age: Some(86400),
-        exposed_headers: Some("X-Custom-Header".to_string()),
+        exposed_headers: Some("Mcp-Session-Id, X-Custom-Header".to_string()),
     };
 
     // Create TLS configuration with the existing certificate and key
@@ -28,6 +31,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         addr: SocketAddr::from(([127, 0, 0, 1], 8090)),
         tls_config: Some(tls_config),
         cors_config: Some(cors_config),
+        ..Default::default()
     };
 
     println!("Starting HTTP/2 server with CORS support on https://127.0.0.1:8090");
@@ -37,11 +41,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Note: The -k flag is used to skip certificate verification for self-signed certificates");
 
     // Start the HTTP/2 server
-    let server_handle = start_http2_server(config, |message| {
+    let server_handle = start_http2_server(config, |message| async move {
         println!("Received message: {:?}", message);
 
-        // Echo the message back
-        Ok(message)
+        // Answer requests with an empty result; notifications need no response
+        Ok(match message {
+            JsonRpcMessage::Request(request) => Some(JsonRpcMessage::Response(JsonRpcResponse {
+                id: request.id,
+                result: Some(serde_json::json!({})),
+                error: None,
+                jsonrpc: JsonRpcVersion::default(),
+            })),
+            _ => None,
+        })
     }).await?;
 
     // Keep the server running until Ctrl+C is pressed
@@ -29,7 +29,7 @@ async fn run_websocket_server() {
                     .wrap(Logger::default())
                     .wrap(Cors::permissive()) // Allow all origins for testing
                     .route("/ws", web::get().to(|req: actix_web::HttpRequest, stream: web::Payload| async move {
-                        let (response, mut session, mut msg_stream) = actix_ws::handle(&req, stream).unwrap();
+                        let (response, mut session, mut msg_stream) = mcp_daemon::transport::handle_ws_upgrade(&req, stream).unwrap();
 
                         // Spawn a task to handle the WebSocket connection
                         actix_web::rt::spawn(async move {
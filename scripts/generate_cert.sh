@@ -11,6 +11,7 @@ fi
 CERT_DIR="certs"
 DOMAIN="localhost"
 DAYS=365
+NAME=""
 
 # Parse command line arguments
 while [[ $# -gt 0 ]]; do
@@ -27,12 +28,17 @@ while [[ $# -gt 0 ]]; do
             DAYS="$2"
             shift 2
             ;;
+        --name)
+            NAME="$2"
+            shift 2
+            ;;
         --help)
             echo "Usage: $0 [options]"
             echo "Options:"
             echo "  --dir DIR       Directory to store certificates (default: certs)"
             echo "  --domain DOMAIN Domain name for the certificate (default: localhost)"
             echo "  --days DAYS     Validity period in days (default: 365)"
+            echo "  --name NAME     Base name of the output files (default: the domain)"
             echo "  --help          Show this help message"
             exit 0
             ;;
@@ -44,27 +50,38 @@ while [[ $# -gt 0 ]]; do
     esac
 done
 
+NAME="${NAME:-$DOMAIN}"
+
+# localhost certificates also cover the loopback addresses
+SAN="DNS:$DOMAIN"
+if [ "$DOMAIN" = "localhost" ]; then
+    SAN="$SAN,IP:127.0.0.1,IP:::1"
+fi
+
 # Create certificate directory if it doesn't exist
 mkdir -p "$CERT_DIR"
 
 # Generate certificate
 echo "Generating self-signed certificate for $DOMAIN (valid for $DAYS days)..."
 openssl req -x509 \
-    -out "$CERT_DIR/$DOMAIN.crt" \
-    -keyout "$CERT_DIR/$DOMAIN.key" \
-    -newkey rsa:2048 -nodes -sha256 \
+    -out "$CERT_DIR/$NAME.crt" \
+    -keyout "$CERT_DIR/$NAME.key" \
+    -newkey rsa:2048 -nodes -sha256 -days "$DAYS" \
     -subj "/CN=$DOMAIN" -extensions EXT -config <( \
-    printf "[dn]\nCN=$DOMAIN\n[req]\ndistinguished_name = dn\n[EXT]\nsubjectAltName=DNS:$DOMAIN\nkeyUsage=digitalSignature\nextendedKeyUsage=serverAuth")
+    printf "[dn]\nCN=$DOMAIN\n[req]\ndistinguished_name = dn\n[EXT]\nsubjectAltName=$SAN\nkeyUsage=digitalSignature\nextendedKeyUsage=serverAuth,clientAuth")
 
 # Check if certificate generation was successful
 if [ $? -eq 0 ]; then
     echo "Certificate generated successfully!"
-    echo "Certificate: $CERT_DIR/$DOMAIN.crt"
-    echo "Private key: $CERT_DIR/$DOMAIN.key"
+    echo "Certificate: $CERT_DIR/$NAME.crt"
+    echo "Private key: $CERT_DIR/$NAME.key"
+    echo ""
+    echo "The certificate is valid for both server and client authentication, so the"
+    echo "same pair can be used as a custom root and as a mutual TLS client certificate."
     echo ""
     echo "To use these certificates with MCP Daemon, configure your application with:"
-    echo "  - cert_path: \"$CERT_DIR/$DOMAIN.crt\""
-    echo "  - key_path: \"$CERT_DIR/$DOMAIN.key\""
+    echo "  - cert_path: \"$CERT_DIR/$NAME.crt\""
+    echo "  - key_path: \"$CERT_DIR/$NAME.key\""
 else
     echo "Failed to generate certificate."
     exit 1
@@ -231,45 +231,45 @@ pub fn run_tui(config: Config) -> Result<(), io::Error> {
             .checked_sub(last_tick.elapsed())
             .unwrap_or_else(|| Duration::from_secs(0));
 
-        if crossterm::event::poll(timeout)? {
-            if let Event::Key(key) = event::read()? {
-                match app.input_mode {
-                    InputMode::Normal => match key.code {
-                        KeyCode::Char('q') => {
-                            app.should_quit = true;
-                            break;
-                        },
-                        KeyCode::Down | KeyCode::Char('j') => {
-                            match app.menu_state {
-                                MenuItem::Servers => app.next_server(),
-                                MenuItem::Clients => app.next_client(),
-                                _ => {}
-                            }
-                        }
-                        KeyCode::Up | KeyCode::Char('k') => {
-                            match app.menu_state {
-                                MenuItem::Servers => app.previous_server(),
-                                MenuItem::Clients => app.previous_client(),
-                                _ => {}
-                            }
+        if crossterm::event::poll(timeout)?
+            && let Event::Key(key) = event::read()?
+        {
+            match app.input_mode {
+                InputMode::Normal => match key.code {
+                    KeyCode::Char('q') => {
+                        app.should_quit = true;
+                        break;
+                    },
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        match app.menu_state {
+                            MenuItem::Servers => app.next_server(),
+                            MenuItem::Clients => app.next_client(),
+                            _ => {}
                         }
-                        KeyCode::Right | KeyCode::Char('l') => app.next_menu(),
-                        KeyCode::Left | KeyCode::Char('h') => app.previous_menu(),
-                        KeyCode::Tab => app.next_menu(),
-                        KeyCode::Char('1') => app.menu_state = MenuItem::Dashboard,
-                        KeyCode::Char('2') => app.menu_state = MenuItem::Servers,
-                        KeyCode::Char('3') => app.menu_state = MenuItem::Clients,
-                        KeyCode::Char('4') => app.menu_state = MenuItem::Settings,
-                        KeyCode::Char('5') => app.menu_state = MenuItem::Logs,
-                        KeyCode::Char('e') => {
-                            app.input_mode = InputMode::Editing;
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        match app.menu_state {
+                            MenuItem::Servers => app.previous_server(),
+                            MenuItem::Clients => app.previous_client(),
+                            _ => {}
                         }
-                        _ => {}
-                    },
-                    InputMode::Editing => if key.code == KeyCode::Esc {
-                        app.input_mode = InputMode::Normal;
-                    },
-                }
+                    }
+                    KeyCode::Right | KeyCode::Char('l') => app.next_menu(),
+                    KeyCode::Left | KeyCode::Char('h') => app.previous_menu(),
+                    KeyCode::Tab => app.next_menu(),
+                    KeyCode::Char('1') => app.menu_state = MenuItem::Dashboard,
+                    KeyCode::Char('2') => app.menu_state = MenuItem::Servers,
+                    KeyCode::Char('3') => app.menu_state = MenuItem::Clients,
+                    KeyCode::Char('4') => app.menu_state = MenuItem::Settings,
+                    KeyCode::Char('5') => app.menu_state = MenuItem::Logs,
+                    KeyCode::Char('e') => {
+                        app.input_mode = InputMode::Editing;
+                    }
+                    _ => {}
+                },
+                InputMode::Editing => if key.code == KeyCode::Esc {
+                    app.input_mode = InputMode::Normal;
+                },
             }
         }
 
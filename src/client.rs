@@ -32,7 +32,7 @@ use std::sync::Arc;
 use derive_ex::Ex;
 use jsoncall::{
     Handler, NotificationContext, Params, RequestContext, RequestContextAs, Response, Result,
-    Session, SessionOptions, SessionResult,
+    Session, SessionError, SessionOptions, SessionResult,
 };
 use serde_json::Map;
 use tokio::{
@@ -49,9 +49,11 @@ use crate::schema::{
     ListPromptsRequestParams, ListPromptsResult, ListResourceTemplatesRequestParams,
     ListResourceTemplatesResult, ListResourcesRequestParams, ListResourcesResult,
     ListRootsResult, ListToolsRequestParams, ListToolsResult, PingRequestParams,
-    ReadResourceRequestParams, ReadResourceResult, Root,
+    ReadResourceRequestParams, ReadResourceResult, Root, SubscribeRequestParams,
+    UnsubscribeRequestParams,
 };
-use crate::server::{Server, DefaultServer};
+use crate::server::{DefaultServer, Server};
+use crate::transport::{Transport, session_io};
 use crate::utils::{Empty, ProtocolVersion};
 /// Trait for implementing [client features]
 ///
@@ -199,6 +201,17 @@ impl ClientBuilder {
         Ok(client)
     }
 
+    /// Builds a [`Client`] that communicates with an MCP server over the specified [`Transport`]
+    ///
+    /// The transport is opened before the [`initialize`] request is sent.
+    ///
+    /// [`initialize`]: https://spec.modelcontextprotocol.io/specification/2025-03-26/basic/lifecycle/#initialization
+    pub async fn build_with_transport(self, transport: impl Transport) -> SessionResult<Client> {
+        transport.open().await.map_err(SessionError::from_error)?;
+        let (reader, writer) = session_io(transport);
+        self.build(reader, writer).await
+    }
+
     /// Builds a [`Client`] using a custom method
     ///
     /// This method returns the values needed for [`Client::initialize`].
@@ -497,6 +510,28 @@ impl Client {
         self.session.request("resources/read", Some(&params)).await
     }
 
+    /// Calls [`resources/subscribe`]
+    ///
+    /// [`resources/subscribe`]: https://spec.modelcontextprotocol.io/specification/2024-11-05/server/resources/#subscriptions
+    pub async fn resources_subscribe(&self, params: SubscribeRequestParams) -> SessionResult<()> {
+        let _: Empty = self
+            .session
+            .request("resources/subscribe", Some(&params))
+            .await?;
+        Ok(())
+    }
+
+    /// Calls [`resources/unsubscribe`]
+    ///
+    /// [`resources/unsubscribe`]: https://spec.modelcontextprotocol.io/specification/2024-11-05/server/resources/#subscriptions
+    pub async fn resources_unsubscribe(&self, params: UnsubscribeRequestParams) -> SessionResult<()> {
+        let _: Empty = self
+            .session
+            .request("resources/unsubscribe", Some(&params))
+            .await?;
+        Ok(())
+    }
+
     /// Calls [`tools/list`]
     ///
     /// [`tools/list`]: https://spec.modelcontextprotocol.io/specification/2024-11-05/client/tools/#listing-tools
@@ -135,6 +135,12 @@ pub mod transport;
 /// for managing the MCP daemon, including configuration, monitoring, and control.
 pub mod cli;
 
+/// Testing utilities for MCP clients and servers
+///
+/// This module provides a scriptable mock server for exercising client code
+/// against canned responses without hand-written fixtures.
+pub mod testing;
+
 // Re-export dependencies and common types
 
 /// Re-export of the jsoncall crate
@@ -167,4 +173,4 @@ pub use jsoncall::bail_public;
 pub use transport::httpd;
 
 #[cfg(feature = "sse")]
-pub use transport::httpd::run_http_server;
+pub use transport::httpd::{run_http_server, start_http_server};
@@ -86,7 +86,7 @@ async fn run_websocket_server() {
                     .wrap(Logger::default())
                     .wrap(Cors::permissive()) // Allow all origins for testing
                     .route("/ws", web::get().to(|req: actix_web::HttpRequest, stream: web::Payload| async move {
-                        let (response, mut session, mut msg_stream) = actix_ws::handle(&req, stream).unwrap();
+                        let (response, mut session, mut msg_stream) = mcp_daemon::transport::handle_ws_upgrade(&req, stream).unwrap();
 
                         // Spawn a task to handle the WebSocket connection
                         actix_web::rt::spawn(async move {
@@ -177,15 +177,16 @@ async fn test_http2_transport() -> Result<()> {
         addr: server_addr,
         tls_config: None, // Use plain HTTP for testing
         cors_config: Some(mcp_daemon::transport::CorsConfig::default()),
+        ..Default::default()
     };
 
     // Start the HTTP/2 server
     let server_handle = mcp_daemon::transport::start_http2_server(
         server_config,
-        |message| {
+        |message| async move {
             // Simple echo server
             eprintln!("HTTP/2 server received: {:?}", message);
-            Ok(message)
+            Ok(Some(message))
         },
     ).await?;
 
@@ -9,6 +9,7 @@
 //! - `default_impls`: Default implementations for schema types
 //! - `protocol`: Protocol-specific constants and definitions
 //! - `annotations`: Type definitions for resource and template annotations
+//! - `validation`: Validation of raw messages against the bundled `spec/schema.json`
 //!
 //! Most types from `types`, `types_ex` and `annotations` are re-exported at the schema module
 //! level for convenience.
@@ -21,6 +22,7 @@ pub mod types_ex;
 pub mod default_impls;
 pub mod protocol;
 pub mod annotations;
+pub mod validation;
 
 pub use schema::*;
 pub use types_ex::*;
@@ -8,7 +8,8 @@ use serde_json::{Map, Value};
 /// Type for handling byte sequences as Base64-encoded strings
 ///
 /// This type is used when you want to handle a byte sequence as a Base64-encoded string in JSON serialization,
-/// and then convert it back to a byte sequence when deserializing.
+/// and then convert it back to a byte sequence when deserializing. Binary formats such as MessagePack and
+/// CBOR, which are not human-readable, carry the bytes as-is.
 ///
 /// # Example
 ///
@@ -31,6 +32,9 @@ impl Serialize for Base64Bytes {
     where
         S: serde::Serializer,
     {
+        if !serializer.is_human_readable() {
+            return serializer.serialize_bytes(&self.0);
+        }
         let s = base64::prelude::BASE64_STANDARD.encode(&self.0);
         serializer.serialize_str(&s)
     }
@@ -41,6 +45,9 @@ impl<'de> Deserialize<'de> for Base64Bytes {
     where
         D: Deserializer<'de>,
     {
+        if !deserializer.is_human_readable() {
+            return deserializer.deserialize_byte_buf(Base64BytesVisitor);
+        }
         let s: Cow<'de, str> = Deserialize::deserialize(deserializer)?;
         base64::prelude::BASE64_STANDARD
             .decode(&*s)
@@ -49,6 +56,40 @@ impl<'de> Deserialize<'de> for Base64Bytes {
     }
 }
 
+/// Accepts raw bytes from binary formats, or base64 text from those that lack a byte type
+struct Base64BytesVisitor;
+
+impl<'de> serde::de::Visitor<'de> for Base64BytesVisitor {
+    type Value = Base64Bytes;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("bytes or a base64 string")
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(Base64Bytes(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(Base64Bytes(v))
+    }
+
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        base64::prelude::BASE64_STANDARD
+            .decode(v)
+            .map_err(E::custom)
+            .map(Base64Bytes)
+    }
+
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(Base64Bytes(bytes))
+    }
+}
+
 /// Type representing an empty JSON object
 ///
 /// This type is used when you want to output an empty JSON object `{}` in JSON serialization,
@@ -211,4 +252,15 @@ mod tests {
         let bytes: Base64Bytes = serde_json::from_value(json).unwrap();
         assert_eq!(bytes.0, vec![1, 2, 3, 4, 5]);
     }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_base64_bytes_binary_format() {
+        let bytes = Base64Bytes(vec![1, 2, 3, 4, 5]);
+        let encoded = rmp_serde::to_vec(&bytes).unwrap();
+        assert_eq!(encoded, [0xc4, 5, 1, 2, 3, 4, 5]);
+
+        let decoded: Base64Bytes = rmp_serde::from_slice(&encoded).unwrap();
+        assert_eq!(decoded, bytes);
+    }
 }
@@ -0,0 +1,481 @@
+//! Validation of MCP messages against `spec/schema.json`
+//!
+//! The generated types in [`schema`](super::schema) accept a superset of what the
+//! specification allows, so they cannot tell whether a peer sent a conforming message.
+//! [`SchemaValidator`] checks raw JSON values against the bundled JSON Schema instead
+//! and reports each violation with a JSON pointer to the failing value.
+//!
+//! Only the draft-07 keywords used by the MCP schema are supported: `$ref`, `type`,
+//! `properties`, `required`, `additionalProperties`, `items`, `anyOf`, `const`, `enum`,
+//! `minimum`, `maximum` and `format` (`uri` and `byte`).
+
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::sync::LazyLock;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde_json::{Map, Value, json};
+
+/// JSON Schema for the MCP protocol, as shipped in `spec/schema.json`
+pub const MCP_SCHEMA_JSON: &str = include_str!("../../spec/schema.json");
+
+static MCP_VALIDATOR: LazyLock<SchemaValidator> = LazyLock::new(|| {
+    SchemaValidator::new(
+        serde_json::from_str(MCP_SCHEMA_JSON).expect("bundled schema is valid JSON"),
+    )
+});
+
+/// A single schema violation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    /// JSON pointer to the failing value (empty for the root)
+    pub pointer: String,
+    /// Description of the violation
+    pub message: String,
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.pointer.is_empty() {
+            write!(f, "(root): {}", self.message)
+        } else {
+            write!(f, "{}: {}", self.pointer, self.message)
+        }
+    }
+}
+
+impl ValidationError {
+    /// Returns true if the violation means the value has a different shape altogether
+    fn is_structural(&self) -> bool {
+        self.message == MISSING_PROPERTY || self.message.starts_with(CONST_MISMATCH)
+    }
+}
+
+const MISSING_PROPERTY: &str = "required property is missing";
+const CONST_MISMATCH: &str = "expected constant";
+
+/// Formats a list of violations as a single line
+pub fn format_errors(errors: &[ValidationError]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Validates JSON values against the definitions of a JSON Schema
+///
+/// Methods are mapped to definitions by the `method` constant of each request and
+/// notification definition. The result definition for a request `FooRequest` is
+/// `FooResult` if it exists, and `EmptyResult` otherwise.
+#[derive(Debug, Clone)]
+pub struct SchemaValidator {
+    definitions: Map<String, Value>,
+    requests: HashMap<String, String>,
+    notifications: HashMap<String, String>,
+}
+
+impl SchemaValidator {
+    /// Creates a validator from a JSON Schema document with a `definitions` object
+    pub fn new(schema: Value) -> Self {
+        let definitions = match schema {
+            Value::Object(mut o) => match o.remove("definitions") {
+                Some(Value::Object(d)) => d,
+                _ => Map::new(),
+            },
+            _ => Map::new(),
+        };
+        let mut requests = HashMap::new();
+        let mut notifications = HashMap::new();
+        for (name, def) in &definitions {
+            let Some(method) = def
+                .pointer("/properties/method/const")
+                .and_then(Value::as_str)
+            else {
+                continue;
+            };
+            if name.ends_with("Request") {
+                requests.insert(method.to_string(), name.clone());
+            } else if name.ends_with("Notification") {
+                notifications.insert(method.to_string(), name.clone());
+            }
+        }
+        Self {
+            definitions,
+            requests,
+            notifications,
+        }
+    }
+
+    /// Returns the validator for the bundled MCP schema
+    pub fn mcp() -> &'static Self {
+        &MCP_VALIDATOR
+    }
+
+    /// Returns the name of the request definition for `method`
+    pub fn request_definition(&self, method: &str) -> Option<&str> {
+        self.requests.get(method).map(String::as_str)
+    }
+
+    /// Returns the name of the notification definition for `method`
+    pub fn notification_definition(&self, method: &str) -> Option<&str> {
+        self.notifications.get(method).map(String::as_str)
+    }
+
+    /// Returns the name of the result definition for the request `method`
+    pub fn result_definition(&self, method: &str) -> Option<&str> {
+        let request = self.request_definition(method)?;
+        let result = format!("{}Result", request.trim_end_matches("Request"));
+        let name = if self.definitions.contains_key(&result) {
+            result
+        } else {
+            "EmptyResult".to_string()
+        };
+        self.definitions
+            .get_key_value(&name)
+            .map(|(k, _)| k.as_str())
+    }
+
+    /// Validates `value` against the definition named `definition`
+    pub fn validate_definition(
+        &self,
+        definition: &str,
+        value: &Value,
+    ) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        match self.definitions.get(definition) {
+            Some(schema) => self.validate_value(schema, value, &mut String::new(), &mut errors),
+            None => errors.push(ValidationError {
+                pointer: String::new(),
+                message: format!("unknown schema definition `{definition}`"),
+            }),
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Validates the parameters of a request
+    ///
+    /// Pointers are relative to the request object, so they start with `/params`.
+    /// Methods not defined in the schema are accepted.
+    pub fn validate_request(
+        &self,
+        method: &str,
+        params: Option<&Value>,
+    ) -> Result<(), Vec<ValidationError>> {
+        match self.request_definition(method) {
+            Some(def) => self.validate_definition(def, &message_value(method, params)),
+            None => Ok(()),
+        }
+    }
+
+    /// Validates the parameters of a notification
+    ///
+    /// Pointers are relative to the notification object, so they start with `/params`.
+    /// Methods not defined in the schema are accepted.
+    pub fn validate_notification(
+        &self,
+        method: &str,
+        params: Option<&Value>,
+    ) -> Result<(), Vec<ValidationError>> {
+        match self.notification_definition(method) {
+            Some(def) => self.validate_definition(def, &message_value(method, params)),
+            None => Ok(()),
+        }
+    }
+
+    /// Validates the result of a request
+    ///
+    /// Methods not defined in the schema are accepted.
+    pub fn validate_result(
+        &self,
+        method: &str,
+        result: &Value,
+    ) -> Result<(), Vec<ValidationError>> {
+        match self.result_definition(method) {
+            Some(def) => self.validate_definition(def, result),
+            None => Ok(()),
+        }
+    }
+
+    fn validate_value(
+        &self,
+        schema: &Value,
+        value: &Value,
+        pointer: &mut String,
+        errors: &mut Vec<ValidationError>,
+    ) {
+        let Value::Object(schema) = schema else {
+            if schema == &Value::Bool(false) {
+                push(errors, pointer, "no value is allowed here");
+            }
+            return;
+        };
+
+        if let Some(r) = schema.get("$ref").and_then(Value::as_str) {
+            match r
+                .strip_prefix("#/definitions/")
+                .and_then(|name| self.definitions.get(name))
+            {
+                Some(target) => self.validate_value(target, value, pointer, errors),
+                None => push(errors, pointer, format!("unresolved reference `{r}`")),
+            }
+        }
+
+        if let Some(ty) = schema.get("type") {
+            let allowed: Vec<&str> = match ty {
+                Value::String(s) => vec![s.as_str()],
+                Value::Array(a) => a.iter().filter_map(Value::as_str).collect(),
+                _ => Vec::new(),
+            };
+            if !allowed.is_empty() && !allowed.iter().any(|t| is_type(value, t)) {
+                push(
+                    errors,
+                    pointer,
+                    format!(
+                        "expected {}, found {}",
+                        allowed.join(" or "),
+                        type_name(value)
+                    ),
+                );
+                return;
+            }
+        }
+
+        if let Some(c) = schema.get("const")
+            && value != c
+        {
+            push(
+                errors,
+                pointer,
+                format!("{CONST_MISMATCH} {c}, found {value}"),
+            );
+        }
+        if let Some(Value::Array(e)) = schema.get("enum")
+            && !e.contains(value)
+        {
+            let allowed = Value::Array(e.clone());
+            push(errors, pointer, format!("{value} is not one of {allowed}"));
+        }
+        if let Some(n) = value.as_f64() {
+            if let Some(min) = schema.get("minimum").and_then(Value::as_f64)
+                && n < min
+            {
+                push(
+                    errors,
+                    pointer,
+                    format!("{n} is less than the minimum of {min}"),
+                );
+            }
+            if let Some(max) = schema.get("maximum").and_then(Value::as_f64)
+                && n > max
+            {
+                push(
+                    errors,
+                    pointer,
+                    format!("{n} is greater than the maximum of {max}"),
+                );
+            }
+        }
+        if let Some(format) = schema.get("format").and_then(Value::as_str)
+            && let Value::String(s) = value
+            && let Err(e) = check_format(format, s)
+        {
+            push(errors, pointer, e);
+        }
+
+        if let Value::Object(obj) = value {
+            self.validate_object(schema, obj, pointer, errors);
+        }
+        if let (Some(items), Value::Array(arr)) = (schema.get("items"), value) {
+            for (i, item) in arr.iter().enumerate() {
+                with_token(pointer, &i.to_string(), |pointer| {
+                    self.validate_value(items, item, pointer, errors)
+                });
+            }
+        }
+
+        if let Some(Value::Array(any_of)) = schema.get("anyOf") {
+            // Report the branch that is structurally closest to the value, so that a
+            // bad field in an otherwise matching variant is pointed at precisely.
+            let mut best: Option<(usize, Vec<ValidationError>)> = None;
+            for branch in any_of {
+                let mut branch_errors = Vec::new();
+                self.validate_value(branch, value, pointer, &mut branch_errors);
+                if branch_errors.is_empty() {
+                    best = None;
+                    break;
+                }
+                let score = branch_errors.iter().filter(|e| e.is_structural()).count();
+                if best.as_ref().is_none_or(|(best_score, best_errors)| {
+                    (score, branch_errors.len()) < (*best_score, best_errors.len())
+                }) {
+                    best = Some((score, branch_errors));
+                }
+            }
+            if let Some((_, best)) = best {
+                errors.extend(best);
+            }
+        }
+    }
+
+    fn validate_object(
+        &self,
+        schema: &Map<String, Value>,
+        obj: &Map<String, Value>,
+        pointer: &mut String,
+        errors: &mut Vec<ValidationError>,
+    ) {
+        let properties = schema.get("properties").and_then(Value::as_object);
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for name in required.iter().filter_map(Value::as_str) {
+                if !obj.contains_key(name) {
+                    with_token(pointer, name, |pointer| {
+                        push(errors, pointer, MISSING_PROPERTY)
+                    });
+                }
+            }
+        }
+        for (name, v) in obj {
+            let property_schema = properties.and_then(|p| p.get(name));
+            with_token(pointer, name, |pointer| {
+                match (property_schema, schema.get("additionalProperties")) {
+                    (Some(s), _) => self.validate_value(s, v, pointer, errors),
+                    (None, Some(Value::Bool(false))) => {
+                        push(errors, pointer, "unknown property is not allowed")
+                    }
+                    (None, Some(additional)) => self.validate_value(additional, v, pointer, errors),
+                    (None, None) => {}
+                }
+            });
+        }
+    }
+}
+
+fn message_value(method: &str, params: Option<&Value>) -> Value {
+    match params {
+        Some(params) => json!({ "method": method, "params": params }),
+        None => json!({ "method": method }),
+    }
+}
+
+fn push(errors: &mut Vec<ValidationError>, pointer: &str, message: impl Into<String>) {
+    errors.push(ValidationError {
+        pointer: pointer.to_string(),
+        message: message.into(),
+    });
+}
+
+fn with_token<R>(pointer: &mut String, token: &str, f: impl FnOnce(&mut String) -> R) -> R {
+    let len = pointer.len();
+    pointer.push('/');
+    pointer.push_str(&token.replace('~', "~0").replace('/', "~1"));
+    let r = f(pointer);
+    pointer.truncate(len);
+    r
+}
+
+fn is_type(value: &Value, ty: &str) -> bool {
+    match ty {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|f| f.fract() == 0.0)
+        }
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn check_format(format: &str, s: &str) -> Result<(), String> {
+    match format {
+        "uri" => url::Url::parse(s)
+            .map(|_| ())
+            .map_err(|e| format!("`{s}` is not a valid URI: {e}")),
+        "byte" => BASE64
+            .decode(s)
+            .map(|_| ())
+            .map_err(|e| format!("value is not valid base64: {e}")),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_method_mapping() {
+        let v = SchemaValidator::mcp();
+        assert_eq!(v.request_definition("tools/call"), Some("CallToolRequest"));
+        assert_eq!(v.result_definition("tools/call"), Some("CallToolResult"));
+        assert_eq!(v.result_definition("ping"), Some("EmptyResult"));
+        assert_eq!(
+            v.notification_definition("notifications/progress"),
+            Some("ProgressNotification")
+        );
+        assert_eq!(v.request_definition("no/such/method"), None);
+    }
+
+    #[test]
+    fn test_valid_messages() {
+        let v = SchemaValidator::mcp();
+        v.validate_request(
+            "tools/call",
+            Some(&json!({ "name": "echo", "arguments": { "x": 1 } })),
+        )
+        .unwrap();
+        v.validate_request("ping", None).unwrap();
+        v.validate_result(
+            "tools/call",
+            &json!({ "content": [{ "type": "text", "text": "hi" }] }),
+        )
+        .unwrap();
+        v.validate_notification("notifications/initialized", None)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_error_pointers() {
+        let v = SchemaValidator::mcp();
+        let errors = v
+            .validate_request("tools/call", Some(&json!({ "arguments": {} })))
+            .unwrap_err();
+        assert_eq!(errors[0].pointer, "/params/name");
+
+        let errors = v
+            .validate_result(
+                "tools/call",
+                &json!({ "content": [{ "type": "text", "text": 1 }] }),
+            )
+            .unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].pointer, "/content/0/text");
+
+        let errors = v
+            .validate_result(
+                "resources/read",
+                &json!({ "contents": [{ "uri": "file:///a", "blob": "not base64!" }] }),
+            )
+            .unwrap_err();
+        assert_eq!(errors[0].pointer, "/contents/0/blob");
+    }
+}
@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use jsoncall::{
     Handler, Hook, NotificationContext, Params, RequestContextAs, RequestId, Response,
-    Result, Session, SessionContext, SessionOptions, SessionResult, bail_public,
+    Result, Session, SessionContext, SessionError, SessionOptions, SessionResult, bail_public,
 };
 use serde::{Serialize, de::DeserializeOwned};
 use serde_json::Map;
@@ -23,6 +23,7 @@ use crate::{
     },
     error::{prompt_not_found, resource_not_found, tool_not_found},
     schema::types_ex::{Empty, ProtocolVersion},
+    transport::{Transport, session_io},
 };
 
 pub use crate::utility::macros::server;
@@ -84,7 +85,10 @@ impl Handler for ServerHandler {
         cx: NotificationContext,
     ) -> Result<Response> {
         match method {
-            "notifications/initialized" => cx.handle(self.initialized(params.to_opt()?)),
+            "notifications/initialized" => {
+                self.initialized(params.to_opt()?, &cx)?;
+                cx.handle(Ok(()))
+            }
             "notifications/cancelled" => self.notifications_cancelled(params.to()?, cx),
             _ => cx.method_not_found(),
         }
@@ -92,8 +96,11 @@ impl Handler for ServerHandler {
 }
 impl ServerHandler {
     pub fn new(server: impl Server) -> Self {
+        Self::from_arc(Arc::new(server))
+    }
+    pub(crate) fn from_arc(server: Arc<dyn Server>) -> Self {
         Self {
-            server: Arc::new(server),
+            server,
             data: None,
             is_initialized: false,
         }
@@ -105,16 +112,23 @@ impl ServerHandler {
             initialize: p,
             protocol_version: ProtocolVersion::LATEST,
         }));
-        Ok(self.server.initialize_result())
+        Ok(Server::initialize_result(&*self.server))
     }
-    fn initialized(&mut self, _p: Option<InitializedNotificationParams>) -> Result<()> {
-        if self.data.is_none() {
+    fn initialized(
+        &mut self,
+        _p: Option<InitializedNotificationParams>,
+        cx: &NotificationContext,
+    ) -> Result<()> {
+        let Some(data) = &self.data else {
             bail_public!(
                 _,
                 "`initialize` request must be called before `initialized` notification"
             );
+        };
+        if !self.is_initialized {
+            self.is_initialized = true;
+            self.server.clone().initialized(cx.session(), data.clone());
         }
-        self.is_initialized = true;
         Ok(())
     }
     fn ping(&self, _p: Option<PingRequestParams>) -> Result<Empty> {
@@ -199,87 +213,136 @@ impl ServerHandler {
 /// ```
 pub trait Server: Send + Sync + 'static {
     /// Returns the initialization result
-    fn initialize_result(&self) -> InitializeResult;
+    fn initialize_result(&self) -> InitializeResult {
+        InitializeResult {
+            capabilities: DefaultServer::capabilities(self),
+            instructions: DefaultServer::instructions(self),
+            meta: Map::new(),
+            protocol_version: ProtocolVersion::LATEST.to_string(),
+            server_info: DefaultServer::server_info(self),
+        }
+    }
+
+    /// Called when the client sends the `notifications/initialized` notification
+    ///
+    /// The session context can be used to send notifications or requests to the client
+    /// once the session is ready.
+    #[allow(unused_variables)]
+    fn initialized(self: Arc<Self>, session: SessionContext, data: Arc<SessionData>) {}
 
     /// Handles prompts/list request
+    #[allow(unused_variables)]
     fn prompts_list(
         self: Arc<Self>,
         p: ListPromptsRequestParams,
         cx: RequestContextAs<ListPromptsResult>,
         data: Arc<SessionData>,
-    ) -> Result<Response>;
+    ) -> Result<Response> {
+        cx.handle(Ok(ListPromptsResult::default()))
+    }
 
     /// Handles prompts/get request
+    #[allow(unused_variables)]
     fn prompts_get(
         self: Arc<Self>,
         p: GetPromptRequestParams,
         cx: RequestContextAs<GetPromptResult>,
         data: Arc<SessionData>,
-    ) -> Result<Response>;
+    ) -> Result<Response> {
+        cx.handle(Err(prompt_not_found(&p.name)))
+    }
 
     /// Handles resources/list request
+    #[allow(unused_variables)]
     fn resources_list(
         self: Arc<Self>,
         p: ListResourcesRequestParams,
         cx: RequestContextAs<ListResourcesResult>,
         data: Arc<SessionData>,
-    ) -> Result<Response>;
+    ) -> Result<Response> {
+        cx.handle(Ok(ListResourcesResult::default()))
+    }
 
     /// Handles resources/read request
+    #[allow(unused_variables)]
     fn resources_read(
         self: Arc<Self>,
         p: ReadResourceRequestParams,
         cx: RequestContextAs<ReadResourceResult>,
         data: Arc<SessionData>,
-    ) -> Result<Response>;
+    ) -> Result<Response> {
+        cx.handle(Err(resource_not_found(&p.uri)))
+    }
 
     /// Handles resources/templates/list request
+    #[allow(unused_variables)]
     fn resources_templates_list(
         self: Arc<Self>,
         p: ListResourceTemplatesRequestParams,
         cx: RequestContextAs<ListResourceTemplatesResult>,
         data: Arc<SessionData>,
-    ) -> Result<Response>;
+    ) -> Result<Response> {
+        cx.handle(Ok(ListResourceTemplatesResult::default()))
+    }
 
     /// Handles tools/list request
+    #[allow(unused_variables)]
     fn tools_list(
         self: Arc<Self>,
         p: ListToolsRequestParams,
         cx: RequestContextAs<ListToolsResult>,
         data: Arc<SessionData>,
-    ) -> Result<Response>;
+    ) -> Result<Response> {
+        cx.handle(Ok(ListToolsResult::default()))
+    }
 
     /// Handles tools/call request
+    #[allow(unused_variables)]
     fn tools_call(
         self: Arc<Self>,
         p: CallToolRequestParams,
         cx: RequestContextAs<CallToolResult>,
         data: Arc<SessionData>,
-    ) -> Result<Response>;
+    ) -> Result<Response> {
+        cx.handle(Err(tool_not_found(&p.name)))
+    }
 
     /// Handles completion/complete request
+    #[allow(unused_variables)]
     fn completion_complete(
         self: Arc<Self>,
         p: CompleteRequestParams,
         cx: RequestContextAs<CompleteResult>,
         data: Arc<SessionData>,
-    ) -> Result<Response>;
+    ) -> Result<Response> {
+        cx.handle(Ok(CompleteResult::default()))
+    }
 
-    /// Handles resources/subscribe request
+    /// Handles [`resources/subscribe`]
+    ///
+    /// [`resources/subscribe`]: https://spec.modelcontextprotocol.io/specification/draft/server/resources/#subscriptions
+    #[allow(unused_variables)]
     fn resources_subscribe(
         self: Arc<Self>,
         p: SubscribeRequestParams,
         cx: RequestContextAs<Empty>,
         data: Arc<SessionData>,
-    ) -> Result<Response>;
+    ) -> Result<Response> {
+        cx.handle(Ok(Empty::default()))
+    }
 
-    /// Handles resources/unsubscribe request
+    /// Handles [`resources/unsubscribe`]
+    ///
+    /// [`resources/unsubscribe`]: https://spec.modelcontextprotocol.io/specification/draft/server/resources/#subscriptions
+    #[allow(unused_variables)]
     fn resources_unsubscribe(
         self: Arc<Self>,
         p: UnsubscribeRequestParams,
         cx: RequestContextAs<Empty>,
         data: Arc<SessionData>,
-    ) -> Result<Response>;
+    ) -> Result<Response> {
+        cx.handle(Ok(Empty::default()))
+    }
 }
 
 /// Trait for default implementation of Server methods
@@ -297,186 +360,9 @@ pub trait DefaultServer: Server {
     fn into_handler(self) -> impl Handler + Send + Sync + 'static
     where
         Self: Sized + Send + Sync + 'static;
-
-    /// Default implementation of initialize_result
-    fn initialize_result(&self) -> InitializeResult {
-        InitializeResult {
-            capabilities: self.capabilities(),
-            instructions: self.instructions(),
-            meta: Map::new(),
-            protocol_version: ProtocolVersion::LATEST.to_string(),
-            server_info: self.server_info(),
-        }
-    }
-
-    fn prompts_list(
-        self: Arc<Self>,
-        p: ListPromptsRequestParams,
-        cx: RequestContextAs<ListPromptsResult>,
-        data: Arc<SessionData>,
-    ) -> Result<Response>;
-
-    fn prompts_get(
-        self: Arc<Self>,
-        p: GetPromptRequestParams,
-        cx: RequestContextAs<GetPromptResult>,
-        data: Arc<SessionData>,
-    ) -> Result<Response>;
-
-    fn resources_list(
-        self: Arc<Self>,
-        p: ListResourcesRequestParams,
-        cx: RequestContextAs<ListResourcesResult>,
-        data: Arc<SessionData>,
-    ) -> Result<Response>;
-
-    fn resources_read(
-        self: Arc<Self>,
-        p: ReadResourceRequestParams,
-        cx: RequestContextAs<ReadResourceResult>,
-        data: Arc<SessionData>,
-    ) -> Result<Response>;
-
-    fn resources_templates_list(
-        self: Arc<Self>,
-        p: ListResourceTemplatesRequestParams,
-        cx: RequestContextAs<ListResourceTemplatesResult>,
-        data: Arc<SessionData>,
-    ) -> Result<Response>;
-
-    fn tools_list(
-        self: Arc<Self>,
-        p: ListToolsRequestParams,
-        cx: RequestContextAs<ListToolsResult>,
-        data: Arc<SessionData>,
-    ) -> Result<Response>;
-
-    fn tools_call(
-        self: Arc<Self>,
-        p: CallToolRequestParams,
-        cx: RequestContextAs<CallToolResult>,
-        data: Arc<SessionData>,
-    ) -> Result<Response>;
-
-    fn completion_complete(
-        self: Arc<Self>,
-        p: CompleteRequestParams,
-        cx: RequestContextAs<CompleteResult>,
-        data: Arc<SessionData>,
-    ) -> Result<Response>;
 }
-// Implementation of the Server trait methods
-impl<T: Server> Server for T {
-    fn initialize_result(&self) -> InitializeResult {
-        InitializeResult {
-            capabilities: self.capabilities(),
-            instructions: self.instructions(),
-            meta: Map::new(),
-            protocol_version: ProtocolVersion::LATEST.to_string(),
-            server_info: self.server_info(),
-        }
-    }
-    fn prompts_list(
-        self: Arc<Self>,
-        _p: ListPromptsRequestParams,
-        cx: RequestContextAs<ListPromptsResult>,
-        _data: Arc<SessionData>,
-    ) -> Result<Response> {
-        cx.handle(Ok(ListPromptsResult::default()))
-    }
-
-    fn prompts_get(
-        self: Arc<Self>,
-        p: GetPromptRequestParams,
-        cx: RequestContextAs<GetPromptResult>,
-        _data: Arc<SessionData>,
-    ) -> Result<Response> {
-        cx.handle(Err(prompt_not_found(&p.name)))
-    }
-
-    fn resources_list(
-        self: Arc<Self>,
-        _p: ListResourcesRequestParams,
-        cx: RequestContextAs<ListResourcesResult>,
-        _data: Arc<SessionData>,
-    ) -> Result<Response> {
-        cx.handle(Ok(ListResourcesResult::default()))
-    }
-
-    fn resources_templates_list(
-        self: Arc<Self>,
-        _p: ListResourceTemplatesRequestParams,
-        cx: RequestContextAs<ListResourceTemplatesResult>,
-        _data: Arc<SessionData>,
-    ) -> Result<Response> {
-        cx.handle(Ok(ListResourceTemplatesResult::default()))
-    }
-
-    fn resources_read(
-        self: Arc<Self>,
-        p: ReadResourceRequestParams,
-        cx: RequestContextAs<ReadResourceResult>,
-        _data: Arc<SessionData>,
-    ) -> Result<Response> {
-        cx.handle(Err(resource_not_found(&p.uri)))
-    }
-
-    fn tools_list(
-        self: Arc<Self>,
-        _p: ListToolsRequestParams,
-        cx: RequestContextAs<ListToolsResult>,
-        _data: Arc<SessionData>,
-    ) -> Result<Response> {
-        cx.handle(Ok(ListToolsResult::default()))
-    }
-
-    fn tools_call(
-        self: Arc<Self>,
-        p: CallToolRequestParams,
-        cx: RequestContextAs<CallToolResult>,
-        _data: Arc<SessionData>,
-    ) -> Result<Response> {
-        cx.handle(Err(tool_not_found(&p.name)))
-    }
-
-    fn completion_complete(
-        self: Arc<Self>,
-        _p: CompleteRequestParams,
-        cx: RequestContextAs<CompleteResult>,
-        _data: Arc<SessionData>,
-    ) -> Result<Response> {
-        cx.handle(Ok(CompleteResult::default()))
-    }
-
-    /// Handles [`resources/subscribe`]
-    ///
-    /// [`resources/subscribe`]: https://spec.modelcontextprotocol.io/specification/draft/server/resources/#subscriptions
-    #[allow(unused_variables)]
-    fn resources_subscribe(
-        self: Arc<Self>,
-        _p: SubscribeRequestParams,
-        cx: RequestContextAs<Empty>,
-        _data: Arc<SessionData>,
-    ) -> Result<Response> {
-        cx.handle(Ok(Empty::default()))
-    }
-
-    /// Handles [`resources/unsubscribe`]
-    ///
-    /// [`resources/unsubscribe`]: https://spec.modelcontextprotocol.io/specification/draft/server/resources/#subscriptions
-    #[allow(unused_variables)]
-    fn resources_unsubscribe(
-        self: Arc<Self>,
-        _p: UnsubscribeRequestParams,
-        cx: RequestContextAs<Empty>,
-        _data: Arc<SessionData>,
-    ) -> Result<Response> {
-        cx.handle(Ok(Empty::default()))
-    }
-}
-
 /// Default implementation of the Server trait
-impl<T: Server> DefaultServer for T {
+impl<T: Server + ?Sized> DefaultServer for T {
     /// Returns `server_info` used in the [`initialize`] request response
     ///
     /// [`initialize`]: https://spec.modelcontextprotocol.io/specification/2025-03-26/basic/lifecycle/#initialization
@@ -514,110 +400,6 @@ impl<T: Server> DefaultServer for T {
         }
     }
 
-    /// Handles [`prompts/list`]
-    ///
-    /// [`prompts/list`]: https://spec.modelcontextprotocol.io/specification/2025-03-26/server/prompts/#listing-prompts
-    #[allow(unused_variables)]
-    fn prompts_list(
-        self: Arc<Self>,
-        p: ListPromptsRequestParams,
-        cx: RequestContextAs<ListPromptsResult>,
-        data: Arc<SessionData>,
-    ) -> Result<Response> {
-        cx.handle(Ok(ListPromptsResult::default()))
-    }
-
-    /// Handles [`prompts/get`]
-    ///
-    /// [`prompts/get`]: https://spec.modelcontextprotocol.io/specification/2025-03-26/server/prompts/#getting-a-prompt
-    #[allow(unused_variables)]
-    fn prompts_get(
-        self: Arc<Self>,
-        p: GetPromptRequestParams,
-        cx: RequestContextAs<GetPromptResult>,
-        data: Arc<SessionData>,
-    ) -> Result<Response> {
-        cx.handle(Err(prompt_not_found(&p.name)))
-    }
-
-    /// Handles [`resources/list`]
-    ///
-    /// [`resources/list`]: https://spec.modelcontextprotocol.io/specification/2025-03-26/server/resources/#listing-resources
-    #[allow(unused_variables)]
-    fn resources_list(
-        self: Arc<Self>,
-        p: ListResourcesRequestParams,
-        cx: RequestContextAs<ListResourcesResult>,
-        data: Arc<SessionData>,
-    ) -> Result<Response> {
-        cx.handle(Ok(ListResourcesResult::default()))
-    }
-
-    /// Handles [`resources/templates/list`]
-    ///
-    /// [`resources/templates/list`]: https://spec.modelcontextprotocol.io/specification/2025-03-26/server/resources/#resource-templates
-    #[allow(unused_variables)]
-    fn resources_templates_list(
-        self: Arc<Self>,
-        p: ListResourceTemplatesRequestParams,
-        cx: RequestContextAs<ListResourceTemplatesResult>,
-        data: Arc<SessionData>,
-    ) -> Result<Response> {
-        cx.handle(Ok(ListResourceTemplatesResult::default()))
-    }
-
-    /// Handles [`resources/read`]
-    ///
-    /// [`resources/read`]: https://spec.modelcontextprotocol.io/specification/2025-03-26/server/resources/#reading-resources
-    #[allow(unused_variables)]
-    fn resources_read(
-        self: Arc<Self>,
-        p: ReadResourceRequestParams,
-        cx: RequestContextAs<ReadResourceResult>,
-        data: Arc<SessionData>,
-    ) -> Result<Response> {
-        cx.handle(Err(resource_not_found(&p.uri)))
-    }
-
-    /// Handles [`tools/list`]
-    ///
-    /// [`tools/list`]: https://spec.modelcontextprotocol.io/specification/2025-03-26/server/tools/#listing-tools
-    #[allow(unused_variables)]
-    fn tools_list(
-        self: Arc<Self>,
-        p: ListToolsRequestParams,
-        cx: RequestContextAs<ListToolsResult>,
-        data: Arc<SessionData>,
-    ) -> Result<Response> {
-        cx.handle(Ok(ListToolsResult::default()))
-    }
-
-    /// Handles [`tools/call`]
-    ///
-    /// [`tools/call`]: https://spec.modelcontextprotocol.io/specification/2025-03-26/server/tools/#calling-a-tool
-    #[allow(unused_variables)]
-    fn tools_call(
-        self: Arc<Self>,
-        p: CallToolRequestParams,
-        cx: RequestContextAs<CallToolResult>,
-        data: Arc<SessionData>,
-    ) -> Result<Response> {
-        cx.handle(Err(tool_not_found(&p.name)))
-    }
-
-    /// Handles [`completion/complete`]
-    ///
-    /// [`completion/complete`]: https://spec.modelcontextprotocol.io/specification/2025-03-26/server/utilities/completion/#completing-a-prompt
-    #[allow(unused_variables)]
-    fn completion_complete(
-        self: Arc<Self>,
-        p: CompleteRequestParams,
-        cx: RequestContextAs<CompleteResult>,
-        data: Arc<SessionData>,
-    ) -> Result<Response> {
-        cx.handle(Ok(CompleteResult::default()))
-    }
-
     /// Gets the JSON RPC `Handler`
     fn into_handler(self) -> impl Handler + Send + Sync + 'static
     where
@@ -713,3 +495,36 @@ pub async fn serve_stdio_with(
         .wait()
         .await
 }
+
+/// Runs an MCP server over the specified [`Transport`]
+///
+/// The transport is opened before the session starts and closed when the session ends.
+pub async fn serve_transport(server: impl Server, transport: impl Transport) -> SessionResult<()> {
+    serve_transport_with(server, transport, &SessionOptions::default()).await
+}
+
+/// Runs a server built at runtime, such as the ones `httpd` builds per session,
+/// over the specified [`Transport`]
+pub(crate) async fn serve_boxed_transport(
+    server: Box<dyn Server>,
+    transport: impl Transport,
+) -> SessionResult<()> {
+    transport.open().await.map_err(SessionError::from_error)?;
+    let (reader, writer) = session_io(transport);
+    Session::new(ServerHandler::from_arc(server.into()), reader, writer, &SessionOptions::default())
+        .wait()
+        .await
+}
+
+/// Runs an MCP server over the specified [`Transport`] with specified options
+pub async fn serve_transport_with(
+    server: impl Server,
+    transport: impl Transport,
+    options: &SessionOptions,
+) -> SessionResult<()> {
+    transport.open().await.map_err(SessionError::from_error)?;
+    let (reader, writer) = session_io(transport);
+    Session::new(ServerHandler::new(server), reader, writer, options)
+        .wait()
+        .await
+}
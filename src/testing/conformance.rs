@@ -0,0 +1,591 @@
+//! Protocol conformance checks for MCP servers
+//!
+//! [`ConformanceSuite`] drives a server over the wire with raw JSON-RPC messages and
+//! checks the lifecycle and protocol rules every MCP server has to follow:
+//!
+//! - `ping` is answered before and after initialization
+//! - other requests are rejected before `initialize` and before `notifications/initialized`
+//! - unknown methods return `-32601` (method not found)
+//! - list results validate against `spec/schema.json` and `nextCursor` round-trips
+//! - a cancelled request does not produce a result
+//! - every result and notification validates against `spec/schema.json`
+//!
+//! The suite can run against a [`Server`] in-process, a command that speaks MCP over
+//! stdio, or any reader/writer pair:
+//!
+//! ```rust,ignore
+//! use mcp_daemon::testing::ConformanceSuite;
+//!
+//! let report = ConformanceSuite::new().run(MyServer::default()).await;
+//! report.assert_passed();
+//! ```
+
+use std::fmt::{self, Display};
+use std::process::Stdio;
+use std::time::Duration;
+
+use jsoncall::{Session, SessionOptions};
+use serde_json::{Value, json};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::process::Command;
+use tokio::time::{Instant, timeout_at};
+
+use crate::schema::validation::{SchemaValidator, format_errors};
+use crate::server::{DefaultServer, Server};
+use crate::utils::ProtocolVersion;
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const CANCEL_DELAY: Duration = Duration::from_millis(50);
+const CANCEL_GRACE: Duration = Duration::from_millis(500);
+
+/// Outcome of a single conformance check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckOutcome {
+    /// The server behaved as required
+    Passed,
+    /// The server violated the rule; the string explains how
+    Failed(String),
+    /// The check could not run; the string explains why
+    Skipped(String),
+}
+
+/// Result of a single conformance check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    /// Name of the check
+    pub name: String,
+    /// Outcome of the check
+    pub outcome: CheckOutcome,
+}
+
+/// Pass/fail report produced by [`ConformanceSuite`]
+#[derive(Debug, Clone, Default)]
+pub struct ConformanceReport {
+    /// Results of all checks, in the order they ran
+    pub checks: Vec<CheckResult>,
+}
+
+impl ConformanceReport {
+    /// Returns true if no check failed
+    pub fn passed(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    /// Returns the failed checks
+    pub fn failures(&self) -> impl Iterator<Item = &CheckResult> {
+        self.checks
+            .iter()
+            .filter(|c| matches!(c.outcome, CheckOutcome::Failed(_)))
+    }
+
+    /// Panics with the full report if any check failed
+    pub fn assert_passed(&self) {
+        assert!(self.passed(), "MCP conformance checks failed:\n{self}");
+    }
+
+    fn push(&mut self, name: impl Into<String>, outcome: CheckOutcome) {
+        self.checks.push(CheckResult {
+            name: name.into(),
+            outcome,
+        });
+    }
+}
+
+impl Display for ConformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+        for c in &self.checks {
+            match &c.outcome {
+                CheckOutcome::Passed => {
+                    passed += 1;
+                    writeln!(f, "[PASS] {}", c.name)?;
+                }
+                CheckOutcome::Failed(reason) => {
+                    failed += 1;
+                    writeln!(f, "[FAIL] {}: {}", c.name, reason)?;
+                }
+                CheckOutcome::Skipped(reason) => {
+                    skipped += 1;
+                    writeln!(f, "[SKIP] {}: {}", c.name, reason)?;
+                }
+            }
+        }
+        write!(f, "{passed} passed, {failed} failed, {skipped} skipped")
+    }
+}
+
+/// Conformance test suite for MCP servers
+///
+/// Cancellation can only be checked with a request that takes long enough to be
+/// cancelled, so it is skipped unless a probe is configured with
+/// [`with_cancellation_probe`](Self::with_cancellation_probe).
+#[derive(Debug, Clone)]
+pub struct ConformanceSuite {
+    timeout: Duration,
+    max_pages: usize,
+    cancellation_probe: Option<(String, Value)>,
+}
+
+impl Default for ConformanceSuite {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConformanceSuite {
+    /// Creates a suite with a 5 second response timeout
+    pub fn new() -> Self {
+        Self {
+            timeout: Duration::from_secs(5),
+            max_pages: 100,
+            cancellation_probe: None,
+        }
+    }
+
+    /// Sets how long to wait for each response
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets how many pages a paginated list may have before it is considered a loop
+    pub fn with_max_pages(mut self, max_pages: usize) -> Self {
+        self.max_pages = max_pages;
+        self
+    }
+
+    /// Sets a slow request used to check that cancellation is honored
+    ///
+    /// The request is cancelled shortly after it is sent; the check fails if the
+    /// server still answers it with a result.
+    pub fn with_cancellation_probe(mut self, method: impl Into<String>, params: Value) -> Self {
+        self.cancellation_probe = Some((method.into(), params));
+        self
+    }
+
+    /// Runs the suite against a [`Server`] in-process
+    pub async fn run(&self, server: impl Server) -> ConformanceReport {
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        let (server_reader, server_writer) = tokio::io::split(server_io);
+        let session = Session::new(
+            server.into_handler(),
+            BufReader::new(server_reader),
+            server_writer,
+            &SessionOptions::default(),
+        );
+        let (client_reader, client_writer) = tokio::io::split(client_io);
+        let report = self
+            .run_io(BufReader::new(client_reader), client_writer)
+            .await;
+        session.shutdown();
+        report
+    }
+
+    /// Spawns `command` and runs the suite against it over stdio
+    pub async fn run_command(&self, command: &mut Command) -> std::io::Result<ConformanceReport> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let report = self.run_io(BufReader::new(stdout), stdin).await;
+        let _ = child.kill().await;
+        Ok(report)
+    }
+
+    /// Runs the suite over a newline-delimited JSON-RPC byte stream
+    pub async fn run_io(
+        &self,
+        reader: impl AsyncBufRead + Send + Unpin,
+        writer: impl AsyncWrite + Send + Unpin,
+    ) -> ConformanceReport {
+        let mut client = WireClient {
+            reader,
+            writer,
+            buf: Vec::new(),
+            next_id: 1,
+            timeout: self.timeout,
+            notifications: Vec::new(),
+            other_responses: Vec::new(),
+        };
+        let mut report = ConformanceReport::default();
+        self.run_checks(&mut client, &mut report).await;
+        check_notifications(&client, &mut report);
+        report
+    }
+
+    async fn run_checks<R, W>(&self, c: &mut WireClient<R, W>, report: &mut ConformanceReport)
+    where
+        R: AsyncBufRead + Send + Unpin,
+        W: AsyncWrite + Send + Unpin,
+    {
+        let outcome = match c.request("ping", None).await {
+            Ok(Reply::Result(r)) => validate_result("ping", &r),
+            Ok(Reply::Error { message, .. }) => {
+                CheckOutcome::Failed(format!("ping was rejected: {message}"))
+            }
+            Err(e) => CheckOutcome::Failed(e),
+        };
+        report.push("ping before initialize", outcome);
+
+        let outcome = expect_rejected(c.request("tools/list", None).await);
+        report.push("request before initialize is rejected", outcome);
+
+        let initialize = json!({
+            "protocolVersion": ProtocolVersion::LATEST.to_string(),
+            "capabilities": {},
+            "clientInfo": { "name": "mcp-conformance", "version": env!("CARGO_PKG_VERSION") },
+        });
+        let capabilities = match c.request("initialize", Some(initialize)).await {
+            Ok(Reply::Result(r)) => {
+                report.push("initialize", validate_result("initialize", &r));
+                r.get("capabilities").cloned().unwrap_or(Value::Null)
+            }
+            Ok(Reply::Error { message, .. }) => {
+                report.push(
+                    "initialize",
+                    CheckOutcome::Failed(format!("initialize was rejected: {message}")),
+                );
+                return;
+            }
+            Err(e) => {
+                report.push("initialize", CheckOutcome::Failed(e));
+                return;
+            }
+        };
+
+        let outcome = expect_rejected(c.request("tools/list", None).await);
+        report.push(
+            "request before initialized notification is rejected",
+            outcome,
+        );
+
+        if let Err(e) = c.notify("notifications/initialized", None).await {
+            report.push("initialized notification", CheckOutcome::Failed(e));
+            return;
+        }
+
+        let outcome = match c.request("ping", None).await {
+            Ok(Reply::Result(r)) => validate_result("ping", &r),
+            Ok(Reply::Error { message, .. }) => {
+                CheckOutcome::Failed(format!("ping was rejected: {message}"))
+            }
+            Err(e) => CheckOutcome::Failed(e),
+        };
+        report.push("ping after initialize", outcome);
+
+        let outcome = match c.request("conformance/unknownMethod", None).await {
+            Ok(Reply::Error {
+                code: METHOD_NOT_FOUND,
+                ..
+            }) => CheckOutcome::Passed,
+            Ok(Reply::Error { code, message }) => CheckOutcome::Failed(format!(
+                "expected error code {METHOD_NOT_FOUND}, got {code} ({message})"
+            )),
+            Ok(Reply::Result(_)) => {
+                CheckOutcome::Failed("unknown method returned a result".to_string())
+            }
+            Err(e) => CheckOutcome::Failed(e),
+        };
+        report.push("unknown method returns method not found", outcome);
+
+        for (capability, method) in [
+            ("prompts", "prompts/list"),
+            ("resources", "resources/list"),
+            ("resources", "resources/templates/list"),
+            ("tools", "tools/list"),
+        ] {
+            let name = format!("{method} results and pagination");
+            if capabilities.get(capability).is_none_or(Value::is_null) {
+                report.push(
+                    name,
+                    CheckOutcome::Skipped(format!("`{capability}` capability not advertised")),
+                );
+                continue;
+            }
+            report.push(name, self.check_pagination(c, method).await);
+        }
+
+        let outcome = match &self.cancellation_probe {
+            Some((method, params)) => check_cancellation(c, method, params.clone()).await,
+            None => CheckOutcome::Skipped("no cancellation probe configured".to_string()),
+        };
+        report.push("cancellation is honored", outcome);
+    }
+
+    async fn check_pagination<R, W>(&self, c: &mut WireClient<R, W>, method: &str) -> CheckOutcome
+    where
+        R: AsyncBufRead + Send + Unpin,
+        W: AsyncWrite + Send + Unpin,
+    {
+        let mut cursors = Vec::new();
+        let mut params = None;
+        for page in 0..self.max_pages {
+            let result = match c.request(method, params.take()).await {
+                Ok(Reply::Result(r)) => r,
+                Ok(Reply::Error { message, .. }) if page == 0 => {
+                    return CheckOutcome::Failed(format!("request was rejected: {message}"));
+                }
+                Ok(Reply::Error { message, .. }) => {
+                    return CheckOutcome::Failed(format!(
+                        "cursor returned by the previous page was rejected: {message}"
+                    ));
+                }
+                Err(e) => return CheckOutcome::Failed(e),
+            };
+            if let CheckOutcome::Failed(e) = validate_result(method, &result) {
+                return CheckOutcome::Failed(format!("page {page}: {e}"));
+            }
+            let Some(cursor) = result.get("nextCursor").and_then(Value::as_str) else {
+                return CheckOutcome::Passed;
+            };
+            if cursors.iter().any(|c| c == cursor) {
+                return CheckOutcome::Failed(format!("cursor `{cursor}` was returned twice"));
+            }
+            cursors.push(cursor.to_string());
+            params = Some(json!({ "cursor": cursor }));
+        }
+        CheckOutcome::Failed(format!("more than {} pages were returned", self.max_pages))
+    }
+}
+
+async fn check_cancellation<R, W>(
+    c: &mut WireClient<R, W>,
+    method: &str,
+    params: Value,
+) -> CheckOutcome
+where
+    R: AsyncBufRead + Send + Unpin,
+    W: AsyncWrite + Send + Unpin,
+{
+    let id = match c.send_request(method, Some(params)).await {
+        Ok(id) => id,
+        Err(e) => return CheckOutcome::Failed(e),
+    };
+    if let Err(e) = c.drain(CANCEL_DELAY).await {
+        return CheckOutcome::Failed(e);
+    }
+    if c.has_result_for(id) {
+        return CheckOutcome::Skipped(format!("`{method}` completed before it could be cancelled"));
+    }
+    let cancelled = json!({ "requestId": id, "reason": "conformance check" });
+    if let Err(e) = c.notify("notifications/cancelled", Some(cancelled)).await {
+        return CheckOutcome::Failed(e);
+    }
+    match c.request("ping", None).await {
+        Ok(Reply::Result(_)) => {}
+        Ok(Reply::Error { message, .. }) => {
+            return CheckOutcome::Failed(format!(
+                "ping after cancellation was rejected: {message}"
+            ));
+        }
+        Err(e) => {
+            return CheckOutcome::Failed(format!(
+                "server stopped responding after cancellation: {e}"
+            ));
+        }
+    }
+    if let Err(e) = c.drain(CANCEL_GRACE).await {
+        return CheckOutcome::Failed(e);
+    }
+    if c.has_result_for(id) {
+        CheckOutcome::Failed(format!(
+            "cancelled `{method}` request still returned a result"
+        ))
+    } else {
+        CheckOutcome::Passed
+    }
+}
+
+fn check_notifications<R, W>(c: &WireClient<R, W>, report: &mut ConformanceReport) {
+    let errors: Vec<String> = c
+        .notifications
+        .iter()
+        .filter_map(|(method, params)| {
+            SchemaValidator::mcp()
+                .validate_notification(method, params.as_ref())
+                .err()
+                .map(|e| format!("{method}: {}", format_errors(&e)))
+        })
+        .collect();
+    let outcome = if errors.is_empty() {
+        CheckOutcome::Passed
+    } else {
+        CheckOutcome::Failed(errors.join("; "))
+    };
+    report.push("notifications conform to schema", outcome);
+}
+
+fn validate_result(method: &str, result: &Value) -> CheckOutcome {
+    match SchemaValidator::mcp().validate_result(method, result) {
+        Ok(()) => CheckOutcome::Passed,
+        Err(e) => CheckOutcome::Failed(format!(
+            "result does not match schema: {}",
+            format_errors(&e)
+        )),
+    }
+}
+
+fn expect_rejected(reply: Result<Reply, String>) -> CheckOutcome {
+    match reply {
+        Ok(Reply::Error { .. }) => CheckOutcome::Passed,
+        Ok(Reply::Result(_)) => {
+            CheckOutcome::Failed("request was answered with a result".to_string())
+        }
+        Err(e) => CheckOutcome::Failed(e),
+    }
+}
+
+enum Reply {
+    Result(Value),
+    Error { code: i64, message: String },
+}
+
+/// Minimal JSON-RPC client that exposes every message on the wire
+struct WireClient<R, W> {
+    reader: R,
+    writer: W,
+    buf: Vec<u8>,
+    next_id: u64,
+    timeout: Duration,
+    notifications: Vec<(String, Option<Value>)>,
+    other_responses: Vec<Value>,
+}
+
+impl<R, W> WireClient<R, W>
+where
+    R: AsyncBufRead + Send + Unpin,
+    W: AsyncWrite + Send + Unpin,
+{
+    async fn request(&mut self, method: &str, params: Option<Value>) -> Result<Reply, String> {
+        let id = self.send_request(method, params).await?;
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let message = match timeout_at(deadline, self.read()).await {
+                Ok(message) => message?,
+                Err(_) => {
+                    return Err(format!(
+                        "no response to `{method}` within {:?}",
+                        self.timeout
+                    ));
+                }
+            };
+            if message.get("id").and_then(Value::as_u64) == Some(id)
+                && message.get("method").is_none()
+            {
+                return Ok(parse_reply(&message));
+            }
+            self.dispatch(message).await?;
+        }
+    }
+
+    async fn send_request(&mut self, method: &str, params: Option<Value>) -> Result<u64, String> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let mut message = json!({ "jsonrpc": "2.0", "id": id, "method": method });
+        if let Some(params) = params {
+            message["params"] = params;
+        }
+        self.write(&message).await?;
+        Ok(id)
+    }
+
+    async fn notify(&mut self, method: &str, params: Option<Value>) -> Result<(), String> {
+        let mut message = json!({ "jsonrpc": "2.0", "method": method });
+        if let Some(params) = params {
+            message["params"] = params;
+        }
+        self.write(&message).await
+    }
+
+    /// Handles incoming messages for `duration`
+    async fn drain(&mut self, duration: Duration) -> Result<(), String> {
+        let deadline = Instant::now() + duration;
+        while let Ok(message) = timeout_at(deadline, self.read()).await {
+            self.dispatch(message?).await?;
+        }
+        Ok(())
+    }
+
+    fn has_result_for(&self, id: u64) -> bool {
+        self.other_responses
+            .iter()
+            .any(|m| m.get("id").and_then(Value::as_u64) == Some(id) && m.get("result").is_some())
+    }
+
+    async fn dispatch(&mut self, message: Value) -> Result<(), String> {
+        match (
+            message.get("method").and_then(Value::as_str),
+            message.get("id"),
+        ) {
+            (Some(_), Some(id)) => {
+                // This client supports no client features
+                let reply = json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": { "code": METHOD_NOT_FOUND, "message": "Method not found" },
+                });
+                self.write(&reply).await?;
+            }
+            (Some(method), None) => {
+                self.notifications
+                    .push((method.to_string(), message.get("params").cloned()));
+            }
+            (None, _) => self.other_responses.push(message),
+        }
+        Ok(())
+    }
+
+    async fn write(&mut self, message: &Value) -> Result<(), String> {
+        let mut line = message.to_string();
+        line.push('\n');
+        self.writer
+            .write_all(line.as_bytes())
+            .await
+            .map_err(|e| format!("failed to write to server: {e}"))?;
+        self.writer
+            .flush()
+            .await
+            .map_err(|e| format!("failed to write to server: {e}"))
+    }
+
+    async fn read(&mut self) -> Result<Value, String> {
+        loop {
+            // `read_until` keeps partially read bytes in `buf`, so a timeout in the
+            // middle of a line does not lose data
+            let n = self
+                .reader
+                .read_until(b'\n', &mut self.buf)
+                .await
+                .map_err(|e| format!("failed to read from server: {e}"))?;
+            if n == 0 && self.buf.is_empty() {
+                return Err("server closed the connection".to_string());
+            }
+            let line = std::mem::take(&mut self.buf);
+            let line = String::from_utf8_lossy(&line);
+            if !line.trim().is_empty() {
+                return serde_json::from_str(&line)
+                    .map_err(|e| format!("server sent invalid JSON ({e}): {}", line.trim()));
+            }
+        }
+    }
+}
+
+fn parse_reply(message: &Value) -> Reply {
+    match message.get("error") {
+        Some(error) => Reply::Error {
+            code: error
+                .get("code")
+                .and_then(Value::as_i64)
+                .unwrap_or_default(),
+            message: error
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+        },
+        None => Reply::Result(message.get("result").cloned().unwrap_or(Value::Null)),
+    }
+}
@@ -0,0 +1,684 @@
+//! Scriptable MCP server for client-side tests
+//!
+//! A [`MockServer`] answers requests according to a list of [`Expectation`]s registered
+//! by the test. Each expectation matches a method name and, optionally, the request
+//! parameters, and describes the response to return and how long to wait before
+//! returning it. Requests that match no expectation are answered with an error and
+//! reported as unexpected.
+//!
+//! Expectations are verified when the `MockServer` created with [`MockServer::new`] is
+//! dropped. Clones share the same expectations but do not verify on drop, so the usual
+//! pattern is to hand a clone to the client and keep the original in the test:
+//!
+//! ```rust,ignore
+//! use mcp_daemon::client::Client;
+//! use mcp_daemon::testing::{Expectation, MockServer};
+//! use serde_json::json;
+//!
+//! let server = MockServer::new();
+//! server.expect(
+//!     Expectation::tool_call("echo")
+//!         .with_params(json!({ "arguments": { "text": "hi" } }))
+//!         .respond_with(json!({ "content": [{ "type": "text", "text": "hi" }] })),
+//! );
+//! let client = Client::with_server(server.clone()).await?;
+//! // ... exercise the client ...
+//! // `server` verifies that `tools/call` was received when it goes out of scope
+//! ```
+
+use std::fmt::{self, Display};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use jsoncall::{Error, ErrorCode, RequestContextAs, Response, Result, SessionContext};
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::{Map, Value};
+use tokio::sync::Notify;
+use tracing::warn;
+
+use crate::schema::{
+    CallToolRequestParams, CallToolResult, CompleteRequestParams, CompleteResult,
+    GetPromptRequestParams, GetPromptResult, Implementation, InitializeResult,
+    ListPromptsRequestParams, ListPromptsResult, ListResourceTemplatesRequestParams,
+    ListResourceTemplatesResult, ListResourcesRequestParams, ListResourcesResult,
+    ListToolsRequestParams, ListToolsResult, ReadResourceRequestParams, ReadResourceResult,
+    ServerCapabilities, SubscribeRequestParams, UnsubscribeRequestParams,
+};
+use crate::server::{DefaultServer, Server, SessionData};
+use crate::utils::{Empty, ProtocolVersion};
+
+/// How many times an [`Expectation`] is expected to match
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Times {
+    /// Must match exactly `n` times; further requests are not matched
+    Exactly(usize),
+    /// Must match at least `n` times
+    AtLeast(usize),
+    /// May match at most `n` times; further requests are not matched
+    AtMost(usize),
+}
+
+impl Times {
+    fn is_exhausted(self, calls: usize) -> bool {
+        match self {
+            Times::Exactly(n) | Times::AtMost(n) => calls >= n,
+            Times::AtLeast(_) => false,
+        }
+    }
+    fn is_satisfied(self, calls: usize) -> bool {
+        match self {
+            Times::Exactly(n) => calls == n,
+            Times::AtLeast(n) => calls >= n,
+            Times::AtMost(n) => calls <= n,
+        }
+    }
+}
+
+impl Display for Times {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Times::Exactly(n) => write!(f, "exactly {n} time(s)"),
+            Times::AtLeast(n) => write!(f, "at least {n} time(s)"),
+            Times::AtMost(n) => write!(f, "at most {n} time(s)"),
+        }
+    }
+}
+
+type ParamsPredicate = Arc<dyn Fn(&Value) -> bool + Send + Sync>;
+
+#[derive(Clone)]
+enum MockResponse {
+    Result(Value),
+    Error { code: ErrorCode, message: String },
+}
+
+/// A request the [`MockServer`] expects to receive, and how to answer it
+///
+/// Without [`respond_with`](Self::respond_with) or
+/// [`respond_with_error`](Self::respond_with_error) the response is an empty object,
+/// which is a valid result for the list methods and `resources/subscribe`.
+#[derive(Clone)]
+pub struct Expectation {
+    method: String,
+    params: Option<Value>,
+    predicate: Option<ParamsPredicate>,
+    response: MockResponse,
+    delay: Option<Duration>,
+    times: Times,
+}
+
+impl Expectation {
+    /// Expects a request with the specified method name
+    pub fn request(method: impl Into<String>) -> Self {
+        Self {
+            method: method.into(),
+            params: None,
+            predicate: None,
+            response: MockResponse::Result(Value::Object(Map::new())),
+            delay: None,
+            times: Times::AtLeast(1),
+        }
+    }
+
+    /// Expects a `tools/call` request for the tool named `name`
+    pub fn tool_call(name: impl Into<String>) -> Self {
+        Self::request("tools/call").with_params(serde_json::json!({ "name": name.into() }))
+    }
+
+    /// Only matches requests whose parameters contain `params`
+    ///
+    /// Objects match if every field in `params` is present in the request and matches
+    /// recursively; other values must be equal. Calling this again merges the objects.
+    pub fn with_params(mut self, params: Value) -> Self {
+        self.params = Some(match (self.params.take(), params) {
+            (Some(Value::Object(mut current)), Value::Object(new)) => {
+                current.extend(new);
+                Value::Object(current)
+            }
+            (_, params) => params,
+        });
+        self
+    }
+
+    /// Only matches requests whose parameters satisfy `predicate`
+    pub fn matching(mut self, predicate: impl Fn(&Value) -> bool + Send + Sync + 'static) -> Self {
+        self.predicate = Some(Arc::new(predicate));
+        self
+    }
+
+    /// Responds with the specified result
+    ///
+    /// # Panics
+    /// Panics if `result` cannot be serialized to JSON.
+    pub fn respond_with(mut self, result: impl Serialize) -> Self {
+        let value = serde_json::to_value(result).expect("mock response must serialize to JSON");
+        self.response = MockResponse::Result(value);
+        self
+    }
+
+    /// Responds with a JSON-RPC error
+    pub fn respond_with_error(mut self, code: ErrorCode, message: impl Into<String>) -> Self {
+        self.response = MockResponse::Error {
+            code,
+            message: message.into(),
+        };
+        self
+    }
+
+    /// Waits for `delay` before sending the response
+    pub fn after(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    /// Sets how many times this expectation must match (default: at least once)
+    pub fn times(mut self, times: Times) -> Self {
+        self.times = times;
+        self
+    }
+
+    fn matches(&self, method: &str, params: &Value) -> bool {
+        self.method == method
+            && self
+                .params
+                .as_ref()
+                .is_none_or(|p| json_contains(params, p))
+            && self.predicate.as_ref().is_none_or(|f| f(params))
+    }
+}
+
+impl fmt::Debug for Expectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Expectation")
+            .field("method", &self.method)
+            .field("params", &self.params)
+            .field("delay", &self.delay)
+            .field("times", &self.times)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A request received by the [`MockServer`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReceivedRequest {
+    /// Method name
+    pub method: String,
+    /// Request parameters
+    pub params: Value,
+}
+
+/// The client's answer to a request sent by the [`MockServer`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientResponse {
+    /// Method name of the request
+    pub method: String,
+    /// Result returned by the client, or the error message
+    pub result: std::result::Result<Value, String>,
+}
+
+struct Registered {
+    expectation: Expectation,
+    calls: usize,
+}
+
+#[derive(Default)]
+struct State {
+    expectations: Vec<Registered>,
+    notifications: Vec<(String, Option<Value>)>,
+    requests: Vec<(String, Option<Value>)>,
+    client_responses: Vec<ClientResponse>,
+    received: Vec<ReceivedRequest>,
+    unexpected: Vec<ReceivedRequest>,
+}
+
+struct Inner {
+    state: Mutex<State>,
+    client_responded: Notify,
+    server_info: Implementation,
+    instructions: Option<String>,
+    capabilities: Option<ServerCapabilities>,
+    allow_unexpected: bool,
+}
+
+/// Scriptable [`Server`] for testing MCP clients
+///
+/// See the [module documentation](self) for an overview.
+pub struct MockServer {
+    inner: Arc<Inner>,
+    verify_on_drop: bool,
+}
+
+impl MockServer {
+    /// Creates a mock server with no expectations
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                state: Mutex::new(State::default()),
+                client_responded: Notify::new(),
+                server_info: Implementation {
+                    name: "mock-server".to_string(),
+                    version: env!("CARGO_PKG_VERSION").to_string(),
+                },
+                instructions: None,
+                capabilities: None,
+                allow_unexpected: false,
+            }),
+            verify_on_drop: true,
+        }
+    }
+
+    fn configure(mut self, f: impl FnOnce(&mut Inner)) -> Self {
+        f(Arc::get_mut(&mut self.inner).expect("MockServer must be configured before cloning"));
+        self
+    }
+
+    /// Sets the `serverInfo` returned from `initialize`
+    ///
+    /// # Panics
+    /// Panics if the mock server has already been cloned.
+    pub fn with_server_info(self, server_info: Implementation) -> Self {
+        self.configure(|inner| inner.server_info = server_info)
+    }
+
+    /// Sets the `instructions` returned from `initialize`
+    ///
+    /// # Panics
+    /// Panics if the mock server has already been cloned.
+    pub fn with_instructions(self, instructions: impl Into<String>) -> Self {
+        let instructions = instructions.into();
+        self.configure(|inner| inner.instructions = Some(instructions))
+    }
+
+    /// Sets the `capabilities` returned from `initialize`
+    ///
+    /// # Panics
+    /// Panics if the mock server has already been cloned.
+    pub fn with_capabilities(self, capabilities: ServerCapabilities) -> Self {
+        self.configure(|inner| inner.capabilities = Some(capabilities))
+    }
+
+    /// Answers unexpected requests with an error without failing verification
+    ///
+    /// # Panics
+    /// Panics if the mock server has already been cloned.
+    pub fn allow_unexpected(self) -> Self {
+        self.configure(|inner| inner.allow_unexpected = true)
+    }
+
+    /// Registers an expectation
+    ///
+    /// Expectations are tried in registration order; the first one that matches and
+    /// is not exhausted answers the request.
+    pub fn expect(&self, expectation: Expectation) -> &Self {
+        self.state().expectations.push(Registered {
+            expectation,
+            calls: 0,
+        });
+        self
+    }
+
+    /// Sends a notification to the client once it sends `notifications/initialized`
+    ///
+    /// # Panics
+    /// Panics if `params` cannot be serialized to JSON.
+    pub fn notify_after_initialize(
+        &self,
+        method: impl Into<String>,
+        params: impl Serialize,
+    ) -> &Self {
+        let params =
+            serde_json::to_value(params).expect("notification params must serialize to JSON");
+        let params = (!params.is_null()).then_some(params);
+        self.state().notifications.push((method.into(), params));
+        self
+    }
+
+    /// Sends a request to the client once it sends `notifications/initialized`
+    ///
+    /// The client's answers are collected by [`client_responses`](Self::client_responses).
+    ///
+    /// # Panics
+    /// Panics if `params` cannot be serialized to JSON.
+    pub fn request_after_initialize(
+        &self,
+        method: impl Into<String>,
+        params: impl Serialize,
+    ) -> &Self {
+        let params = serde_json::to_value(params).expect("request params must serialize to JSON");
+        let params = (!params.is_null()).then_some(params);
+        self.state().requests.push((method.into(), params));
+        self
+    }
+
+    /// Waits until the client has answered at least `n` requests sent by
+    /// [`request_after_initialize`](Self::request_after_initialize) and returns the answers
+    /// in the order they arrived
+    pub async fn client_responses(&self, n: usize) -> Vec<ClientResponse> {
+        loop {
+            let notified = self.inner.client_responded.notified();
+            {
+                let state = self.state();
+                if state.client_responses.len() >= n {
+                    return state.client_responses.clone();
+                }
+            }
+            notified.await;
+        }
+    }
+
+    /// Returns all requests received so far, in order
+    pub fn received_requests(&self) -> Vec<ReceivedRequest> {
+        self.state().received.clone()
+    }
+
+    /// Checks that every expectation was satisfied and no unexpected request was received
+    pub fn try_verify(&self) -> std::result::Result<(), String> {
+        let state = self.state();
+        let mut failures = Vec::new();
+        for r in &state.expectations {
+            if !r.expectation.times.is_satisfied(r.calls) {
+                failures.push(format!(
+                    "expected `{}` {} matching {}, but it matched {} time(s)",
+                    r.expectation.method,
+                    r.expectation.times,
+                    r.expectation
+                        .params
+                        .as_ref()
+                        .map_or_else(|| "any params".to_string(), |p| p.to_string()),
+                    r.calls
+                ));
+            }
+        }
+        if !self.inner.allow_unexpected {
+            for r in &state.unexpected {
+                failures.push(format!(
+                    "unexpected request `{}` with {}",
+                    r.method, r.params
+                ));
+            }
+        }
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "MockServer verification failed:\n  {}",
+                failures.join("\n  ")
+            ))
+        }
+    }
+
+    /// Like [`try_verify`](Self::try_verify), but panics on failure
+    pub fn verify(&self) {
+        if let Err(message) = self.try_verify() {
+            panic!("{message}");
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.inner.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn dispatch<R>(
+        &self,
+        method: &str,
+        p: &impl Serialize,
+        cx: RequestContextAs<R>,
+    ) -> Result<Response>
+    where
+        R: Serialize + DeserializeOwned + Send + 'static,
+    {
+        let params = serde_json::to_value(p).unwrap_or(Value::Null);
+        let matched = {
+            let mut state = self.state();
+            state.received.push(ReceivedRequest {
+                method: method.to_string(),
+                params: params.clone(),
+            });
+            let found = state.expectations.iter_mut().find(|r| {
+                !r.expectation.times.is_exhausted(r.calls) && r.expectation.matches(method, &params)
+            });
+            match found {
+                Some(r) => {
+                    r.calls += 1;
+                    Some((r.expectation.response.clone(), r.expectation.delay))
+                }
+                None => {
+                    state.unexpected.push(ReceivedRequest {
+                        method: method.to_string(),
+                        params,
+                    });
+                    None
+                }
+            }
+        };
+        let Some((response, delay)) = matched else {
+            return cx.handle(Err(Error::new(ErrorCode::METHOD_NOT_FOUND).with_message(
+                format!("MockServer has no expectation for `{method}`"),
+                true,
+            )));
+        };
+        let result = match response {
+            MockResponse::Result(value) => serde_json::from_value::<R>(value).map_err(|e| {
+                Error::new(ErrorCode::INTERNAL_ERROR).with_message(
+                    format!("Mock response for `{method}` does not match the result type: {e}"),
+                    true,
+                )
+            }),
+            MockResponse::Error { code, message } => {
+                Err(Error::new(code).with_message(message, true))
+            }
+        };
+        match delay {
+            Some(delay) => cx.handle_async(async move {
+                tokio::time::sleep(delay).await;
+                result
+            }),
+            None => cx.handle(result),
+        }
+    }
+}
+
+impl Default for MockServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clone for MockServer {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            verify_on_drop: false,
+        }
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if self.verify_on_drop && !std::thread::panicking() {
+            self.verify();
+        }
+    }
+}
+
+impl Server for MockServer {
+    fn initialize_result(&self) -> InitializeResult {
+        InitializeResult {
+            capabilities: self
+                .inner
+                .capabilities
+                .clone()
+                .unwrap_or_else(|| DefaultServer::capabilities(self)),
+            instructions: self.inner.instructions.clone(),
+            meta: Map::new(),
+            protocol_version: ProtocolVersion::LATEST.to_string(),
+            server_info: self.inner.server_info.clone(),
+        }
+    }
+
+    fn initialized(self: Arc<Self>, session: SessionContext, _data: Arc<SessionData>) {
+        let (notifications, requests) = {
+            let state = self.state();
+            (state.notifications.clone(), state.requests.clone())
+        };
+        for (method, params) in notifications {
+            if let Err(e) = session.notification(&method, params.as_ref()) {
+                warn!("MockServer failed to send `{}`: {}", method, e);
+            }
+        }
+        for (method, params) in requests {
+            let server = self.clone();
+            let session = session.clone();
+            tokio::spawn(async move {
+                let result = session
+                    .request::<Value>(&method, params.as_ref())
+                    .await
+                    .map_err(|e| e.to_string());
+                server
+                    .state()
+                    .client_responses
+                    .push(ClientResponse { method, result });
+                server.inner.client_responded.notify_waiters();
+            });
+        }
+    }
+
+    fn prompts_list(
+        self: Arc<Self>,
+        p: ListPromptsRequestParams,
+        cx: RequestContextAs<ListPromptsResult>,
+        _data: Arc<SessionData>,
+    ) -> Result<Response> {
+        self.dispatch("prompts/list", &p, cx)
+    }
+
+    fn prompts_get(
+        self: Arc<Self>,
+        p: GetPromptRequestParams,
+        cx: RequestContextAs<GetPromptResult>,
+        _data: Arc<SessionData>,
+    ) -> Result<Response> {
+        self.dispatch("prompts/get", &p, cx)
+    }
+
+    fn resources_list(
+        self: Arc<Self>,
+        p: ListResourcesRequestParams,
+        cx: RequestContextAs<ListResourcesResult>,
+        _data: Arc<SessionData>,
+    ) -> Result<Response> {
+        self.dispatch("resources/list", &p, cx)
+    }
+
+    fn resources_read(
+        self: Arc<Self>,
+        p: ReadResourceRequestParams,
+        cx: RequestContextAs<ReadResourceResult>,
+        _data: Arc<SessionData>,
+    ) -> Result<Response> {
+        self.dispatch("resources/read", &p, cx)
+    }
+
+    fn resources_templates_list(
+        self: Arc<Self>,
+        p: ListResourceTemplatesRequestParams,
+        cx: RequestContextAs<ListResourceTemplatesResult>,
+        _data: Arc<SessionData>,
+    ) -> Result<Response> {
+        self.dispatch("resources/templates/list", &p, cx)
+    }
+
+    fn tools_list(
+        self: Arc<Self>,
+        p: ListToolsRequestParams,
+        cx: RequestContextAs<ListToolsResult>,
+        _data: Arc<SessionData>,
+    ) -> Result<Response> {
+        self.dispatch("tools/list", &p, cx)
+    }
+
+    fn tools_call(
+        self: Arc<Self>,
+        p: CallToolRequestParams,
+        cx: RequestContextAs<CallToolResult>,
+        _data: Arc<SessionData>,
+    ) -> Result<Response> {
+        self.dispatch("tools/call", &p, cx)
+    }
+
+    fn completion_complete(
+        self: Arc<Self>,
+        p: CompleteRequestParams,
+        cx: RequestContextAs<CompleteResult>,
+        _data: Arc<SessionData>,
+    ) -> Result<Response> {
+        self.dispatch("completion/complete", &p, cx)
+    }
+
+    fn resources_subscribe(
+        self: Arc<Self>,
+        p: SubscribeRequestParams,
+        cx: RequestContextAs<Empty>,
+        _data: Arc<SessionData>,
+    ) -> Result<Response> {
+        self.dispatch("resources/subscribe", &p, cx)
+    }
+
+    fn resources_unsubscribe(
+        self: Arc<Self>,
+        p: UnsubscribeRequestParams,
+        cx: RequestContextAs<Empty>,
+        _data: Arc<SessionData>,
+    ) -> Result<Response> {
+        self.dispatch("resources/unsubscribe", &p, cx)
+    }
+}
+
+/// Returns true if `actual` contains everything in `expected`
+fn json_contains(actual: &Value, expected: &Value) -> bool {
+    match (actual, expected) {
+        (Value::Object(actual), Value::Object(expected)) => expected
+            .iter()
+            .all(|(k, v)| actual.get(k).is_some_and(|a| json_contains(a, v))),
+        _ => actual == expected,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_json_contains() {
+        let actual = json!({ "name": "echo", "arguments": { "text": "hi", "n": 1 } });
+        assert!(json_contains(&actual, &json!({ "name": "echo" })));
+        assert!(json_contains(
+            &actual,
+            &json!({ "arguments": { "text": "hi" } })
+        ));
+        assert!(!json_contains(
+            &actual,
+            &json!({ "arguments": { "text": "bye" } })
+        ));
+        assert!(!json_contains(&actual, &json!({ "missing": null })));
+    }
+
+    #[test]
+    fn test_times() {
+        assert!(Times::Exactly(2).is_exhausted(2));
+        assert!(!Times::AtLeast(1).is_exhausted(10));
+        assert!(Times::AtMost(1).is_satisfied(0));
+        assert!(!Times::Exactly(1).is_satisfied(0));
+    }
+
+    #[test]
+    fn test_unmet_expectation_fails_verification() {
+        let server = MockServer::new();
+        server.expect(Expectation::tool_call("echo"));
+        let err = server.try_verify().unwrap_err();
+        assert!(err.contains("tools/call"));
+        // Satisfy the expectation so the drop-time verification passes
+        server.state().expectations[0].calls = 1;
+    }
+}
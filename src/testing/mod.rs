@@ -0,0 +1,16 @@
+//! Utilities for testing MCP clients and servers
+//!
+//! This module provides [`MockServer`], a scriptable [`Server`](crate::server::Server)
+//! implementation for exercising client code without writing a hand-rolled fixture
+//! for every test. Because it implements `Server`, it can be driven in-process with
+//! [`Client::with_server`](crate::client::Client::with_server) or served over any
+//! transport with [`serve_transport`](crate::server::serve_transport).
+//!
+//! [`ConformanceSuite`] checks any server implementation, in-process or spawned as a
+//! command, against the lifecycle rules of the protocol and `spec/schema.json`.
+
+pub mod conformance;
+pub mod mock_server;
+
+pub use conformance::{CheckOutcome, CheckResult, ConformanceReport, ConformanceSuite};
+pub use mock_server::{ClientResponse, Expectation, MockServer, ReceivedRequest, Times};
@@ -0,0 +1,130 @@
+//! Adapter that lets a JSON-RPC [`Session`](jsoncall::Session) run over any [`Transport`]
+//!
+//! `jsoncall` sessions speak newline-delimited JSON over a byte stream. The bridge
+//! creates an in-process pipe for the session and pumps messages between that pipe
+//! and the transport in both directions.
+
+use std::sync::Arc;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, ReadHalf, WriteHalf};
+use tokio::sync::Mutex;
+use tracing::{debug, error, warn};
+
+use super::{
+    JsonRpcError, JsonRpcMessage, JsonRpcResponse, JsonRpcVersion, Message, Transport,
+    TransportErrorCode,
+};
+
+/// JSON-RPC error code used to answer requests the transport refused to send
+const INVALID_REQUEST: i32 = -32600;
+
+/// Size of the in-process pipe buffer between the session and the transport
+const PIPE_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Byte stream halves to be handed to [`Session::new`](jsoncall::Session::new)
+pub(crate) type SessionIo = (
+    BufReader<ReadHalf<tokio::io::DuplexStream>>,
+    WriteHalf<tokio::io::DuplexStream>,
+);
+
+/// Connects `transport` to a byte stream suitable for a JSON-RPC session
+///
+/// Messages received from the transport are written to the session, and lines written
+/// by the session are parsed and sent through the transport. When the transport
+/// reports end of stream the session sees EOF; when the session closes its writer the
+/// transport is closed.
+///
+/// Requests the transport refuses with [`TransportErrorCode::InvalidMessage`] are
+/// answered with an error response so the caller does not wait forever.
+pub(crate) fn session_io(transport: impl Transport) -> SessionIo {
+    let transport = Arc::new(transport);
+    let (session_side, bridge_side) = tokio::io::duplex(PIPE_BUFFER_SIZE);
+    let (session_reader, session_writer) = tokio::io::split(session_side);
+    let (bridge_reader, bridge_writer) = tokio::io::split(bridge_side);
+    let bridge_writer = Arc::new(Mutex::new(bridge_writer));
+
+    tokio::spawn(pump_incoming(transport.clone(), bridge_writer.clone()));
+    tokio::spawn(pump_outgoing(transport, bridge_reader, bridge_writer));
+
+    (BufReader::new(session_reader), session_writer)
+}
+
+type SessionWriter = Arc<Mutex<WriteHalf<tokio::io::DuplexStream>>>;
+
+/// Writes a message to the session; returns false once the session input is closed
+async fn write_to_session(writer: &SessionWriter, message: &Message) -> bool {
+    let mut line = match serde_json::to_string(message) {
+        Ok(line) => line,
+        Err(e) => {
+            error!("Failed to serialize message: {}", e);
+            return true;
+        }
+    };
+    line.push('\n');
+    writer.lock().await.write_all(line.as_bytes()).await.is_ok()
+}
+
+async fn pump_incoming(transport: Arc<impl Transport>, writer: SessionWriter) {
+    loop {
+        let message = match transport.receive().await {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(e) => {
+                error!("Transport receive failed: {}", e);
+                break;
+            }
+        };
+        if !write_to_session(&writer, &message).await {
+            break;
+        }
+    }
+    debug!("Transport closed, ending session input");
+    let _ = writer.lock().await.shutdown().await;
+}
+
+async fn pump_outgoing(
+    transport: Arc<impl Transport>,
+    reader: ReadHalf<tokio::io::DuplexStream>,
+    writer: SessionWriter,
+) {
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let message: Message = match serde_json::from_str(&line) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("Dropping message that cannot be sent over transport: {}", e);
+                continue;
+            }
+        };
+        match transport.send(&message).await {
+            Ok(()) => {}
+            Err(e) if e.code() == Some(TransportErrorCode::InvalidMessage) => {
+                warn!("Transport refused message: {}", e);
+                if let JsonRpcMessage::Request(request) = message {
+                    let response = JsonRpcMessage::Response(JsonRpcResponse {
+                        id: request.id,
+                        result: None,
+                        error: Some(JsonRpcError {
+                            code: INVALID_REQUEST,
+                            message: e.to_string(),
+                            data: None,
+                        }),
+                        jsonrpc: JsonRpcVersion::default(),
+                    });
+                    write_to_session(&writer, &response).await;
+                }
+            }
+            Err(e) => {
+                error!("Transport send failed: {}", e);
+                break;
+            }
+        }
+    }
+    debug!("Session output closed, closing transport");
+    if let Err(e) = transport.close().await {
+        debug!("Error closing transport: {}", e);
+    }
+}
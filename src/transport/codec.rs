@@ -0,0 +1,368 @@
+//! Message codecs
+//!
+//! A [`Codec`] turns a [`Message`] into bytes and back. Transports default to
+//! [`JsonCodec`]; the binary [`MessagePackCodec`] (feature `msgpack`) and [`CborCodec`]
+//! (feature `cbor`) cut the size of blob-heavy messages by carrying base64 payloads,
+//! such as `BlobResourceContents::blob` and image or audio `data`, as raw bytes.
+//! Decoding turns those bytes back into base64 strings, so the [`Message`] seen by the
+//! rest of the crate is identical whichever codec carried it.
+//!
+//! Stream and WebSocket transports accept a codec; the HTTP and SSE transports stay
+//! JSON-only, as their content types require.
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use crate::transport::{Message, Result, TransportError, TransportErrorCode};
+
+/// Serialization format for messages on the wire
+pub trait Codec: Debug + Send + Sync + 'static {
+    /// Short name of the format, e.g. `json`
+    fn name(&self) -> &'static str;
+
+    /// Whether encoded messages are binary rather than UTF-8 text
+    fn is_binary(&self) -> bool;
+
+    /// WebSocket subprotocol that selects this codec
+    fn subprotocol(&self) -> &'static str;
+
+    /// Encodes a message
+    ///
+    /// # Errors
+    /// - `TransportErrorCode::MessageSendFailed` if the message cannot be serialized
+    fn encode(&self, message: &Message) -> Result<Vec<u8>>;
+
+    /// Decodes a message
+    ///
+    /// # Errors
+    /// - `TransportErrorCode::InvalidMessage` if the bytes are not a valid message
+    fn decode(&self, bytes: &[u8]) -> Result<Message>;
+}
+
+/// Returns the default codec, [`JsonCodec`]
+pub fn default_codec() -> Arc<dyn Codec> {
+    Arc::new(JsonCodec)
+}
+
+/// Returns the codec selected by a WebSocket subprotocol, if it is supported
+pub fn codec_for_subprotocol(subprotocol: &str) -> Option<Arc<dyn Codec>> {
+    match subprotocol {
+        JSON_SUBPROTOCOL => Some(Arc::new(JsonCodec)),
+        #[cfg(feature = "msgpack")]
+        MSGPACK_SUBPROTOCOL => Some(Arc::new(MessagePackCodec)),
+        #[cfg(feature = "cbor")]
+        CBOR_SUBPROTOCOL => Some(Arc::new(CborCodec)),
+        _ => None,
+    }
+}
+
+const JSON_SUBPROTOCOL: &str = "mcp";
+#[cfg(feature = "msgpack")]
+const MSGPACK_SUBPROTOCOL: &str = "mcp.msgpack";
+#[cfg(feature = "cbor")]
+const CBOR_SUBPROTOCOL: &str = "mcp.cbor";
+
+fn encode_error(e: impl std::fmt::Display) -> TransportError {
+    TransportError::new(
+        TransportErrorCode::MessageSendFailed,
+        format!("Failed to serialize message: {}", e)
+    )
+}
+
+fn decode_error(e: impl std::fmt::Display) -> TransportError {
+    TransportError::new(
+        TransportErrorCode::InvalidMessage,
+        format!("Failed to parse message: {}", e)
+    )
+}
+
+/// JSON text, the format defined by the MCP specification
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+impl Codec for JsonCodec {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn is_binary(&self) -> bool {
+        false
+    }
+
+    fn subprotocol(&self) -> &'static str {
+        JSON_SUBPROTOCOL
+    }
+
+    fn encode(&self, message: &Message) -> Result<Vec<u8>> {
+        serde_json::to_vec(message).map_err(encode_error)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Message> {
+        serde_json::from_slice(bytes).map_err(decode_error)
+    }
+}
+
+/// MessagePack, with base64 payloads carried as `bin`
+#[cfg(feature = "msgpack")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessagePackCodec;
+
+#[cfg(feature = "msgpack")]
+impl Codec for MessagePackCodec {
+    fn name(&self) -> &'static str {
+        "msgpack"
+    }
+
+    fn is_binary(&self) -> bool {
+        true
+    }
+
+    fn subprotocol(&self) -> &'static str {
+        MSGPACK_SUBPROTOCOL
+    }
+
+    fn encode(&self, message: &Message) -> Result<Vec<u8>> {
+        let value = serde_json::to_value(message).map_err(encode_error)?;
+        rmp_serde::to_vec(&binary::Wire(&value)).map_err(encode_error)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Message> {
+        let binary::WireValue(value) = rmp_serde::from_slice(bytes).map_err(decode_error)?;
+        serde_json::from_value(value).map_err(decode_error)
+    }
+}
+
+/// CBOR, with base64 payloads carried as byte strings
+#[cfg(feature = "cbor")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CborCodec;
+
+#[cfg(feature = "cbor")]
+impl Codec for CborCodec {
+    fn name(&self) -> &'static str {
+        "cbor"
+    }
+
+    fn is_binary(&self) -> bool {
+        true
+    }
+
+    fn subprotocol(&self) -> &'static str {
+        CBOR_SUBPROTOCOL
+    }
+
+    fn encode(&self, message: &Message) -> Result<Vec<u8>> {
+        let value = serde_json::to_value(message).map_err(encode_error)?;
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&binary::Wire(&value), &mut bytes).map_err(encode_error)?;
+        Ok(bytes)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Message> {
+        let binary::WireValue(value) = ciborium::from_reader(bytes).map_err(decode_error)?;
+        serde_json::from_value(value).map_err(decode_error)
+    }
+}
+
+/// Mapping between JSON values and binary formats with a native byte type
+#[cfg(any(feature = "msgpack", feature = "cbor"))]
+mod binary {
+    use std::fmt;
+
+    use base64::Engine;
+    use base64::prelude::BASE64_STANDARD;
+    use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+    use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+    use serde_json::{Map, Number, Value};
+
+    /// Serializes a value, writing known base64 fields as bytes
+    pub(super) struct Wire<'a>(pub &'a Value);
+
+    impl Serialize for Wire<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            match self.0 {
+                Value::Array(items) => {
+                    let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                    for item in items {
+                        seq.serialize_element(&Wire(item))?;
+                    }
+                    seq.end()
+                }
+                Value::Object(object) => {
+                    let mut map = serializer.serialize_map(Some(object.len()))?;
+                    for (key, value) in object {
+                        match base64_field(object, key, value) {
+                            Some(bytes) => map.serialize_entry(key, &Bytes(&bytes))?,
+                            None => map.serialize_entry(key, &Wire(value))?,
+                        }
+                    }
+                    map.end()
+                }
+                value => value.serialize(serializer),
+            }
+        }
+    }
+
+    struct Bytes<'a>(&'a [u8]);
+
+    impl Serialize for Bytes<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(self.0)
+        }
+    }
+
+    /// Decodes `value` if `key` holds base64 data in a blob resource or media content
+    ///
+    /// Only strings that re-encode to the same text are converted, so decoding
+    /// reproduces the original message exactly.
+    fn base64_field(object: &Map<String, Value>, key: &str, value: &Value) -> Option<Vec<u8>> {
+        let is_payload = match key {
+            "blob" => object.contains_key("uri"),
+            "data" => object.contains_key("mimeType")
+                && matches!(object.get("type").and_then(Value::as_str), Some("image" | "audio")),
+            _ => false,
+        };
+        let text = value.as_str().filter(|_| is_payload)?;
+        let bytes = BASE64_STANDARD.decode(text).ok()?;
+        (BASE64_STANDARD.encode(&bytes) == text).then_some(bytes)
+    }
+
+    /// Deserializes a value, reading byte strings as base64 text
+    pub(super) struct WireValue(pub Value);
+
+    impl<'de> Deserialize<'de> for WireValue {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_any(WireVisitor).map(WireValue)
+        }
+    }
+
+    struct WireVisitor;
+
+    impl<'de> Visitor<'de> for WireVisitor {
+        type Value = Value;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a JSON-compatible value")
+        }
+
+        fn visit_unit<E>(self) -> Result<Value, E> {
+            Ok(Value::Null)
+        }
+
+        fn visit_none<E>(self) -> Result<Value, E> {
+            Ok(Value::Null)
+        }
+
+        fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+            deserializer.deserialize_any(self)
+        }
+
+        fn visit_bool<E>(self, v: bool) -> Result<Value, E> {
+            Ok(Value::Bool(v))
+        }
+
+        fn visit_i64<E>(self, v: i64) -> Result<Value, E> {
+            Ok(Value::Number(v.into()))
+        }
+
+        fn visit_u64<E>(self, v: u64) -> Result<Value, E> {
+            Ok(Value::Number(v.into()))
+        }
+
+        fn visit_f64<E: de::Error>(self, v: f64) -> Result<Value, E> {
+            Number::from_f64(v)
+                .map(Value::Number)
+                .ok_or_else(|| E::custom("non-finite number"))
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Value, E> {
+            Ok(Value::String(v.to_string()))
+        }
+
+        fn visit_string<E>(self, v: String) -> Result<Value, E> {
+            Ok(Value::String(v))
+        }
+
+        fn visit_bytes<E>(self, v: &[u8]) -> Result<Value, E> {
+            Ok(Value::String(BASE64_STANDARD.encode(v)))
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+            let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+            while let Some(WireValue(item)) = seq.next_element()? {
+                items.push(item);
+            }
+            Ok(Value::Array(items))
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+            let mut object = Map::new();
+            while let Some((key, WireValue(value))) = map.next_entry::<String, WireValue>()? {
+                object.insert(key, value);
+            }
+            Ok(Value::Object(object))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{JsonRpcMessage, JsonRpcResponse, JsonRpcVersion};
+    use serde_json::json;
+
+    fn blob_response() -> Message {
+        JsonRpcMessage::Response(JsonRpcResponse {
+            id: 7,
+            result: Some(json!({
+                "contents": [{ "uri": "file:///a.bin", "blob": "AAECAwQFBgcICQ==" }],
+                "content": [{ "type": "image", "mimeType": "image/png", "data": "iVBORw0KGgo=" }],
+                "note": "AAECAwQFBgcICQ==",
+            })),
+            error: None,
+            jsonrpc: JsonRpcVersion::default(),
+        })
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let message = blob_response();
+        let bytes = JsonCodec.encode(&message).unwrap();
+        assert_eq!(JsonCodec.decode(&bytes).unwrap(), message);
+    }
+
+    #[test]
+    fn test_invalid_input_is_invalid_message() {
+        let error = JsonCodec.decode(b"not json").unwrap_err();
+        assert_eq!(error.code(), Some(TransportErrorCode::InvalidMessage));
+    }
+
+    #[test]
+    fn test_default_subprotocol() {
+        assert_eq!(codec_for_subprotocol("mcp").unwrap().name(), "json");
+        assert!(codec_for_subprotocol("mcp.unknown").is_none());
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_msgpack_round_trip_carries_raw_bytes() {
+        let message = blob_response();
+        let bytes = MessagePackCodec.encode(&message).unwrap();
+        assert_eq!(MessagePackCodec.decode(&bytes).unwrap(), message);
+
+        // The blob travels as bin 8 with its 10 raw bytes, the unrelated field as text
+        assert!(bytes.windows(12).any(|w| w == [0xc4, 10, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9]));
+        assert!(bytes.windows(16).any(|w| w == b"AAECAwQFBgcICQ=="));
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_cbor_round_trip_carries_raw_bytes() {
+        let message = blob_response();
+        let bytes = CborCodec.encode(&message).unwrap();
+        assert_eq!(CborCodec.decode(&bytes).unwrap(), message);
+
+        // Byte string of length 10 followed by the raw bytes
+        assert!(bytes.windows(11).any(|w| w == [0x4a, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9]));
+    }
+}
@@ -152,7 +152,7 @@ pub enum TransportError {
 
     #[error("WebSocket error: {0}")]
     /// WebSocket error
-    WebSocket(#[from] tokio_tungstenite::tungstenite::Error),
+    WebSocket(Box<tokio_tungstenite::tungstenite::Error>),
 
     #[error("HTTP error: {0}")]
     /// HTTP error
@@ -175,6 +175,12 @@ pub enum TransportError {
     Jwt(#[from] jsonwebtoken::errors::Error),
 }
 
+impl From<tokio_tungstenite::tungstenite::Error> for TransportError {
+    fn from(err: tokio_tungstenite::tungstenite::Error) -> Self {
+        Self::WebSocket(Box::new(err))
+    }
+}
+
 impl<T> From<tokio::sync::mpsc::error::SendError<T>> for TransportError {
     fn from(err: tokio::sync::mpsc::error::SendError<T>) -> Self {
         Self::Channel(err.to_string())
@@ -257,7 +263,7 @@ mod tests {
         let error = TransportError::new(TransportErrorCode::ConnectionFailed, "Failed to connect");
         assert_eq!(error.to_string(), "Failed to establish connection: Failed to connect");
 
-        let io_error = std::io::Error::new(std::io::ErrorKind::Other, "IO error");
+        let io_error = std::io::Error::other("IO error");
         let error = TransportError::with_source(
             TransportErrorCode::ConnectionFailed,
             "Failed to connect",
@@ -271,7 +277,7 @@ mod tests {
         let error = TransportError::new(TransportErrorCode::ConnectionFailed, "Failed to connect");
         assert_eq!(error.code(), Some(TransportErrorCode::ConnectionFailed));
 
-        let io_error = std::io::Error::new(std::io::ErrorKind::Other, "JSON error");
+        let io_error = std::io::Error::other("JSON error");
         let error = TransportError::Json(serde_json::Error::io(io_error));
         assert_eq!(error.code(), None);
     }
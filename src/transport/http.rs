@@ -25,7 +25,7 @@ pub enum ClientHttpTransport {
     /// WebSocket transport
     Ws(ClientWsTransport),
     /// HTTP/2 transport
-    Http2(super::http2::ClientHttp2Transport),
+    Http2(Box<super::http2::ClientHttp2Transport>),
 }
 
 #[async_trait]
@@ -243,6 +243,6 @@ impl Http2Builder {
             self.config.tls_config
         );
 
-        ClientHttpTransport::Http2(transport)
+        ClientHttpTransport::Http2(Box::new(transport))
     }
 }
\ No newline at end of file
@@ -1,38 +1,55 @@
 //! HTTP/2 transport implementation for the Model Context Protocol
 //! This module provides a transport layer for HTTP/2-based communication.
 
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::sync::{Arc, OnceLock};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::net::SocketAddr;
 use std::fs::File;
 use std::io::BufReader;
 #[cfg(feature = "acme")]
 use std::path::PathBuf;
-#[cfg(feature = "acme")]
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
 use bytes::Bytes;
-use http_body_util::{BodyExt, Full};
-use hyper::body::Incoming;
+use futures::future::BoxFuture;
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Full, StreamBody};
+use hyper::body::{Frame, Incoming};
 use hyper::server::conn::http2;
 use hyper::{Method, Request, Response, StatusCode};
-use hyper_util::rt::TokioIo;
+use hyper_rustls::{FixedServerNameResolver, HttpsConnector, HttpsConnectorBuilder};
+use hyper_util::client::legacy::Client as HttpClient;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use tokio_rustls::rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use tokio_rustls::rustls::crypto::{CryptoProvider, verify_tls12_signature, verify_tls13_signature};
+use tokio_rustls::rustls::{ClientConfig as RustlsClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
 use tokio_rustls::rustls::ServerConfig as RustlsServerConfig;
-use tokio_rustls::rustls::pki_types::PrivateKeyDer;
-use rustls_pemfile::{certs, pkcs8_private_keys};
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpListener;
-use tokio::sync::{mpsc, Mutex, broadcast};
+use tokio::sync::{oneshot, watch};
+use tokio::task::JoinSet;
 use tokio_rustls::TlsAcceptor;
-use tracing::{debug, error, info};
-
-// TLS support is implemented using hyper-rustls
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
 
 #[cfg(feature = "acme")]
 use rustls_acme;
 
-use crate::transport::{Message, Result, Transport, TransportError, TransportErrorCode};
+use crate::server::{Server, serve_transport};
+use crate::transport::monitoring::{self, Outcome, Probe, ServerMetrics};
+use crate::transport::origin;
+use crate::transport::shutdown::{self, CLOSE_TIMEOUT};
+use crate::transport::{
+    JsonRpcError, JsonRpcMessage, JsonRpcResponse, JsonRpcVersion, Message, MessageQueue, MonitoringConfig, OriginPolicy,
+    QueueConfig, ReloadingCertResolver, RequestId, Result, ShutdownConfig, Transport, TransportError, TransportErrorCode,
+    accept_backoff,
+};
 
 /// TLS configuration for HTTP/2 client
 #[derive(Debug, Clone)]
@@ -43,34 +60,59 @@ pub enum ClientTlsConfig {
     Default,
     /// Custom TLS configuration with specific root certificates
     Custom {
-        /// Path to the root certificate file
+        /// Path to the root certificate file; empty to use the system roots
         root_cert_path: String,
         /// Whether to verify the server certificate
+        ///
+        /// Disabling verification accepts any certificate and is only meant for
+        /// development against self-signed servers.
         verify_server: bool,
         /// Path to the client certificate file (for mutual TLS)
         client_cert_path: Option<String>,
         /// Path to the client key file (for mutual TLS)
         client_key_path: Option<String>,
         /// Server name for SNI (Server Name Indication)
+        ///
+        /// Also the name the server certificate is verified against, in place of the
+        /// host in the URL.
         server_name: Option<String>,
     },
 }
 
+/// HTTP/2 client over a hyper-rustls connector
+type Http2Client = HttpClient<HttpsConnector<HttpConnector>, Full<Bytes>>;
+
 /// Client-side HTTP/2 transport
+///
+/// Messages are POSTed to `{url}/message`; a response in the body is queued for
+/// `receive`. Requests are POSTed in the background, so `send` returns without waiting
+/// for the server to answer, and a request that fails is answered with an error
+/// response in the queue. While open, the transport also holds a streaming GET on `{url}/events`
+/// and queues every JSON-RPC message the server pushes as a server-sent event. All
+/// requests share one pooled HTTP/2 connection, and carry the session ID the server
+/// last returned in its [`SESSION_ID_HEADER`].
 #[derive(Debug, Clone)]
 pub struct ClientHttp2Transport {
     /// URL to connect to
     url: url::Url,
     /// Headers to include in requests
-    headers: std::collections::HashMap<String, String>,
+    headers: HashMap<String, String>,
     /// Flag to track if the transport is open
     is_open: Arc<AtomicBool>,
-    /// Channel for receiving messages
-    rx: Arc<Mutex<Option<broadcast::Receiver<Message>>>>,
-    /// Channel for sending messages
-    tx: Arc<Mutex<Option<broadcast::Sender<Message>>>>,
+    /// Messages waiting for `receive`; created on `open`
+    incoming: Arc<std::sync::Mutex<Option<MessageQueue>>>,
+    /// Size and overflow policy of the receive queue
+    receive_queue: QueueConfig,
     /// TLS configuration
     tls_config: ClientTlsConfig,
+    /// Client built from `tls_config`; created on `open`
+    client: Arc<std::sync::Mutex<Option<Http2Client>>>,
+    /// Task reading the event stream
+    events_task: Arc<std::sync::Mutex<Option<tokio::task::JoinHandle<()>>>>,
+    /// Requests waiting for the server's response
+    requests: Arc<std::sync::Mutex<JoinSet<()>>>,
+    /// Session ID assigned by the server
+    session_id: Arc<std::sync::Mutex<Option<String>>>,
 }
 
 impl ClientHttp2Transport {
@@ -80,14 +122,23 @@ impl ClientHttp2Transport {
     /// * `url` - URL to connect to
     /// * `headers` - Headers to include in requests
     /// * `tls_config` - TLS configuration
-    pub fn new(url: url::Url, headers: std::collections::HashMap<String, String>, tls_config: ClientTlsConfig) -> Self {
+    ///
+    /// The URL's scheme must match `tls_config`: `https` with TLS and `http` with
+    /// `ClientTlsConfig::None`. Otherwise `open` fails with
+    /// `TransportErrorCode::ConfigurationError`, so that an `https` URL is never
+    /// silently reached over plaintext.
+    pub fn new(url: url::Url, headers: HashMap<String, String>, tls_config: ClientTlsConfig) -> Self {
         Self {
             url,
             headers,
             is_open: Arc::new(AtomicBool::new(false)),
-            rx: Arc::new(Mutex::new(None)),
-            tx: Arc::new(Mutex::new(None)),
+            incoming: Arc::new(std::sync::Mutex::new(None)),
+            receive_queue: QueueConfig::default(),
             tls_config,
+            client: Arc::new(std::sync::Mutex::new(None)),
+            events_task: Arc::new(std::sync::Mutex::new(None)),
+            requests: Arc::new(std::sync::Mutex::new(JoinSet::new())),
+            session_id: Arc::new(std::sync::Mutex::new(None)),
         }
     }
 
@@ -97,7 +148,7 @@ impl ClientHttp2Transport {
     /// * `url` - URL to connect to
     /// * `headers` - Headers to include in requests
     /// * `use_tls` - Whether to use TLS
-    pub fn new_with_tls_flag(url: url::Url, headers: std::collections::HashMap<String, String>, use_tls: bool) -> Self {
+    pub fn new_with_tls_flag(url: url::Url, headers: HashMap<String, String>, use_tls: bool) -> Self {
         let tls_config = if use_tls {
             ClientTlsConfig::Default
         } else {
@@ -106,6 +157,12 @@ impl ClientHttp2Transport {
         Self::new(url, headers, tls_config)
     }
 
+    /// Sets the size and overflow policy of the queue of received messages
+    pub fn with_receive_queue(mut self, config: QueueConfig) -> Self {
+        self.receive_queue = config;
+        self
+    }
+
     /// Checks if the transport is open
     pub fn is_open(&self) -> bool {
         self.is_open.load(Ordering::Relaxed)
@@ -120,6 +177,188 @@ impl ClientHttp2Transport {
     pub fn use_tls(&self) -> bool {
         !matches!(self.tls_config, ClientTlsConfig::None)
     }
+
+    /// Returns a request builder for an endpoint under the transport's URL, with the
+    /// custom headers applied
+    fn request(&self, method: Method, endpoint: &str) -> hyper::http::request::Builder {
+        build_request(&self.url, &self.headers, &self.session_id, method, endpoint)
+    }
+
+    /// Returns the session ID the server assigned, once it has responded
+    pub fn session_id(&self) -> Option<String> {
+        self.session_id.lock().unwrap().clone()
+    }
+}
+
+/// Returns a request builder for an endpoint under `url`, with `headers` and the
+/// session ID applied
+fn build_request(
+    url: &url::Url,
+    headers: &HashMap<String, String>,
+    session_id: &std::sync::Mutex<Option<String>>,
+    method: Method,
+    endpoint: &str,
+) -> hyper::http::request::Builder {
+    let mut request = Request::builder()
+        .method(method)
+        .uri(endpoint_uri(url, endpoint));
+    if let Some(session_id) = session_id.lock().unwrap().as_deref() {
+        request = request.header(SESSION_ID_HEADER, session_id);
+    }
+    headers.iter().fold(request, |req, (key, value)| {
+        req.header(key, value)
+    })
+}
+
+/// Remembers the session ID a response carries
+fn update_session_id<B>(session_id: &std::sync::Mutex<Option<String>>, response: &Response<B>) {
+    if let Some(id) = response.headers().get(SESSION_ID_HEADER).and_then(|id| id.to_str().ok()) {
+        *session_id.lock().unwrap() = Some(id.to_string());
+    }
+}
+
+/// Server-to-client half of [`ClientHttp2Transport`]
+struct EventStream {
+    /// URL the `events` endpoint is resolved against
+    url: url::Url,
+    /// Headers to include in the request
+    headers: HashMap<String, String>,
+    /// Session ID shared with the transport
+    session_id: Arc<std::sync::Mutex<Option<String>>>,
+    /// Open flag of the transport; gone once every clone of it is dropped
+    open: std::sync::Weak<AtomicBool>,
+}
+
+impl EventStream {
+    /// Keeps the event stream open until the transport closes, reconnecting after
+    /// [`EVENTS_RETRY_DELAY`] whenever the server ends it
+    async fn run(&self, client: Http2Client, queue: MessageQueue) {
+        loop {
+            match self.read(&client, &queue).await {
+                Ok(true) => debug!("HTTP/2 event stream ended"),
+                Ok(false) => return,
+                Err(e) => warn!("HTTP/2 event stream failed: {}", e),
+            }
+            tokio::time::sleep(EVENTS_RETRY_DELAY).await;
+            let open = self.open.upgrade().is_some_and(|open| open.load(Ordering::Relaxed));
+            if !open || queue.is_closed() {
+                return;
+            }
+        }
+    }
+
+    /// Reads one event stream, returning whether it is worth reconnecting
+    async fn read(&self, client: &Http2Client, queue: &MessageQueue) -> Result<bool> {
+        let request = build_request(&self.url, &self.headers, &self.session_id, Method::GET, "events")
+            .header("accept", "text/event-stream")
+            .body(Full::new(Bytes::new()))
+            .map_err(|e| TransportError::new(
+                TransportErrorCode::ConnectionFailed,
+                format!("Failed to build event stream request: {}", e)
+            ))?;
+
+        let response = client.request(request).await.map_err(|e| TransportError::new(
+            TransportErrorCode::ConnectionFailed,
+            format!("Failed to open event stream: {}", e)
+        ))?;
+
+        update_session_id(&self.session_id, &response);
+        let status = response.status();
+        if status == StatusCode::NOT_FOUND || status == StatusCode::METHOD_NOT_ALLOWED {
+            debug!("Server has no HTTP/2 event stream ({})", status);
+            return Ok(false);
+        }
+        if !status.is_success() {
+            return Err(TransportError::new(
+                TransportErrorCode::ConnectionFailed,
+                format!("Event stream request failed with status {}", status)
+            ));
+        }
+
+        debug!("HTTP/2 event stream open");
+        let mut body = response.into_body();
+        let mut parser = EventParser::default();
+        while let Some(frame) = body.frame().await {
+            let frame = frame.map_err(|e| TransportError::new(
+                TransportErrorCode::ConnectionFailed,
+                format!("Failed to read event stream: {}", e)
+            ))?;
+            let Some(chunk) = frame.data_ref() else {
+                continue;
+            };
+            for data in parser.feed(chunk) {
+                let message = match serde_json::from_str::<Message>(&data) {
+                    Ok(message) => message,
+                    Err(_) => {
+                        debug!("Ignoring non-JSON-RPC event: {}", data);
+                        continue;
+                    }
+                };
+                if let Err(e) = queue.push(message).await {
+                    if queue.is_closed() {
+                        return Ok(false);
+                    }
+                    error!("Failed to queue HTTP/2 event: {}", e);
+                }
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// Delay before reopening an event stream the server has ended
+const EVENTS_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Incremental parser for a `text/event-stream` body
+///
+/// Only `data` fields matter to the transport; comments, event names and IDs are
+/// skipped.
+#[derive(Debug, Default)]
+struct EventParser {
+    /// Bytes of the current, incomplete line
+    line: Vec<u8>,
+    /// `data` lines of the current event
+    data: Vec<String>,
+}
+
+impl EventParser {
+    /// Consumes a chunk of the stream, returning the data of each event it completes
+    fn feed(&mut self, chunk: &[u8]) -> Vec<String> {
+        let mut events = Vec::new();
+        for &byte in chunk {
+            if byte != b'\n' {
+                self.line.push(byte);
+                continue;
+            }
+            if self.line.last() == Some(&b'\r') {
+                self.line.pop();
+            }
+            let line = String::from_utf8_lossy(&self.line).into_owned();
+            self.line.clear();
+
+            if line.is_empty() {
+                if !self.data.is_empty() {
+                    events.push(self.data.join("\n"));
+                    self.data.clear();
+                }
+            } else if let Some(data) = line.strip_prefix("data:") {
+                self.data.push(data.strip_prefix(' ').unwrap_or(data).to_string());
+            }
+        }
+        events
+    }
+}
+
+/// Resolves an endpoint against a base URL, keeping its scheme, port, path and query
+///
+/// `https://host:8443/mcp` and `https://host:8443/mcp/` both resolve `message` to
+/// `https://host:8443/mcp/message`.
+fn endpoint_uri(base: &url::Url, endpoint: &str) -> String {
+    let mut url = base.clone();
+    let path = format!("{}/{}", url.path().trim_end_matches('/'), endpoint);
+    url.set_path(&path);
+    url.to_string()
 }
 
 #[async_trait]
@@ -141,97 +380,47 @@ impl Transport for ClientHttp2Transport {
 
         debug!("Sending HTTP/2 message");
 
-        // Create the HTTP request
-        let scheme = if self.use_tls() { "https" } else { "http" };
-        let uri = format!("{}://{}/message", scheme, self.url.host_str().unwrap_or("localhost"));
-
-        let request = Request::builder()
-            .method("POST")
-            .uri(uri)
-            .header("content-type", "application/json");
-
-        // Add custom headers
-        let request = self.headers.iter().fold(request, |req, (key, value)| {
-            req.header(key, value)
-        });
+        let Some(client) = self.client.lock().unwrap().clone() else {
+            return Err(TransportError::new(
+                TransportErrorCode::ConnectionClosed,
+                "HTTP/2 transport is not open".to_string(),
+            ));
+        };
 
         // Build the request with the JSON body
-        let request = request
+        let request = self.request(Method::POST, "message")
+            .header("content-type", "application/json")
             .body(Full::new(Bytes::from(json)))
             .map_err(|e| TransportError::new(
                 TransportErrorCode::MessageSendFailed,
                 format!("Failed to build request: {}", e)
             ))?;
-
-        // Log TLS configuration
-        match &self.tls_config {
-            ClientTlsConfig::None => {
-                debug!("TLS is disabled");
-            },
-            ClientTlsConfig::Default => {
-                debug!("TLS is enabled with system root certificates");
-            },
-            ClientTlsConfig::Custom {
-                root_cert_path,
-                verify_server,
-                client_cert_path,
-                client_key_path,
-                server_name
-            } => {
-                debug!("TLS is enabled with custom root certificate: {}", root_cert_path);
-                if !verify_server {
-                    debug!("Server certificate verification is disabled");
-                }
-                if let Some(client_cert) = client_cert_path {
-                    debug!("Client certificate is provided: {}", client_cert);
-                    if let Some(client_key) = client_key_path {
-                        debug!("Client key is provided: {}", client_key);
-                    } else {
-                        error!("Client certificate is provided but client key is missing");
-                    }
-                }
-                if let Some(sni) = server_name {
-                    debug!("SNI is enabled with server name: {}", sni);
-                }
+        let queue = self.incoming.lock().unwrap().clone();
+
+        // Notifications and responses are acknowledged right away, so they are sent
+        // in order. Requests may take as long as the server needs to answer them and
+        // must not hold up anything sent after them.
+        let JsonRpcMessage::Request(outgoing) = message else {
+            if let Some(response) = post_message(&client, request, &self.session_id).await? {
+                queue_message(queue.as_ref(), response).await;
             }
-        }
+            return Ok(());
+        };
 
-        // Create the HTTP client
-        // For now, we'll use the HTTP connector for all requests
-        // In a future update, we'll implement a proper type-erased client that can handle both HTTP and HTTPS
-        debug!("Using HTTP connector for all requests");
-
-        // Create the client with HTTP connector
-        let client = hyper_util::client::legacy::Client::builder(hyper_util::rt::TokioExecutor::new())
-            .http2_only(true)
-            .build_http();
-
-        match client.request(request).await {
-            Ok(response) => {
-                if response.status().is_success() {
-                    debug!("HTTP/2 message sent successfully");
-                    Ok(())
-                } else {
-                    let status = response.status();
-                    let _body = response.collect().await
-                        .map(|b| String::from_utf8_lossy(b.to_bytes().as_ref()).to_string())
-                        .unwrap_or_else(|_| "Failed to read response body".to_string());
-
-                    error!("HTTP/2 request failed with status {}", status);
-                    Err(TransportError::new(
-                        TransportErrorCode::MessageSendFailed,
-                        format!("HTTP/2 request failed with status {}", status)
-                    ))
-                }
-            },
-            Err(e) => {
-                error!("HTTP/2 request failed");
-                Err(TransportError::new(
-                    TransportErrorCode::MessageSendFailed,
-                    format!("HTTP/2 request failed: {}", e)
-                ))
-            }
-        }
+        let id = outgoing.id;
+        let session_id = self.session_id.clone();
+        let mut requests = self.requests.lock().unwrap();
+        while requests.try_join_next().is_some() {}
+        requests.spawn(async move {
+            let response = match post_message(&client, request, &session_id).await {
+                Ok(Some(response)) => response,
+                Ok(None) => internal_error(id, "HTTP/2 server returned no response"),
+                Err(e) => internal_error(id, &e.to_string()),
+            };
+            queue_message(queue.as_ref(), response).await;
+        });
+
+        Ok(())
     }
 
     async fn receive(&self) -> Result<Option<Message>> {
@@ -242,41 +431,20 @@ impl Transport for ClientHttp2Transport {
             ));
         }
 
-        // Check if we have a valid receiver
-        let mut rx_guard = self.rx.lock().await;
-        if let Some(rx) = rx_guard.as_mut() {
-            match rx.recv().await {
-                Ok(message) => {
-                    debug!("HTTP/2 received message");
-                    Ok(Some(message))
-                },
-                Err(broadcast::error::RecvError::Closed) => {
-                    debug!("HTTP/2 channel closed");
-                    // Channel is closed, clear our reference to it
-                    *rx_guard = None;
-                    self.set_open(false);
-                    Ok(None)
-                },
-                Err(broadcast::error::RecvError::Lagged(n)) => {
-                    // We lagged behind, log a warning but continue
-                    debug!("HTTP/2 channel lagged behind by {} messages", n);
-                    // Try to receive again with a new subscription
-                    if let Some(tx) = self.tx.lock().await.as_ref() {
-                        *rx_guard = Some(tx.subscribe());
-                        // Return None this time, the next call will get a message
-                        Ok(None)
-                    } else {
-                        // No sender available, channel is effectively closed
-                        *rx_guard = None;
-                        self.set_open(false);
-                        Ok(None)
-                    }
-                }
+        let Some(queue) = self.incoming.lock().unwrap().clone() else {
+            debug!("HTTP/2 receive called but no queue is available");
+            return Ok(None);
+        };
+        match queue.pop().await? {
+            Some(message) => {
+                debug!("HTTP/2 received message");
+                Ok(Some(message))
+            }
+            None => {
+                debug!("HTTP/2 receive queue closed");
+                self.set_open(false);
+                Ok(None)
             }
-        } else {
-            // No receiver available
-            debug!("HTTP/2 receive called but no receiver is available");
-            Ok(None)
         }
     }
 
@@ -288,12 +456,27 @@ impl Transport for ClientHttp2Transport {
 
         debug!("Opening HTTP/2 transport");
 
-        // Create channels for message passing
-        let (tx, rx) = broadcast::channel(1000);
-
-        // Store the sender and receiver
-        *self.tx.lock().await = Some(tx);
-        *self.rx.lock().await = Some(rx);
+        // Build the client up front so a bad TLS configuration fails here rather
+        // than on the first send
+        check_scheme(&self.url, &self.tls_config)?;
+        let client = build_http2_client(&self.tls_config)?;
+        *self.client.lock().unwrap() = Some(client.clone());
+
+        // Create the queue for incoming messages
+        let queue = MessageQueue::new(self.receive_queue);
+        *self.incoming.lock().unwrap() = Some(queue.clone());
+
+        // Listen for server-initiated messages until the transport is closed or dropped
+        *self.session_id.lock().unwrap() = None;
+        let events = EventStream {
+            url: self.url.clone(),
+            headers: self.headers.clone(),
+            session_id: self.session_id.clone(),
+            open: Arc::downgrade(&self.is_open),
+        };
+        *self.events_task.lock().unwrap() = Some(tokio::spawn(async move {
+            events.run(client, queue).await;
+        }));
 
         // Mark the transport as open
         self.set_open(true);
@@ -309,9 +492,18 @@ impl Transport for ClientHttp2Transport {
 
         debug!("Closing HTTP/2 transport");
 
-        // Clear the channels
-        *self.tx.lock().await = None;
-        *self.rx.lock().await = None;
+        // Close the queue, waking any pending `receive`
+        if let Some(queue) = self.incoming.lock().unwrap().take() {
+            queue.close();
+        }
+
+        // Stop the event stream and outstanding requests, and drop the client with
+        // its pooled connection
+        if let Some(task) = self.events_task.lock().unwrap().take() {
+            task.abort();
+        }
+        self.requests.lock().unwrap().abort_all();
+        self.client.lock().unwrap().take();
 
         // Mark the transport as closed
         self.set_open(false);
@@ -320,13 +512,254 @@ impl Transport for ClientHttp2Transport {
     }
 }
 
+/// POSTs a message and returns the message in the response body, if any
+async fn post_message(
+    client: &Http2Client,
+    request: Request<Full<Bytes>>,
+    session_id: &std::sync::Mutex<Option<String>>,
+) -> Result<Option<Message>> {
+    let response = client.request(request).await.map_err(|e| {
+        error!("HTTP/2 request failed");
+        TransportError::new(
+            TransportErrorCode::MessageSendFailed,
+            format!("HTTP/2 request failed: {}", e)
+        )
+    })?;
+
+    update_session_id(session_id, &response);
+    let status = response.status();
+    let body = response.into_body().collect().await
+        .map_err(|e| TransportError::new(
+            TransportErrorCode::MessageSendFailed,
+            format!("Failed to read HTTP/2 response body: {}", e)
+        ))?
+        .to_bytes();
+
+    if !status.is_success() {
+        error!("HTTP/2 request failed with status {}", status);
+        return Err(TransportError::new(
+            TransportErrorCode::MessageSendFailed,
+            format!("HTTP/2 request failed with status {}: {}", status, String::from_utf8_lossy(&body))
+        ));
+    }
+
+    debug!("HTTP/2 message sent successfully");
+
+    // Notifications and responses are acknowledged with an empty body; requests
+    // get their response in it
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(None);
+    }
+    serde_json::from_slice::<Message>(&body)
+        .map(Some)
+        .map_err(|e| TransportError::new(
+            TransportErrorCode::InvalidMessage,
+            format!("Failed to parse HTTP/2 response: {}", e)
+        ))
+}
+
+/// Queues a message for `receive`, unless the transport has been closed
+async fn queue_message(queue: Option<&MessageQueue>, message: Message) {
+    if let Some(queue) = queue {
+        // The queue reports overflow to `receive`; the message itself was delivered
+        if let Err(e) = queue.push(message).await {
+            error!("Failed to queue HTTP/2 response: {}", e);
+        }
+    }
+}
+
+/// Checks that `url` is `https` exactly when `tls_config` enables TLS
+fn check_scheme(url: &url::Url, tls_config: &ClientTlsConfig) -> Result<()> {
+    let use_tls = !matches!(tls_config, ClientTlsConfig::None);
+    match (url.scheme(), use_tls) {
+        ("https", true) | ("http", false) => Ok(()),
+        (scheme, _) => Err(TransportError::new(
+            TransportErrorCode::ConfigurationError,
+            format!(
+                "URL scheme `{}` does not match the TLS configuration, which {} TLS",
+                scheme,
+                if use_tls { "enables" } else { "disables" }
+            ),
+        )),
+    }
+}
+
+/// Builds the HTTP/2 client for a TLS configuration
+///
+/// `ClientTlsConfig::None` still gets a TLS-capable connector; it is simply never used
+/// for `http` URIs.
+fn build_http2_client(tls_config: &ClientTlsConfig) -> Result<Http2Client> {
+    let builder = HttpsConnectorBuilder::new()
+        .with_tls_config(load_client_tls_config(tls_config)?)
+        .https_or_http();
+
+    let builder = match tls_config {
+        ClientTlsConfig::Custom { server_name: Some(name), .. } => {
+            debug!("SNI is enabled with server name: {}", name);
+            let name = ServerName::try_from(name.clone()).map_err(|e| {
+                TransportError::new(
+                    TransportErrorCode::ConfigurationError,
+                    format!("Invalid TLS server name {:?}: {}", name, e),
+                )
+            })?;
+            builder.with_server_name_resolver(FixedServerNameResolver::new(name))
+        }
+        _ => builder,
+    };
+
+    let connector = builder.enable_http2().build();
+    Ok(HttpClient::builder(TokioExecutor::new())
+        .http2_only(true)
+        .build(connector))
+}
+
+/// Builds the rustls client configuration for a TLS configuration
+///
+/// # Arguments
+/// * `tls_config` - TLS configuration
+///
+/// # Returns
+/// A result containing the client configuration
+fn load_client_tls_config(tls_config: &ClientTlsConfig) -> Result<RustlsClientConfig> {
+    let builder = RustlsClientConfig::builder();
+
+    let (root_cert_path, verify_server, client_cert_path, client_key_path) = match tls_config {
+        ClientTlsConfig::None => {
+            return Ok(builder
+                .with_root_certificates(RootCertStore::empty())
+                .with_no_client_auth());
+        }
+        ClientTlsConfig::Default => (None, true, None, None),
+        ClientTlsConfig::Custom {
+            root_cert_path,
+            verify_server,
+            client_cert_path,
+            client_key_path,
+            ..
+        } => (
+            Some(root_cert_path.as_str()).filter(|path| !path.is_empty()),
+            *verify_server,
+            client_cert_path.as_deref(),
+            client_key_path.as_deref(),
+        ),
+    };
+
+    let builder = if verify_server {
+        let roots = match root_cert_path {
+            Some(path) => {
+                debug!("TLS is enabled with custom root certificate: {}", path);
+                load_root_cert(path)?
+            }
+            None => {
+                debug!("TLS is enabled with system root certificates");
+                load_system_roots()?
+            }
+        };
+        builder.with_root_certificates(roots)
+    } else {
+        warn!("Server certificate verification is disabled");
+        let provider = builder.crypto_provider().clone();
+        builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(NoServerVerification(provider)))
+    };
+
+    match (client_cert_path, client_key_path) {
+        (Some(cert_path), Some(key_path)) => {
+            debug!("Client certificate is provided: {}", cert_path);
+            let (certs, key) = load_cert_and_key(cert_path, key_path)?;
+            builder.with_client_auth_cert(certs, key).map_err(|e| {
+                TransportError::new(
+                    TransportErrorCode::ConfigurationError,
+                    format!("Invalid client certificate or key: {}", e),
+                )
+            })
+        }
+        (None, None) => Ok(builder.with_no_client_auth()),
+        _ => Err(TransportError::new(
+            TransportErrorCode::ConfigurationError,
+            "Mutual TLS needs both a client certificate and a client key".to_string(),
+        )),
+    }
+}
+
+/// Loads the platform's trusted root certificates
+fn load_system_roots() -> Result<RootCertStore> {
+    let native = rustls_native_certs::load_native_certs();
+    for e in &native.errors {
+        warn!("Failed to load a system root certificate: {}", e);
+    }
+
+    let mut root_store = RootCertStore::empty();
+    let (added, _) = root_store.add_parsable_certificates(native.certs);
+    if added == 0 {
+        return Err(TransportError::new(
+            TransportErrorCode::ConfigurationError,
+            "No system root certificates found".to_string(),
+        ));
+    }
+
+    Ok(root_store)
+}
+
+/// Server certificate verifier for `verify_server: false`
+///
+/// Accepts any certificate but still checks handshake signatures, so the connection
+/// is encrypted, just not authenticated.
+#[derive(Debug)]
+struct NoServerVerification(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for NoServerVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
 /// Server-side HTTP/2 transport
+///
+/// One MCP session of an HTTP/2 server. Messages POSTed by the client are received
+/// from it; sending a response to a request that is still waiting on its POST
+/// completes that POST, and everything else goes out on the session's event stream
+/// (`GET /events`).
 #[derive(Debug, Clone)]
 pub struct ServerHttp2Transport {
-    /// Channel for sending messages
-    tx: Arc<Mutex<Option<mpsc::Sender<Message>>>>,
-    /// Channel for receiving messages
-    rx: Arc<Mutex<Option<broadcast::Receiver<Message>>>>,
+    /// Session ID, sent to the client in the `Mcp-Session-Id` header
+    id: Arc<str>,
+    /// Messages from the client waiting for `receive`
+    incoming: MessageQueue,
+    /// Messages waiting for the event stream
+    outgoing: MessageQueue,
+    /// POSTed requests waiting for their response, by request ID
+    pending: Arc<std::sync::Mutex<HashMap<RequestId, oneshot::Sender<Message>>>>,
     /// Flag to track if the transport is open
     is_open: Arc<AtomicBool>,
 }
@@ -338,24 +771,28 @@ impl Default for ServerHttp2Transport {
 }
 
 impl ServerHttp2Transport {
-    /// Creates a new HTTP/2 server transport
+    /// Creates a new HTTP/2 server transport for a fresh session
     pub fn new() -> Self {
-        Self {
-            tx: Arc::new(Mutex::new(None)),
-            rx: Arc::new(Mutex::new(None)),
-            is_open: Arc::new(AtomicBool::new(false)),
-        }
+        Self::with_queue(QueueConfig::default())
     }
 
-    /// Creates a new HTTP/2 server transport with the given channels
-    pub fn with_channels(tx: mpsc::Sender<Message>, rx: broadcast::Receiver<Message>) -> Self {
+    /// Creates a new HTTP/2 server transport whose incoming and outgoing queues use
+    /// `config`
+    pub fn with_queue(config: QueueConfig) -> Self {
         Self {
-            tx: Arc::new(Mutex::new(Some(tx))),
-            rx: Arc::new(Mutex::new(Some(rx))),
+            id: Uuid::new_v4().to_string().into(),
+            incoming: MessageQueue::new(config),
+            outgoing: MessageQueue::new(config),
+            pending: Arc::new(std::sync::Mutex::new(HashMap::new())),
             is_open: Arc::new(AtomicBool::new(true)),
         }
     }
 
+    /// Returns the session ID
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
     /// Checks if the transport is open
     pub fn is_open(&self) -> bool {
         self.is_open.load(Ordering::Relaxed)
@@ -375,20 +812,60 @@ impl ServerHttp2Transport {
             ));
         }
 
-        if let Some(tx) = self.tx.lock().await.as_ref() {
-            tx.send(message).await.map_err(|e| {
-                self.set_open(false);
-                TransportError::new(
-                    TransportErrorCode::MessageSendFailed,
-                    format!("Failed to send message: {}", e),
-                )
-            })?;
-            Ok(())
-        } else {
-            Err(TransportError::new(
-                TransportErrorCode::MessageSendFailed,
-                "No sender available".to_string(),
-            ))
+        if let JsonRpcMessage::Response(response) = &message {
+            let waiting = self.pending.lock().unwrap().remove(&response.id);
+            if let Some(waiting) = waiting {
+                debug!("Answering HTTP/2 request {} in its POST response", response.id);
+                // The POST is gone if the client disconnected; nothing left to answer
+                let _ = waiting.send(message);
+                return Ok(());
+            }
+        }
+
+        self.outgoing.push(message).await
+    }
+
+    /// Hands a message POSTed by the client to the session
+    ///
+    /// For a request, returns the receiver its response will be delivered to.
+    async fn deliver(&self, message: Message) -> Result<Option<oneshot::Receiver<Message>>> {
+        let waiting = match &message {
+            JsonRpcMessage::Request(request) => {
+                let (tx, rx) = oneshot::channel();
+                let mut pending = self.pending.lock().unwrap();
+                if pending.contains_key(&request.id) {
+                    return Err(TransportError::new(
+                        TransportErrorCode::InvalidMessage,
+                        format!("Request {} is already in progress", request.id),
+                    ));
+                }
+                pending.insert(request.id, tx);
+                Some(rx)
+            }
+            _ => None,
+        };
+
+        if let Err(e) = self.incoming.push(message.clone()).await {
+            if let JsonRpcMessage::Request(request) = &message {
+                self.pending.lock().unwrap().remove(&request.id);
+            }
+            return Err(e);
+        }
+        Ok(waiting)
+    }
+
+    /// Returns whether requests are waiting for their response
+    fn has_pending(&self) -> bool {
+        !self.pending.lock().unwrap().is_empty()
+    }
+
+    /// Answers the POSTs of requests still waiting for a response with an error, so
+    /// the client's calls fail instead of hanging
+    fn cancel_pending(&self) {
+        let pending: Vec<_> = self.pending.lock().unwrap().drain().collect();
+        for (id, waiting) in pending {
+            debug!("Cancelling HTTP/2 request {} of session {}", id, self.id);
+            let _ = waiting.send(shutdown::refused(id));
         }
     }
 }
@@ -407,54 +884,21 @@ impl Transport for ServerHttp2Transport {
             ));
         }
 
-        // Check if we have a valid receiver
-        let mut rx_guard = self.rx.lock().await;
-        if let Some(rx) = rx_guard.as_mut() {
-            match rx.recv().await {
-                Ok(message) => {
-                    debug!("HTTP/2 server received message");
-                    Ok(Some(message))
-                },
-                Err(broadcast::error::RecvError::Closed) => {
-                    debug!("HTTP/2 server channel closed");
-                    // Channel is closed, clear our reference to it
-                    *rx_guard = None;
-                    self.set_open(false);
-                    Ok(None)
-                },
-                Err(broadcast::error::RecvError::Lagged(n)) => {
-                    // We lagged behind, log a warning but continue
-                    debug!("HTTP/2 server channel lagged behind by {} messages", n);
-                    // Return None this time, the next call will get a message
-                    Ok(None)
-                }
+        match self.incoming.pop().await? {
+            Some(message) => {
+                debug!("HTTP/2 server received message");
+                Ok(Some(message))
+            }
+            None => {
+                debug!("HTTP/2 server receive queue closed");
+                self.set_open(false);
+                Ok(None)
             }
-        } else {
-            // No receiver available
-            debug!("HTTP/2 server receive called but no receiver is available");
-            Ok(None)
         }
     }
 
     async fn open(&self) -> Result<()> {
-        if self.is_open() {
-            debug!("HTTP/2 server transport already open");
-            return Ok(());
-        }
-
-        debug!("Opening HTTP/2 server transport");
-
-        // Create channels for message passing
-        let (tx, _) = mpsc::channel(100);
-        let (_broadcast_tx, rx) = broadcast::channel(1000);
-
-        // Store the sender and receiver
-        *self.tx.lock().await = Some(tx);
-        *self.rx.lock().await = Some(rx);
-
-        // Mark the transport as open
-        self.set_open(true);
-
+        // Sessions are open from the moment the server creates them
         Ok(())
     }
 
@@ -464,11 +908,13 @@ impl Transport for ServerHttp2Transport {
             return Ok(());
         }
 
-        debug!("Closing HTTP/2 server transport");
+        debug!("Closing HTTP/2 session {}", self.id);
 
-        // Clear the channels
-        *self.tx.lock().await = None;
-        *self.rx.lock().await = None;
+        // Close the queues, waking any pending `receive` and ending the event stream;
+        // POSTs still waiting for a response fail
+        self.incoming.close();
+        self.outgoing.close();
+        self.pending.lock().unwrap().clear();
 
         // Mark the transport as closed
         self.set_open(false);
@@ -480,13 +926,15 @@ impl Transport for ServerHttp2Transport {
 /// CORS configuration for HTTP/2 server
 #[derive(Debug, Clone)]
 pub struct CorsConfig {
-    /// Allowed origins (comma-separated list or * for all)
+    /// Allowed origins (comma-separated list or * for all), as `scheme://host[:port]`;
+    /// a host of `*.example.com` matches its subdomains. `*` is answered with a literal
+    /// `*` and never with credentials.
     pub allowed_origins: String,
-    /// Allowed methods (comma-separated list or * for all)
+    /// Allowed methods (comma-separated list or * for all requested ones)
     pub allowed_methods: String,
-    /// Allowed headers (comma-separated list or * for all)
+    /// Allowed headers (comma-separated list or * for all requested ones)
     pub allowed_headers: String,
-    /// Whether to allow credentials
+    /// Whether to allow credentials for the origins listed explicitly
     pub allow_credentials: bool,
     /// Maximum age for preflight requests in seconds
     pub max_age: Option<u32>,
@@ -500,13 +948,29 @@ impl Default for CorsConfig {
             allowed_origins: "*".to_string(),
             allowed_methods: "GET, POST, OPTIONS".to_string(),
             allowed_headers: "*".to_string(),
-            allow_credentials: true,
+            allow_credentials: false,
             max_age: Some(86400), // 24 hours
-            exposed_headers: None,
+            exposed_headers: Some(SESSION_ID_HEADER.to_string()),
         }
     }
 }
 
+impl CorsConfig {
+    /// Returns the `Access-Control-Allow-Origin` value for a request from `origin`, or
+    /// `None` if the origin is not allowed
+    ///
+    /// Allowed origins are echoed back, as a list is not a valid value. If every origin
+    /// is allowed the answer is a literal `*`, which browsers refuse to combine with
+    /// credentials, so that arbitrary sites cannot make credentialed reads.
+    fn allow_origin<'a>(&self, origin: &'a str) -> Option<&'a str> {
+        let allowed = self.allowed_origins.split(',').map(str::trim);
+        if allowed.clone().any(|allowed| allowed == "*") {
+            return Some("*");
+        }
+        origin::origin_allowed(allowed, origin).then_some(origin)
+    }
+}
+
 /// HTTP/2 server configuration
 #[derive(Debug, Clone)]
 pub struct Http2ServerConfig {
@@ -516,6 +980,15 @@ pub struct Http2ServerConfig {
     pub tls_config: Option<TlsConfig>,
     /// CORS configuration
     pub cors_config: Option<CorsConfig>,
+    /// Origins and hosts requests are accepted from and for, against DNS rebinding;
+    /// others get `403 Forbidden` without a session being created. The default only
+    /// allows local ones; `None` accepts every request.
+    pub origin_policy: Option<OriginPolicy>,
+    /// Size and overflow policy of each session's incoming and outgoing queues
+    pub receive_queue: QueueConfig,
+    /// Authentication of the `/healthz`, `/readyz` and `/metrics` endpoints, which are
+    /// served without the origin check; `None`, the default, disables them
+    pub monitoring: Option<MonitoringConfig>,
 }
 
 impl Default for Http2ServerConfig {
@@ -524,6 +997,9 @@ impl Default for Http2ServerConfig {
             addr: SocketAddr::from(([127, 0, 0, 1], 8080)),
             tls_config: None,
             cors_config: Some(CorsConfig::default()),
+            origin_policy: Some(OriginPolicy::default()),
+            receive_queue: QueueConfig::default(),
+            monitoring: None,
         }
     }
 }
@@ -555,21 +1031,148 @@ pub enum TlsConfig {
     },
 }
 
-/// Starts an HTTP/2 server
+/// Header carrying the session ID of HTTP/2 requests and responses
+pub const SESSION_ID_HEADER: &str = "Mcp-Session-Id";
+
+/// JSON-RPC error code for internal errors
+const INTERNAL_ERROR: i32 = -32603;
+
+/// Transport label of HTTP/2 sessions in metrics
+const TRANSPORT_LABEL: &str = "http2";
+
+/// Response body of the HTTP/2 server
+type Body = BoxBody<Bytes, Infallible>;
+
+/// Runs one session of an HTTP/2 server until its transport closes
+type SessionRunner = Arc<dyn Fn(ServerHttp2Transport) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// Open sessions of an HTTP/2 server, by ID
+type Sessions = Arc<std::sync::Mutex<HashMap<String, ServerHttp2Transport>>>;
+
+/// Lifecycle of an HTTP/2 server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    /// Accepting connections and requests
+    Running,
+    /// Shutting down: no new connections, and new requests are refused
+    Draining,
+    /// Sessions are closed; connections are sent GOAWAY
+    Closing,
+}
+
+/// Waits until the server's phase satisfies `reached`; never completes if the server is gone
+async fn wait_for_phase(phase: &mut watch::Receiver<Phase>, reached: impl Fn(Phase) -> bool) {
+    if phase.wait_for(|phase| reached(*phase)).await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+/// State shared by the connections of an HTTP/2 server
+struct ServerState {
+    sessions: Sessions,
+    run_session: SessionRunner,
+    cors_config: Option<CorsConfig>,
+    origin_policy: Option<OriginPolicy>,
+    receive_queue: QueueConfig,
+    phase: Arc<watch::Sender<Phase>>,
+    monitoring: Option<MonitoringConfig>,
+    metrics: Arc<ServerMetrics>,
+}
+
+/// The session of a connection, started by the first request it serves
+struct ConnectionSession {
+    session: OnceLock<ServerHttp2Transport>,
+    /// Hands the session to the connection's runner once started
+    started: std::sync::Mutex<Option<oneshot::Sender<ServerHttp2Transport>>>,
+}
+
+impl ConnectionSession {
+    /// Returns the connection's session, starting it if needed
+    fn get_or_start(&self, state: &ServerState) -> ServerHttp2Transport {
+        self.session
+            .get_or_init(|| {
+                let session = ServerHttp2Transport::with_queue(state.receive_queue);
+                state.sessions.lock().unwrap().insert(session.id().to_string(), session.clone());
+                debug!("HTTP/2 session {} started", session.id());
+                if let Some(started) = self.started.lock().unwrap().take() {
+                    let _ = started.send(session.clone());
+                }
+                session
+            })
+            .clone()
+    }
+}
+
+/// Starts an HTTP/2 server that answers messages with an async handler
+///
+/// Every connection is its own session, identified to the client by the
+/// [`SESSION_ID_HEADER`] of each response; a request carrying that header is routed to
+/// the session it names, whichever connection it arrives on. The handler is called
+/// concurrently for each message the client POSTs to `/message`. What it returns for a
+/// request is the body of that request's POST; anything else it returns, and anything
+/// sent through the session's transport (see [`ServerHandle::sessions`]), is streamed
+/// to `GET /events`. A request the handler fails, or returns nothing for, is answered
+/// with a JSON-RPC internal error.
 ///
 /// # Arguments
 /// * `config` - Server configuration
-/// * `callback` - Callback function to handle incoming messages
+/// * `handler` - Async function handling each incoming message
 ///
 /// # Returns
 /// A result containing the server handle
-pub async fn start_http2_server<F>(
+///
+/// # Errors
+/// - `TransportErrorCode::ConfigurationError` if the TLS configuration cannot be loaded
+/// - `TransportErrorCode::ConnectionFailed` if the address cannot be bound
+pub async fn start_http2_server<F, Fut>(
     config: Http2ServerConfig,
-    callback: F,
+    handler: F,
 ) -> Result<ServerHandle>
 where
-    F: Fn(Message) -> Result<Message> + Send + Sync + 'static,
+    F: Fn(Message) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Option<Message>>> + Send + 'static,
+{
+    let handler = Arc::new(handler);
+    start_server(config, Arc::new(move |transport| {
+        Box::pin(run_handler(handler.clone(), transport))
+    })).await
+}
+
+/// Starts an HTTP/2 server running an MCP [`Server`] for each session
+///
+/// `build_server` is called once per connection, so every session has its own
+/// `ServerHandler` state. Sessions are routed as for [`start_http2_server`].
+///
+/// # Errors
+/// - `TransportErrorCode::ConfigurationError` if the TLS configuration cannot be loaded
+/// - `TransportErrorCode::ConnectionFailed` if the address cannot be bound
+pub async fn serve_http2<F, S>(config: Http2ServerConfig, build_server: F) -> Result<ServerHandle>
+where
+    F: Fn() -> S + Send + Sync + 'static,
+    S: Server,
 {
+    start_server(config, Arc::new(move |transport| {
+        let server = build_server();
+        Box::pin(async move {
+            let id = transport.id().to_string();
+            if let Err(e) = serve_transport(server, transport).await {
+                debug!("HTTP/2 session {} ended with error: {}", id, e);
+            }
+        })
+    })).await
+}
+
+/// Binds the listener and accepts connections, each with its own session
+async fn start_server(config: Http2ServerConfig, run_session: SessionRunner) -> Result<ServerHandle> {
+    // Load TLS before binding so configuration errors surface immediately
+    let acceptor = match &config.tls_config {
+        Some(tls_config) => {
+            let server_config = load_rustls_server_config(tls_config).await?;
+            Some(TlsAcceptor::from(Arc::new(server_config)))
+        }
+        None => None,
+    };
+
     // Create a TCP listener
     let listener = TcpListener::bind(&config.addr).await.map_err(|e| {
         TransportError::new(
@@ -577,241 +1180,286 @@ where
             format!("Failed to bind to address: {}", e),
         )
     })?;
+    let local_addr = listener.local_addr().map_err(|e| {
+        TransportError::new(
+            TransportErrorCode::ConnectionFailed,
+            format!("Failed to read local address: {}", e),
+        )
+    })?;
 
-    info!("HTTP/2 server listening on {}", config.addr);
-
-    // Create a channel for incoming messages
-    let (tx, mut rx) = mpsc::channel::<Message>(100);
-    let (broadcast_tx, _) = broadcast::channel::<Message>(1000);
-
-    // Create a transport
-    let transport = ServerHttp2Transport::with_channels(tx, broadcast_tx.subscribe());
-
-    // Create a callback wrapper
-    let callback = Arc::new(callback);
-
-    // Clone these for the server task
-    let server_callback = callback.clone();
-    let server_broadcast_tx = broadcast_tx.clone();
-
-    // Clone the listener for the server task
-    let server_listener = listener;
-    let server_config = config;
-    let cors_config = server_config.cors_config.clone();
-
-    // Start the server task
-    let server_task = tokio::spawn(async move {
-        // Process incoming connections
-        if let Some(tls_config) = &server_config.tls_config {
-            // Load TLS configuration
-            let tls_config_result = match load_tls_config(tls_config).await {
-                Ok(config) => config,
-                Err(e) => {
-                    error!("Failed to load TLS configuration: {}", e);
-                    return;
-                }
-            };
-
-            match tls_config_result {
-                TlsConfigResult::Manual(config) => {
-                    // Create TLS acceptor for manual configuration
-                    let tls_acceptor = TlsAcceptor::from(Arc::new(config));
-
-                    // Accept TLS connections
-                    while let Ok((stream, addr)) = server_listener.accept().await {
-                        info!("Accepted connection from {}", addr);
-
-                        // Accept TLS connection
-                        let acceptor = tls_acceptor.clone();
-                        let tls_stream = match acceptor.accept(stream).await {
-                            Ok(stream) => stream,
-                            Err(e) => {
-                                error!("Failed to accept TLS connection: {}", e);
-                                continue;
-                            }
-                        };
-
-                        // Clone these for each connection to avoid ownership issues
-                        let connection_callback = server_callback.clone();
-                        let connection_broadcast_tx = server_broadcast_tx.clone();
-                        let connection_cors_config = cors_config.clone();
-
-                        tokio::spawn(async move {
-                            if let Err(e) = handle_http2_connection(
-                                tls_stream,
-                                connection_callback,
-                                connection_broadcast_tx,
-                                connection_cors_config,
-                            ).await {
-                                error!("HTTP/2 connection error: {}", e);
-                            }
-                        });
-                    }
-                },
-                #[cfg(feature = "acme")]
-                TlsConfigResult::Acme(server_config) => {
-                    // Create TLS acceptor for ACME configuration
-                    let tls_acceptor = TlsAcceptor::from(Arc::new(server_config));
+    info!("HTTP/2 server listening on {}", local_addr);
+
+    let sessions = Sessions::default();
+    let phase = Arc::new(watch::Sender::new(Phase::Running));
+    let state = Arc::new(ServerState {
+        sessions: sessions.clone(),
+        run_session,
+        cors_config: config.cors_config,
+        origin_policy: config.origin_policy,
+        receive_queue: config.receive_queue,
+        phase: phase.clone(),
+        monitoring: config.monitoring,
+        metrics: Arc::new(ServerMetrics::default()),
+    });
 
-                    // Accept TLS connections
-                    while let Ok((stream, addr)) = server_listener.accept().await {
+    // Start the server task; aborting it drops every connection and session. Once
+    // shutdown begins it stops accepting, and ends when the connections have.
+    let server_task = tokio::spawn(async move {
+        let mut connections = JoinSet::new();
+        let mut phase = state.phase.subscribe();
+        loop {
+            tokio::select! {
+                _ = wait_for_phase(&mut phase, |phase| phase != Phase::Running) => break,
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, addr)) => {
                         info!("Accepted connection from {}", addr);
-
-                        // Accept TLS connection
-                        let acceptor = tls_acceptor.clone();
-                        let tls_stream = match acceptor.accept(stream).await {
-                            Ok(stream) => stream,
-                            Err(e) => {
-                                error!("Failed to accept TLS connection: {}", e);
-                                continue;
-                            }
-                        };
-
-                        // Clone these for each connection to avoid ownership issues
-                        let connection_callback = server_callback.clone();
-                        let connection_broadcast_tx = server_broadcast_tx.clone();
-                        let connection_cors_config = cors_config.clone();
-
-                        tokio::spawn(async move {
-                            if let Err(e) = handle_http2_connection(
-                                tls_stream,
-                                connection_callback,
-                                connection_broadcast_tx,
-                                connection_cors_config,
-                            ).await {
+                        let acceptor = acceptor.clone();
+                        let state = state.clone();
+                        connections.spawn(async move {
+                            let result = match acceptor {
+                                Some(acceptor) => match acceptor.accept(stream).await {
+                                    Ok(stream) => handle_http2_connection(stream, state).await,
+                                    Err(e) => {
+                                        error!("Failed to accept TLS connection: {}", e);
+                                        return;
+                                    }
+                                },
+                                None => handle_http2_connection(stream, state).await,
+                            };
+                            if let Err(e) = result {
                                 error!("HTTP/2 connection error: {}", e);
                             }
                         });
                     }
-                }
-            }
-        } else {
-            // Accept plain TCP connections
-            while let Ok((stream, addr)) = server_listener.accept().await {
-                info!("Accepted connection from {}", addr);
-
-                // Clone these for each connection to avoid ownership issues
-                let connection_callback = server_callback.clone();
-                let connection_broadcast_tx = server_broadcast_tx.clone();
-                let connection_cors_config = cors_config.clone();
-
-                tokio::spawn(async move {
-                    if let Err(e) = handle_http2_connection(
-                        stream,
-                        connection_callback,
-                        connection_broadcast_tx,
-                        connection_cors_config,
-                    ).await {
-                        error!("HTTP/2 connection error: {}", e);
-                    }
-                });
-            }
-        }
-    });
-
-    // Clone these for the message processing task
-    let message_callback = callback.clone();
-    let message_broadcast_tx = broadcast_tx.clone();
-
-    // Start the message processing task
-    let message_task = tokio::spawn(async move {
-        while let Some(message) = rx.recv().await {
-            debug!("Received message from client");
-
-            // Process the message using the callback
-            match message_callback(message) {
-                Ok(response) => {
-                    debug!("Sending response to client");
-                    if message_broadcast_tx.send(response).is_err() {
-                        error!("Failed to send response (no receivers)");
+                    Err(e) => {
+                        error!("Failed to accept HTTP/2 connection: {}", e);
+                        accept_backoff().await;
                     }
                 },
-                Err(e) => {
-                    error!("Failed to process message: {}", e);
-                }
+                Some(_) = connections.join_next(), if !connections.is_empty() => {}
             }
         }
+
+        drop(listener);
+        info!("HTTP/2 server on {} stopped accepting connections", local_addr);
+        while connections.join_next().await.is_some() {}
     });
 
     // Return the server handle
     Ok(ServerHandle {
-        transport,
+        local_addr,
+        sessions,
+        phase,
         server_task,
-        message_task,
+    })
+}
+
+/// Feeds a session's messages to `handler`, sending back what it returns
+async fn run_handler<F, Fut>(handler: Arc<F>, transport: ServerHttp2Transport)
+where
+    F: Fn(Message) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Option<Message>>> + Send + 'static,
+{
+    let mut calls = JoinSet::new();
+    loop {
+        tokio::select! {
+            message = transport.receive() => {
+                let Ok(Some(message)) = message else {
+                    break;
+                };
+                let handler = handler.clone();
+                let transport = transport.clone();
+                calls.spawn(async move {
+                    let request_id = match &message {
+                        JsonRpcMessage::Request(request) => Some(request.id),
+                        _ => None,
+                    };
+                    let response = match (handler(message).await, request_id) {
+                        (Ok(Some(response)), _) => response,
+                        (Ok(None), None) => return,
+                        (Ok(None), Some(id)) => {
+                            error!("HTTP/2 handler returned no response to request {}", id);
+                            internal_error(id, "No response")
+                        }
+                        (Err(e), Some(id)) => {
+                            error!("Failed to process message: {}", e);
+                            internal_error(id, &e.to_string())
+                        }
+                        (Err(e), None) => {
+                            error!("Failed to process message: {}", e);
+                            return;
+                        }
+                    };
+                    if let Err(e) = transport.send(&response).await {
+                        debug!("Failed to send HTTP/2 response: {}", e);
+                    }
+                });
+            }
+            Some(_) = calls.join_next(), if !calls.is_empty() => {}
+        }
+    }
+}
+
+/// Returns a JSON-RPC internal error response to request `id`
+fn internal_error(id: RequestId, message: &str) -> Message {
+    JsonRpcMessage::Response(JsonRpcResponse {
+        id,
+        result: None,
+        error: Some(JsonRpcError {
+            code: INTERNAL_ERROR,
+            message: message.to_string(),
+            data: None,
+        }),
+        jsonrpc: JsonRpcVersion::default(),
     })
 }
 
 /// Handle for the HTTP/2 server
 #[derive(Debug)]
 pub struct ServerHandle {
-    /// Transport for sending and receiving messages
-    pub transport: ServerHttp2Transport,
+    /// Address the server is listening on
+    local_addr: SocketAddr,
+    /// Open sessions, by ID
+    sessions: Sessions,
+    /// Lifecycle of the server, advanced by `shutdown`
+    phase: Arc<watch::Sender<Phase>>,
     /// Task handle for the server
     server_task: tokio::task::JoinHandle<()>,
-    /// Task handle for message processing
-    message_task: tokio::task::JoinHandle<()>,
 }
 
 impl ServerHandle {
-    /// Stops the server
-    pub async fn stop(self) -> Result<()> {
-        // Close the transport
-        self.transport.close().await?;
+    /// Returns the address the server is listening on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
 
-        // Abort the tasks
-        self.server_task.abort();
-        self.message_task.abort();
+    /// Returns the transports of the open sessions
+    ///
+    /// Sending through one of them reaches that client's event stream.
+    pub fn sessions(&self) -> Vec<ServerHttp2Transport> {
+        self.sessions.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Shuts the server down gracefully
+    ///
+    /// Stops accepting connections, sends `config.notification` to every session's
+    /// event stream, and waits up to `config.grace_period` for the requests the
+    /// sessions are working on to finish; requests POSTed meanwhile are answered with
+    /// an error. The POSTs of requests still running are then answered with an error
+    /// and their handlers dropped, the sessions closed, and the connections sent
+    /// GOAWAY.
+    pub async fn shutdown(mut self, config: ShutdownConfig) -> Result<()> {
+        let deadline = Instant::now() + config.grace_period;
+        self.phase.send_replace(Phase::Draining);
+
+        let sessions = self.sessions();
+        info!("Shutting down HTTP/2 server, {} sessions open", sessions.len());
+        if let Some(notification) = &config.notification {
+            for session in &sessions {
+                if let Err(e) = session.send_message(notification.clone()).await {
+                    debug!("Failed to notify HTTP/2 session {} of the shutdown: {}", session.id(), e);
+                }
+            }
+        }
+
+        let finished = shutdown::drain(deadline, || sessions.iter().all(|session| !session.has_pending())).await;
+        if !finished {
+            warn!("Cancelling HTTP/2 requests still running after the shutdown grace period");
+        }
+        for session in &sessions {
+            session.cancel_pending();
+            let _ = session.close().await;
+        }
+
+        self.phase.send_replace(Phase::Closing);
+        if tokio::time::timeout(CLOSE_TIMEOUT, &mut self.server_task).await.is_err() {
+            debug!("HTTP/2 connections did not close in time, dropping them");
+            self.server_task.abort();
+            let _ = (&mut self.server_task).await;
+        }
+        self.sessions.lock().unwrap().clear();
+        Ok(())
+    }
 
+    /// Stops the server, ending all connections and sessions
+    ///
+    /// Requests in progress are dropped; use [`shutdown`](Self::shutdown) to let them
+    /// finish.
+    pub async fn stop(self) -> Result<()> {
+        self.server_task.abort();
+        let _ = self.server_task.await;
+        for session in self.sessions.lock().unwrap().drain().map(|(_, session)| session) {
+            session.incoming.close();
+            session.outgoing.close();
+        }
         Ok(())
     }
 }
 
 /// Handles an HTTP/2 connection
 ///
+/// The connection gets its own session, started by its first accepted request, which
+/// ends with it.
+///
 /// # Arguments
 /// * `stream` - The TCP or TLS stream
-/// * `callback` - Callback function to handle incoming messages
-/// * `broadcast_tx` - Channel for broadcasting messages to clients
-/// * `cors_config` - Optional CORS configuration
+/// * `state` - State shared by the server's connections
 ///
 /// # Returns
 /// A result indicating success or failure
-async fn handle_http2_connection<S, F>(
-    stream: S,
-    callback: Arc<F>,
-    broadcast_tx: broadcast::Sender<Message>,
-    cors_config: Option<CorsConfig>,
-) -> Result<()>
+async fn handle_http2_connection<S>(stream: S, state: Arc<ServerState>) -> Result<()>
 where
     S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
-    F: Fn(Message) -> Result<Message> + Send + Sync + 'static,
 {
-    // Wrap the stream with TokioIo
-    let io = TokioIo::new(stream);
-
-    // Create the HTTP/2 connection
-    let connection = http2::Builder::new(hyper_util::rt::TokioExecutor::new())
-        .enable_connect_protocol() // Enable CONNECT protocol
-        .serve_connection(io, hyper::service::service_fn(move |req| {
-            let callback = callback.clone();
-            let broadcast_tx = broadcast_tx.clone();
-            let cors_config = cors_config.clone();
-
-            async move {
-                handle_http2_request(req, callback, broadcast_tx, cors_config.as_ref()).await
+    let (started, session_started) = oneshot::channel();
+    let connection_session = Arc::new(ConnectionSession {
+        session: OnceLock::new(),
+        started: std::sync::Mutex::new(Some(started)),
+    });
+    let runner = async {
+        if let Ok(session) = session_started.await {
+            (state.run_session)(session).await;
+        }
+    };
+
+    let connection = async {
+        // Wrap the stream with TokioIo
+        let io = TokioIo::new(stream);
+
+        // Serve the connection until it ends, sending GOAWAY once the server closes
+        let service_state = state.clone();
+        let service_session = connection_session.clone();
+        let connection = http2::Builder::new(TokioExecutor::new())
+            .enable_connect_protocol() // Enable CONNECT protocol
+            .serve_connection(io, hyper::service::service_fn(move |req| {
+                let state = service_state.clone();
+                let session = service_session.clone();
+                async move { handle_http2_request(req, &state, &session).await }
+            }));
+        tokio::pin!(connection);
+        let mut phase = state.phase.subscribe();
+        let result = tokio::select! {
+            result = connection.as_mut() => result,
+            _ = wait_for_phase(&mut phase, |phase| phase == Phase::Closing) => {
+                connection.as_mut().graceful_shutdown();
+                connection.await
             }
-        }));
+        };
 
-    // Start the connection
-    if let Err(e) = connection.await {
-        error!("HTTP/2 connection error: {}", e);
-        return Err(TransportError::new(
-            TransportErrorCode::ConnectionFailed,
-            format!("HTTP/2 connection error: {}", e),
-        ));
-    }
+        // End the session with its connection; without one, the runner is done too
+        connection_session.started.lock().unwrap().take();
+        if let Some(session) = connection_session.session.get() {
+            state.sessions.lock().unwrap().remove(session.id());
+            let _ = session.close().await;
+        }
+        result
+    };
 
-    Ok(())
+    let (result, ()) = tokio::join!(connection, runner);
+    result.map_err(|e| TransportError::new(
+        TransportErrorCode::ConnectionFailed,
+        format!("HTTP/2 connection error: {}", e),
+    ))
 }
 
 /// Adds CORS headers to a response builder based on the CORS configuration
@@ -819,24 +1467,24 @@ where
 /// # Arguments
 /// * `response_builder` - The response builder to add headers to
 /// * `cors` - The CORS configuration
+/// * `allowed_origin` - The `Access-Control-Allow-Origin` value for the request
 ///
 /// # Returns
 /// The response builder with CORS headers added
 fn add_cors_headers(
     mut response_builder: hyper::http::response::Builder,
     cors: &CorsConfig,
+    allowed_origin: &str,
 ) -> hyper::http::response::Builder {
-    response_builder = response_builder
-        .header("Access-Control-Allow-Origin", &cors.allowed_origins)
-        .header("Access-Control-Allow-Methods", &cors.allowed_methods)
-        .header("Access-Control-Allow-Headers", &cors.allowed_headers);
+    response_builder = response_builder.header("Access-Control-Allow-Origin", allowed_origin);
 
-    if cors.allow_credentials {
-        response_builder = response_builder.header("Access-Control-Allow-Credentials", "true");
+    // The response depends on the origin unless every origin gets the same one
+    if allowed_origin != "*" {
+        response_builder = response_builder.header("Vary", "Origin");
     }
 
-    if let Some(max_age) = cors.max_age {
-        response_builder = response_builder.header("Access-Control-Max-Age", max_age.to_string());
+    if cors.allow_credentials && allowed_origin != "*" {
+        response_builder = response_builder.header("Access-Control-Allow-Credentials", "true");
     }
 
     if let Some(exposed_headers) = &cors.exposed_headers {
@@ -853,175 +1501,338 @@ fn add_cors_headers(
 /// * `cors_config` - The CORS configuration
 ///
 /// # Returns
-/// A result containing the HTTP response
+/// The HTTP response
 fn handle_cors_preflight(
-    _req: Request<Incoming>,
+    req: Request<Incoming>,
     cors_config: Option<&CorsConfig>,
-) -> std::result::Result<Response<Full<Bytes>>, hyper::Error> {
+) -> Response<Body> {
     let mut response_builder = Response::builder()
         .status(StatusCode::NO_CONTENT);
 
-    // Add CORS headers if configured
-    if let Some(cors) = cors_config {
-        response_builder = add_cors_headers(response_builder, cors);
+    let origin = req.headers().get("origin").and_then(|h| h.to_str().ok());
+    let (Some(cors), Some(origin)) = (cors_config, origin) else {
+        return response_builder.body(empty()).unwrap();
+    };
+    let Some(allowed_origin) = cors.allow_origin(origin) else {
+        return cors_rejection(origin);
+    };
+    response_builder = add_cors_headers(response_builder, cors, allowed_origin);
+
+    // `*` is taken literally for requests with credentials, so allow what is
+    // requested instead
+    let requested = |name: &str| req.headers().get(name).and_then(|h| h.to_str().ok());
+    let allowed_methods = match cors.allowed_methods.trim() {
+        "*" => requested("access-control-request-method"),
+        methods => Some(methods),
+    };
+    if let Some(methods) = allowed_methods {
+        response_builder = response_builder.header("Access-Control-Allow-Methods", methods);
+    }
+    let allowed_headers = match cors.allowed_headers.trim() {
+        "*" => requested("access-control-request-headers"),
+        headers => Some(headers),
+    };
+    if let Some(headers) = allowed_headers {
+        response_builder = response_builder.header("Access-Control-Allow-Headers", headers);
+    }
+
+    if let Some(max_age) = cors.max_age {
+        response_builder = response_builder.header("Access-Control-Max-Age", max_age.to_string());
     }
 
-    Ok(response_builder.body(Full::new(Bytes::from(""))).unwrap())
+    response_builder.body(empty()).unwrap()
+}
+
+/// Response to a request from an origin CORS does not allow, as `actix-cors` answers
+/// them in `httpd`
+fn cors_rejection(origin: &str) -> Response<Body> {
+    warn!("Rejecting HTTP/2 request from origin {} not allowed by CORS", origin);
+    text_response(
+        Response::builder(),
+        StatusCode::BAD_REQUEST,
+        "Origin is not allowed to make this request",
+    )
 }
 
 /// Handles an HTTP/2 request
 ///
 /// # Arguments
 /// * `req` - The HTTP request
-/// * `callback` - Callback function to handle incoming messages
-/// * `broadcast_tx` - Channel for broadcasting messages to clients
-/// * `cors_config` - Optional CORS configuration
+/// * `state` - State shared by the server's connections
+/// * `connection_session` - Session of the connection the request arrived on
 ///
 /// # Returns
 /// A result containing the HTTP response
-async fn handle_http2_request<F>(
+async fn handle_http2_request(
     req: Request<Incoming>,
-    callback: Arc<F>,
-    broadcast_tx: broadcast::Sender<Message>,
-    cors_config: Option<&CorsConfig>,
-) -> std::result::Result<Response<Full<Bytes>>, hyper::Error>
-where
-    F: Fn(Message) -> Result<Message> + Send + Sync + 'static,
-{
+    state: &ServerState,
+    connection_session: &ConnectionSession,
+) -> std::result::Result<Response<Body>, Infallible> {
+    let cors_config = state.cors_config.as_ref();
+
+    // Probes are answered before the origin check, which orchestrators would fail
+    if let (Some(monitoring), Some(probe)) = (&state.monitoring, Probe::from_path(req.uri().path()))
+        && req.method() == Method::GET
+    {
+        return Ok(handle_probe(&req, state, monitoring, probe));
+    }
+
+    // Reject requests from disallowed origins before anything else
+    if let Some(policy) = &state.origin_policy {
+        let origin = req.headers().get("origin").and_then(|h| h.to_str().ok());
+        let host = req
+            .uri()
+            .authority()
+            .map(|authority| authority.as_str())
+            .or_else(|| req.headers().get("host").and_then(|h| h.to_str().ok()));
+        if !policy.allows(origin, host) {
+            warn!(
+                "Rejecting HTTP/2 request from origin {} for host {}",
+                origin.unwrap_or("-"),
+                host.unwrap_or("-")
+            );
+            return Ok(text_response(Response::builder(), StatusCode::FORBIDDEN, "Origin not allowed"));
+        }
+    }
+
     // Handle CORS preflight requests
     if req.method() == Method::OPTIONS {
-        return handle_cors_preflight(req, cors_config);
+        return Ok(handle_cors_preflight(req, cors_config));
     }
 
-    let response = match (req.method().as_str(), req.uri().path()) {
-        // Handle POST /message
-        ("POST", "/message") => {
-            // Read the request body
-            let body_bytes = match req.collect().await {
-                Ok(collected) => collected.to_bytes(),
-                Err(e) => {
-                    error!("Failed to read request body: {}", e);
-                    let mut response_builder = Response::builder()
-                        .status(StatusCode::BAD_REQUEST);
-
-                    // Add CORS headers if configured
-                    if let Some(cors) = cors_config {
-                        response_builder = add_cors_headers(response_builder, cors);
-                    }
-
-                    return Ok(response_builder
-                        .body(Full::new(Bytes::from(format!("Failed to read request body: {}", e))))
-                        .unwrap());
-                }
-            };
+    let mut response_builder = Response::builder();
 
-            // Parse the message
-            let message = match serde_json::from_slice::<Message>(&body_bytes) {
-                Ok(message) => message,
-                Err(e) => {
-                    error!("Failed to parse message: {}", e);
-                    let mut response_builder = Response::builder()
-                        .status(StatusCode::BAD_REQUEST);
-
-                    // Add CORS headers if configured
-                    if let Some(cors) = cors_config {
-                        response_builder = add_cors_headers(response_builder, cors);
-                    }
+    // Add CORS headers for cross-origin requests if configured
+    let origin = req.headers().get("origin").and_then(|h| h.to_str().ok());
+    if let (Some(cors), Some(origin)) = (cors_config, origin) {
+        match cors.allow_origin(origin) {
+            Some(allowed_origin) => {
+                response_builder = add_cors_headers(response_builder, cors, allowed_origin);
+            }
+            None => return Ok(cors_rejection(origin)),
+        }
+    }
 
-                    return Ok(response_builder
-                        .body(Full::new(Bytes::from(format!("Failed to parse message: {}", e))))
-                        .unwrap());
+    // Route the request to the session it names, or to the connection's own
+    let session = match req.headers().get(SESSION_ID_HEADER) {
+        Some(id) => {
+            let session = id.to_str().ok()
+                .and_then(|id| state.sessions.lock().unwrap().get(id).cloned());
+            match session {
+                Some(session) => session,
+                None => {
+                    return Ok(text_response(response_builder, StatusCode::NOT_FOUND, "Unknown session"));
                 }
-            };
-
-            // Process the message
-            match callback(message) {
-                Ok(response) => {
-                    // Broadcast the response
-                    if broadcast_tx.send(response.clone()).is_err() {
-                        error!("Failed to broadcast response (no receivers)");
-                    }
+            }
+        }
+        None => connection_session.get_or_start(state),
+    };
+    let response_builder = response_builder.header(SESSION_ID_HEADER, session.id());
 
-                    // Return a success response
-                    let json = match serde_json::to_string(&response) {
-                        Ok(json) => json,
-                        Err(e) => {
-                            error!("Failed to serialize response: {}", e);
-                            let mut response_builder = Response::builder()
-                                .status(StatusCode::INTERNAL_SERVER_ERROR);
-
-                            // Add CORS headers if configured
-                            if let Some(cors) = cors_config {
-                                response_builder = add_cors_headers(response_builder, cors);
+    let metrics = state.metrics.clone();
+    let response = match (req.method().as_str(), req.uri().path()) {
+        // Handle POST /message
+        ("POST", "/message") => handle_message(req, response_builder, state, &session).await,
+        // Handle GET /events
+        ("GET", "/events") => {
+            let events = futures::stream::unfold(session.outgoing.clone(), move |queue| {
+                let metrics = metrics.clone();
+                async move {
+                    loop {
+                        let message = match queue.pop().await {
+                            Ok(Some(message)) => message,
+                            Ok(None) => return None,
+                            Err(e) => {
+                                error!("Dropped HTTP/2 events: {}", e);
+                                metrics.error(&e);
+                                continue;
                             }
-
-                            return Ok(response_builder
-                                .body(Full::new(Bytes::from(format!("Failed to serialize response: {}", e))))
-                                .unwrap());
+                        };
+                        match serde_json::to_string(&message) {
+                            Ok(json) => {
+                                let event = Bytes::from(format!("data: {}\n\n", json));
+                                metrics.sent(TRANSPORT_LABEL, event.len());
+                                return Some((Ok(Frame::data(event)), queue));
+                            }
+                            Err(e) => error!("Failed to serialize event: {}", e),
                         }
-                    };
-
-                    let mut response_builder = Response::builder()
-                        .status(StatusCode::OK)
-                        .header("content-type", "application/json");
-
-                    // Add CORS headers if configured
-                    if let Some(cors) = cors_config {
-                        response_builder = add_cors_headers(response_builder, cors);
                     }
-
-                    response_builder.body(Full::new(Bytes::from(json))).unwrap()
-                },
-                Err(e) => {
-                    error!("Failed to process message: {}", e);
-                    let mut response_builder = Response::builder()
-                        .status(StatusCode::INTERNAL_SERVER_ERROR);
-
-                    // Add CORS headers if configured
-                    if let Some(cors) = cors_config {
-                        response_builder = add_cors_headers(response_builder, cors);
-                    }
-
-                    response_builder
-                        .body(Full::new(Bytes::from(format!("Failed to process message: {}", e))))
-                        .unwrap()
                 }
-            }
-        },
-        // Handle GET /events
-        ("GET", "/events") => {
-            let mut response_builder = Response::builder()
+            });
+
+            response_builder
                 .status(StatusCode::OK)
                 .header("content-type", "text/event-stream")
                 .header("cache-control", "no-cache")
-                .header("connection", "keep-alive");
-
-            // Add CORS headers if configured
-            if let Some(cors) = cors_config {
-                response_builder = add_cors_headers(response_builder, cors);
-            }
-
-            response_builder
-                .body(Full::new(Bytes::from("data: Connected\n\n")))
+                .body(StreamBody::new(events).boxed())
                 .unwrap()
         },
         // Handle other requests
-        _ => {
-            let mut response_builder = Response::builder()
-                .status(StatusCode::NOT_FOUND);
+        _ => text_response(response_builder, StatusCode::NOT_FOUND, "Not found"),
+    };
+
+    // Complete bodies are counted here, the event stream as it is written
+    if let Some(len) = hyper::body::Body::size_hint(response.body()).exact() {
+        state.metrics.sent(TRANSPORT_LABEL, len as usize);
+    }
+    Ok(response)
+}
+
+/// Answers a request for a health, readiness or metrics endpoint
+fn handle_probe(
+    req: &Request<Incoming>,
+    state: &ServerState,
+    monitoring: &MonitoringConfig,
+    probe: Probe,
+) -> Response<Body> {
+    let sessions = {
+        let sessions = state.sessions.lock().unwrap();
+        monitoring::count_sessions(&[TRANSPORT_LABEL], sessions.keys().map(|_| TRANSPORT_LABEL))
+    };
+    let response = monitoring.respond(
+        probe,
+        req.headers().get("authorization").and_then(|h| h.to_str().ok()),
+        *state.phase.borrow() == Phase::Running,
+        &state.metrics,
+        &sessions,
+    );
+    Response::builder()
+        .status(response.status)
+        .header("content-type", response.content_type)
+        .body(full(response.body))
+        .unwrap()
+}
+
+/// Handles a message POSTed to a session, answering a request with its response
+async fn handle_message(
+    req: Request<Incoming>,
+    response_builder: hyper::http::response::Builder,
+    state: &ServerState,
+    session: &ServerHttp2Transport,
+) -> Response<Body> {
+    // Read the request body
+    let body_bytes = match req.collect().await {
+        Ok(collected) => collected.to_bytes(),
+        Err(e) => {
+            error!("Failed to read request body: {}", e);
+            return text_response(
+                response_builder,
+                StatusCode::BAD_REQUEST,
+                format!("Failed to read request body: {}", e),
+            );
+        }
+    };
+
+    state.metrics.received(TRANSPORT_LABEL, body_bytes.len());
+
+    // Parse the message
+    let message = match serde_json::from_slice::<Message>(&body_bytes) {
+        Ok(message) => message,
+        Err(e) => {
+            error!("Failed to parse message: {}", e);
+            return text_response(
+                response_builder,
+                StatusCode::BAD_REQUEST,
+                format!("Failed to parse message: {}", e),
+            );
+        }
+    };
 
-            // Add CORS headers if configured
-            if let Some(cors) = cors_config {
-                response_builder = add_cors_headers(response_builder, cors);
+    // Requests arriving during shutdown are refused; anything else may still be
+    // needed to finish the requests in progress
+    let draining = *state.phase.borrow() != Phase::Running;
+    if let JsonRpcMessage::Request(request) = &message
+        && draining
+    {
+        state.metrics.request_finished(&request.method, Outcome::Error, None);
+        return json_response(response_builder, &shutdown::refused(request.id));
+    }
+    let method = match &message {
+        JsonRpcMessage::Request(request) => Some(request.method.clone()),
+        _ => None,
+    };
+    let received = Instant::now();
+
+    // Hand it to the session
+    let waiting = match session.deliver(message).await {
+        Ok(waiting) => waiting,
+        Err(e) => {
+            error!("Failed to deliver message: {}", e);
+            state.metrics.error(&e);
+            if let Some(method) = &method {
+                state.metrics.request_finished(method, Outcome::Error, None);
             }
+            let status = if e.code() == Some(TransportErrorCode::InvalidMessage) {
+                StatusCode::BAD_REQUEST
+            } else {
+                StatusCode::SERVICE_UNAVAILABLE
+            };
+            return text_response(response_builder, status, e.to_string());
+        }
+    };
 
-            response_builder
-                .body(Full::new(Bytes::from("Not found")))
-                .unwrap()
+    // Notifications and responses are only acknowledged
+    let Some(waiting) = waiting else {
+        return response_builder
+            .status(StatusCode::ACCEPTED)
+            .body(empty())
+            .unwrap();
+    };
+
+    let method = method.unwrap_or_default();
+    let response = match waiting.await {
+        Ok(response) => response,
+        Err(_) => {
+            state.metrics.request_finished(&method, Outcome::Error, Some(received.elapsed()));
+            return text_response(
+                response_builder,
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Session closed before responding",
+            );
         }
     };
 
-    Ok(response)
+    // Return the response in the body
+    state.metrics.request_finished(&method, Outcome::of(&response), Some(received.elapsed()));
+    json_response(response_builder, &response)
+}
+
+/// Returns a response with `message` as its JSON body
+fn json_response(response_builder: hyper::http::response::Builder, message: &Message) -> Response<Body> {
+    match serde_json::to_string(message) {
+        Ok(json) => response_builder
+            .status(StatusCode::OK)
+            .header("content-type", "application/json")
+            .body(full(json))
+            .unwrap(),
+        Err(e) => {
+            error!("Failed to serialize response: {}", e);
+            text_response(
+                response_builder,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to serialize response: {}", e),
+            )
+        }
+    }
+}
+
+/// Returns a response with a plain-text body
+fn text_response(
+    response_builder: hyper::http::response::Builder,
+    status: StatusCode,
+    text: impl Into<Bytes>,
+) -> Response<Body> {
+    response_builder.status(status).body(full(text)).unwrap()
+}
+
+/// Returns a complete response body
+fn full(body: impl Into<Bytes>) -> Body {
+    Full::new(body.into()).boxed()
+}
+
+/// Returns an empty response body
+fn empty() -> Body {
+    full(Bytes::new())
 }
 
 /// Result of loading TLS configuration
@@ -1056,6 +1867,18 @@ async fn load_tls_config(tls_config: &TlsConfig) -> Result<TlsConfigResult> {
     }
 }
 
+/// Loads the rustls server configuration for `tls_config`, whatever its source
+///
+/// The configuration advertises HTTP/2 via ALPN; callers serving other protocols
+/// should reset `alpn_protocols`.
+pub(crate) async fn load_rustls_server_config(tls_config: &TlsConfig) -> Result<RustlsServerConfig> {
+    match load_tls_config(tls_config).await? {
+        TlsConfigResult::Manual(config) => Ok(config),
+        #[cfg(feature = "acme")]
+        TlsConfigResult::Acme(config) => Ok(config),
+    }
+}
+
 /// Loads a root certificate from a file
 ///
 /// # Arguments
@@ -1063,8 +1886,7 @@ async fn load_tls_config(tls_config: &TlsConfig) -> Result<TlsConfigResult> {
 ///
 /// # Returns
 /// A result containing the root certificate store
-#[allow(dead_code)]
-fn load_root_cert(path: &str) -> Result<rustls::RootCertStore> {
+pub(crate) fn load_root_cert(path: &str) -> Result<RootCertStore> {
     // Open the certificate file
     let cert_file = File::open(path).map_err(|e| {
         TransportError::new(
@@ -1094,7 +1916,7 @@ fn load_root_cert(path: &str) -> Result<rustls::RootCertStore> {
     }
 
     // Create a root certificate store
-    let mut root_store = rustls::RootCertStore::empty();
+    let mut root_store = RootCertStore::empty();
 
     // Add the certificates to the store
     for cert in certs {
@@ -1109,41 +1931,39 @@ fn load_root_cert(path: &str) -> Result<rustls::RootCertStore> {
     Ok(root_store)
 }
 
-/// Loads client certificates for mutual TLS
+/// Loads a certificate chain and its private key
+///
+/// The key may be PKCS#8, PKCS#1 (RSA) or SEC1 (EC); the first one in the file is used.
 ///
 /// # Arguments
-/// * `cert_path` - Path to the client certificate file
-/// * `key_path` - Path to the client key file
+/// * `cert_path` - Path to the PEM certificate chain
+/// * `key_path` - Path to the PEM private key
 ///
 /// # Returns
-/// A result containing the client certificate and key
-#[allow(dead_code)]
-fn load_client_cert(cert_path: &str, key_path: &str) -> Result<(Vec<rustls::pki_types::CertificateDer<'static>>, rustls::pki_types::PrivateKeyDer<'static>)> {
+/// A result containing the certificate chain and key
+pub(crate) fn load_cert_and_key(cert_path: &str, key_path: &str) -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
     // Open the certificate file
     let cert_file = File::open(cert_path).map_err(|e| {
         TransportError::new(
             TransportErrorCode::ConfigurationError,
-            format!("Failed to open client certificate file: {}", e),
+            format!("Failed to open certificate file {}: {}", cert_path, e),
         )
     })?;
 
-    // Create a BufReader for the certificate file
-    let mut cert_reader = BufReader::new(cert_file);
-
     // Parse the certificates
-    let certs = rustls_pemfile::certs(&mut cert_reader)
+    let certs = rustls_pemfile::certs(&mut BufReader::new(cert_file))
         .collect::<std::result::Result<Vec<_>, _>>()
         .map_err(|e| {
             TransportError::new(
                 TransportErrorCode::ConfigurationError,
-                format!("Failed to parse client certificate: {}", e),
+                format!("Failed to parse certificate file {}: {}", cert_path, e),
             )
         })?;
 
     if certs.is_empty() {
         return Err(TransportError::new(
             TransportErrorCode::ConfigurationError,
-            "No certificates found in the client certificate file".to_string(),
+            format!("No certificates found in {}", cert_path),
         ));
     }
 
@@ -1151,60 +1971,33 @@ fn load_client_cert(cert_path: &str, key_path: &str) -> Result<(Vec<rustls::pki_
     let key_file = File::open(key_path).map_err(|e| {
         TransportError::new(
             TransportErrorCode::ConfigurationError,
-            format!("Failed to open client key file: {}", e),
+            format!("Failed to open key file {}: {}", key_path, e),
         )
     })?;
 
-    // Create a BufReader for the key file
-    let mut key_reader = BufReader::new(key_file);
-
-    // Parse the private key
-    let mut keys = rustls_pemfile::pkcs8_private_keys(&mut key_reader)
-        .collect::<std::result::Result<Vec<_>, _>>()
+    // Parse the first private key, whether PKCS#8, PKCS#1 (RSA) or SEC1 (EC)
+    let key = rustls_pemfile::private_key(&mut BufReader::new(key_file))
         .map_err(|e| {
             TransportError::new(
                 TransportErrorCode::ConfigurationError,
-                format!("Failed to parse client key: {}", e),
+                format!("Failed to parse key file {}: {}", key_path, e),
             )
-        })?;
-
-    if keys.is_empty() {
-        // Try parsing as RSA key if PKCS8 parsing failed
-        key_reader = BufReader::new(File::open(key_path).map_err(|e| {
+        })?
+        .ok_or_else(|| {
             TransportError::new(
                 TransportErrorCode::ConfigurationError,
-                format!("Failed to reopen client key file: {}", e),
+                format!("No private keys found in {}", key_path),
             )
-        })?);
-
-        let rsa_keys = rustls_pemfile::rsa_private_keys(&mut key_reader)
-            .collect::<std::result::Result<Vec<_>, _>>()
-            .map_err(|e| {
-                TransportError::new(
-                    TransportErrorCode::ConfigurationError,
-                    format!("Failed to parse client key as RSA: {}", e),
-                )
-            })?;
-
-        if rsa_keys.is_empty() {
-            return Err(TransportError::new(
-                TransportErrorCode::ConfigurationError,
-                "No private keys found in the client key file".to_string(),
-            ));
-        }
-
-        // Convert RSA key to PKCS8
-        return Ok((certs, rustls::pki_types::PrivateKeyDer::Pkcs1(rsa_keys.into_iter().next().unwrap())));
-    }
-
-    // Use the first key
-    let key = keys.remove(0);
+        })?;
 
-    Ok((certs, rustls::pki_types::PrivateKeyDer::Pkcs8(key)))
+    Ok((certs, key))
 }
 
 /// Loads TLS configuration from certificate and key files
 ///
+/// The files are watched and reloaded on change or `SIGHUP`, so rotated certificates
+/// are served to new connections without a restart.
+///
 /// # Arguments
 /// * `cert_path` - Path to the certificate file
 /// * `key_path` - Path to the key file
@@ -1212,63 +2005,9 @@ fn load_client_cert(cert_path: &str, key_path: &str) -> Result<(Vec<rustls::pki_
 /// # Returns
 /// A result containing the TLS configuration
 async fn load_manual_tls_config(cert_path: &str, key_path: &str) -> Result<RustlsServerConfig> {
-    // Open the certificate file
-    let cert_file = File::open(cert_path).map_err(|e| {
-        TransportError::new(
-            TransportErrorCode::ConfigurationError,
-            format!("Failed to open certificate file: {}", e),
-        )
-    })?;
-
-    // Open the key file
-    let key_file = File::open(key_path).map_err(|e| {
-        TransportError::new(
-            TransportErrorCode::ConfigurationError,
-            format!("Failed to open key file: {}", e),
-        )
-    })?;
-
-    // Create readers
-    let mut cert_reader = BufReader::new(cert_file);
-    let mut key_reader = BufReader::new(key_file);
-
-    // Parse the certificate
-    let cert_chain = certs(&mut cert_reader)
-        .collect::<std::result::Result<Vec<_>, _>>()
-        .map_err(|e| {
-            TransportError::new(
-                TransportErrorCode::ConfigurationError,
-                format!("Failed to parse certificate: {}", e),
-            )
-        })?;
-
-    // Parse the key
-    let mut keys = pkcs8_private_keys(&mut key_reader)
-        .collect::<std::result::Result<Vec<_>, _>>()
-        .map_err(|e| {
-            TransportError::new(
-                TransportErrorCode::ConfigurationError,
-                format!("Failed to parse key: {}", e),
-            )
-        })?;
-
-    if keys.is_empty() {
-        return Err(TransportError::new(
-            TransportErrorCode::ConfigurationError,
-            "No private keys found".to_string(),
-        ));
-    }
-
-    // Create TLS config
-    let mut config = RustlsServerConfig::builder()
-        .with_no_client_auth()
-        .with_single_cert(cert_chain, PrivateKeyDer::Pkcs8(keys.remove(0)))
-        .map_err(|e| {
-            TransportError::new(
-                TransportErrorCode::ConfigurationError,
-                format!("Failed to create TLS config: {}", e),
-            )
-        })?;
+    let builder = RustlsServerConfig::builder().with_no_client_auth();
+    let resolver = ReloadingCertResolver::watch(cert_path, key_path, builder.crypto_provider().clone())?;
+    let mut config = builder.with_cert_resolver(resolver);
 
     // Configure ALPN protocols to advertise HTTP/2 support
     config.alpn_protocols = vec![b"h2".to_vec()];
@@ -1348,4 +2087,27 @@ async fn load_acme_tls_config(
     server_config.alpn_protocols = vec![b"h2".to_vec()];
 
     Ok(server_config)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_uri_keeps_port_path_and_query() {
+        let base = url::Url::parse("https://example.com:8443/mcp?token=abc").unwrap();
+        assert_eq!(endpoint_uri(&base, "message"), "https://example.com:8443/mcp/message?token=abc");
+
+        let base = url::Url::parse("http://127.0.0.1:8080/").unwrap();
+        assert_eq!(endpoint_uri(&base, "events"), "http://127.0.0.1:8080/events");
+    }
+
+    #[test]
+    fn test_event_parser_handles_split_chunks() {
+        let mut parser = EventParser::default();
+        assert!(parser.feed(b": keep-alive\n\nevent: message\ndata: {\"a\"").is_empty());
+        assert_eq!(parser.feed(b":1}\r\n\r\ndata: one\ndata:two\n\n"), vec![
+            "{\"a\":1}".to_string(),
+            "one\ntwo".to_string(),
+        ]);
+    }
+}
@@ -1,22 +1,43 @@
 use actix_web::middleware::Logger;
 use actix_web::web::Payload;
 use actix_web::web::Query;
-use actix_web::{web, App, HttpResponse, HttpServer, Either, Responder};
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer, Either, Responder};
 use actix_cors::Cors;
 use anyhow::Result;
 
 use uuid::Uuid;
 
-use crate::server::Server;
-use crate::transport::middleware::{AuthConfig, JwtAuth};
+use crate::schema::Implementation;
+use crate::server::{Server, serve_boxed_transport};
+use crate::transport::origin;
+use crate::transport::middleware::{AuthConfig, JwtAuth, OriginCheck};
 use crate::transport::ServerHttpTransport;
-use crate::transport::{handle_ws_connection, Message, ServerWsTransport};
+use crate::transport::{
+    handle_ws_connection_with, handle_ws_upgrade, negotiate_codec, HeartbeatConfig, JsonRpcMessage, Message, MessageQueue,
+    QueueConfig, ServerWsTransport, WsConnectionConfig,
+};
 use crate::transport::ServerSseTransport;
+use crate::transport::http2::load_root_cert;
+use crate::transport::monitoring::{self, Outcome, Probe, ServerMetrics};
+use crate::transport::shutdown::{self, CLOSE_TIMEOUT, InFlight, SHUTDOWN_REASON};
+use crate::transport::{
+    HEALTH_PATH, METRICS_PATH, MonitoringConfig, OriginPolicy, READY_PATH, ReloadingCertResolver, ShutdownConfig,
+    Transport, TransportError, TransportErrorCode,
+};
 use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
 use std::collections::HashMap;
+use async_trait::async_trait;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use tokio::sync::broadcast;
-use tracing::{debug, error, info};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::watch;
+use tokio_rustls::rustls::ServerConfig as RustlsServerConfig;
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tracing::{debug, error, info, warn};
+use url::Url;
 
 /// Server-side SSE transport that handles HTTP POST requests for incoming messages
 /// and sends responses via SSE
@@ -39,20 +60,79 @@ pub struct MessageQuery {
 #[derive(Clone)]
 /// Configuration for the SSE HTTP server
 pub struct ServerConfig {
-    /// Port to listen on
+    /// Port to listen on when `bind_addrs` is empty
     pub port: u16,
+    /// Addresses to listen on, IPv4 or IPv6; an empty list means `127.0.0.1:{port}`
+    ///
+    /// Port 0 picks a free port, reported by [`HttpServerHandle::local_addrs`]. IPv6
+    /// addresses only accept IPv6, so list both `0.0.0.0` and `[::]` for dual-stack.
+    pub bind_addrs: Vec<SocketAddr>,
+    /// Externally visible base URL (e.g. `https://mcp.example.com/tools`) that the
+    /// advertised endpoints are built from, for servers behind a reverse proxy
+    pub public_url: Option<Url>,
+    /// Whether to build the advertised endpoints from the `X-Forwarded-Proto`,
+    /// `X-Forwarded-Host` and `X-Forwarded-Prefix` request headers when `public_url`
+    /// is not set; only enable this behind a proxy that sets them
+    pub trust_forwarded_headers: bool,
+    /// Path the `/sse`, `/message` and `/ws` routes are mounted under, e.g. `/mcp`
+    pub path_prefix: String,
     /// Optional CORS configuration
     pub cors: Option<CorsConfig>,
+    /// Origins and hosts requests are accepted from and for, against DNS rebinding;
+    /// others get `403 Forbidden`. The host of `public_url` is allowed in addition.
+    /// The default only allows local ones, so servers reached under another name must
+    /// list it; `None` accepts every request.
+    pub origin_policy: Option<OriginPolicy>,
     /// Optional TLS configuration
     pub tls: Option<TlsConfig>,
+    /// Ping settings for WebSocket connections; `None` disables heartbeats
+    pub ws_heartbeat: Option<HeartbeatConfig>,
+    /// Size and overflow policy of each WebSocket session's receive queue
+    pub receive_queue: QueueConfig,
+    /// Interval of SSE keep-alive comments; a disconnected SSE client is noticed,
+    /// and its session closed, on the first write that fails
+    pub sse_keep_alive: Duration,
+    /// Idle timeout and limits on concurrent sessions
+    pub session_limits: SessionLimits,
+    /// Authentication of the `/healthz`, `/readyz` and `/metrics` endpoints, which are
+    /// served at the root regardless of `path_prefix` and without the JWT and origin
+    /// checks; `None`, the default, disables them
+    pub monitoring: Option<MonitoringConfig>,
+}
+
+/// Idle timeout and limits on the SSE and WebSocket sessions a server keeps open
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SessionLimits {
+    /// Sessions that exchange no message in either direction for this long are
+    /// closed, and their transport fails with `TransportErrorCode::SessionExpired`;
+    /// `None` keeps idle sessions open
+    pub idle_timeout: Option<Duration>,
+    /// Maximum number of concurrent sessions; further clients get
+    /// `503 Service Unavailable`
+    pub max_sessions: Option<usize>,
+    /// Maximum number of concurrent sessions per client IP address; further clients
+    /// from that address get `429 Too Many Requests`. Behind a proxy, enable
+    /// `trust_forwarded_headers` so the address is taken from the last
+    /// `X-Forwarded-For` entry, which that proxy appended.
+    pub max_sessions_per_ip: Option<usize>,
 }
 
 impl Default for ServerConfig {
     fn default() -> Self {
         Self {
             port: 8080,
+            bind_addrs: Vec::new(),
+            public_url: None,
+            trust_forwarded_headers: false,
+            path_prefix: String::new(),
             cors: None,
+            origin_policy: Some(OriginPolicy::default()),
             tls: None,
+            ws_heartbeat: Some(HeartbeatConfig::default()),
+            receive_queue: QueueConfig::default(),
+            sse_keep_alive: Duration::from_secs(15),
+            session_limits: SessionLimits::default(),
+            monitoring: None,
         }
     }
 }
@@ -60,9 +140,11 @@ impl Default for ServerConfig {
 #[derive(Clone)]
 /// Configuration for CORS
 pub struct CorsConfig {
-    /// Allowed origin for CORS
+    /// Allowed origins for CORS (comma-separated list or * for all), as
+    /// `scheme://host[:port]`; a host of `*.example.com` matches its subdomains. `*`
+    /// is answered with a literal `*` and never with credentials.
     pub allowed_origin: String,
-    /// Whether to allow credentials
+    /// Whether to allow credentials for the origins listed explicitly
     pub allow_credentials: bool,
     /// Maximum age for CORS preflight requests
     pub max_age: Option<usize>,
@@ -73,22 +155,695 @@ pub struct CorsConfig {
 pub struct TlsConfig {
     /// Path to the TLS certificate file
     pub cert_path: String,
-    /// Path to the TLS key file
+    /// Path to the TLS key file (PKCS#8, RSA or EC)
     pub key_path: String,
+    /// Path to the CA certificates client certificates are verified against;
+    /// `None` disables client authentication
+    pub client_ca_path: Option<String>,
+    /// Whether clients must present a certificate; without one they are only
+    /// verified if they offer it
+    pub require_client_cert: bool,
+}
+
+impl TlsConfig {
+    /// Creates a TLS configuration without client authentication
+    pub fn new(cert_path: impl Into<String>, key_path: impl Into<String>) -> Self {
+        Self {
+            cert_path: cert_path.into(),
+            key_path: key_path.into(),
+            client_ca_path: None,
+            require_client_cert: false,
+        }
+    }
+
+    /// Verifies client certificates against the CA certificates in `ca_path`,
+    /// rejecting clients without one if `required`
+    pub fn with_client_ca(mut self, ca_path: impl Into<String>, required: bool) -> Self {
+        self.client_ca_path = Some(ca_path.into());
+        self.require_client_cert = required;
+        self
+    }
+}
+
+/// Builds the rustls configuration for `tls`, reloading the certificate when its files change
+///
+/// # Errors
+/// - `TransportErrorCode::ConfigurationError` if a certificate or key cannot be loaded,
+///   or the key does not match the certificate
+fn load_rustls_config(tls: &TlsConfig) -> std::result::Result<RustlsServerConfig, TransportError> {
+    let builder = RustlsServerConfig::builder();
+    let builder = match &tls.client_ca_path {
+        Some(ca_path) => {
+            let roots = Arc::new(load_root_cert(ca_path)?);
+            let verifier = WebPkiClientVerifier::builder(roots);
+            let verifier = if tls.require_client_cert {
+                verifier
+            } else {
+                verifier.allow_unauthenticated()
+            };
+            let verifier = verifier.build().map_err(|e| TransportError::new(
+                TransportErrorCode::ConfigurationError,
+                format!("Invalid client CA certificates in {}: {}", ca_path, e),
+            ))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let resolver = ReloadingCertResolver::watch(&tls.cert_path, &tls.key_path, builder.crypto_provider().clone())?;
+    Ok(builder.with_cert_resolver(resolver))
 }
 
 // Type alias for the complex build_server function signature
 type BuildServerFn = Arc<dyn Fn(ServerHttpTransport) -> futures::future::BoxFuture<'static, Result<Box<dyn Server>>> + Send + Sync>;
 
+/// Open sessions by session ID
+pub type Sessions = Arc<Mutex<HashMap<String, HttpSession>>>;
+
+/// How a session's client is connected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SessionKind {
+    /// Server-Sent Events stream, with messages posted to `/message`
+    Sse,
+    /// WebSocket connection
+    WebSocket,
+}
+
+impl SessionKind {
+    /// Every kind, as labelled in metrics
+    const LABELS: [&'static str; 2] = ["sse", "websocket"];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Sse => "sse",
+            Self::WebSocket => "websocket",
+        }
+    }
+}
+
+/// A connected SSE or WebSocket client
+#[derive(Debug, Clone)]
+pub struct HttpSession {
+    id: String,
+    kind: SessionKind,
+    transport: ServerHttpTransport,
+    peer_addr: Option<SocketAddr>,
+    client_ip: Option<IpAddr>,
+    client_info: Option<Implementation>,
+    connected_at: SystemTime,
+    control: Arc<SessionControl>,
+}
+
+impl HttpSession {
+    fn new(id: &str, kind: SessionKind, transport: ServerHttpTransport, req: &HttpRequest, state: &SessionState) -> Self {
+        Self {
+            id: id.to_string(),
+            kind,
+            transport,
+            peer_addr: req.peer_addr(),
+            client_ip: state.client_ip(req),
+            client_info: None,
+            connected_at: SystemTime::now(),
+            control: Arc::new(SessionControl::default()),
+        }
+    }
+
+    /// Returns the session ID
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns how the client is connected
+    pub fn kind(&self) -> SessionKind {
+        self.kind
+    }
+
+    /// Returns the transport the session's server is served over
+    pub fn transport(&self) -> &ServerHttpTransport {
+        &self.transport
+    }
+
+    /// Returns the address of the connection, which is the proxy's behind a reverse proxy
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer_addr
+    }
+
+    /// Returns the client address session limits are counted against
+    pub fn client_ip(&self) -> Option<IpAddr> {
+        self.client_ip
+    }
+
+    /// Returns the `clientInfo` the client sent in its `initialize` request, or `None`
+    /// before it has initialized
+    pub fn client_info(&self) -> Option<&Implementation> {
+        self.client_info.as_ref()
+    }
+
+    /// Returns when the client connected
+    pub fn connected_at(&self) -> SystemTime {
+        self.connected_at
+    }
+}
+
+/// State shared by a session's entry in [`Sessions`] and the task serving it
+#[derive(Debug)]
+struct SessionControl {
+    /// Requests the session's server is working on
+    in_flight: InFlight,
+    /// Set to close the session, when the server shuts down
+    closing: watch::Sender<bool>,
+}
+
+impl Default for SessionControl {
+    fn default() -> Self {
+        Self {
+            in_flight: InFlight::default(),
+            closing: watch::Sender::new(false),
+        }
+    }
+}
+
+impl SessionControl {
+    /// Completes once the session is asked to close
+    async fn closed(&self) {
+        let _ = self.closing.subscribe().wait_for(|closing| *closing).await;
+    }
+}
+
+/// The open sessions of a server, for inspecting them and pushing messages such as
+/// `notifications/tools/list_changed` to their clients
+///
+/// Obtained from [`HttpServerHandle::sessions`]; clones share the same sessions.
+#[derive(Debug, Clone)]
+pub struct SessionRegistry {
+    sessions: Sessions,
+}
+
+impl SessionRegistry {
+    /// Returns a snapshot of the open sessions
+    pub fn list(&self) -> Vec<HttpSession> {
+        self.sessions.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Returns the session with `session_id`, if it is open
+    pub fn get(&self, session_id: &str) -> Option<HttpSession> {
+        self.sessions.lock().unwrap().get(session_id).cloned()
+    }
+
+    /// Returns the number of open sessions
+    pub fn len(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+
+    /// Returns whether no session is open
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Sends `message` to the client of `session_id`
+    ///
+    /// # Errors
+    /// - `TransportErrorCode::SessionNotFound` if no such session is open
+    /// - Any error from sending over the session's transport
+    pub async fn send(&self, session_id: &str, message: &Message) -> crate::transport::Result<()> {
+        let session = self.get(session_id).ok_or_else(|| {
+            TransportError::new(
+                TransportErrorCode::SessionNotFound,
+                format!("Session {} not found", session_id),
+            )
+        })?;
+        session.transport.send(message).await
+    }
+
+    /// Sends `message` to the client of every open session
+    ///
+    /// Returns the outcome by session ID; a failure for one session does not stop
+    /// the others from receiving the message.
+    pub async fn send_all(&self, message: &Message) -> HashMap<String, crate::transport::Result<()>> {
+        self.send_where(|_| true, message).await
+    }
+
+    /// Sends `message` to the clients of the open sessions matching `filter`, e.g.
+    /// only initialized ones or those connected over WebSocket
+    ///
+    /// Returns the outcome by session ID.
+    pub async fn send_where(
+        &self,
+        filter: impl Fn(&HttpSession) -> bool,
+        message: &Message,
+    ) -> HashMap<String, crate::transport::Result<()>> {
+        let targets: Vec<HttpSession> = self.list().into_iter().filter(|session| filter(session)).collect();
+        let sends = targets.iter().map(|session| async move {
+            let result = session.transport.send(message).await;
+            if let Err(e) = &result {
+                warn!("Failed to send message to session {}: {}", session.id, e);
+            }
+            (session.id.clone(), result)
+        });
+        futures::future::join_all(sends).await.into_iter().collect()
+    }
+}
+
 #[derive(Clone)]
 /// State for managing SSE sessions
 pub struct SessionState {
-    sessions: Arc<Mutex<HashMap<String, ServerHttpTransport>>>,
-    port: u16,
+    sessions: Sessions,
+    endpoints: Endpoints,
     build_server: BuildServerFn,
+    ws_heartbeat: Option<HeartbeatConfig>,
+    receive_queue: QueueConfig,
+    sse_keep_alive: Duration,
+    limits: SessionLimits,
+    /// Set once the server is shutting down
+    draining: Arc<AtomicBool>,
+    metrics: Arc<ServerMetrics>,
+    monitoring: Option<MonitoringConfig>,
+}
+
+impl SessionState {
+    /// Address of the client making `req`, from `X-Forwarded-For` if forwarding
+    /// headers are trusted
+    ///
+    /// Only the last entry is used: it was appended by the proxy in front of the
+    /// server, while earlier ones are whatever the client sent.
+    fn client_ip(&self, req: &HttpRequest) -> Option<IpAddr> {
+        if self.endpoints.trust_forwarded_headers
+            && let Some(ip) = last_forwarded_for(req).and_then(|ip| ip.parse().ok())
+        {
+            return Some(ip);
+        }
+        req.peer_addr().map(|addr| addr.ip())
+    }
+
+    /// Adds a session, or returns the response to reject it with if a limit is reached
+    fn register(&self, session_id: &str, session: HttpSession) -> std::result::Result<(), Box<HttpResponse>> {
+        let mut sessions = self.sessions.lock().unwrap();
+        if self.draining.load(Ordering::Relaxed) {
+            return Err(Box::new(HttpResponse::ServiceUnavailable().body(SHUTDOWN_REASON)));
+        }
+        if let Some(max_sessions) = self.limits.max_sessions
+            && sessions.len() >= max_sessions
+        {
+            warn!("Rejecting session: {} sessions open", sessions.len());
+            return Err(Box::new(HttpResponse::ServiceUnavailable().body("Too many sessions")));
+        }
+        if let (Some(max_sessions), Some(ip)) = (self.limits.max_sessions_per_ip, session.client_ip)
+            && sessions.values().filter(|open| open.client_ip == Some(ip)).count() >= max_sessions
+        {
+            warn!("Rejecting session: too many sessions from {}", ip);
+            return Err(Box::new(
+                HttpResponse::TooManyRequests().body("Too many sessions from this address"),
+            ));
+        }
+        sessions.insert(session_id.to_string(), session);
+        Ok(())
+    }
+}
+
+/// Serves a session until its client disconnects, its server ends or the server
+/// shuts down, then removes the session and drops the server
+///
+/// `connection` completes when the client is gone.
+async fn run_session(state: SessionState, session: HttpSession, connection: impl Future<Output = ()>) {
+    let HttpSession {
+        id: session_id,
+        kind,
+        transport,
+        control,
+        ..
+    } = session;
+    let activity = Arc::new(Activity::new(state.limits.idle_timeout));
+    let serve = async {
+        match (state.build_server)(transport.clone()).await {
+            Ok(server) => {
+                let session = SessionTransport {
+                    inner: transport.clone(),
+                    activity: activity.clone(),
+                    control: control.clone(),
+                    draining: state.draining.clone(),
+                    metrics: state.metrics.clone(),
+                    kind,
+                    sessions: state.sessions.clone(),
+                    session_id: session_id.clone(),
+                };
+                if let Err(e) = serve_boxed_transport(server, session).await {
+                    debug!("Session {} ended with error: {}", session_id, e);
+                }
+            }
+            Err(e) => error!("Failed to build server for session {}: {:?}", session_id, e),
+        }
+    };
+
+    let mut shutting_down = false;
+    tokio::select! {
+        _ = serve => {}
+        _ = connection => debug!("Client of session {} disconnected", session_id),
+        _ = control.closed() => shutting_down = true,
+    }
+    if activity.is_expired() {
+        info!("Session {} expired after being idle", session_id);
+    }
+
+    state.sessions.lock().unwrap().remove(&session_id);
+    let closed = match &transport {
+        ServerHttpTransport::Ws(ws) if shutting_down => {
+            ws.close_with_reason(actix_ws::CloseReason {
+                code: actix_ws::CloseCode::Away,
+                description: Some(SHUTDOWN_REASON.to_string()),
+            })
+            .await
+        }
+        _ => transport.close().await,
+    };
+    if let Err(e) = closed {
+        debug!("Error closing session {}: {}", session_id, e);
+    }
+    info!("Session {} closed", session_id);
+}
+
+/// When a session last exchanged a message, for expiring idle sessions
+struct Activity {
+    last: Mutex<Instant>,
+    idle_timeout: Option<Duration>,
+}
+
+impl Activity {
+    fn new(idle_timeout: Option<Duration>) -> Self {
+        Self {
+            last: Mutex::new(Instant::now()),
+            idle_timeout,
+        }
+    }
+
+    fn touch(&self) {
+        *self.last.lock().unwrap() = Instant::now();
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.idle_timeout.map(|timeout| *self.last.lock().unwrap() + timeout)
+    }
+
+    fn is_expired(&self) -> bool {
+        self.deadline().is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Completes once the session has been idle for the timeout; never without one
+    async fn expired(&self) {
+        while let Some(deadline) = self.deadline() {
+            if Instant::now() >= deadline {
+                return;
+            }
+            tokio::time::sleep_until(deadline.into()).await;
+        }
+        std::future::pending().await
+    }
+
+    fn expired_error() -> TransportError {
+        TransportError::new(
+            TransportErrorCode::SessionExpired,
+            "Session expired after being idle",
+        )
+    }
+}
+
+/// Transport a session's server is served over, recording activity, in-flight
+/// requests, metrics and the client's `initialize` request; once the session is idle
+/// for too long, it fails with `SessionExpired`, ending the session
+///
+/// While the server shuts down, requests are answered with an error rather than
+/// handed to the session's server.
+#[derive(Clone)]
+struct SessionTransport {
+    inner: ServerHttpTransport,
+    activity: Arc<Activity>,
+    control: Arc<SessionControl>,
+    draining: Arc<AtomicBool>,
+    metrics: Arc<ServerMetrics>,
+    kind: SessionKind,
+    sessions: Sessions,
+    session_id: String,
+}
+
+impl SessionTransport {
+    /// Stores the `clientInfo` of an `initialize` request in the session
+    fn record_client_info(&self, message: &Message) {
+        let JsonRpcMessage::Request(request) = message else {
+            return;
+        };
+        if request.method != "initialize" {
+            return;
+        }
+        let client_info = request
+            .params
+            .as_ref()
+            .and_then(|params| params.get("clientInfo"))
+            .and_then(|info| serde_json::from_value::<Implementation>(info.clone()).ok());
+        if let Some(session) = self.sessions.lock().unwrap().get_mut(&self.session_id) {
+            session.client_info = client_info;
+        }
+    }
+
+    /// Size of `message` as JSON, which is what SSE sends and WebSocket defaults to
+    fn message_size(message: &Message) -> usize {
+        serde_json::to_vec(message).map_or(0, |json| json.len())
+    }
+}
+
+#[async_trait]
+impl Transport for SessionTransport {
+    async fn send(&self, message: &Message) -> crate::transport::Result<()> {
+        if self.activity.is_expired() {
+            return Err(Activity::expired_error());
+        }
+        if let Err(e) = self.inner.send(message).await {
+            self.metrics.error(&e);
+            return Err(e);
+        }
+        self.activity.touch();
+        self.metrics.sent(self.kind.label(), Self::message_size(message));
+        if let Some((method, latency)) = self.control.in_flight.sent(message) {
+            self.metrics.request_finished(&method, Outcome::of(message), Some(latency));
+        }
+        Ok(())
+    }
+
+    async fn receive(&self) -> crate::transport::Result<Option<Message>> {
+        loop {
+            let message = tokio::select! {
+                message = self.inner.receive() => message,
+                _ = self.activity.expired() => Err(Activity::expired_error()),
+            };
+            let message = match message {
+                Ok(Some(message)) => message,
+                Ok(None) => return Ok(None),
+                Err(e) => {
+                    self.metrics.error(&e);
+                    return Err(e);
+                }
+            };
+            self.activity.touch();
+            self.metrics.received(self.kind.label(), Self::message_size(&message));
+            if let JsonRpcMessage::Request(request) = &message
+                && self.draining.load(Ordering::Relaxed)
+            {
+                debug!("Refusing request {} during shutdown", request.id);
+                self.metrics.request_finished(&request.method, Outcome::Error, None);
+                self.inner.send(&shutdown::refused(request.id)).await?;
+                continue;
+            }
+            self.record_client_info(&message);
+            self.control.in_flight.received(&message);
+            return Ok(Some(message));
+        }
+    }
+
+    async fn open(&self) -> crate::transport::Result<()> {
+        self.inner.open().await
+    }
+
+    async fn close(&self) -> crate::transport::Result<()> {
+        self.inner.close().await
+    }
+}
+
+/// How the URLs advertised to clients are built
+#[derive(Clone, Debug, Default)]
+struct Endpoints {
+    /// Whether the server is served over TLS
+    tls: bool,
+    public_url: Option<Url>,
+    trust_forwarded_headers: bool,
+    /// Normalized route prefix: empty, or starting with `/` without a trailing one
+    path_prefix: String,
+}
+
+impl Endpoints {
+    /// URL clients post messages for `session_id` to
+    ///
+    /// Without a public URL or trusted forwarding headers, this is the local address
+    /// the request arrived on, so it is reachable whichever address was bound.
+    fn message_url(&self, req: &HttpRequest, session_id: &str) -> String {
+        let path = format!("{}/message?sessionId={}", self.path_prefix, session_id);
+        if let Some(public_url) = &self.public_url {
+            return format!("{}{}", public_url.as_str().trim_end_matches('/'), path);
+        }
+
+        let mut scheme = if self.tls { "https" } else { "http" }.to_string();
+        let mut host = req.app_config().local_addr().to_string();
+        let mut prefix = String::new();
+        if self.trust_forwarded_headers {
+            if let Some(proto) = forwarded_header(req, "X-Forwarded-Proto")
+                && matches!(proto.as_str(), "http" | "https")
+            {
+                scheme = proto;
+            }
+            if let Some(forwarded_host) = forwarded_header(req, "X-Forwarded-Host")
+                && forwarded_host
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || ".-:[]".contains(c))
+            {
+                host = forwarded_host;
+            }
+            if let Some(forwarded_prefix) = forwarded_header(req, "X-Forwarded-Prefix") {
+                prefix = normalize_path_prefix(&forwarded_prefix);
+            }
+        }
+        format!("{scheme}://{host}{prefix}{path}")
+    }
+}
+
+/// First value of a forwarding header; proxies append to comma separated lists
+fn forwarded_header(req: &HttpRequest, name: &str) -> Option<String> {
+    let value = req.headers().get(name)?.to_str().ok()?;
+    let first = value.split(',').next()?.trim();
+    (!first.is_empty()).then(|| first.to_string())
+}
+
+/// Last entry of `X-Forwarded-For`, the one added by the proxy closest to the server
+fn last_forwarded_for(req: &HttpRequest) -> Option<String> {
+    let value = req.headers().get("X-Forwarded-For")?.to_str().ok()?;
+    let last = value.rsplit(',').next()?.trim();
+    (!last.is_empty()).then(|| last.to_string())
+}
+
+/// Turns `mcp`, `/mcp/` or `/mcp` into `/mcp`, and `/` into an empty prefix
+fn normalize_path_prefix(prefix: &str) -> String {
+    let trimmed = prefix.trim_matches('/');
+    if trimmed.is_empty() {
+        String::new()
+    } else {
+        format!("/{}", trimmed)
+    }
+}
+
+/// Binds a listener for `addr`; IPv6 sockets are IPv6-only so an IPv4 listener on
+/// the same port can coexist with them
+fn bind_listener(addr: SocketAddr) -> std::result::Result<std::net::TcpListener, TransportError> {
+    let bind = || -> std::io::Result<std::net::TcpListener> {
+        let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+        #[cfg(not(windows))]
+        socket.set_reuse_address(true)?;
+        if addr.is_ipv6() {
+            socket.set_only_v6(true)?;
+        }
+        socket.bind(&addr.into())?;
+        socket.listen(1024)?;
+        Ok(socket.into())
+    };
+    bind().map_err(|e| TransportError::new(
+        TransportErrorCode::ConnectionFailed,
+        format!("Failed to bind to {}: {}", addr, e),
+    ))
+}
+
+/// Handle for a server started with [`start_http_server`]
+#[derive(Debug)]
+pub struct HttpServerHandle {
+    local_addrs: Vec<SocketAddr>,
+    sessions: SessionRegistry,
+    draining: Arc<AtomicBool>,
+    metrics: Arc<ServerMetrics>,
+    server: actix_web::dev::ServerHandle,
+    task: tokio::task::JoinHandle<std::io::Result<()>>,
+}
+
+impl HttpServerHandle {
+    /// Returns the addresses the server is listening on, with the actual ports
+    pub fn local_addrs(&self) -> &[SocketAddr] {
+        &self.local_addrs
+    }
+
+    /// Returns the server's open sessions; the registry stays usable while the
+    /// handle is waited on or stopped elsewhere
+    pub fn sessions(&self) -> SessionRegistry {
+        self.sessions.clone()
+    }
+
+    /// Stops the server and closes its connections
+    ///
+    /// Requests in progress are dropped; use [`shutdown`](Self::shutdown) to let them
+    /// finish.
+    pub async fn stop(self) -> Result<()> {
+        self.server.stop(false).await;
+        self.wait().await
+    }
+
+    /// Shuts the server down gracefully
+    ///
+    /// Stops accepting sessions, sends `config.notification` to every session, and
+    /// waits up to `config.grace_period` for the requests they are working on to
+    /// finish. New SSE streams and WebSockets are refused with `503 Service
+    /// Unavailable` meanwhile, while SSE clients can still post to their sessions;
+    /// requests they post are answered with an error. Requests still running are then
+    /// cancelled and answered with an error, the sessions closed
+    /// (SSE streams end and WebSockets are closed as going away), and the server
+    /// stopped.
+    pub async fn shutdown(self, config: ShutdownConfig) -> Result<()> {
+        let deadline = Instant::now() + config.grace_period;
+        self.draining.store(true, Ordering::Relaxed);
+
+        let sessions = self.sessions.list();
+        info!("Shutting down, {} sessions open", sessions.len());
+        if let Some(notification) = &config.notification {
+            self.sessions.send_all(notification).await;
+        }
+
+        let finished = shutdown::drain(deadline, || {
+            sessions.iter().all(|session| session.control.in_flight.is_empty())
+        })
+        .await;
+        if !finished {
+            warn!("Cancelling requests still running after the shutdown grace period");
+        }
+        for session in &sessions {
+            for (id, method) in session.control.in_flight.take() {
+                debug!("Cancelling {} request {} of session {}", method, id, session.id);
+                self.metrics.request_finished(&method, Outcome::Cancelled, None);
+                if let Err(e) = session.transport.send(&shutdown::refused(id)).await {
+                    debug!("Failed to answer cancelled request of session {}: {}", session.id, e);
+                }
+            }
+            session.control.closing.send_replace(true);
+        }
+
+        // Give the sessions a moment to send their close frames before the connections go
+        shutdown::drain(Instant::now() + CLOSE_TIMEOUT, || self.sessions.is_empty()).await;
+        self.stop().await
+    }
+
+    /// Waits until the server stops
+    async fn wait(self) -> Result<()> {
+        self.task.await??;
+        Ok(())
+    }
 }
 
 /// Run a server instance with the specified transport
+///
+/// With `config.tls` set, the server only accepts TLS connections; certificate problems
+/// are reported before anything is bound rather than falling back to plain HTTP.
+/// Runs until the server stops; use [`start_http_server`] to get the bound addresses
+/// and the [`SessionRegistry`] for pushing messages to clients.
 pub async fn run_http_server<F, Fut>(
     config: ServerConfig,
     jwt_secret: Option<String>,
@@ -98,70 +853,170 @@ where
     F: Fn(ServerHttpTransport) -> Fut + Send + Sync + 'static,
     Fut: futures::Future<Output = Result<Box<dyn Server>>> + Send + 'static,
 {
-    let protocol = if config.tls.is_some() { "https" } else { "http" };
-    info!("Starting server on {}://127.0.0.1:{}", protocol, config.port);
-    info!("WebSocket endpoint: {}://127.0.0.1:{}/ws", protocol.replace("http", "ws"), config.port);
-    info!("SSE endpoint: {}://127.0.0.1:{}/sse", protocol, config.port);
+    start_http_server(config, jwt_secret, build_server).await?.wait().await
+}
+
+/// Binds the server to the configured addresses and runs it in the background
+///
+/// # Errors
+/// - `TransportErrorCode::ConfigurationError` if the TLS configuration cannot be loaded
+/// - `TransportErrorCode::ConnectionFailed` if an address cannot be bound
+pub async fn start_http_server<F, Fut>(
+    config: ServerConfig,
+    jwt_secret: Option<String>,
+    build_server: F,
+) -> Result<HttpServerHandle>
+where
+    F: Fn(ServerHttpTransport) -> Fut + Send + Sync + 'static,
+    Fut: futures::Future<Output = Result<Box<dyn Server>>> + Send + 'static,
+{
+    // Load TLS before binding so a bad certificate stops the server from starting
+    let tls_config = config.tls.as_ref().map(load_rustls_config).transpose()?;
+
+    let bind_addrs = if config.bind_addrs.is_empty() {
+        vec![SocketAddr::from(([127, 0, 0, 1], config.port))]
+    } else {
+        config.bind_addrs.clone()
+    };
+    let listeners = bind_addrs
+        .into_iter()
+        .map(bind_listener)
+        .collect::<std::result::Result<Vec<_>, _>>()?;
 
-    let sessions = Arc::new(Mutex::new(HashMap::new()));
+    let sessions: Sessions = Arc::new(Mutex::new(HashMap::new()));
+    let registry = SessionRegistry {
+        sessions: sessions.clone(),
+    };
+    let draining = Arc::new(AtomicBool::new(false));
+    let handle_draining = draining.clone();
+    let metrics = Arc::new(ServerMetrics::default());
+    let handle_metrics = metrics.clone();
 
     // Box the future when creating the Arc
     let build_server =
         Arc::new(move |t| Box::pin(build_server(t)) as futures::future::BoxFuture<_>);
 
+    let endpoints = Endpoints {
+        tls: tls_config.is_some(),
+        public_url: config.public_url.clone(),
+        trust_forwarded_headers: config.trust_forwarded_headers,
+        path_prefix: normalize_path_prefix(&config.path_prefix),
+    };
+    let path_prefix = endpoints.path_prefix.clone();
+    let origin_policy = config.origin_policy.clone().map(|policy| {
+        match config.public_url.as_ref().and_then(|url| url.host_str()) {
+            Some(host) => policy.with_host(host),
+            None => policy,
+        }
+    });
+
     let auth_config = jwt_secret.map(|jwt_secret| AuthConfig { jwt_secret });
     // Configure and run the server
     let mut server = HttpServer::new(move || {
         let cors = if let Some(cors_config) = &config.cors {
-            Cors::default()
-                .allowed_origin(&cors_config.allowed_origin)
+            // Matched origins are echoed back, the same as in the HTTP/2 server, except
+            // that `*` is answered literally and without credentials
+            let allowed_origins = cors_config.allowed_origin.clone();
+            let any_origin = allowed_origins.split(',').any(|allowed| allowed.trim() == "*");
+            let cors = Cors::default()
                 .allow_any_method()
                 .allow_any_header()
-                .supports_credentials()
-                .max_age(cors_config.max_age.unwrap_or(3600))
+                .max_age(cors_config.max_age.unwrap_or(3600));
+            if any_origin {
+                cors.allow_any_origin().send_wildcard()
+            } else {
+                let cors = cors.allowed_origin_fn(move |origin, _| {
+                    origin
+                        .to_str()
+                        .is_ok_and(|origin| origin::origin_allowed(allowed_origins.split(','), origin))
+                });
+                if cors_config.allow_credentials {
+                    cors.supports_credentials()
+                } else {
+                    cors
+                }
+            }
         } else {
             Cors::default()
         };
 
-        App::new()
+        // The monitoring endpoints come first, outside the checks of the MCP endpoints
+        let mut app = App::new()
             .wrap(Logger::default())
-            .wrap(JwtAuth::new(auth_config.clone()))
-            .wrap(cors)
             .app_data(web::Data::new(SessionState {
                 sessions: sessions.clone(),
                 build_server: build_server.clone(),
-                port: config.port,
-            }))
-            .route("/sse", web::get().to(sse_handler))
-            .route("/message", web::post().to(message_handler))
-            .route("/ws", web::get().to(ws_handler))
+                endpoints: endpoints.clone(),
+                ws_heartbeat: config.ws_heartbeat,
+                receive_queue: config.receive_queue,
+                sse_keep_alive: config.sse_keep_alive,
+                limits: config.session_limits,
+                draining: draining.clone(),
+                metrics: metrics.clone(),
+                monitoring: config.monitoring.clone(),
+            }));
+        if config.monitoring.is_some() {
+            for path in [HEALTH_PATH, READY_PATH, METRICS_PATH] {
+                app = app.route(path, web::get().to(monitoring_handler));
+            }
+        }
+        app.service(
+            web::scope(&endpoints.path_prefix)
+                .wrap(JwtAuth::new(auth_config.clone()))
+                .wrap(cors)
+                .wrap(OriginCheck::new(origin_policy.clone()))
+                .route("/sse", web::get().to(sse_handler))
+                .route("/message", web::post().to(message_handler))
+                .route("/ws", web::get().to(ws_handler)),
+        )
     });
 
-    // Add TLS if configured
-    if config.tls.is_some() {
-        // TLS is not fully implemented yet
-        error!("TLS support is not fully implemented yet");
-        info!("Falling back to non-TLS connection");
-        server = server.bind(("127.0.0.1", config.port))?;
-    } else {
-        server = server.bind(("127.0.0.1", config.port))?;
+    // Serve the bound listeners, over TLS if configured
+    for listener in listeners {
+        server = match &tls_config {
+            Some(tls_config) => server.listen_rustls_0_23(listener, tls_config.clone())?,
+            None => server.listen(listener)?,
+        };
+    }
+
+    let local_addrs = server.addrs();
+    let protocol = if tls_config.is_some() { "https" } else { "http" };
+    for addr in &local_addrs {
+        info!("Starting server on {}://{}", protocol, addr);
+        info!("WebSocket endpoint: {}://{}{}/ws", protocol.replace("http", "ws"), addr, path_prefix);
+        info!("SSE endpoint: {}://{}{}/sse", protocol, addr, path_prefix);
     }
 
-    server.run().await?;
-    Ok(())
+    let server = server.run();
+    let handle = server.handle();
+    Ok(HttpServerHandle {
+        local_addrs,
+        sessions: registry,
+        draining: handle_draining,
+        metrics: handle_metrics,
+        server: handle,
+        task: tokio::spawn(server),
+    })
 }
 
 /// Run an HTTP server with the specified parameters
 pub async fn http_server(
     port: u16,
-    sessions: Arc<Mutex<HashMap<String, ServerHttpTransport>>>,
+    sessions: Sessions,
     auth_config: Option<AuthConfig>,
     build_server: BuildServerFn,
 ) -> std::result::Result<(), std::io::Error> {
     let session_state = SessionState {
         sessions,
         build_server,
-        port,
+        endpoints: Endpoints::default(),
+        ws_heartbeat: Some(HeartbeatConfig::default()),
+        receive_queue: QueueConfig::default(),
+        sse_keep_alive: Duration::from_secs(15),
+        limits: SessionLimits::default(),
+        draining: Arc::new(AtomicBool::new(false)),
+        metrics: Arc::new(ServerMetrics::default()),
+        monitoring: None,
     };
 
     let server = HttpServer::new(move || {
@@ -169,6 +1024,7 @@ pub async fn http_server(
         App::new()
             .wrap(Logger::default())
             .wrap(JwtAuth::new(auth_config.clone()))
+            .wrap(OriginCheck::new(Some(OriginPolicy::default())))
             .app_data(web::Data::new(session_state))
             .route("/sse", web::get().to(sse_handler))
             .route("/message", web::post().to(message_handler))
@@ -180,6 +1036,27 @@ pub async fn http_server(
     server.await
 }
 
+/// Serves the health, readiness and metrics endpoints
+async fn monitoring_handler(req: HttpRequest, session_state: web::Data<SessionState>) -> HttpResponse {
+    let (Some(monitoring), Some(probe)) = (&session_state.monitoring, Probe::from_path(req.path())) else {
+        return HttpResponse::NotFound().finish();
+    };
+    let sessions = {
+        let sessions = session_state.sessions.lock().unwrap();
+        monitoring::count_sessions(&SessionKind::LABELS, sessions.values().map(|session| session.kind.label()))
+    };
+    let response = monitoring.respond(
+        probe,
+        req.headers().get("Authorization").and_then(|h| h.to_str().ok()),
+        !session_state.draining.load(Ordering::Relaxed),
+        &session_state.metrics,
+        &sessions,
+    );
+    HttpResponse::build(actix_web::http::StatusCode::from_u16(response.status).unwrap())
+        .content_type(response.content_type)
+        .body(response.body)
+}
+
 /// Handles SSE requests
 ///
 /// # Arguments
@@ -200,14 +1077,20 @@ pub async fn sse_handler(
     let session_id = Uuid::new_v4().to_string();
 
     // Create new SSE transport with responder
-    let (transport, responder) = ServerSseTransport::new_with_responder(100);
+    let (transport, responder) =
+        ServerSseTransport::new_with_keep_alive(100, session_state.sse_keep_alive);
 
     // Store transport in sessions map
-    session_state
-        .sessions
-        .lock()
-        .unwrap()
-        .insert(session_id.clone(), ServerHttpTransport::Sse(transport.clone()));
+    let session = HttpSession::new(
+        &session_id,
+        SessionKind::Sse,
+        ServerHttpTransport::Sse(transport.clone()),
+        &req,
+        &session_state,
+    );
+    if let Err(response) = session_state.register(&session_id, session.clone()) {
+        return Either::Left(*response);
+    }
 
     info!(
         "SSE connection established for {} with session_id {}",
@@ -215,31 +1098,19 @@ pub async fn sse_handler(
     );
 
     // Send initial endpoint info
-    let port = session_state.port;
-    let endpoint_info = format!("http://127.0.0.1:{port}/message?sessionId={session_id}");
+    let endpoint_info = session_state.endpoints.message_url(&req, &session_id);
     if let Err(e) = transport.send_event("endpoint", endpoint_info).await {
         error!("Error sending endpoint info: {}", e);
+        session_state.sessions.lock().unwrap().remove(&session_id);
         return Either::Left(HttpResponse::InternalServerError().finish());
     }
 
-    // Create and spawn the server instance
-    let transport_for_server = transport.clone();
-    let build_server = session_state.build_server.clone();
-    tokio::spawn(async move {
-        match build_server(ServerHttpTransport::Sse(transport_for_server)).await {
-            Ok(_server) => {
-                // The server is already running, no need to call listen
-                debug!("Server created successfully");
-                // Keep the task alive
-                loop {
-                    tokio::time::sleep(std::time::Duration::from_secs(60)).await;
-                }
-            }
-            Err(e) => {
-                error!("Failed to build server: {:?}", e);
-            }
-        }
-    });
+    // Serve the session until the client disconnects
+    let connection = {
+        let transport = transport.clone();
+        async move { transport.disconnected().await }
+    };
+    actix_web::rt::spawn(run_session(session_state.get_ref().clone(), session, connection));
 
     // Return the SSE responder wrapped in Either::Right
     Either::Right(responder)
@@ -253,11 +1124,11 @@ async fn message_handler(
     if let Some(session_id) = &query.session_id {
         let transport = {
             let sessions = session_state.sessions.lock().unwrap();
-            sessions.get(session_id).cloned()
+            sessions.get(session_id).map(|session| session.transport.clone())
         };
         if let Some(transport) = transport {
             match transport {
-                ServerHttpTransport::Sse(sse) => match sse.send_message(message.into_inner()).await
+                ServerHttpTransport::Sse(sse) => match sse.deliver(message.into_inner()).await
                 {
                     Ok(_) => {
                         debug!("Successfully sent message to session {}", session_id);
@@ -286,7 +1157,7 @@ async fn ws_handler(
     body: Payload,
     session_state: web::Data<SessionState>,
 ) -> Result<HttpResponse, actix_web::Error> {
-    let (response, session, msg_stream) = actix_ws::handle(&req, body)?;
+    let (response, session, msg_stream) = handle_ws_upgrade(&req, body)?;
 
     let client_ip = req
         .peer_addr()
@@ -295,36 +1166,38 @@ async fn ws_handler(
 
     info!("New WebSocket connection from {}", client_ip);
 
-    // Create channels for message passing
-    let (tx, rx) = broadcast::channel(100);
-    let transport =
-        ServerHttpTransport::Ws(ServerWsTransport::new(session.clone(), rx.resubscribe()));
+    // Messages from the client are passed to the transport through its receive queue;
+    // messages to the client are written by the transport directly, so the
+    // connection handler's outgoing queue stays empty
+    let outgoing = MessageQueue::new(QueueConfig { capacity: 1, ..Default::default() });
+    let mut ws_config = WsConnectionConfig {
+        heartbeat: session_state.ws_heartbeat,
+        ..Default::default()
+    };
+    if let Some(codec) = negotiate_codec(&req) {
+        ws_config.codec = codec;
+    }
+    let (transport, incoming) = ServerWsTransport::new_with_queue(session.clone(), session_state.receive_queue);
+    let transport = ServerHttpTransport::Ws(
+        transport
+            .with_metrics(ws_config.metrics.clone())
+            .with_codec(ws_config.codec.clone()),
+    );
 
     // Store transport in sessions map
     let session_id = Uuid::new_v4().to_string();
-    session_state
-        .sessions
-        .lock()
-        .unwrap()
-        .insert(session_id, transport.clone());
-
-    // Start WebSocket handling in the background
-    actix_web::rt::spawn(async move {
-        let _ = handle_ws_connection(session, msg_stream, tx.clone(), rx.resubscribe()).await;
-    });
+    let http_session = HttpSession::new(&session_id, SessionKind::WebSocket, transport.clone(), &req, &session_state);
+    if let Err(response) = session_state.register(&session_id, http_session.clone()) {
+        return Ok(*response);
+    }
 
-    // Spawn server instance
-    let build_server = session_state.build_server.clone();
-    actix_web::rt::spawn(async move {
-        if let Ok(_server) = build_server(transport).await {
-            // The server is already running, no need to call listen
-            debug!("WebSocket server created successfully");
-            // Keep the task alive
-            loop {
-                tokio::time::sleep(std::time::Duration::from_secs(60)).await;
-            }
+    // Serve the session until the WebSocket closes
+    let connection = async move {
+        if let Err(e) = handle_ws_connection_with(session, msg_stream, incoming, outgoing, ws_config).await {
+            debug!("WebSocket connection ended with error: {}", e);
         }
-    });
+    };
+    actix_web::rt::spawn(run_session(session_state.get_ref().clone(), http_session, connection));
 
     Ok(response)
 }
\ No newline at end of file
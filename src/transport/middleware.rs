@@ -7,6 +7,9 @@ use futures::future::LocalBoxFuture;
 use jsonwebtoken::{decode, DecodingKey, Validation};
 use serde::{Deserialize, Serialize};
 use std::future::{ready, Ready};
+use tracing::warn;
+
+use super::OriginPolicy;
 
 /// Claims in a JWT token
 #[derive(Debug, Serialize, Deserialize)]
@@ -123,3 +126,84 @@ where
         }
     }
 }
+
+
+/// Rejects requests whose `Origin` or `Host` header the policy does not allow with
+/// `403 Forbidden`, before they reach any handler
+pub struct OriginCheck(Option<OriginPolicy>);
+
+impl OriginCheck {
+    /// Creates an origin check; `None` accepts every request
+    pub fn new(policy: Option<OriginPolicy>) -> Self {
+        OriginCheck(policy)
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for OriginCheck
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = OriginCheckMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(OriginCheckMiddleware {
+            service,
+            policy: self.0.clone(),
+        }))
+    }
+}
+
+/// Middleware for validating the `Origin` and `Host` headers
+pub struct OriginCheckMiddleware<S> {
+    service: S,
+    policy: Option<OriginPolicy>,
+}
+
+impl<S, B> Service<ServiceRequest> for OriginCheckMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        if let Some(policy) = &self.policy {
+            let header = |name| req.headers().get(name).and_then(|h| h.to_str().ok());
+            // HTTP/2 carries the host as the `:authority` of the URI, not as a header
+            let host = req
+                .uri()
+                .authority()
+                .map(|authority| authority.as_str())
+                .or_else(|| header("Host"));
+            let origin = header("Origin");
+            if !policy.allows(origin, host) {
+                warn!(
+                    "Rejecting request from origin {} for host {}",
+                    origin.unwrap_or("-"),
+                    host.unwrap_or("-")
+                );
+                let (req, _) = req.into_parts();
+                return Box::pin(async move {
+                    Ok(
+                        ServiceResponse::new(req, HttpResponse::Forbidden().body("Origin not allowed"))
+                            .map_into_right_body(),
+                    )
+                });
+            }
+        }
+
+        let fut = self.service.call(req);
+        Box::pin(async move { fut.await.map(ServiceResponse::map_into_left_body) })
+    }
+}
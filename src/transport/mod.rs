@@ -8,16 +8,41 @@ use serde::{Deserialize, Serialize};
 mod error;
 pub use error::{TransportError, TransportErrorCode};
 
+mod origin;
+pub use origin::OriginPolicy;
+
 pub mod middleware;
-pub use self::middleware::{AuthConfig, JwtAuth};
+pub use self::middleware::{AuthConfig, JwtAuth, OriginCheck};
 
 /// Result type for transport operations
 pub type Result<T> = std::result::Result<T, TransportError>;
 
+mod codec;
+pub use codec::*;
+mod queue;
+pub use queue::*;
 mod stdio;
 pub use stdio::*;
+mod process;
+pub use process::*;
+#[cfg(unix)]
+mod unix;
+#[cfg(unix)]
+pub use unix::*;
+mod tcp;
+pub use tcp::*;
 mod inmemory;
 pub use inmemory::*;
+mod bridge;
+pub(crate) use bridge::session_io;
+mod tls_reload;
+pub(crate) use tls_reload::ReloadingCertResolver;
+mod shutdown;
+pub use shutdown::ShutdownConfig;
+mod monitoring;
+pub use monitoring::{HEALTH_PATH, METRICS_PATH, MonitoringConfig, READY_PATH};
+mod validation;
+pub use validation::*;
 
 #[cfg(feature = "sse")]
 pub mod sse;
@@ -77,6 +102,14 @@ pub trait Transport: Send + Sync + 'static {
     async fn close(&self) -> Result<()>;
 }
 
+/// Pauses an accept loop after a failed `accept`
+///
+/// Accept errors are usually transient (e.g. out of file descriptors), so listeners
+/// back off briefly and keep accepting instead of shutting down.
+pub(crate) async fn accept_backoff() {
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+}
+
 /// Request ID type
 pub type RequestId = u64;
 
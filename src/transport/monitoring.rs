@@ -0,0 +1,375 @@
+//! Health, readiness and metrics endpoints of the HTTP servers
+//!
+//! Servers answer `/healthz` while they run, `/readyz` until they start shutting down,
+//! and `/metrics` with their metrics in the Prometheus text format. The endpoints are
+//! served outside the JWT authentication and origin checks of the MCP endpoints, so
+//! orchestrators can probe them, and can be given their own bearer token instead.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::{JsonRpcMessage, Message, TransportError};
+
+/// Path of the liveness probe
+pub const HEALTH_PATH: &str = "/healthz";
+/// Path of the readiness probe
+pub const READY_PATH: &str = "/readyz";
+/// Path of the Prometheus metrics
+pub const METRICS_PATH: &str = "/metrics";
+
+/// Upper bounds of the request latency histogram buckets, in seconds
+const LATENCY_BUCKETS: [f64; 12] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+/// Number of distinct methods labelled in metrics; further ones, which clients can make
+/// up at will, are counted as `other`
+const MAX_METHOD_LABELS: usize = 100;
+
+/// Content type of the Prometheus text format
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// How a server serves its health, readiness and metrics endpoints
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MonitoringConfig {
+    /// Token the endpoints require as `Authorization: Bearer <token>`, independent of
+    /// the JWT authentication of the MCP endpoints; `None` serves them to anyone
+    pub bearer_token: Option<String>,
+}
+
+impl MonitoringConfig {
+    /// Answers a request for `probe` with the given `Authorization` header
+    ///
+    /// `ready` is whether the server accepts new sessions, and `sessions` its open
+    /// sessions by transport.
+    pub(crate) fn respond(
+        &self,
+        probe: Probe,
+        authorization: Option<&str>,
+        ready: bool,
+        metrics: &ServerMetrics,
+        sessions: &[(&str, usize)],
+    ) -> ProbeResponse {
+        if let Some(token) = &self.bearer_token {
+            let presented = authorization.and_then(|value| value.strip_prefix("Bearer "));
+            if !presented.is_some_and(|presented| constant_time_eq(presented.as_bytes(), token.as_bytes())) {
+                return ProbeResponse::text(401, "Unauthorized");
+            }
+        }
+        match probe {
+            Probe::Health => ProbeResponse::text(200, "ok"),
+            Probe::Ready if ready => ProbeResponse::text(200, "ready"),
+            Probe::Ready => ProbeResponse::text(503, "shutting down"),
+            Probe::Metrics => ProbeResponse {
+                status: 200,
+                content_type: METRICS_CONTENT_TYPE,
+                body: metrics.render(sessions),
+            },
+        }
+    }
+}
+
+/// Compares secrets in time independent of where they differ
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// An endpoint served by [`MonitoringConfig::respond`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Probe {
+    Health,
+    Ready,
+    Metrics,
+}
+
+impl Probe {
+    /// Returns the endpoint served at `path`, if any
+    pub(crate) fn from_path(path: &str) -> Option<Self> {
+        match path {
+            HEALTH_PATH => Some(Self::Health),
+            READY_PATH => Some(Self::Ready),
+            METRICS_PATH => Some(Self::Metrics),
+            _ => None,
+        }
+    }
+}
+
+/// Response to a probe, for the server to send in its HTTP library's types
+#[derive(Debug)]
+pub(crate) struct ProbeResponse {
+    pub(crate) status: u16,
+    pub(crate) content_type: &'static str,
+    pub(crate) body: String,
+}
+
+impl ProbeResponse {
+    fn text(status: u16, body: &str) -> Self {
+        Self {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: body.to_string(),
+        }
+    }
+}
+
+/// How a request ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Outcome {
+    /// Answered with a result
+    Success,
+    /// Answered with an error, or not answered because the session failed
+    Error,
+    /// Cancelled during shutdown
+    Cancelled,
+}
+
+impl Outcome {
+    /// Outcome of the request `response` answers
+    pub(crate) fn of(response: &Message) -> Self {
+        match response {
+            JsonRpcMessage::Response(response) if response.error.is_none() => Self::Success,
+            JsonRpcMessage::Response(_) => Self::Error,
+            _ => Self::Cancelled,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Success => "success",
+            Self::Error => "error",
+            Self::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// Request latencies of one method
+#[derive(Debug, Default)]
+struct Histogram {
+    /// Observations per bucket of [`LATENCY_BUCKETS`], not cumulative
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+}
+
+#[derive(Debug, Default)]
+struct MetricsState {
+    /// Answered requests by method and outcome
+    requests: BTreeMap<(String, Outcome), u64>,
+    latency: BTreeMap<String, Histogram>,
+    /// Message bytes by transport
+    bytes_received: BTreeMap<&'static str, u64>,
+    bytes_sent: BTreeMap<&'static str, u64>,
+    /// Transport errors by `TransportErrorCode`
+    errors: BTreeMap<String, u64>,
+}
+
+impl MetricsState {
+    /// Label for `method`, bounding the number of distinct labels
+    fn method_label(&self, method: &str) -> String {
+        if self.latency.contains_key(method) || self.latency.len() < MAX_METHOD_LABELS {
+            method.to_string()
+        } else {
+            "other".to_string()
+        }
+    }
+}
+
+/// Metrics of a server's sessions, shared by them
+#[derive(Debug, Default)]
+pub(crate) struct ServerMetrics {
+    state: Mutex<MetricsState>,
+}
+
+impl ServerMetrics {
+    /// Records a request to `method` that ended with `outcome` after `latency`, if it
+    /// reached the session's server
+    pub(crate) fn request_finished(&self, method: &str, outcome: Outcome, latency: Option<Duration>) {
+        let mut state = self.state.lock().unwrap();
+        let method = state.method_label(method);
+        let histogram = state.latency.entry(method.clone()).or_default();
+        if let Some(latency) = latency {
+            histogram.observe(latency.as_secs_f64());
+        }
+        *state.requests.entry((method, outcome)).or_default() += 1;
+    }
+
+    /// Records `bytes` received over `transport`
+    pub(crate) fn received(&self, transport: &'static str, bytes: usize) {
+        *self.state.lock().unwrap().bytes_received.entry(transport).or_default() += bytes as u64;
+    }
+
+    /// Records `bytes` sent over `transport`
+    pub(crate) fn sent(&self, transport: &'static str, bytes: usize) {
+        *self.state.lock().unwrap().bytes_sent.entry(transport).or_default() += bytes as u64;
+    }
+
+    /// Records a transport error
+    pub(crate) fn error(&self, error: &TransportError) {
+        let code = match error.code() {
+            Some(code) => format!("{:?}", code),
+            None => "Unknown".to_string(),
+        };
+        *self.state.lock().unwrap().errors.entry(code).or_default() += 1;
+    }
+
+    /// Renders the metrics, with `sessions` open by transport, in the Prometheus text
+    /// format
+    pub(crate) fn render(&self, sessions: &[(&str, usize)]) -> String {
+        let state = self.state.lock().unwrap();
+        let mut out = String::new();
+
+        header(&mut out, "mcp_sessions_active", "gauge", "Open sessions by transport");
+        for (transport, count) in sessions {
+            sample(&mut out, "mcp_sessions_active", &[("transport", transport)], *count);
+        }
+
+        header(&mut out, "mcp_requests_total", "counter", "Requests by MCP method and outcome");
+        for ((method, outcome), count) in &state.requests {
+            sample(&mut out, "mcp_requests_total", &[("method", method), ("outcome", outcome.label())], *count);
+        }
+
+        header(
+            &mut out,
+            "mcp_request_duration_seconds",
+            "histogram",
+            "Time from receiving a request to sending its response, by MCP method",
+        );
+        for (method, histogram) in &state.latency {
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(histogram.buckets) {
+                cumulative += count;
+                let le = bound.to_string();
+                sample(&mut out, "mcp_request_duration_seconds_bucket", &[("method", method), ("le", &le)], cumulative);
+            }
+            sample(&mut out, "mcp_request_duration_seconds_bucket", &[("method", method), ("le", "+Inf")], histogram.count);
+            sample(&mut out, "mcp_request_duration_seconds_sum", &[("method", method)], histogram.sum);
+            sample(&mut out, "mcp_request_duration_seconds_count", &[("method", method)], histogram.count);
+        }
+
+        for (name, help, bytes) in [
+            ("mcp_received_bytes_total", "Bytes of messages received by transport", &state.bytes_received),
+            ("mcp_sent_bytes_total", "Bytes of messages sent by transport", &state.bytes_sent),
+        ] {
+            header(&mut out, name, "counter", help);
+            for (transport, count) in bytes {
+                sample(&mut out, name, &[("transport", transport)], *count);
+            }
+        }
+
+        header(&mut out, "mcp_transport_errors_total", "counter", "Transport errors by TransportErrorCode");
+        for (code, count) in &state.errors {
+            sample(&mut out, "mcp_transport_errors_total", &[("code", code)], *count);
+        }
+        out
+    }
+}
+
+/// Writes the `HELP` and `TYPE` lines of a metric
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Writes a sample line with escaped label values
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+    let labels: Vec<String> = labels
+        .iter()
+        .map(|(label, value)| {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+            format!("{}=\"{}\"", label, value)
+        })
+        .collect();
+    let _ = writeln!(out, "{}{{{}}} {}", name, labels.join(","), value);
+}
+
+/// Number of sessions by transport label, including transports without sessions
+pub(crate) fn count_sessions<'a>(
+    transports: &[&'static str],
+    sessions: impl IntoIterator<Item = &'a str>,
+) -> Vec<(&'static str, usize)> {
+    let mut counts: HashMap<&str, usize> = transports.iter().map(|transport| (*transport, 0)).collect();
+    for transport in sessions {
+        if let Some(count) = counts.get_mut(transport) {
+            *count += 1;
+        }
+    }
+    transports.iter().map(|transport| (*transport, counts[transport])).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{JsonRpcError, JsonRpcResponse, JsonRpcVersion, TransportErrorCode};
+
+    fn response(error: bool) -> Message {
+        JsonRpcMessage::Response(JsonRpcResponse {
+            id: 1,
+            result: (!error).then(|| serde_json::json!({})),
+            error: error.then(|| JsonRpcError {
+                code: -32603,
+                message: "failed".to_string(),
+                data: None,
+            }),
+            jsonrpc: JsonRpcVersion::default(),
+        })
+    }
+
+    #[test]
+    fn test_metrics_render_in_prometheus_text_format() {
+        let metrics = ServerMetrics::default();
+        metrics.request_finished("tools/call", Outcome::of(&response(false)), Some(Duration::from_millis(20)));
+        metrics.request_finished("tools/call", Outcome::of(&response(true)), Some(Duration::from_secs(60)));
+        metrics.request_finished("tools/\"call\"", Outcome::Cancelled, None);
+        metrics.received("sse", 10);
+        metrics.sent("sse", 32);
+        metrics.error(&TransportError::new(TransportErrorCode::SessionExpired, "idle"));
+
+        let text = metrics.render(&count_sessions(&["sse", "websocket"], ["sse", "sse", "http2"]));
+        for line in [
+            "# TYPE mcp_sessions_active gauge",
+            "mcp_sessions_active{transport=\"sse\"} 2",
+            "mcp_sessions_active{transport=\"websocket\"} 0",
+            "mcp_requests_total{method=\"tools/call\",outcome=\"success\"} 1",
+            "mcp_requests_total{method=\"tools/call\",outcome=\"error\"} 1",
+            "mcp_requests_total{method=\"tools/\\\"call\\\"\",outcome=\"cancelled\"} 1",
+            "# TYPE mcp_request_duration_seconds histogram",
+            "mcp_request_duration_seconds_bucket{method=\"tools/call\",le=\"0.01\"} 0",
+            "mcp_request_duration_seconds_bucket{method=\"tools/call\",le=\"0.025\"} 1",
+            "mcp_request_duration_seconds_bucket{method=\"tools/call\",le=\"30\"} 1",
+            "mcp_request_duration_seconds_bucket{method=\"tools/call\",le=\"+Inf\"} 2",
+            "mcp_request_duration_seconds_count{method=\"tools/call\"} 2",
+            "mcp_received_bytes_total{transport=\"sse\"} 10",
+            "mcp_sent_bytes_total{transport=\"sse\"} 32",
+            "mcp_transport_errors_total{code=\"SessionExpired\"} 1",
+        ] {
+            assert!(text.lines().any(|l| l == line), "missing {:?} in\n{}", line, text);
+        }
+    }
+
+    #[test]
+    fn test_bearer_token_is_required_when_configured() {
+        let metrics = ServerMetrics::default();
+        let open = MonitoringConfig::default();
+        assert_eq!(open.respond(Probe::Health, None, true, &metrics, &[]).status, 200);
+        assert_eq!(open.respond(Probe::Ready, None, false, &metrics, &[]).status, 503);
+
+        let protected = MonitoringConfig {
+            bearer_token: Some("secret".to_string()),
+        };
+        assert_eq!(protected.respond(Probe::Metrics, None, true, &metrics, &[]).status, 401);
+        assert_eq!(protected.respond(Probe::Metrics, Some("Bearer secrez"), true, &metrics, &[]).status, 401);
+        let response = protected.respond(Probe::Metrics, Some("Bearer secret"), true, &metrics, &[]);
+        assert_eq!(response.status, 200);
+        assert_eq!(response.content_type, METRICS_CONTENT_TYPE);
+    }
+}
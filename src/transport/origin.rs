@@ -0,0 +1,187 @@
+//! Validation of the `Origin` and `Host` headers of requests to HTTP servers
+//!
+//! A web page can make the browser showing it send requests to servers on the user's
+//! machine, and through DNS rebinding even read the responses. Servers check that
+//! requests name an allowed host and, when sent by a browser, come from an allowed
+//! origin before serving them.
+
+use url::Url;
+
+/// The origins and hosts a server accepts requests from and for
+///
+/// The default only accepts requests addressed to `localhost`, `127.0.0.1` or `[::1]`,
+/// from pages served by those hosts or from clients that send no `Origin`, such as
+/// non-browser MCP clients.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OriginPolicy {
+    /// Origins browsers may send requests from, as `scheme://host[:port]`, e.g.
+    /// `https://app.example.com`; a host of `*.example.com` matches its subdomains,
+    /// a port of `*` any port, and `*` any origin
+    pub allowed_origins: Vec<String>,
+    /// Host names requests may be addressed to in their `Host` header (or HTTP/2
+    /// `:authority`), without port; `*` allows any
+    pub allowed_hosts: Vec<String>,
+    /// Whether requests without an `Origin` header are accepted
+    pub allow_missing_origin: bool,
+}
+
+impl Default for OriginPolicy {
+    fn default() -> Self {
+        let hosts = ["localhost", "127.0.0.1", "[::1]"];
+        Self {
+            allowed_origins: ["http", "https"]
+                .iter()
+                .flat_map(|scheme| hosts.iter().map(move |host| format!("{}://{}:*", scheme, host)))
+                .collect(),
+            allowed_hosts: hosts.iter().map(|host| host.to_string()).collect(),
+            allow_missing_origin: true,
+        }
+    }
+}
+
+impl OriginPolicy {
+    /// Also accepts requests from pages served by `origin`
+    pub fn with_origin(mut self, origin: impl Into<String>) -> Self {
+        self.allowed_origins.push(origin.into());
+        self
+    }
+
+    /// Also accepts requests addressed to `host`
+    pub fn with_host(mut self, host: impl Into<String>) -> Self {
+        self.allowed_hosts.push(host.into());
+        self
+    }
+
+    /// Returns whether a request with these `Origin` and `Host` header values is
+    /// accepted
+    pub fn allows(&self, origin: Option<&str>, host: Option<&str>) -> bool {
+        self.allows_host(host) && self.allows_origin(origin)
+    }
+
+    fn allows_host(&self, host: Option<&str>) -> bool {
+        if self.allowed_hosts.iter().any(|allowed| allowed == "*") {
+            return true;
+        }
+        let Some(host) = host.and_then(host_name) else {
+            return false;
+        };
+        self.allowed_hosts
+            .iter()
+            .filter_map(|allowed| host_name(allowed))
+            .any(|allowed| allowed == host)
+    }
+
+    fn allows_origin(&self, origin: Option<&str>) -> bool {
+        match origin {
+            Some(origin) => origin_allowed(self.allowed_origins.iter().map(String::as_str), origin),
+            None => self.allow_missing_origin,
+        }
+    }
+}
+
+/// Whether `origin` matches one of the `scheme://host[:port]` patterns in `allowed`
+///
+/// Hosts of the form `*.example.com` match any subdomain of `example.com`, a port of
+/// `*` matches any port, and a pattern of `*` matches any origin.
+pub(crate) fn origin_allowed<'a>(allowed: impl IntoIterator<Item = &'a str>, origin: &str) -> bool {
+    // Opaque origins (`null`) do not parse, and only match `*`
+    let origin = Url::parse(origin).ok();
+    allowed.into_iter().map(str::trim).any(|allowed| {
+        allowed == "*" || origin.as_ref().is_some_and(|origin| origin_matches(allowed, origin))
+    })
+}
+
+/// Lowercase host of a `host[:port]` authority, without the brackets of IPv6 addresses
+fn host_name(authority: &str) -> Option<String> {
+    let authority = authority.trim().to_ascii_lowercase();
+    let host = match authority.strip_prefix('[') {
+        Some(rest) => rest.split(']').next()?,
+        None => authority.split(':').next()?,
+    };
+    (!host.is_empty()).then(|| host.to_string())
+}
+
+/// Whether `origin` matches the `scheme://host[:port]` pattern `allowed`
+fn origin_matches(allowed: &str, origin: &Url) -> bool {
+    let Some((scheme, authority)) = allowed.split_once("://") else {
+        return false;
+    };
+    if !scheme.eq_ignore_ascii_case(origin.scheme()) {
+        return false;
+    }
+
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => (host, Some(port)),
+        _ => (authority, None),
+    };
+    let (Some(host), Some(origin_host)) = (host_name(host), origin.host_str().and_then(host_name)) else {
+        return false;
+    };
+    let host_matches = match host.strip_prefix("*.") {
+        Some(domain) => origin_host
+            .strip_suffix(domain)
+            .is_some_and(|subdomain| subdomain.len() > 1 && subdomain.ends_with('.')),
+        None => origin_host == host,
+    };
+    if !host_matches {
+        return false;
+    }
+    match port {
+        Some("*") => true,
+        Some(port) => port.parse().ok() == origin.port_or_known_default(),
+        None => origin.port().is_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_policy_only_accepts_localhost() {
+        let policy = OriginPolicy::default();
+        assert!(policy.allows(None, Some("localhost:8080")));
+        assert!(policy.allows(None, Some("127.0.0.1")));
+        assert!(policy.allows(None, Some("[::1]:8080")));
+        assert!(policy.allows(Some("http://localhost:3000"), Some("localhost:8080")));
+        assert!(policy.allows(Some("https://[::1]"), Some("[::1]:8080")));
+
+        // A rebound name resolving to 127.0.0.1 still carries it in `Host`
+        assert!(!policy.allows(None, Some("attacker.example:8080")));
+        assert!(!policy.allows(None, None));
+        assert!(!policy.allows(Some("http://attacker.example"), Some("localhost:8080")));
+        assert!(!policy.allows(Some("null"), Some("localhost:8080")));
+        assert!(!policy.allows(Some("http://localhost.attacker.example"), Some("localhost")));
+    }
+
+    #[test]
+    fn test_origins_match_scheme_host_and_port() {
+        let policy = OriginPolicy {
+            allowed_origins: vec!["https://app.example.com".to_string()],
+            allowed_hosts: vec!["*".to_string()],
+            allow_missing_origin: false,
+        };
+        assert!(policy.allows(Some("https://app.example.com"), None));
+        assert!(policy.allows(Some("https://APP.example.com:443"), None));
+        assert!(!policy.allows(Some("http://app.example.com"), None));
+        assert!(!policy.allows(Some("https://app.example.com:8443"), None));
+        assert!(!policy.allows(None, None));
+
+        let policy = policy.with_origin("https://app.example.com:8443");
+        assert!(policy.allows(Some("https://app.example.com:8443"), None));
+    }
+
+    #[test]
+    fn test_wildcard_subdomains() {
+        let allowed = ["https://*.example.com", "http://*.dev.example.com:*"];
+        assert!(origin_allowed(allowed, "https://app.example.com"));
+        assert!(origin_allowed(allowed, "https://a.b.example.com"));
+        assert!(origin_allowed(allowed, "http://app.dev.example.com:3000"));
+        assert!(!origin_allowed(allowed, "https://example.com"));
+        assert!(!origin_allowed(allowed, "https://attacker-example.com"));
+        assert!(!origin_allowed(allowed, "https://example.com.attacker.example"));
+        assert!(!origin_allowed(allowed, "http://app.example.com"));
+        assert!(!origin_allowed(allowed, "null"));
+        assert!(origin_allowed(["*"], "null"));
+    }
+}
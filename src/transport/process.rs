@@ -0,0 +1,349 @@
+use async_trait::async_trait;
+use std::collections::VecDeque;
+use std::process::{ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::pin::Pin;
+use std::sync::{Arc, OnceLock};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWrite, BufReader};
+use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+use crate::transport::{Codec, Framing, StdioTransport, Transport, Message, Result, TransportError, TransportErrorCode, default_codec};
+
+/// Number of stderr lines kept for error reports
+const STDERR_TAIL_LINES: usize = 10;
+
+/// Callback invoked with each line the child process writes to stderr
+pub type StderrHandler = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// Transport that launches an MCP server process and talks to it over stdio
+///
+/// The child is spawned by [`open`](Transport::open) and owned by the transport. Lines
+/// written to stderr are forwarded to `tracing`, or to a callback set with
+/// [`with_stderr_handler`](Self::with_stderr_handler).
+///
+/// [`close`](Transport::close) closes the child's stdin and waits for the grace period;
+/// if the child is still running it is sent SIGTERM (on Unix), and if it outlives
+/// another grace period it is killed. If the child exits on its own, `receive` fails
+/// with [`TransportErrorCode::ConnectionClosed`] describing the exit status and the
+/// last lines of stderr.
+pub struct ChildProcessTransport {
+    command: std::sync::Mutex<Option<Command>>,
+    io: OnceLock<StdioTransport<ChildStdout, OwnedStdin>>,
+    child: Mutex<Option<Child>>,
+    pid: OnceLock<u32>,
+    exit_status: std::sync::Mutex<Option<ExitStatus>>,
+    stderr_handler: Option<StderrHandler>,
+    stderr_tail: Arc<std::sync::Mutex<VecDeque<String>>>,
+    stderr_reader: std::sync::Mutex<Option<JoinHandle<()>>>,
+    grace_period: Duration,
+    buffer_size: Option<usize>,
+    framing: Framing,
+    codec: Arc<dyn Codec>,
+    closing: AtomicBool,
+}
+
+impl ChildProcessTransport {
+    /// Creates a transport that will spawn `command` when opened
+    ///
+    /// The command's stdin, stdout and stderr are replaced with pipes.
+    pub fn new(command: Command) -> Self {
+        Self {
+            command: std::sync::Mutex::new(Some(command)),
+            io: OnceLock::new(),
+            child: Mutex::new(None),
+            pid: OnceLock::new(),
+            exit_status: std::sync::Mutex::new(None),
+            stderr_handler: None,
+            stderr_tail: Arc::new(std::sync::Mutex::new(VecDeque::new())),
+            stderr_reader: std::sync::Mutex::new(None),
+            grace_period: Duration::from_secs(5),
+            buffer_size: None,
+            framing: Framing::Auto,
+            codec: default_codec(),
+            closing: AtomicBool::new(false),
+        }
+    }
+
+    /// Sets a callback that receives each stderr line instead of `tracing`
+    pub fn with_stderr_handler(mut self, handler: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        self.stderr_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Sets how long `close` waits after each shutdown step (default: 5 seconds)
+    pub fn with_grace_period(mut self, grace_period: Duration) -> Self {
+        self.grace_period = grace_period;
+        self
+    }
+
+    /// Sets the maximum length of a single incoming message
+    pub fn with_buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = Some(buffer_size);
+        self
+    }
+
+    /// Sets the framing used on the child's stdio (default: [`Framing::Auto`])
+    pub fn with_framing(mut self, framing: Framing) -> Self {
+        self.framing = framing;
+        self
+    }
+
+    /// Sets the message codec used on the child's stdio (default: JSON)
+    pub fn with_codec(mut self, codec: Arc<dyn Codec>) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// Returns the process id of the child, once spawned
+    pub fn id(&self) -> Option<u32> {
+        self.pid.get().copied()
+    }
+
+    /// Returns the exit status of the child, once it has exited
+    pub fn exit_status(&self) -> Option<ExitStatus> {
+        *self.exit_status.lock().unwrap()
+    }
+
+    fn io(&self) -> Result<&StdioTransport<ChildStdout, OwnedStdin>> {
+        self.io.get().ok_or_else(|| TransportError::new(
+            TransportErrorCode::InvalidState,
+            "Child process transport not opened"
+        ))
+    }
+
+    fn spawn_stderr_reader(&self, stderr: ChildStderr, pid: u32) {
+        let handler = self.stderr_handler.clone();
+        let tail = self.stderr_tail.clone();
+        let reader = tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                {
+                    let mut tail = tail.lock().unwrap();
+                    if tail.len() == STDERR_TAIL_LINES {
+                        tail.pop_front();
+                    }
+                    tail.push_back(line.clone());
+                }
+                match &handler {
+                    Some(handler) => handler(&line),
+                    None => info!("[pid {}] {}", pid, line),
+                }
+            }
+        });
+        *self.stderr_reader.lock().unwrap() = Some(reader);
+    }
+
+    /// Waits up to `timeout` for the child to exit and records its status
+    async fn wait_for_exit(&self, child: &mut Child, timeout: Duration) -> Option<ExitStatus> {
+        let status = tokio::time::timeout(timeout, child.wait()).await.ok()?.ok()?;
+        *self.exit_status.lock().unwrap() = Some(status);
+        Some(status)
+    }
+
+    fn exit_error(&self, status: Option<ExitStatus>) -> TransportError {
+        let mut message = match status {
+            Some(status) => format!("Server process exited with {}", status),
+            None => "Server process closed its stdout".to_string(),
+        };
+        let tail = self.stderr_tail.lock().unwrap();
+        if !tail.is_empty() {
+            message.push_str("; stderr: ");
+            message.push_str(&tail.iter().cloned().collect::<Vec<_>>().join("\n"));
+        }
+        TransportError::new(TransportErrorCode::ConnectionClosed, message)
+    }
+}
+
+/// Child stdin that is closed on shutdown
+///
+/// Shutting down a `ChildStdin` does not close the pipe, so the child would never
+/// see end of file; this wrapper drops the handle instead.
+struct OwnedStdin(Option<ChildStdin>);
+
+impl AsyncWrite for OwnedStdin {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        match self.0.as_mut() {
+            Some(stdin) => Pin::new(stdin).poll_write(cx, buf),
+            None => Poll::Ready(Err(std::io::ErrorKind::BrokenPipe.into())),
+        }
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self.0.as_mut() {
+            Some(stdin) => Pin::new(stdin).poll_flush(cx),
+            None => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let result = self.as_mut().poll_flush(cx);
+        if result.is_ready() {
+            self.0 = None;
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl Transport for ChildProcessTransport {
+    async fn send(&self, message: &Message) -> Result<()> {
+        self.io()?.send(message).await
+    }
+
+    async fn receive(&self) -> Result<Option<Message>> {
+        match self.io()?.receive().await? {
+            Some(message) => Ok(Some(message)),
+            None if self.closing.load(Ordering::Relaxed) => Ok(None),
+            None => {
+                let status = match self.child.lock().await.as_mut() {
+                    Some(child) => self.wait_for_exit(child, self.grace_period).await,
+                    None => self.exit_status(),
+                };
+                // Let the stderr reader catch up so the error includes the last lines
+                let reader = self.stderr_reader.lock().unwrap().take();
+                if let Some(reader) = reader {
+                    let _ = tokio::time::timeout(self.grace_period, reader).await;
+                }
+                Err(self.exit_error(status))
+            }
+        }
+    }
+
+    async fn open(&self) -> Result<()> {
+        let Some(mut command) = self.command.lock().unwrap().take() else {
+            return Err(TransportError::new(
+                TransportErrorCode::InvalidState,
+                "Child process was already spawned"
+            ));
+        };
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| TransportError::new(
+                TransportErrorCode::ConnectionFailed,
+                format!("Failed to spawn server process: {}", e)
+            ))?;
+        let pid = child.id().unwrap_or_default();
+        debug!("Spawned server process {}", pid);
+        let _ = self.pid.set(pid);
+
+        let stdin = OwnedStdin(child.stdin.take());
+        let stdout = child.stdout.take().expect("stdout is piped");
+        if let Some(stderr) = child.stderr.take() {
+            self.spawn_stderr_reader(stderr, pid);
+        }
+        let io = match self.buffer_size {
+            Some(buffer_size) => StdioTransport::with_buffer_size(stdout, stdin, buffer_size),
+            None => StdioTransport::new(stdout, stdin),
+        };
+        let _ = self.io.set(io.with_framing(self.framing).with_codec(self.codec.clone()));
+        *self.child.lock().await = Some(child);
+        Ok(())
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.closing.store(true, Ordering::Relaxed);
+        if let Ok(io) = self.io() {
+            // Closing stdin asks a well-behaved server to exit
+            if let Err(e) = io.close().await {
+                debug!("Failed to close server stdin: {}", e);
+            }
+        }
+
+        let mut guard = self.child.lock().await;
+        let Some(child) = guard.as_mut() else {
+            return Ok(());
+        };
+        if self.wait_for_exit(child, self.grace_period).await.is_some() {
+            return Ok(());
+        }
+
+        #[cfg(unix)]
+        if let Some(pid) = child.id() {
+            use nix::sys::signal::{Signal, kill};
+            use nix::unistd::Pid;
+            warn!("Server process {} did not exit after stdin was closed, sending SIGTERM", pid);
+            if kill(Pid::from_raw(pid as i32), Signal::SIGTERM).is_ok()
+                && self.wait_for_exit(child, self.grace_period).await.is_some()
+            {
+                return Ok(());
+            }
+        }
+
+        warn!("Killing server process {}", child.id().unwrap_or_default());
+        child.kill().await.map_err(|e| TransportError::new(
+            TransportErrorCode::CloseError,
+            format!("Failed to kill server process: {}", e)
+        ))?;
+        self.wait_for_exit(child, self.grace_period).await;
+        Ok(())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::transport::{JsonRpcMessage, JsonRpcNotification, JsonRpcVersion};
+    use std::time::Instant;
+
+    fn sh(script: &str) -> Command {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(script);
+        command
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_and_close() -> Result<()> {
+        let transport = ChildProcessTransport::new(sh("cat"));
+        transport.open().await?;
+        let message = JsonRpcMessage::Notification(JsonRpcNotification {
+            method: "notifications/initialized".to_string(),
+            params: None,
+            jsonrpc: JsonRpcVersion::default(),
+        });
+        transport.send(&message).await?;
+        assert_eq!(transport.receive().await?, Some(message));
+
+        transport.close().await?;
+        assert!(transport.exit_status().unwrap().success());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_exit_status_and_stderr() -> Result<()> {
+        let lines = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let captured = lines.clone();
+        let transport = ChildProcessTransport::new(sh("echo oops >&2; exit 3"))
+            .with_stderr_handler(move |line| captured.lock().unwrap().push(line.to_string()));
+        transport.open().await?;
+
+        let err = transport.receive().await.unwrap_err();
+        assert_eq!(err.code(), Some(TransportErrorCode::ConnectionClosed));
+        assert!(err.to_string().contains("exit status: 3"), "{err}");
+        assert!(err.to_string().contains("oops"), "{err}");
+        assert_eq!(*lines.lock().unwrap(), vec!["oops".to_string()]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_close_escalates_to_kill() -> Result<()> {
+        let transport = ChildProcessTransport::new(sh("trap '' TERM; while true; do sleep 1; done"))
+            .with_grace_period(Duration::from_millis(200));
+        transport.open().await?;
+        // Give the shell time to install the trap
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let start = Instant::now();
+        transport.close().await?;
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(!transport.exit_status().unwrap().success());
+        Ok(())
+    }
+}
@@ -0,0 +1,305 @@
+//! Bounded queue between a transport's connection task and `receive`
+//!
+//! Incoming messages wait in a [`MessageQueue`] until `receive` takes them. The queue
+//! holds at most [`QueueConfig::capacity`] messages; what happens when it is full is
+//! decided by the [`OverflowPolicy`]. The default policy blocks the connection task, so
+//! a slow consumer slows the peer down instead of losing messages.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::Notify;
+use tracing::error;
+
+use crate::transport::{Message, Result, TransportError, TransportErrorCode};
+
+/// What a full [`MessageQueue`] does with a new message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Wait until `receive` makes room; the peer is not read from in the meantime
+    #[default]
+    Block,
+    /// Reject the message; `receive` reports the loss as
+    /// `TransportErrorCode::MessageReceiveFailed` after the messages queued before it
+    Error,
+    /// Discard the oldest queued message and log a `MessageReceiveFailed` error
+    DropOldest,
+}
+
+/// Size and overflow behaviour of a transport's receive queue
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueConfig {
+    /// Maximum number of messages waiting to be received
+    pub capacity: usize,
+    /// What to do when the queue is full
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 1000,
+            overflow: OverflowPolicy::Block,
+        }
+    }
+}
+
+enum Entry {
+    Message(Message),
+    /// Messages rejected under [`OverflowPolicy::Error`]
+    Overflow(usize),
+}
+
+struct State {
+    entries: VecDeque<Entry>,
+    /// Number of `Entry::Message`s in `entries`
+    messages: usize,
+    closed: bool,
+}
+
+struct Shared {
+    config: QueueConfig,
+    state: Mutex<State>,
+    readable: Notify,
+    writable: Notify,
+}
+
+/// Bounded multi-producer queue of incoming messages
+///
+/// Clones share the same queue. Closing it wakes every waiter; messages already queued
+/// are still received, after which `pop` returns `Ok(None)`.
+#[derive(Clone)]
+pub struct MessageQueue {
+    shared: Arc<Shared>,
+}
+
+impl std::fmt::Debug for MessageQueue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.shared.state.lock().unwrap();
+        f.debug_struct("MessageQueue")
+            .field("config", &self.shared.config)
+            .field("len", &state.messages)
+            .field("closed", &state.closed)
+            .finish()
+    }
+}
+
+impl MessageQueue {
+    /// Creates an empty, open queue
+    ///
+    /// A capacity of zero is treated as one.
+    pub fn new(config: QueueConfig) -> Self {
+        let config = QueueConfig {
+            capacity: config.capacity.max(1),
+            ..config
+        };
+        Self {
+            shared: Arc::new(Shared {
+                config,
+                state: Mutex::new(State {
+                    entries: VecDeque::new(),
+                    messages: 0,
+                    closed: false,
+                }),
+                readable: Notify::new(),
+                writable: Notify::new(),
+            }),
+        }
+    }
+
+    /// Adds a message, applying the overflow policy if the queue is full
+    ///
+    /// # Errors
+    /// - `TransportErrorCode::ConnectionClosed` if the queue is closed
+    /// - `TransportErrorCode::MessageReceiveFailed` if the queue is full and the
+    ///   policy is [`OverflowPolicy::Error`]
+    pub async fn push(&self, message: Message) -> Result<()> {
+        let shared = &self.shared;
+        loop {
+            let writable = shared.writable.notified();
+            tokio::pin!(writable);
+            writable.as_mut().enable();
+            {
+                let mut state = shared.state.lock().unwrap();
+                if state.closed {
+                    return Err(TransportError::new(
+                        TransportErrorCode::ConnectionClosed,
+                        "Receive queue is closed"
+                    ));
+                }
+                if state.messages >= shared.config.capacity {
+                    match shared.config.overflow {
+                        OverflowPolicy::Block => {}
+                        OverflowPolicy::Error => {
+                            match state.entries.back_mut() {
+                                Some(Entry::Overflow(count)) => *count += 1,
+                                _ => state.entries.push_back(Entry::Overflow(1)),
+                            }
+                            drop(state);
+                            shared.readable.notify_one();
+                            return Err(overflow_error(1, shared.config.capacity));
+                        }
+                        OverflowPolicy::DropOldest => {
+                            let position = state.entries.iter()
+                                .position(|entry| matches!(entry, Entry::Message(_)));
+                            if let Some(position) = position {
+                                state.entries.remove(position);
+                                state.messages -= 1;
+                            }
+                            error!("{}", TransportError::new(
+                                TransportErrorCode::MessageReceiveFailed,
+                                format!(
+                                    "Receive queue full ({} messages); dropped the oldest message",
+                                    shared.config.capacity
+                                )
+                            ));
+                        }
+                    }
+                }
+                if state.messages < shared.config.capacity {
+                    state.entries.push_back(Entry::Message(message));
+                    state.messages += 1;
+                    drop(state);
+                    shared.readable.notify_one();
+                    return Ok(());
+                }
+            }
+            writable.await;
+        }
+    }
+
+    /// Takes the next message, waiting until one arrives
+    ///
+    /// Returns `Ok(None)` once the queue is closed and empty.
+    ///
+    /// # Errors
+    /// - `TransportErrorCode::MessageReceiveFailed` in place of messages rejected under
+    ///   [`OverflowPolicy::Error`]
+    pub async fn pop(&self) -> Result<Option<Message>> {
+        let shared = &self.shared;
+        loop {
+            let readable = shared.readable.notified();
+            tokio::pin!(readable);
+            readable.as_mut().enable();
+            {
+                let mut state = shared.state.lock().unwrap();
+                match state.entries.pop_front() {
+                    Some(Entry::Message(message)) => {
+                        state.messages -= 1;
+                        drop(state);
+                        shared.writable.notify_one();
+                        return Ok(Some(message));
+                    }
+                    Some(Entry::Overflow(count)) => {
+                        return Err(overflow_error(count, shared.config.capacity));
+                    }
+                    None if state.closed => return Ok(None),
+                    None => {}
+                }
+            }
+            readable.await;
+        }
+    }
+
+    /// Closes the queue; pending and future `push`es fail
+    pub fn close(&self) {
+        self.shared.state.lock().unwrap().closed = true;
+        self.shared.readable.notify_waiters();
+        self.shared.writable.notify_waiters();
+    }
+
+    /// Returns whether the queue has been closed
+    pub fn is_closed(&self) -> bool {
+        self.shared.state.lock().unwrap().closed
+    }
+
+    /// Returns the number of queued messages
+    pub fn len(&self) -> usize {
+        self.shared.state.lock().unwrap().messages
+    }
+
+    /// Returns whether no messages are queued
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn overflow_error(count: usize, capacity: usize) -> TransportError {
+    TransportError::new(
+        TransportErrorCode::MessageReceiveFailed,
+        format!("Receive queue full ({} messages); {} message(s) rejected", capacity, count)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{JsonRpcMessage, JsonRpcNotification, JsonRpcVersion};
+    use std::time::Duration;
+
+    fn message(n: usize) -> Message {
+        JsonRpcMessage::Notification(JsonRpcNotification {
+            method: format!("test/{}", n),
+            params: None,
+            jsonrpc: JsonRpcVersion::default(),
+        })
+    }
+
+    fn queue(capacity: usize, overflow: OverflowPolicy) -> MessageQueue {
+        MessageQueue::new(QueueConfig { capacity, overflow })
+    }
+
+    #[tokio::test]
+    async fn test_block_waits_for_room() {
+        let queue = queue(2, OverflowPolicy::Block);
+        queue.push(message(0)).await.unwrap();
+        queue.push(message(1)).await.unwrap();
+
+        let producer = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.push(message(2)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!producer.is_finished());
+
+        for n in 0..3 {
+            assert_eq!(queue.pop().await.unwrap(), Some(message(n)));
+        }
+        producer.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_error_reports_rejected_messages_in_order() {
+        let queue = queue(1, OverflowPolicy::Error);
+        queue.push(message(0)).await.unwrap();
+        let error = queue.push(message(1)).await.unwrap_err();
+        assert_eq!(error.code(), Some(TransportErrorCode::MessageReceiveFailed));
+        assert!(queue.push(message(2)).await.is_err());
+
+        assert_eq!(queue.pop().await.unwrap(), Some(message(0)));
+        let error = queue.pop().await.unwrap_err();
+        assert!(error.to_string().contains("2 message(s) rejected"));
+        queue.push(message(3)).await.unwrap();
+        assert_eq!(queue.pop().await.unwrap(), Some(message(3)));
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest_keeps_newest() {
+        let queue = queue(2, OverflowPolicy::DropOldest);
+        for n in 0..4 {
+            queue.push(message(n)).await.unwrap();
+        }
+        assert_eq!(queue.pop().await.unwrap(), Some(message(2)));
+        assert_eq!(queue.pop().await.unwrap(), Some(message(3)));
+    }
+
+    #[tokio::test]
+    async fn test_close_drains_then_ends() {
+        let queue = queue(4, OverflowPolicy::Block);
+        queue.push(message(0)).await.unwrap();
+        queue.close();
+        assert!(queue.push(message(1)).await.is_err());
+        assert_eq!(queue.pop().await.unwrap(), Some(message(0)));
+        assert_eq!(queue.pop().await.unwrap(), None);
+    }
+}
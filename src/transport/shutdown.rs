@@ -0,0 +1,150 @@
+//! Graceful shutdown of servers
+//!
+//! Shutting down a server stops it from accepting connections, optionally notifies
+//! every session, and gives the requests its sessions are working on until a deadline
+//! to finish. Requests still running then are cancelled: their handlers are dropped
+//! and the client gets an error response for each of them, so its calls fail instead
+//! of waiting for an answer that will never come.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::{JsonRpcError, JsonRpcMessage, JsonRpcResponse, JsonRpcVersion, Message, RequestId};
+
+/// JSON-RPC error code for internal errors
+const INTERNAL_ERROR: i32 = -32603;
+
+/// How often to check whether in-flight requests have finished
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// How long closed sessions and connections get to wind down once the grace period
+/// is over, before they are dropped
+pub(crate) const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Reason given to clients for requests cancelled and refused during shutdown
+pub(crate) const SHUTDOWN_REASON: &str = "Server is shutting down";
+
+/// How a server shuts down
+#[derive(Debug, Clone)]
+pub struct ShutdownConfig {
+    /// How long in-flight requests such as `tools/call` get to finish; requests still
+    /// running afterwards are cancelled
+    pub grace_period: Duration,
+    /// Notification sent to every session once the server stops accepting
+    /// connections, e.g. a server-specific `notifications/shutdown`; `None` sends none
+    pub notification: Option<Message>,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            grace_period: Duration::from_secs(30),
+            notification: None,
+        }
+    }
+}
+
+/// Requests a session received and has not answered yet, with their methods and
+/// when they arrived
+#[derive(Debug, Default)]
+pub(crate) struct InFlight {
+    requests: Mutex<HashMap<RequestId, (String, Instant)>>,
+}
+
+impl InFlight {
+    /// Records a request received from the client
+    pub(crate) fn received(&self, message: &Message) {
+        if let JsonRpcMessage::Request(request) = message {
+            self.requests.lock().unwrap().insert(request.id, (request.method.clone(), Instant::now()));
+        }
+    }
+
+    /// Forgets the request a response sent to the client answers, returning its
+    /// method and how long it took
+    pub(crate) fn sent(&self, message: &Message) -> Option<(String, Duration)> {
+        let JsonRpcMessage::Response(response) = message else {
+            return None;
+        };
+        let (method, received) = self.requests.lock().unwrap().remove(&response.id)?;
+        Some((method, received.elapsed()))
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.requests.lock().unwrap().is_empty()
+    }
+
+    /// Removes and returns the unanswered requests
+    pub(crate) fn take(&self) -> Vec<(RequestId, String)> {
+        self.requests.lock().unwrap().drain().map(|(id, (method, _))| (id, method)).collect()
+    }
+}
+
+/// Waits until `is_drained` holds or `deadline` passes; returns whether it held
+pub(crate) async fn drain(deadline: Instant, is_drained: impl Fn() -> bool) -> bool {
+    loop {
+        if is_drained() {
+            return true;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return false;
+        }
+        tokio::time::sleep(DRAIN_POLL_INTERVAL.min(deadline - now)).await;
+    }
+}
+
+/// Error response to request `id`, which arrived after shutdown began or was cancelled
+/// when the grace period ran out
+pub(crate) fn refused(id: RequestId) -> Message {
+    JsonRpcMessage::Response(JsonRpcResponse {
+        id,
+        result: None,
+        error: Some(JsonRpcError {
+            code: INTERNAL_ERROR,
+            message: SHUTDOWN_REASON.to_string(),
+            data: None,
+        }),
+        jsonrpc: JsonRpcVersion::default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{JsonRpcNotification, JsonRpcRequest};
+
+    fn request(id: RequestId) -> Message {
+        JsonRpcMessage::Request(JsonRpcRequest {
+            id,
+            method: "tools/call".to_string(),
+            params: None,
+            jsonrpc: JsonRpcVersion::default(),
+        })
+    }
+
+    #[test]
+    fn test_in_flight_requests_are_tracked_until_answered() {
+        let in_flight = InFlight::default();
+        in_flight.received(&request(1));
+        in_flight.received(&request(2));
+        in_flight.received(&JsonRpcMessage::Notification(JsonRpcNotification {
+            method: "notifications/progress".to_string(),
+            params: None,
+            jsonrpc: JsonRpcVersion::default(),
+        }));
+
+        in_flight.sent(&refused(1));
+        assert!(!in_flight.is_empty());
+        assert_eq!(in_flight.take(), vec![(2, "tools/call".to_string())]);
+        assert!(in_flight.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_drain_gives_up_at_the_deadline() {
+        let start = Instant::now();
+        assert!(!drain(start + Duration::from_millis(50), || false).await);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert!(drain(Instant::now() + Duration::from_secs(5), || true).await);
+    }
+}
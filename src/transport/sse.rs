@@ -8,21 +8,29 @@ use async_trait::async_trait;
 use actix_web_lab::sse;
 use bytestring::ByteString;
 use serde::Serialize;
-use tokio::sync::mpsc;
+use futures::StreamExt;
+use tokio::sync::{mpsc, watch};
 use tokio_stream::wrappers::ReceiverStream;
 
-use crate::transport::{Message, Result, Transport, TransportError, TransportErrorCode};
+use crate::transport::{
+    Message, MessageQueue, QueueConfig, Result, Transport, TransportError, TransportErrorCode,
+};
 
 /// Server-side SSE transport implementation
 ///
-/// This transport is unidirectional (server to client only) as per the SSE protocol.
-/// It sends messages to the client using Server-Sent Events.
+/// Messages to the client are sent as Server-Sent Events. Messages from the client
+/// arrive out of band (in `httpd`, as POST requests) and are handed to the transport
+/// with [`ServerSseTransport::deliver`], after which `receive` returns them.
 #[derive(Debug, Clone)]
 pub struct ServerSseTransport {
     /// Channel sender for SSE events
     sender: mpsc::Sender<Result<sse::Event>>,
     /// Flag to track if the transport is open
     is_open: Arc<AtomicBool>,
+    /// Messages delivered by the client, waiting to be received
+    incoming: MessageQueue,
+    /// Set on close to end the event stream
+    closed: Arc<watch::Sender<bool>>,
 }
 
 impl ServerSseTransport {
@@ -41,6 +49,8 @@ impl ServerSseTransport {
         Self {
             sender: tx,
             is_open: Arc::new(AtomicBool::new(true)),
+            incoming: MessageQueue::new(QueueConfig::default()),
+            closed: Arc::new(watch::channel(false).0),
         }
     }
 
@@ -52,15 +62,32 @@ impl ServerSseTransport {
     /// # Returns
     /// A tuple containing the transport and an actix-web responder
     pub fn new_with_responder(capacity: usize) -> (Self, impl actix_web::Responder) {
+        Self::new_with_keep_alive(capacity, Duration::from_secs(15))
+    }
+
+    /// Like [`ServerSseTransport::new_with_responder`], sending keep-alive comments
+    /// every `keep_alive`
+    ///
+    /// A client that went away is only noticed when writing to it fails, so the
+    /// interval also bounds how long a dead stream goes unnoticed.
+    pub fn new_with_keep_alive(capacity: usize, keep_alive: Duration) -> (Self, impl actix_web::Responder) {
         let (tx, rx) = mpsc::channel(capacity);
         let transport = Self {
             sender: tx,
             is_open: Arc::new(AtomicBool::new(true)),
+            incoming: MessageQueue::new(QueueConfig::default()),
+            closed: Arc::new(watch::channel(false).0),
         };
 
+        // The stream ends when the transport is closed, which ends the response
+        let mut closed = transport.closed.subscribe();
+        let events = ReceiverStream::new(rx).take_until(async move {
+            let _ = closed.wait_for(|closed| *closed).await;
+        });
+
         // Create the SSE responder with keep-alive
-        let responder = sse::Sse::from_stream(ReceiverStream::new(rx))
-            .with_keep_alive(Duration::from_secs(15));
+        let responder = sse::Sse::from_stream(events)
+            .with_keep_alive(keep_alive);
 
         (transport, responder)
     }
@@ -81,6 +108,20 @@ impl ServerSseTransport {
         self.is_open.store(open, std::sync::atomic::Ordering::Relaxed);
     }
 
+    /// Hands a message from the client to the transport, to be returned by `receive`
+    ///
+    /// # Errors
+    /// - `TransportErrorCode::ConnectionClosed` if the transport is closed
+    pub async fn deliver(&self, message: Message) -> Result<()> {
+        self.incoming.push(message).await
+    }
+
+    /// Waits until the client is gone: the event stream was dropped, either because
+    /// the connection closed or because the transport was closed
+    pub async fn disconnected(&self) {
+        self.sender.closed().await
+    }
+
     /// Sends a message through the SSE channel
     pub async fn send_message(&self, message: Message) -> Result<()> {
         let json = serde_json::to_string(&message)?;
@@ -152,9 +193,7 @@ impl Transport for ServerSseTransport {
     }
 
     async fn receive(&self) -> Result<Option<Message>> {
-        // SSE is unidirectional (server to client only)
-        // This method always returns None as we can't receive messages from the client
-        Ok(None)
+        self.incoming.pop().await
     }
 
     async fn open(&self) -> Result<()> {
@@ -166,9 +205,10 @@ impl Transport for ServerSseTransport {
     async fn close(&self) -> Result<()> {
         // Mark the transport as closed
         self.set_open(false);
+        self.incoming.close();
 
-        // We can't actually close the SSE connection from the server side
-        // The client will detect the closure when the HTTP connection is closed
+        // End the event stream; the client sees the response finish
+        self.closed.send_replace(true);
         Ok(())
     }
 }
@@ -383,4 +423,22 @@ The restoration process:
         // Test keep-alive during long analysis
         transport.send_comment("keep-alive").await.unwrap();
     }
-}
\ No newline at end of file
+
+    #[actix_web::test]
+    async fn test_delivered_messages_are_received_until_closed() {
+        let (transport, _responder) = ServerSseTransport::new_with_responder(100);
+        let message: Message = serde_json::from_value(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/initialized"
+        }))
+        .unwrap();
+
+        transport.deliver(message.clone()).await.unwrap();
+        assert_eq!(transport.receive().await.unwrap(), Some(message.clone()));
+
+        transport.close().await.unwrap();
+        assert_eq!(transport.receive().await.unwrap(), None);
+        let err = transport.deliver(message).await.unwrap_err();
+        assert_eq!(err.code(), Some(TransportErrorCode::ConnectionClosed));
+    }
+}
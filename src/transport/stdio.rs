@@ -1,60 +1,139 @@
 use async_trait::async_trait;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, Stdin, Stdout};
 use tokio::process::{ChildStdin, ChildStdout};
 use tokio::sync::Mutex;
-use crate::transport::{Transport, Message, Result, TransportError, TransportErrorCode};
+use tracing::warn;
+use crate::transport::{Codec, Transport, Message, Result, TransportError, TransportErrorCode, default_codec};
 
-/// Transport implementation for communicating with a child process via stdin/stdout
+/// Default maximum length of a single message, in bytes
+const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// How messages are delimited on a stdio stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Framing {
+    /// Detect the framing from the first bytes received
+    ///
+    /// Until the peer's framing is known, outgoing messages are newline-delimited.
+    #[default]
+    Auto,
+    /// One JSON message per line
+    NewlineDelimited,
+    /// LSP-style `Content-Length` headers followed by the JSON body
+    ///
+    /// Messages may contain raw newlines, e.g. pretty-printed JSON.
+    ContentLength,
+}
+
+/// Transport implementation for JSON messages over a reader/writer pair
+///
+/// By default this transport communicates with a child process by reading from its
+/// stdout and writing to its stdin, but it works over any `AsyncRead`/`AsyncWrite`
+/// pair such as pipes, sockets or `tokio::io::duplex` streams. Use
+/// [`StdioTransport::server`] to serve over the current process's stdin/stdout.
 ///
-/// This transport allows bidirectional communication with a child process by
-/// reading from its stdout and writing to its stdin.
-pub struct StdioTransport {
-    /// Reader for the child process's stdout
-    reader: Mutex<BufReader<ChildStdout>>,
-    /// Writer for the child process's stdin
-    writer: Mutex<ChildStdin>,
+/// Messages are newline-delimited or framed with `Content-Length` headers; see
+/// [`Framing`]. They are JSON unless another [`Codec`] is set with
+/// [`StdioTransport::with_codec`].
+pub struct StdioTransport<R = ChildStdout, W = ChildStdin> {
+    /// Reader for incoming messages
+    reader: Mutex<BufReader<R>>,
+    /// Writer for outgoing messages
+    writer: Mutex<W>,
     /// Flag to track if the transport is open
     is_open: Arc<AtomicBool>,
-    /// Buffer size for reading lines
+    /// Maximum length of a single incoming message, in bytes
     buffer_size: usize,
+    /// Configured or detected framing
+    framing: std::sync::Mutex<Framing>,
+    /// Message serialization format
+    codec: Arc<dyn Codec>,
+}
+
+impl StdioTransport<Stdin, Stdout> {
+    /// Creates a transport over the current process's stdin and stdout
+    ///
+    /// This is the transport an MCP server uses when it is launched by a client.
+    pub fn server() -> Self {
+        Self::new(tokio::io::stdin(), tokio::io::stdout())
+    }
 }
 
-impl StdioTransport {
+impl<R, W> StdioTransport<R, W>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
     /// Creates a new stdio transport
     ///
     /// # Arguments
-    /// * `stdout` - The child process's stdout
-    /// * `stdin` - The child process's stdin
+    /// * `reader` - Stream to read messages from, e.g. a child process's stdout
+    /// * `writer` - Stream to write messages to, e.g. a child process's stdin
     ///
     /// # Returns
     /// A new StdioTransport instance
-    pub fn new(stdout: ChildStdout, stdin: ChildStdin) -> Self {
-        Self {
-            reader: Mutex::new(BufReader::new(stdout)),
-            writer: Mutex::new(stdin),
-            is_open: Arc::new(AtomicBool::new(true)),
-            buffer_size: 64 * 1024, // 64KB buffer size by default
-        }
+    pub fn new(reader: R, writer: W) -> Self {
+        Self::with_buffer_size(reader, writer, DEFAULT_BUFFER_SIZE)
     }
 
     /// Creates a new stdio transport with a custom buffer size
     ///
     /// # Arguments
-    /// * `stdout` - The child process's stdout
-    /// * `stdin` - The child process's stdin
-    /// * `buffer_size` - The buffer size for reading lines
+    /// * `reader` - Stream to read messages from, e.g. a child process's stdout
+    /// * `writer` - Stream to write messages to, e.g. a child process's stdin
+    /// * `buffer_size` - The maximum length of a single incoming line
     ///
     /// # Returns
     /// A new StdioTransport instance
-    pub fn with_buffer_size(stdout: ChildStdout, stdin: ChildStdin, buffer_size: usize) -> Self {
+    pub fn with_buffer_size(reader: R, writer: W, buffer_size: usize) -> Self {
         Self {
-            reader: Mutex::new(BufReader::new(stdout)),
-            writer: Mutex::new(stdin),
+            reader: Mutex::new(BufReader::new(reader)),
+            writer: Mutex::new(writer),
             is_open: Arc::new(AtomicBool::new(true)),
             buffer_size,
+            framing: std::sync::Mutex::new(Framing::Auto),
+            codec: default_codec(),
+        }
+    }
+
+    /// Sets the framing mode (default: [`Framing::Auto`])
+    ///
+    /// Binary codecs keep [`Framing::ContentLength`] whatever is set here, before or
+    /// after [`StdioTransport::with_codec`].
+    pub fn with_framing(self, framing: Framing) -> Self {
+        let framing = if self.codec.is_binary() && framing != Framing::ContentLength {
+            warn!(
+                "{} codec cannot use {:?} framing; keeping Content-Length",
+                self.codec.name(),
+                framing
+            );
+            Framing::ContentLength
+        } else {
+            framing
+        };
+        *self.framing.lock().unwrap() = framing;
+        self
+    }
+
+    /// Sets the message codec (default: [`JsonCodec`](crate::transport::JsonCodec))
+    ///
+    /// Binary codecs cannot be newline-delimited, so they always use
+    /// [`Framing::ContentLength`].
+    pub fn with_codec(mut self, codec: Arc<dyn Codec>) -> Self {
+        if codec.is_binary() {
+            *self.framing.lock().unwrap() = Framing::ContentLength;
         }
+        self.codec = codec;
+        self
+    }
+
+    /// Returns the framing in use
+    ///
+    /// With [`Framing::Auto`] this reports the detected framing once the first
+    /// message has been received.
+    pub fn framing(&self) -> Framing {
+        *self.framing.lock().unwrap()
     }
 
     /// Checks if the transport is open
@@ -72,10 +151,161 @@ impl StdioTransport {
     pub fn set_open(&self, open: bool) {
         self.is_open.store(open, Ordering::Relaxed);
     }
+
+    /// Reads one line, without the trailing newline
+    ///
+    /// Returns `Ok(None)` at end of stream. A line longer than `buffer_size` is
+    /// discarded up to its newline so the next read starts at a message boundary.
+    async fn read_line(&self, reader: &mut BufReader<R>) -> Result<Option<Vec<u8>>> {
+        let mut line = Vec::new();
+        let mut too_large = false;
+        loop {
+            let available = reader.fill_buf().await.map_err(|e| {
+                self.set_open(false);
+                TransportError::new(
+                    TransportErrorCode::MessageReceiveFailed,
+                    format!("Failed to read line: {}", e)
+                )
+            })?;
+            if available.is_empty() {
+                if line.is_empty() && !too_large {
+                    return Ok(None);
+                }
+                break;
+            }
+            let (chunk, done) = match available.iter().position(|&b| b == b'\n') {
+                Some(i) => (&available[..i], true),
+                None => (available, false),
+            };
+            if line.len() + chunk.len() > self.buffer_size {
+                too_large = true;
+                line.clear();
+            } else if !too_large {
+                line.extend_from_slice(chunk);
+            }
+            let consumed = chunk.len() + usize::from(done);
+            reader.consume(consumed);
+            if done {
+                break;
+            }
+        }
+        if too_large {
+            return Err(TransportError::new(
+                TransportErrorCode::MessageTooLarge,
+                format!("Message exceeds maximum line length of {} bytes", self.buffer_size)
+            ));
+        }
+        Ok(Some(line))
+    }
+
+    /// Skips leading whitespace and picks the framing from the first byte
+    ///
+    /// JSON messages start with `{` or `[`; anything else is taken to be a header.
+    /// Returns `Ok(None)` at end of stream.
+    async fn detect_framing(&self, reader: &mut BufReader<R>) -> Result<Option<Framing>> {
+        loop {
+            let available = reader.fill_buf().await.map_err(|e| {
+                self.set_open(false);
+                TransportError::new(
+                    TransportErrorCode::MessageReceiveFailed,
+                    format!("Failed to read message: {}", e)
+                )
+            })?;
+            if available.is_empty() {
+                return Ok(None);
+            }
+            match available.iter().position(|b| !b.is_ascii_whitespace()) {
+                Some(i) => {
+                    let framing = match available[i] {
+                        b'{' | b'[' => Framing::NewlineDelimited,
+                        _ => Framing::ContentLength,
+                    };
+                    reader.consume(i);
+                    return Ok(Some(framing));
+                }
+                None => {
+                    let len = available.len();
+                    reader.consume(len);
+                }
+            }
+        }
+    }
+
+    /// Reads one `Content-Length` framed message body
+    ///
+    /// Returns `Ok(None)` at end of stream. A body longer than `buffer_size` is
+    /// discarded so the next read starts at a message boundary.
+    async fn read_framed(&self, reader: &mut BufReader<R>) -> Result<Option<Vec<u8>>> {
+        let mut content_length = None;
+        let mut saw_header = false;
+        loop {
+            let Some(line) = self.read_line(reader).await? else {
+                if saw_header {
+                    self.set_open(false);
+                    return Err(TransportError::new(
+                        TransportErrorCode::MessageReceiveFailed,
+                        "Stream ended inside message headers"
+                    ));
+                }
+                return Ok(None);
+            };
+            let line = line.trim_ascii();
+            if line.is_empty() {
+                if saw_header {
+                    break;
+                }
+                continue;
+            }
+            saw_header = true;
+            let header = String::from_utf8_lossy(line);
+            let Some((name, value)) = header.split_once(':') else {
+                return Err(TransportError::new(
+                    TransportErrorCode::InvalidMessage,
+                    format!("Malformed header: {}", header)
+                ));
+            };
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = Some(value.trim().parse::<usize>().map_err(|e| TransportError::new(
+                    TransportErrorCode::InvalidMessage,
+                    format!("Invalid Content-Length `{}`: {}", value.trim(), e)
+                ))?);
+            }
+        }
+        let Some(length) = content_length else {
+            return Err(TransportError::new(
+                TransportErrorCode::InvalidMessage,
+                "Message headers lack Content-Length"
+            ));
+        };
+
+        let read_error = |e: std::io::Error| {
+            self.set_open(false);
+            TransportError::new(
+                TransportErrorCode::MessageReceiveFailed,
+                format!("Failed to read message body: {}", e)
+            )
+        };
+        if length > self.buffer_size {
+            tokio::io::copy(&mut (&mut *reader).take(length as u64), &mut tokio::io::sink())
+                .await
+                .map_err(read_error)?;
+            return Err(TransportError::new(
+                TransportErrorCode::MessageTooLarge,
+                format!("Message of {} bytes exceeds maximum size of {} bytes", length, self.buffer_size)
+            ));
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).await.map_err(read_error)?;
+        Ok(Some(body))
+    }
 }
 
 #[async_trait]
-impl Transport for StdioTransport {
+impl<R, W> Transport for StdioTransport<R, W>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
     async fn send(&self, message: &Message) -> Result<()> {
         // Check if the transport is open
         if !self.is_open() {
@@ -85,40 +315,29 @@ impl Transport for StdioTransport {
             ));
         }
 
-        // Serialize the message to JSON
-        let message_str = serde_json::to_string(message)
-            .map_err(|e| TransportError::new(
-                TransportErrorCode::MessageSendFailed,
-                format!("Failed to serialize message: {}", e)
-            ))?;
-
-        // Send the message to the child process's stdin
-        let mut writer = self.writer.lock().await;
-
-        // Write the message, followed by a newline
-        match writer.write_all(message_str.as_bytes()).await {
-            Ok(_) => {},
-            Err(e) => {
-                // If writing fails, mark the transport as closed
-                self.set_open(false);
-                return Err(TransportError::new(
-                    TransportErrorCode::MessageSendFailed,
-                    format!("Failed to write message: {}", e)
-                ));
+        // Serialize the message and frame it
+        let body = self.codec.encode(message)?;
+        let mut frame = Vec::with_capacity(body.len() + 32);
+        match self.framing() {
+            Framing::ContentLength => {
+                frame.extend_from_slice(format!("Content-Length: {}\r\n\r\n", body.len()).as_bytes());
+                frame.extend_from_slice(&body);
+            }
+            Framing::Auto | Framing::NewlineDelimited => {
+                frame.extend_from_slice(&body);
+                frame.push(b'\n');
             }
         }
 
-        // Write a newline to terminate the message
-        match writer.write_all(b"\n").await {
-            Ok(_) => {},
-            Err(e) => {
-                // If writing fails, mark the transport as closed
-                self.set_open(false);
-                return Err(TransportError::new(
-                    TransportErrorCode::MessageSendFailed,
-                    format!("Failed to write newline: {}", e)
-                ));
-            }
+        // Send the message to the writer
+        let mut writer = self.writer.lock().await;
+        if let Err(e) = writer.write_all(&frame).await {
+            // If writing fails, mark the transport as closed
+            self.set_open(false);
+            return Err(TransportError::new(
+                TransportErrorCode::MessageSendFailed,
+                format!("Failed to write message: {}", e)
+            ));
         }
 
         // Flush the writer to ensure the message is sent
@@ -144,40 +363,43 @@ impl Transport for StdioTransport {
             ));
         }
 
-        // Allocate a buffer for the message with the configured buffer size
-        let mut line = String::with_capacity(self.buffer_size);
-
         // Lock the reader
         let mut reader = self.reader.lock().await;
 
-        // Read a line from the child process's stdout
-        let bytes_read = match reader.read_line(&mut line).await {
-            Ok(bytes) => bytes,
-            Err(e) => {
-                // If reading fails, mark the transport as closed
-                self.set_open(false);
-                return Err(TransportError::new(
-                    TransportErrorCode::MessageReceiveFailed,
-                    format!("Failed to read line: {}", e)
-                ));
+        // Work out the peer's framing from its first message
+        let mut framing = self.framing();
+        if framing == Framing::Auto {
+            match self.detect_framing(&mut reader).await? {
+                Some(detected) => {
+                    *self.framing.lock().unwrap() = detected;
+                    framing = detected;
+                }
+                None => {
+                    // The stream is closed
+                    self.set_open(false);
+                    return Ok(None);
+                }
             }
-        };
+        }
 
-        // If we read 0 bytes, the stream is closed
-        if bytes_read == 0 {
-            // Mark the transport as closed
+        let body = match framing {
+            Framing::ContentLength => self.read_framed(&mut reader).await?,
+            Framing::Auto | Framing::NewlineDelimited => loop {
+                // Read lines until a non-empty one arrives
+                match self.read_line(&mut reader).await? {
+                    Some(line) if line.trim_ascii().is_empty() => continue,
+                    line => break line,
+                }
+            },
+        };
+        let Some(body) = body else {
+            // The stream is closed
             self.set_open(false);
             return Ok(None);
-        }
+        };
 
-        // Parse the message from JSON
-        match serde_json::from_str::<Message>(line.trim()) {
-            Ok(message) => Ok(Some(message)),
-            Err(e) => Err(TransportError::new(
-                TransportErrorCode::InvalidMessage,
-                format!("Failed to parse message: {}", e)
-            ))
-        }
+        // Parse the message
+        self.codec.decode(&body).map(Some)
     }
 
     async fn open(&self) -> Result<()> {
@@ -190,9 +412,126 @@ impl Transport for StdioTransport {
         // Mark the transport as closed
         self.set_open(false);
 
-        // We don't actually close the stdin/stdout handles here
-        // They will be closed when the child process terminates
-        // or when the StdioTransport is dropped
+        // Shut down the writer so the peer sees end of stream; the reader is
+        // released when the StdioTransport is dropped
+        self.writer.lock().await.shutdown().await.map_err(|e| TransportError::new(
+            TransportErrorCode::CloseError,
+            format!("Failed to close writer: {}", e)
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{JsonRpcMessage, JsonRpcNotification, JsonRpcVersion};
+    use tokio::io::{DuplexStream, ReadHalf, WriteHalf};
+
+    type DuplexTransport = StdioTransport<ReadHalf<DuplexStream>, WriteHalf<DuplexStream>>;
+
+    fn pair(buffer_size: usize) -> (DuplexTransport, DuplexTransport) {
+        let (a, b) = tokio::io::duplex(1024);
+        let (a_read, a_write) = tokio::io::split(a);
+        let (b_read, b_write) = tokio::io::split(b);
+        (
+            StdioTransport::with_buffer_size(a_read, a_write, buffer_size),
+            StdioTransport::with_buffer_size(b_read, b_write, buffer_size),
+        )
+    }
+
+    fn notification(method: &str) -> Message {
+        JsonRpcMessage::Notification(JsonRpcNotification {
+            method: method.to_string(),
+            params: None,
+            jsonrpc: JsonRpcVersion::default(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_and_eof() -> Result<()> {
+        let (client, server) = pair(DEFAULT_BUFFER_SIZE);
+        client.send(&notification("notifications/initialized")).await?;
+        assert_eq!(server.receive().await?, Some(notification("notifications/initialized")));
+
+        client.close().await?;
+        assert_eq!(server.receive().await?, None);
+        assert!(!server.is_open());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_line_longer_than_buffer_size() -> Result<()> {
+        let (client, server) = pair(64);
+        client.send(&notification(&"x".repeat(100))).await?;
+        client.send(&notification("short")).await?;
+
+        let err = server.receive().await.unwrap_err();
+        assert_eq!(err.code(), Some(TransportErrorCode::MessageTooLarge));
+        // The oversized line is skipped and the next message is intact
+        assert_eq!(server.receive().await?, Some(notification("short")));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_content_length_auto_detected() -> Result<()> {
+        let (peer, server) = tokio::io::duplex(1024);
+        let (server_read, server_write) = tokio::io::split(server);
+        let server = StdioTransport::new(server_read, server_write);
+        let (mut peer_read, mut peer_write) = tokio::io::split(peer);
+
+        // Pretty-printed body with raw newlines, followed by a second message
+        let body = "{\n  \"jsonrpc\": \"2.0\",\n  \"method\": \"first\"\n}";
+        let second = r#"{"jsonrpc":"2.0","method":"second"}"#;
+        let input = format!(
+            "Content-Length: {}\r\nContent-Type: application/json\r\n\r\n{}Content-Length: {}\r\n\r\n{}",
+            body.len(), body, second.len(), second
+        );
+        peer_write.write_all(input.as_bytes()).await.unwrap();
+
+        assert_eq!(server.receive().await?, Some(notification("first")));
+        assert_eq!(server.framing(), Framing::ContentLength);
+        assert_eq!(server.receive().await?, Some(notification("second")));
+
+        // Replies use the detected framing
+        server.send(&notification("reply")).await?;
+        let reply = serde_json::to_string(&notification("reply")).unwrap();
+        let expected = format!("Content-Length: {}\r\n\r\n{}", reply.len(), reply);
+        let mut received = vec![0; expected.len()];
+        peer_read.read_exact(&mut received).await.unwrap();
+        assert_eq!(String::from_utf8(received).unwrap(), expected);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_content_length_round_trip() -> Result<()> {
+        let (client, server) = pair(64);
+        let client = client.with_framing(Framing::ContentLength);
+        client.send(&notification("notifications/initialized")).await?;
+        assert_eq!(server.receive().await?, Some(notification("notifications/initialized")));
+        assert_eq!(server.framing(), Framing::ContentLength);
+
+        client.send(&notification(&"x".repeat(100))).await?;
+        client.send(&notification("short")).await?;
+        let err = server.receive().await.unwrap_err();
+        assert_eq!(err.code(), Some(TransportErrorCode::MessageTooLarge));
+        assert_eq!(server.receive().await?, Some(notification("short")));
+        Ok(())
+    }
+
+    #[cfg(feature = "cbor")]
+    #[tokio::test]
+    async fn test_binary_codec_uses_content_length() -> Result<()> {
+        let (client, server) = pair(DEFAULT_BUFFER_SIZE);
+        let client = client.with_codec(Arc::new(crate::transport::CborCodec));
+        assert_eq!(client.framing(), Framing::ContentLength);
+        // Setting the framing afterwards cannot break the binary frames
+        let client = client.with_framing(Framing::NewlineDelimited);
+        assert_eq!(client.framing(), Framing::ContentLength);
+
+        // Codecs are not detected, so both sides must be configured
+        let server = server.with_codec(Arc::new(crate::transport::CborCodec));
+        client.send(&notification("notifications/initialized")).await?;
+        assert_eq!(server.receive().await?, Some(notification("notifications/initialized")));
         Ok(())
     }
 }
@@ -0,0 +1,256 @@
+//! Plain TCP transport
+//!
+//! Newline-delimited JSON over a `TcpStream`, optionally wrapped in rustls TLS. This is
+//! the stdio framing on a socket, for exposing a server to another machine without
+//! the HTTP machinery in `httpd`. [`ClientTcpTransport`] connects to a server;
+//! [`serve_tcp`] listens and runs a separate [`Server`] session for every connection.
+
+use std::net::SocketAddr;
+use std::sync::{Arc, OnceLock};
+
+use async_trait::async_trait;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::{JoinHandle, JoinSet};
+use tokio_rustls::rustls::ClientConfig;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+use tracing::{debug, error, info};
+
+use crate::server::{Server, serve_transport};
+use crate::transport::http2::load_rustls_server_config;
+use crate::transport::{
+    Codec, Message, Result, StdioTransport, TlsConfig, Transport, TransportError,
+    TransportErrorCode, accept_backoff, default_codec,
+};
+
+type BoxedReader = Box<dyn AsyncRead + Unpin + Send>;
+type BoxedWriter = Box<dyn AsyncWrite + Unpin + Send>;
+
+/// Transport over a connected stream such as a TCP or TLS connection
+pub type StreamTransport = StdioTransport<BoxedReader, BoxedWriter>;
+
+impl StdioTransport<BoxedReader, BoxedWriter> {
+    /// Creates a transport over a bidirectional stream
+    pub fn from_stream<S>(stream: S) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let (reader, writer) = tokio::io::split(stream);
+        Self::new(Box::new(reader), Box::new(writer))
+    }
+}
+
+/// Client-side transport that connects to a TCP server when opened
+pub struct ClientTcpTransport {
+    addr: String,
+    tls: Option<(Arc<ClientConfig>, ServerName<'static>)>,
+    codec: Arc<dyn Codec>,
+    inner: OnceLock<StreamTransport>,
+}
+
+impl ClientTcpTransport {
+    /// Creates a transport for `addr`, e.g. `"127.0.0.1:9000"`
+    pub fn new(addr: impl Into<String>) -> Self {
+        Self {
+            addr: addr.into(),
+            tls: None,
+            codec: default_codec(),
+            inner: OnceLock::new(),
+        }
+    }
+
+    /// Connects over TLS, verifying the server as `server_name`
+    pub fn with_tls(mut self, config: Arc<ClientConfig>, server_name: ServerName<'static>) -> Self {
+        self.tls = Some((config, server_name));
+        self
+    }
+
+    /// Sets the message codec (default: JSON)
+    pub fn with_codec(mut self, codec: Arc<dyn Codec>) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    fn inner(&self) -> Result<&StreamTransport> {
+        self.inner.get().ok_or_else(|| TransportError::new(
+            TransportErrorCode::InvalidState,
+            "TCP transport not opened"
+        ))
+    }
+}
+
+#[async_trait]
+impl Transport for ClientTcpTransport {
+    async fn send(&self, message: &Message) -> Result<()> {
+        self.inner()?.send(message).await
+    }
+
+    async fn receive(&self) -> Result<Option<Message>> {
+        self.inner()?.receive().await
+    }
+
+    async fn open(&self) -> Result<()> {
+        if self.inner.get().is_some() {
+            return Ok(());
+        }
+        let stream = TcpStream::connect(&self.addr).await.map_err(|e| TransportError::new(
+            TransportErrorCode::ConnectionFailed,
+            format!("Failed to connect to {}: {}", self.addr, e)
+        ))?;
+        let transport = match &self.tls {
+            Some((config, server_name)) => {
+                let stream = TlsConnector::from(config.clone())
+                    .connect(server_name.clone(), stream)
+                    .await
+                    .map_err(|e| TransportError::new(
+                        TransportErrorCode::HandshakeFailed,
+                        format!("TLS handshake with {} failed: {}", self.addr, e)
+                    ))?;
+                StreamTransport::from_stream(stream)
+            }
+            None => StreamTransport::from_stream(stream),
+        };
+        let _ = self.inner.set(transport.with_codec(self.codec.clone()));
+        Ok(())
+    }
+
+    async fn close(&self) -> Result<()> {
+        match self.inner.get() {
+            Some(inner) => inner.close().await,
+            None => Ok(()),
+        }
+    }
+}
+
+/// Configuration for [`serve_tcp`]
+#[derive(Debug, Clone)]
+pub struct TcpServerConfig {
+    /// Address to bind to; port 0 picks a free port
+    pub addr: SocketAddr,
+    /// TLS configuration, loaded the same way as for the HTTP/2 server
+    pub tls_config: Option<TlsConfig>,
+    /// Message codec used on every connection
+    pub codec: Arc<dyn Codec>,
+}
+
+impl TcpServerConfig {
+    /// Creates a plain TCP configuration for `addr`
+    pub fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            tls_config: None,
+            codec: default_codec(),
+        }
+    }
+
+    /// Serves over TLS
+    pub fn with_tls(mut self, tls_config: TlsConfig) -> Self {
+        self.tls_config = Some(tls_config);
+        self
+    }
+
+    /// Sets the message codec (default: JSON)
+    pub fn with_codec(mut self, codec: Arc<dyn Codec>) -> Self {
+        self.codec = codec;
+        self
+    }
+}
+
+/// Handle for a server started with [`serve_tcp`]
+#[derive(Debug)]
+pub struct TcpServerHandle {
+    local_addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl TcpServerHandle {
+    /// Returns the address the server is listening on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stops accepting connections and ends all sessions
+    pub async fn stop(self) -> Result<()> {
+        self.task.abort();
+        let _ = self.task.await;
+        Ok(())
+    }
+}
+
+/// Listens on a TCP socket and serves each connection with its own session
+///
+/// `build_server` is called once per accepted connection. With TLS configured, the
+/// certificate and key are loaded before binding so configuration errors surface
+/// immediately; a failed handshake only drops that connection.
+///
+/// # Errors
+/// - `TransportErrorCode::ConfigurationError` if the TLS configuration cannot be loaded
+/// - `TransportErrorCode::ConnectionFailed` if the address cannot be bound
+pub async fn serve_tcp<F, S>(config: TcpServerConfig, build_server: F) -> Result<TcpServerHandle>
+where
+    F: Fn() -> S + Send + Sync + 'static,
+    S: Server,
+{
+    let acceptor = match &config.tls_config {
+        Some(tls_config) => {
+            let mut server_config = load_rustls_server_config(tls_config).await?;
+            server_config.alpn_protocols.clear();
+            Some(TlsAcceptor::from(Arc::new(server_config)))
+        }
+        None => None,
+    };
+
+    let listener = TcpListener::bind(config.addr).await.map_err(|e| TransportError::new(
+        TransportErrorCode::ConnectionFailed,
+        format!("Failed to bind to address: {}", e)
+    ))?;
+    let local_addr = listener.local_addr().map_err(|e| TransportError::new(
+        TransportErrorCode::ConnectionFailed,
+        format!("Failed to read local address: {}", e)
+    ))?;
+    info!(
+        "TCP server listening on {}{}",
+        local_addr,
+        if acceptor.is_some() { " (TLS)" } else { "" }
+    );
+
+    let task = tokio::spawn(async move {
+        let mut sessions = JoinSet::new();
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, addr)) => {
+                        debug!("Accepted TCP connection from {}", addr);
+                        let acceptor = acceptor.clone();
+                        let codec = config.codec.clone();
+                        let server = build_server();
+                        sessions.spawn(async move {
+                            let transport = match acceptor {
+                                Some(acceptor) => match acceptor.accept(stream).await {
+                                    Ok(stream) => StreamTransport::from_stream(stream),
+                                    Err(e) => {
+                                        error!("TLS handshake with {} failed: {}", addr, e);
+                                        return;
+                                    }
+                                },
+                                None => StreamTransport::from_stream(stream),
+                            };
+                            let transport = transport.with_codec(codec);
+                            if let Err(e) = serve_transport(server, transport).await {
+                                debug!("TCP session with {} ended with error: {}", addr, e);
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        error!("Failed to accept TCP connection: {}", e);
+                        accept_backoff().await;
+                    }
+                },
+                Some(_) = sessions.join_next(), if !sessions.is_empty() => {}
+            }
+        }
+    });
+
+    Ok(TcpServerHandle { local_addr, task })
+}
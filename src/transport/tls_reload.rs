@@ -0,0 +1,277 @@
+//! Hot reloading of server certificates
+//!
+//! Servers configured with certificate and key files serve them through a
+//! [`ReloadingCertResolver`], which reloads the files when they change or the process
+//! receives `SIGHUP`. New handshakes pick up the new certificate; established
+//! connections keep the one they were negotiated with.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, Weak};
+use std::time::Duration;
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_rustls::rustls::crypto::CryptoProvider;
+use tokio_rustls::rustls::server::{ClientHello, ResolvesServerCert};
+use tokio_rustls::rustls::sign::CertifiedKey;
+use tracing::{debug, error, info, warn};
+
+use super::http2::load_cert_and_key;
+use super::{Result, TransportError, TransportErrorCode};
+
+/// How long to wait for related file changes (certificate and key written one after
+/// the other) to settle before reloading
+const SETTLE_DELAY: Duration = Duration::from_millis(250);
+
+/// Serves the certificate in `cert_path`, reloading it and `key_path` when they change
+pub(crate) struct ReloadingCertResolver {
+    cert_path: String,
+    key_path: String,
+    provider: Arc<CryptoProvider>,
+    current: RwLock<Arc<CertifiedKey>>,
+    /// Keeps the file watcher alive; `None` if it could not be started
+    watcher: Option<RecommendedWatcher>,
+    reload_task: JoinHandle<()>,
+}
+
+impl ReloadingCertResolver {
+    /// Loads the certificate and key and starts watching them for changes
+    ///
+    /// Must be called within a Tokio runtime. Watching stops when the resolver is
+    /// dropped, i.e. once the server configuration using it is gone.
+    ///
+    /// # Errors
+    /// - `TransportErrorCode::ConfigurationError` if the files cannot be loaded or the
+    ///   key does not match the certificate
+    pub(crate) fn watch(cert_path: &str, key_path: &str, provider: Arc<CryptoProvider>) -> Result<Arc<Self>> {
+        let current = load_certified_key(cert_path, key_path, &provider)?;
+
+        let (changes_tx, changes_rx) = mpsc::unbounded_channel();
+        let watcher = match watch_files(&[cert_path, key_path], changes_tx) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                warn!(
+                    "Cannot watch {} for changes, reload it with SIGHUP instead: {}",
+                    cert_path, e
+                );
+                None
+            }
+        };
+        let changes_rx = watcher.as_ref().map(|_| changes_rx);
+
+        Ok(Arc::new_cyclic(|resolver| Self {
+            cert_path: cert_path.to_string(),
+            key_path: key_path.to_string(),
+            provider,
+            current: RwLock::new(current),
+            watcher,
+            reload_task: tokio::spawn(reload_on_change(resolver.clone(), changes_rx)),
+        }))
+    }
+
+    /// Reloads the certificate and key, keeping the current ones if that fails
+    ///
+    /// Returns whether the certificate changed.
+    pub(crate) fn reload(&self) -> Result<bool> {
+        let loaded = load_certified_key(&self.cert_path, &self.key_path, &self.provider)?;
+        let mut current = self.current.write().unwrap_or_else(|e| e.into_inner());
+        if current.cert == loaded.cert {
+            return Ok(false);
+        }
+        *current = loaded;
+        Ok(true)
+    }
+
+    /// Reloads and logs the outcome; failures leave the listener serving the old certificate
+    fn reload_logged(&self) {
+        match self.reload() {
+            Ok(true) => info!("Reloaded TLS certificate from {}", self.cert_path),
+            Ok(false) => debug!("TLS certificate in {} is unchanged", self.cert_path),
+            Err(e) => error!(
+                "Failed to reload TLS certificate from {}, keeping the current one: {}",
+                self.cert_path, e
+            ),
+        }
+    }
+}
+
+impl ResolvesServerCert for ReloadingCertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap_or_else(|e| e.into_inner()).clone())
+    }
+}
+
+impl fmt::Debug for ReloadingCertResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReloadingCertResolver")
+            .field("cert_path", &self.cert_path)
+            .field("key_path", &self.key_path)
+            .field("watching", &self.watcher.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for ReloadingCertResolver {
+    fn drop(&mut self) {
+        self.reload_task.abort();
+    }
+}
+
+/// Loads `cert_path` and `key_path`, checking that they belong together
+fn load_certified_key(cert_path: &str, key_path: &str, provider: &CryptoProvider) -> Result<Arc<CertifiedKey>> {
+    let (cert_chain, key) = load_cert_and_key(cert_path, key_path)?;
+    CertifiedKey::from_der(cert_chain, key, provider)
+        .map(Arc::new)
+        .map_err(|e| {
+            TransportError::new(
+                TransportErrorCode::ConfigurationError,
+                format!("Invalid TLS certificate or key in {} / {}: {}", cert_path, key_path, e),
+            )
+        })
+}
+
+/// Watches the directories containing `paths`, so files replaced by a rename (as
+/// certificate managers and mounted secrets do) are noticed too
+fn watch_files(paths: &[&str], changes: mpsc::UnboundedSender<()>) -> notify::Result<RecommendedWatcher> {
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        // Reading the files on reload shows up as access events; only react to writes
+        if let Ok(event) = event
+            && matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_))
+        {
+            let _ = changes.send(());
+        }
+    })?;
+
+    let mut dirs: Vec<PathBuf> = paths
+        .iter()
+        .map(|path| match Path::new(path).parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        })
+        .collect();
+    dirs.sort();
+    dirs.dedup();
+    for dir in dirs {
+        watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+    }
+    Ok(watcher)
+}
+
+/// Reloads `resolver` on file changes and on `SIGHUP` until it is dropped
+async fn reload_on_change(resolver: Weak<ReloadingCertResolver>, mut changes: Option<mpsc::UnboundedReceiver<()>>) {
+    let mut hangup = Hangup::new();
+    loop {
+        tokio::select! {
+            changed = next_change(&mut changes) => {
+                if !changed {
+                    changes = None;
+                    continue;
+                }
+                tokio::time::sleep(SETTLE_DELAY).await;
+                if let Some(changes) = changes.as_mut() {
+                    while changes.try_recv().is_ok() {}
+                }
+            }
+            _ = hangup.recv() => debug!("Received SIGHUP, reloading TLS certificate"),
+        }
+
+        match resolver.upgrade() {
+            Some(resolver) => resolver.reload_logged(),
+            None => break,
+        }
+    }
+}
+
+/// Waits for the next file change; returns `false` once the watcher is gone, and
+/// never resolves without one
+async fn next_change(changes: &mut Option<mpsc::UnboundedReceiver<()>>) -> bool {
+    match changes {
+        Some(changes) => changes.recv().await.is_some(),
+        None => std::future::pending().await,
+    }
+}
+
+/// `SIGHUP` notifications, on platforms that have them
+struct Hangup {
+    #[cfg(unix)]
+    signal: Option<tokio::signal::unix::Signal>,
+}
+
+impl Hangup {
+    fn new() -> Self {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{SignalKind, signal};
+            let signal = signal(SignalKind::hangup())
+                .map_err(|e| warn!("Cannot listen for SIGHUP to reload TLS certificates: {}", e))
+                .ok();
+            Self { signal }
+        }
+        #[cfg(not(unix))]
+        Self {}
+    }
+
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        if let Some(signal) = self.signal.as_mut()
+            && signal.recv().await.is_some()
+        {
+            return;
+        }
+        std::future::pending::<()>().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_rustls::rustls::crypto::aws_lc_rs;
+
+    const CERT: &str = "certs/localhost.example.crt";
+    const KEY: &str = "certs/localhost.example.key";
+    const ROTATED_CERT: &str = "certs/localhost.rotated.crt";
+    const ROTATED_KEY: &str = "certs/localhost.rotated.key";
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mcp-tls-reload-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn served(resolver: &ReloadingCertResolver) -> Arc<CertifiedKey> {
+        resolver.current.read().unwrap().clone()
+    }
+
+    #[tokio::test]
+    async fn test_reload_keeps_current_certificate_on_failure() {
+        let dir = temp_dir();
+        let cert_path = dir.join("server.crt");
+        let key_path = dir.join("server.key");
+        std::fs::copy(CERT, &cert_path).unwrap();
+        std::fs::copy(KEY, &key_path).unwrap();
+
+        let resolver = ReloadingCertResolver::watch(
+            cert_path.to_str().unwrap(),
+            key_path.to_str().unwrap(),
+            Arc::new(aws_lc_rs::default_provider()),
+        )
+        .unwrap();
+        let original = served(&resolver);
+        assert!(!resolver.reload().unwrap());
+
+        // A certificate whose key has not been replaced yet is rejected
+        std::fs::copy(ROTATED_CERT, &cert_path).unwrap();
+        let err = resolver.reload().unwrap_err();
+        assert_eq!(err.code(), Some(TransportErrorCode::ConfigurationError));
+        assert_eq!(served(&resolver).cert, original.cert);
+
+        // The watcher may get there first, so only the outcome is checked
+        std::fs::copy(ROTATED_KEY, &key_path).unwrap();
+        resolver.reload().unwrap();
+        assert_ne!(served(&resolver).cert, original.cert);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
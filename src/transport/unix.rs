@@ -0,0 +1,300 @@
+//! Unix domain socket transport
+//!
+//! Newline-delimited JSON over a `UnixStream`, for local IPC without exposing a TCP port.
+//! [`ClientUnixTransport`] connects to a socket; [`serve_unix`] listens on one and runs
+//! a separate [`Server`] session for every accepted connection.
+
+use std::fs::{DirBuilder, Permissions};
+use std::io::ErrorKind;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use async_trait::async_trait;
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::task::{JoinHandle, JoinSet};
+use tracing::{debug, error, info, warn};
+
+use crate::server::{Server, serve_transport};
+use crate::transport::{
+    Codec, Message, Result, StdioTransport, Transport, TransportError, TransportErrorCode,
+    accept_backoff, default_codec,
+};
+
+/// Transport over a connected Unix domain socket
+pub type UnixStreamTransport = StdioTransport<OwnedReadHalf, OwnedWriteHalf>;
+
+impl StdioTransport<OwnedReadHalf, OwnedWriteHalf> {
+    /// Creates a transport over a connected Unix domain socket
+    pub fn from_unix_stream(stream: UnixStream) -> Self {
+        let (reader, writer) = stream.into_split();
+        Self::new(reader, writer)
+    }
+}
+
+/// Client-side transport that connects to a Unix domain socket when opened
+pub struct ClientUnixTransport {
+    path: PathBuf,
+    codec: Arc<dyn Codec>,
+    inner: OnceLock<UnixStreamTransport>,
+}
+
+impl ClientUnixTransport {
+    /// Creates a transport for the socket at `path`
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            codec: default_codec(),
+            inner: OnceLock::new(),
+        }
+    }
+
+    /// Sets the message codec (default: JSON)
+    pub fn with_codec(mut self, codec: Arc<dyn Codec>) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    fn inner(&self) -> Result<&UnixStreamTransport> {
+        self.inner.get().ok_or_else(|| TransportError::new(
+            TransportErrorCode::InvalidState,
+            "Unix socket transport not opened"
+        ))
+    }
+}
+
+#[async_trait]
+impl Transport for ClientUnixTransport {
+    async fn send(&self, message: &Message) -> Result<()> {
+        self.inner()?.send(message).await
+    }
+
+    async fn receive(&self) -> Result<Option<Message>> {
+        self.inner()?.receive().await
+    }
+
+    async fn open(&self) -> Result<()> {
+        if self.inner.get().is_some() {
+            return Ok(());
+        }
+        let stream = UnixStream::connect(&self.path).await.map_err(|e| TransportError::new(
+            TransportErrorCode::ConnectionFailed,
+            format!("Failed to connect to {}: {}", self.path.display(), e)
+        ))?;
+        let transport = UnixStreamTransport::from_unix_stream(stream).with_codec(self.codec.clone());
+        let _ = self.inner.set(transport);
+        Ok(())
+    }
+
+    async fn close(&self) -> Result<()> {
+        match self.inner.get() {
+            Some(inner) => inner.close().await,
+            None => Ok(()),
+        }
+    }
+}
+
+/// Configuration for [`serve_unix`]
+#[derive(Debug, Clone)]
+pub struct UnixServerConfig {
+    /// Path of the socket file
+    pub path: PathBuf,
+    /// Mode applied to the socket file before it becomes reachable, e.g. `0o600`
+    pub permissions: Option<u32>,
+    /// Whether to remove a leftover socket file that nothing is listening on
+    pub remove_stale: bool,
+    /// Message codec used on every connection
+    pub codec: Arc<dyn Codec>,
+}
+
+impl UnixServerConfig {
+    /// Creates a configuration for `path` that removes stale sockets and leaves
+    /// permissions to the process umask
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            permissions: None,
+            remove_stale: true,
+            codec: default_codec(),
+        }
+    }
+
+    /// Sets the mode applied to the socket file
+    pub fn with_permissions(mut self, mode: u32) -> Self {
+        self.permissions = Some(mode);
+        self
+    }
+
+    /// Sets whether a stale socket file is removed before binding
+    pub fn with_remove_stale(mut self, remove_stale: bool) -> Self {
+        self.remove_stale = remove_stale;
+        self
+    }
+
+    /// Sets the message codec (default: JSON)
+    pub fn with_codec(mut self, codec: Arc<dyn Codec>) -> Self {
+        self.codec = codec;
+        self
+    }
+}
+
+/// Handle for a server started with [`serve_unix`]
+#[derive(Debug)]
+pub struct UnixServerHandle {
+    path: PathBuf,
+    task: JoinHandle<()>,
+}
+
+impl UnixServerHandle {
+    /// Returns the path of the socket file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Stops accepting connections, ends all sessions and removes the socket file
+    pub async fn stop(self) -> Result<()> {
+        self.task.abort();
+        let _ = self.task.await;
+        remove_socket(&self.path)
+    }
+}
+
+/// Listens on a Unix domain socket and serves each connection with its own session
+///
+/// `build_server` is called once per accepted connection.
+///
+/// # Errors
+/// - `TransportErrorCode::ConnectionFailed` if the socket cannot be bound, or another
+///   process is already listening on it
+/// - `TransportErrorCode::ConfigurationError` if the path exists and is not a stale
+///   socket that may be removed, or the permissions cannot be applied
+pub async fn serve_unix<F, S>(config: UnixServerConfig, build_server: F) -> Result<UnixServerHandle>
+where
+    F: Fn() -> S + Send + Sync + 'static,
+    S: Server,
+{
+    let path = config.path;
+    let codec = config.codec;
+    if let Ok(metadata) = std::fs::symlink_metadata(&path) {
+        if !metadata.file_type().is_socket() {
+            return Err(TransportError::new(
+                TransportErrorCode::ConfigurationError,
+                format!("{} exists and is not a socket", path.display())
+            ));
+        }
+        match UnixStream::connect(&path).await {
+            Ok(_) => {
+                return Err(TransportError::new(
+                    TransportErrorCode::ConnectionFailed,
+                    format!("Another server is listening on {}", path.display())
+                ));
+            }
+            Err(e) if config.remove_stale && e.kind() == ErrorKind::ConnectionRefused => {
+                warn!("Removing stale socket {}", path.display());
+                remove_socket(&path)?;
+            }
+            Err(_) => {
+                return Err(TransportError::new(
+                    TransportErrorCode::ConfigurationError,
+                    format!("Socket {} already exists", path.display())
+                ));
+            }
+        }
+    }
+
+    let listener = match config.permissions {
+        Some(mode) => bind_with_permissions(&path, mode)?,
+        None => UnixListener::bind(&path).map_err(|e| TransportError::new(
+            TransportErrorCode::ConnectionFailed,
+            format!("Failed to bind to {}: {}", path.display(), e)
+        ))?,
+    };
+    info!("Unix socket server listening on {}", path.display());
+
+    let task = tokio::spawn(async move {
+        let mut sessions = JoinSet::new();
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => {
+                        debug!("Accepted Unix socket connection");
+                        let transport = UnixStreamTransport::from_unix_stream(stream)
+                            .with_codec(codec.clone());
+                        let server = build_server();
+                        sessions.spawn(async move {
+                            if let Err(e) = serve_transport(server, transport).await {
+                                debug!("Unix socket session ended with error: {}", e);
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        error!("Failed to accept Unix socket connection: {}", e);
+                        accept_backoff().await;
+                    }
+                },
+                Some(_) = sessions.join_next(), if !sessions.is_empty() => {}
+            }
+        }
+    });
+
+    Ok(UnixServerHandle { path, task })
+}
+
+/// Binds a socket at `path` that is never reachable with permissions other than `mode`
+///
+/// The socket is bound inside a private (0700) directory next to `path`, given its
+/// permissions there and then renamed into place, so no other user can connect in the
+/// window between binding and applying the mode.
+fn bind_with_permissions(path: &Path, mode: u32) -> Result<UnixListener> {
+    static NEXT_DIR: AtomicU64 = AtomicU64::new(0);
+
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let private_dir = parent.join(format!(
+        ".mcp-{}-{}",
+        std::process::id(),
+        NEXT_DIR.fetch_add(1, Ordering::Relaxed)
+    ));
+    DirBuilder::new().mode(0o700).create(&private_dir).map_err(|e| TransportError::new(
+        TransportErrorCode::ConfigurationError,
+        format!("Failed to create {}: {}", private_dir.display(), e)
+    ))?;
+    let private_path = private_dir.join("socket");
+
+    let result = UnixListener::bind(&private_path)
+        .map_err(|e| TransportError::new(
+            TransportErrorCode::ConnectionFailed,
+            format!("Failed to bind to {}: {}", path.display(), e)
+        ))
+        .and_then(|listener| {
+            std::fs::set_permissions(&private_path, Permissions::from_mode(mode)).map_err(|e| {
+                TransportError::new(
+                    TransportErrorCode::ConfigurationError,
+                    format!("Failed to set permissions on {}: {}", path.display(), e)
+                )
+            })?;
+            std::fs::rename(&private_path, path).map_err(|e| TransportError::new(
+                TransportErrorCode::ConnectionFailed,
+                format!("Failed to bind to {}: {}", path.display(), e)
+            ))?;
+            Ok(listener)
+        });
+    let _ = remove_socket(&private_path);
+    let _ = std::fs::remove_dir(&private_dir);
+    result
+}
+
+fn remove_socket(path: &Path) -> Result<()> {
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(TransportError::new(
+            TransportErrorCode::ConfigurationError,
+            format!("Failed to remove socket {}: {}", path.display(), e)
+        )),
+    }
+}
@@ -0,0 +1,322 @@
+//! Opt-in schema validation for transport traffic
+//!
+//! [`ValidatingTransport`] wraps another [`Transport`] and checks every request,
+//! result and notification passing through it against `spec/schema.json`. Violations
+//! are reported with a JSON pointer to the failing field, either as a log entry or by
+//! rejecting the message.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use serde_json::json;
+use tracing::{error, warn};
+
+use super::{
+    JsonRpcError, JsonRpcMessage, JsonRpcResponse, JsonRpcVersion, Message, RequestId, Result,
+    Transport, TransportError, TransportErrorCode,
+};
+use crate::schema::validation::{SchemaValidator, ValidationError, format_errors};
+
+const INVALID_PARAMS: i32 = -32602;
+const INTERNAL_ERROR: i32 = -32603;
+
+/// What [`ValidatingTransport`] does with a message that does not conform to the schema
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ValidationMode {
+    /// Log a warning and pass the message through unchanged
+    #[default]
+    Log,
+    /// Refuse the message
+    ///
+    /// Incoming requests are answered with an `invalid params` error, incoming results
+    /// are replaced by an error response, and incoming notifications are dropped.
+    /// Outgoing results are replaced by an `internal error` response, and outgoing
+    /// requests and notifications fail with [`TransportErrorCode::InvalidMessage`].
+    /// When the transport drives a session, refused requests are answered locally with
+    /// an error response and refused notifications are dropped.
+    Reject,
+}
+
+#[derive(Clone, Copy)]
+enum Direction {
+    Incoming,
+    Outgoing,
+}
+
+/// Transport wrapper that validates messages against the MCP schema
+///
+/// Results are validated against the result definition of the request they answer,
+/// so the wrapper remembers the method of every request in flight in both directions.
+/// A request is forgotten once it is answered or a `notifications/cancelled` for it
+/// passes through. Methods that are not defined in the schema are not validated.
+pub struct ValidatingTransport<T> {
+    inner: T,
+    mode: ValidationMode,
+    validator: &'static SchemaValidator,
+    sent_requests: Mutex<HashMap<RequestId, String>>,
+    received_requests: Mutex<HashMap<RequestId, String>>,
+}
+
+impl<T: Transport> ValidatingTransport<T> {
+    /// Wraps `inner`, validating against the bundled MCP schema
+    pub fn new(inner: T, mode: ValidationMode) -> Self {
+        Self {
+            inner,
+            mode,
+            validator: SchemaValidator::mcp(),
+            sent_requests: Mutex::new(HashMap::new()),
+            received_requests: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the wrapped transport
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Validates a message; returns the method it belongs to and any violations
+    fn check(
+        &self,
+        message: &Message,
+        direction: Direction,
+    ) -> Option<(String, Vec<ValidationError>)> {
+        let (own, peer) = match direction {
+            Direction::Incoming => (&self.received_requests, &self.sent_requests),
+            Direction::Outgoing => (&self.sent_requests, &self.received_requests),
+        };
+        let (method, result) = match message {
+            JsonRpcMessage::Request(r) => {
+                own.lock().unwrap().insert(r.id, r.method.clone());
+                (
+                    r.method.clone(),
+                    self.validator
+                        .validate_request(&r.method, r.params.as_ref()),
+                )
+            }
+            JsonRpcMessage::Notification(n) => {
+                // A cancelled request is never answered; its sender cancels it
+                if n.method == "notifications/cancelled" {
+                    let id = n.params.as_ref().and_then(|p| p["requestId"].as_u64());
+                    if let Some(id) = id {
+                        own.lock().unwrap().remove(&id);
+                    }
+                }
+                (
+                    n.method.clone(),
+                    self.validator
+                        .validate_notification(&n.method, n.params.as_ref()),
+                )
+            }
+            JsonRpcMessage::Response(r) => {
+                let method = peer.lock().unwrap().remove(&r.id)?;
+                let result = match (&r.result, &r.error) {
+                    (Some(result), None) => self.validator.validate_result(&method, result),
+                    _ => Ok(()),
+                };
+                (method, result)
+            }
+        };
+        result.err().map(|errors| (method, errors))
+    }
+}
+
+fn error_response(
+    id: RequestId,
+    code: i32,
+    message: String,
+    errors: &[ValidationError],
+) -> Message {
+    let data = errors
+        .iter()
+        .map(|e| json!({ "pointer": e.pointer, "message": e.message }))
+        .collect::<Vec<_>>();
+    JsonRpcMessage::Response(JsonRpcResponse {
+        id,
+        result: None,
+        error: Some(JsonRpcError {
+            code,
+            message,
+            data: Some(json!({ "schemaErrors": data })),
+        }),
+        jsonrpc: JsonRpcVersion::default(),
+    })
+}
+
+#[async_trait]
+impl<T: Transport> Transport for ValidatingTransport<T> {
+    async fn send(&self, message: &Message) -> Result<()> {
+        let Some((method, errors)) = self.check(message, Direction::Outgoing) else {
+            return self.inner.send(message).await;
+        };
+        let details = format_errors(&errors);
+        match (self.mode, message) {
+            (ValidationMode::Log, _) => {
+                warn!(
+                    "Outgoing `{}` does not conform to schema: {}",
+                    method, details
+                );
+                self.inner.send(message).await
+            }
+            (ValidationMode::Reject, JsonRpcMessage::Response(r)) => {
+                error!(
+                    "Replacing non-conforming `{}` result with an error: {}",
+                    method, details
+                );
+                let message = format!("Result of `{method}` does not conform to schema: {details}");
+                self.inner
+                    .send(&error_response(r.id, INTERNAL_ERROR, message, &errors))
+                    .await
+            }
+            (ValidationMode::Reject, message) => {
+                if let JsonRpcMessage::Request(r) = message {
+                    self.sent_requests.lock().unwrap().remove(&r.id);
+                }
+                Err(TransportError::new(
+                    TransportErrorCode::InvalidMessage,
+                    format!("`{method}` does not conform to schema: {details}"),
+                ))
+            }
+        }
+    }
+
+    async fn receive(&self) -> Result<Option<Message>> {
+        loop {
+            let Some(message) = self.inner.receive().await? else {
+                return Ok(None);
+            };
+            let Some((method, errors)) = self.check(&message, Direction::Incoming) else {
+                return Ok(Some(message));
+            };
+            let details = format_errors(&errors);
+            match (self.mode, message) {
+                (ValidationMode::Log, message) => {
+                    warn!(
+                        "Incoming `{}` does not conform to schema: {}",
+                        method, details
+                    );
+                    return Ok(Some(message));
+                }
+                (ValidationMode::Reject, JsonRpcMessage::Request(r)) => {
+                    warn!("Rejecting non-conforming `{}` request: {}", method, details);
+                    self.received_requests.lock().unwrap().remove(&r.id);
+                    let message = format!("Invalid params for `{method}`: {details}");
+                    self.inner
+                        .send(&error_response(r.id, INVALID_PARAMS, message, &errors))
+                        .await?;
+                }
+                (ValidationMode::Reject, JsonRpcMessage::Response(r)) => {
+                    warn!("Rejecting non-conforming `{}` result: {}", method, details);
+                    let message =
+                        format!("Result of `{method}` does not conform to schema: {details}");
+                    return Ok(Some(error_response(r.id, INTERNAL_ERROR, message, &errors)));
+                }
+                (ValidationMode::Reject, JsonRpcMessage::Notification(_)) => {
+                    warn!(
+                        "Dropping non-conforming `{}` notification: {}",
+                        method, details
+                    );
+                }
+            }
+        }
+    }
+
+    async fn open(&self) -> Result<()> {
+        self.inner.open().await
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.inner.close().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{ClientInMemoryTransport, JsonRpcNotification, JsonRpcRequest};
+
+    async fn echo_server(transport: crate::transport::ServerInMemoryTransport) {
+        while let Ok(Some(message)) = transport.receive().await {
+            if transport.send(&message).await.is_err() {
+                break;
+            }
+        }
+    }
+
+    fn request(id: RequestId, method: &str, params: serde_json::Value) -> Message {
+        JsonRpcMessage::Request(JsonRpcRequest {
+            id,
+            method: method.to_string(),
+            params: Some(params),
+            jsonrpc: JsonRpcVersion::default(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_reject_outgoing_notification() -> Result<()> {
+        let inner = ClientInMemoryTransport::new(|t| tokio::spawn(echo_server(t)));
+        let transport = ValidatingTransport::new(inner, ValidationMode::Reject);
+        transport.open().await?;
+
+        let invalid = JsonRpcMessage::Notification(JsonRpcNotification {
+            method: "notifications/progress".to_string(),
+            params: Some(json!({ "progress": "half" })),
+            jsonrpc: JsonRpcVersion::default(),
+        });
+        let err = transport.send(&invalid).await.unwrap_err();
+        assert_eq!(err.code(), Some(TransportErrorCode::InvalidMessage));
+        assert!(err.to_string().contains("/params/progress"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reject_incoming_request() -> Result<()> {
+        let inner = ClientInMemoryTransport::new(|t| tokio::spawn(echo_server(t)));
+        let transport = ValidatingTransport::new(inner, ValidationMode::Reject);
+        transport.open().await?;
+
+        // Bypass validation on the way out; the echo server sends the request back,
+        // where it arrives as an incoming request and is answered with an error, which
+        // is echoed back in turn
+        let invalid = request(2, "tools/call", json!({ "arguments": {} }));
+        transport.inner().send(&invalid).await?;
+        let Some(JsonRpcMessage::Response(response)) = transport.receive().await? else {
+            panic!("expected error response");
+        };
+        assert_eq!(response.id, 2);
+        let error = response.error.unwrap();
+        assert_eq!(error.code, INVALID_PARAMS);
+        assert_eq!(
+            error.data.unwrap()["schemaErrors"][0]["pointer"],
+            "/params/name"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_requests_are_forgotten() -> Result<()> {
+        let inner = ClientInMemoryTransport::new(|t| tokio::spawn(echo_server(t)));
+        let transport = ValidatingTransport::new(inner, ValidationMode::Log);
+        transport.open().await?;
+
+        transport
+            .send(&request(1, "tools/call", json!({ "name": "echo" })))
+            .await?;
+        assert!(transport.sent_requests.lock().unwrap().contains_key(&1));
+
+        let cancelled = JsonRpcMessage::Notification(JsonRpcNotification {
+            method: "notifications/cancelled".to_string(),
+            params: Some(json!({ "requestId": 1 })),
+            jsonrpc: JsonRpcVersion::default(),
+        });
+        transport.send(&cancelled).await?;
+        assert!(transport.sent_requests.lock().unwrap().is_empty());
+
+        // The echoed request and cancellation clean up the incoming side the same way
+        transport.receive().await?;
+        assert!(transport.received_requests.lock().unwrap().contains_key(&1));
+        transport.receive().await?;
+        assert!(transport.received_requests.lock().unwrap().is_empty());
+        Ok(())
+    }
+}
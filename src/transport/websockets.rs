@@ -1,36 +1,84 @@
-use super::{Message, Transport};
+use super::{
+    Codec, JsonCodec, MessageQueue, QueueConfig, codec_for_subprotocol, default_codec, JsonRpcError, JsonRpcMessage, JsonRpcNotification, JsonRpcRequest, JsonRpcResponse,
+    JsonRpcVersion, Message, RequestId, Transport,
+};
 use super::Result;
 use super::error::{TransportError, TransportErrorCode};
 use actix_ws::{Message as WsMessage, Session};
 use async_trait::async_trait;
+use futures::future::BoxFuture;
 use futures::{SinkExt, StreamExt};
 use reqwest::header::{HeaderName, HeaderValue};
+use std::collections::{BTreeSet, VecDeque};
+use std::future::Future;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 use std::{collections::HashMap, str::FromStr};
-use tokio::sync::{broadcast, Mutex};
+use tokio::sync::{oneshot, watch, Mutex};
 use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message as TungsteniteMessage};
-use tracing::{debug, info};
+use tracing::{debug, error, info, warn};
 
 // Type aliases to simplify complex types
 type WsStream = tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
 type WsSink = futures::stream::SplitSink<WsStream, TungsteniteMessage>;
-type MessageSender = broadcast::Sender<Message>;
-type MessageReceiver = broadcast::Receiver<Message>;
+type WsSource = futures::stream::SplitStream<WsStream>;
+
+/// JSON-RPC error code for failures inside the transport
+const INTERNAL_ERROR: i32 = -32603;
+
+/// WebSocket subprotocol identifying MCP JSON-RPC messages
+///
+/// Binary codecs use variants of it, see [`Codec::subprotocol`].
+pub const MCP_SUBPROTOCOL: &str = "mcp";
+
+/// An encoded message, ready to be sent as a WebSocket frame
+enum Frame {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+/// Encodes a message as a text frame with text codecs and a binary frame otherwise
+fn encode_frame(codec: &dyn Codec, message: &Message) -> Result<Frame> {
+    let bytes = codec.encode(message)?;
+    if codec.is_binary() {
+        return Ok(Frame::Binary(bytes));
+    }
+    String::from_utf8(bytes).map(Frame::Text).map_err(|e| TransportError::new(
+        TransportErrorCode::MessageSendFailed,
+        format!("{} codec produced invalid UTF-8: {}", codec.name(), e)
+    ))
+}
+
+/// Decodes a frame payload; text frames are always JSON, binary frames use `codec`
+fn decode_frame(codec: &dyn Codec, payload: &[u8], is_text: bool) -> Result<Message> {
+    if is_text {
+        JsonCodec.decode(payload)
+    } else {
+        codec.decode(payload)
+    }
+}
 
 #[derive(Clone)]
 /// WebSocket transport implementation for the server side
+///
+/// Messages from the client are read by [`handle_ws_connection_with`] into a bounded
+/// [`MessageQueue`], from which `receive` takes them, so a slow consumer applies the
+/// queue's overflow policy instead of losing messages.
 pub struct ServerWsTransport {
     session: Arc<Mutex<Option<Session>>>,
-    rx: Arc<Mutex<Option<broadcast::Receiver<Message>>>>,
-    tx: Arc<Mutex<Option<broadcast::Sender<Message>>>>,
+    incoming: MessageQueue,
+    metrics: ConnectionMetrics,
+    codec: Arc<dyn Codec>,
 }
 
 impl std::fmt::Debug for ServerWsTransport {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ServerWsTransport")
             .field("session", &"<Session>")
-            .field("rx", &self.rx)
-            .field("tx", &self.tx)
+            .field("incoming", &self.incoming)
+            .field("metrics", &self.metrics)
+            .field("codec", &self.codec)
             .finish()
     }
 }
@@ -40,54 +88,422 @@ impl ServerWsTransport {
     ///
     /// # Arguments
     /// * `session` - The WebSocket session
-    /// * `rx` - Channel receiver for incoming messages
-    pub fn new(session: Session, rx: broadcast::Receiver<Message>) -> Self {
-        // We need to create a new sender since we can't get it from the receiver
-        let (tx, _) = broadcast::channel(100);
-
+    /// * `incoming` - Queue the connection handler pushes incoming messages to
+    pub fn new(session: Session, incoming: MessageQueue) -> Self {
         Self {
             session: Arc::new(Mutex::new(Some(session))),
-            rx: Arc::new(Mutex::new(Some(rx))),
-            tx: Arc::new(Mutex::new(Some(tx))),
+            incoming,
+            metrics: ConnectionMetrics::default(),
+            codec: default_codec(),
         }
     }
 
-    /// Create a new server-side WebSocket transport with a new channel
+    /// Create a new server-side WebSocket transport with a new receive queue
     ///
     /// # Arguments
     /// * `session` - The WebSocket session
-    /// * `capacity` - The capacity of the broadcast channel
-    pub fn new_with_channel(session: Session, capacity: usize) -> (Self, broadcast::Sender<Message>) {
-        let (tx, rx) = broadcast::channel(capacity);
+    /// * `config` - Size and overflow policy of the receive queue
+    ///
+    /// Pass the returned queue to [`handle_ws_connection_with`] as its `incoming`.
+    pub fn new_with_queue(session: Session, config: QueueConfig) -> (Self, MessageQueue) {
+        let incoming = MessageQueue::new(config);
+        (Self::new(session, incoming.clone()), incoming)
+    }
 
-        let transport = Self {
-            session: Arc::new(Mutex::new(Some(session))),
-            rx: Arc::new(Mutex::new(Some(rx))),
-            tx: Arc::new(Mutex::new(Some(tx.clone()))),
-        };
+    /// Shares `metrics` with this transport
+    ///
+    /// Pass the same metrics to [`handle_ws_connection_with`] so the values measured by
+    /// the connection handler are visible through [`ServerWsTransport::metrics`].
+    pub fn with_metrics(mut self, metrics: ConnectionMetrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Returns the liveness metrics of the connection
+    pub fn metrics(&self) -> &ConnectionMetrics {
+        &self.metrics
+    }
+
+    /// Sets the codec for outgoing messages (default: JSON)
+    ///
+    /// Use the codec negotiated by [`negotiate_codec`] and pass the same one to
+    /// [`handle_ws_connection_with`].
+    pub fn with_codec(mut self, codec: Arc<dyn Codec>) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// Closes the connection with `reason` in the close frame, e.g. `GoingAway` when
+    /// the server shuts down; [`Transport::close`] closes it as `Normal`
+    pub async fn close_with_reason(&self, reason: actix_ws::CloseReason) -> Result<()> {
+        debug!("Closing server WebSocket connection");
+
+        // Take the session to ensure we don't leave dangling references
+        let mut session_guard = self.session.lock().await;
+        if let Some(session) = session_guard.take() {
+            // Send the close frame and ignore errors if the connection is already closed
+            match session.close(Some(reason)).await {
+                Ok(_) => {
+                    debug!("Server WebSocket connection closed successfully");
+                },
+                Err(e) => {
+                    debug!("Error closing server WebSocket connection (may already be closed): {}", e);
+                }
+            }
+        } else {
+            debug!("Server WebSocket connection already closed");
+        }
+
+        // Messages already queued can still be received
+        self.incoming.close();
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Transport for ServerWsTransport {
+    async fn receive(&self) -> Result<Option<Message>> {
+        let message = self.incoming.pop().await?;
+        match &message {
+            Some(message) => debug!("Server received WebSocket message: {:?}", message),
+            None => debug!("Server WebSocket receive queue closed"),
+        }
+        Ok(message)
+    }
+
+    async fn send(&self, message: &Message) -> Result<()> {
+        let mut session_guard = self.session.lock().await;
+        if let Some(session) = session_guard.as_mut() {
+            // Serialize the message
+            let frame = encode_frame(self.codec.as_ref(), message)?;
+
+            debug!("Server sending WebSocket message: {:?}", message);
+
+            // Send the message
+            let sent = match frame {
+                Frame::Text(text) => session.text(text).await,
+                Frame::Binary(bytes) => session.binary(bytes).await,
+            };
+            match sent {
+                Ok(_) => {
+                    debug!("Server successfully sent WebSocket message");
+                    Ok(())
+                },
+                Err(e) => {
+                    // If sending fails, the session is likely closed
+                    debug!("Server failed to send WebSocket message: {}", e);
+                    // Clear the session reference
+                    *session_guard = None;
+                    Err(TransportError::new(
+                        TransportErrorCode::MessageSendFailed,
+                        format!("Failed to send message: {}", e)
+                    ))
+                }
+            }
+        } else {
+            debug!("Server attempted to send WebSocket message with no active session");
+            Err(TransportError::new(
+                TransportErrorCode::ConnectionClosed,
+                "No active WebSocket session"
+            ))
+        }
+    }
+
+    async fn open(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.close_with_reason(actix_ws::CloseReason {
+            code: actix_ws::CloseCode::Normal,
+            description: Some("Server initiated close".to_string()),
+        })
+        .await
+    }
+}
+
+/// Ping settings for detecting dead WebSocket peers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeartbeatConfig {
+    /// Time between pings
+    pub interval: Duration,
+    /// How long to wait for a pong before the connection is considered dead
+    pub timeout: Duration,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(30),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct MetricsState {
+    last_seen: Option<Instant>,
+    rtt: Option<Duration>,
+}
+
+/// Liveness metrics of a WebSocket connection
+///
+/// Clones share the same underlying values, so a handle can be kept while the
+/// connection is driven elsewhere.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionMetrics {
+    state: Arc<std::sync::Mutex<MetricsState>>,
+}
+
+impl ConnectionMetrics {
+    /// Returns when the last frame of any kind was received from the peer
+    pub fn last_seen(&self) -> Option<Instant> {
+        self.state.lock().unwrap().last_seen
+    }
+
+    /// Returns the round-trip time measured by the most recent ping
+    pub fn rtt(&self) -> Option<Duration> {
+        self.state.lock().unwrap().rtt
+    }
+
+    fn record_seen(&self) {
+        self.state.lock().unwrap().last_seen = Some(Instant::now());
+    }
+
+    fn record_rtt(&self, rtt: Duration) {
+        self.state.lock().unwrap().rtt = Some(rtt);
+    }
+}
+
+/// Heartbeat bookkeeping shared by the client and server connection loops
+struct Heartbeat {
+    config: HeartbeatConfig,
+    metrics: ConnectionMetrics,
+    next_ping: Instant,
+    /// Payload and send time of the ping awaiting its pong
+    outstanding: Option<(u64, Instant)>,
+    sequence: u64,
+}
+
+enum HeartbeatAction {
+    /// Send a ping with this payload
+    Ping(Vec<u8>),
+    /// The peer did not answer in time
+    TimedOut,
+}
 
-        (transport, tx)
+impl Heartbeat {
+    fn new(config: HeartbeatConfig, metrics: ConnectionMetrics) -> Self {
+        Self {
+            config,
+            metrics,
+            next_ping: Instant::now() + config.interval,
+            outstanding: None,
+            sequence: 0,
+        }
+    }
+
+    /// Returns when [`Heartbeat::poll`] next has something to do
+    fn deadline(&self) -> Instant {
+        match self.outstanding {
+            Some((_, sent)) => sent + self.config.timeout,
+            None => self.next_ping,
+        }
+    }
+
+    /// Called at the deadline
+    fn poll(&mut self) -> HeartbeatAction {
+        let now = Instant::now();
+        if let Some((_, sent)) = self.outstanding
+            && now >= sent + self.config.timeout
+        {
+            return HeartbeatAction::TimedOut;
+        }
+        self.sequence += 1;
+        self.outstanding = Some((self.sequence, now));
+        self.next_ping = now + self.config.interval;
+        HeartbeatAction::Ping(self.sequence.to_be_bytes().to_vec())
+    }
+
+    /// Called for every pong; pongs that do not answer the outstanding ping are ignored
+    fn pong(&mut self, payload: &[u8]) {
+        if let Some((sequence, sent)) = self.outstanding
+            && payload == sequence.to_be_bytes()
+        {
+            self.metrics.record_rtt(sent.elapsed());
+            self.outstanding = None;
+        }
+    }
+
+    fn timeout_error(&self) -> TransportError {
+        TransportError::new(
+            TransportErrorCode::ConnectionTimeout,
+            format!("No pong received within {:?}", self.config.timeout)
+        )
+    }
+}
+
+/// Waits until the heartbeat deadline, or forever without a heartbeat
+async fn heartbeat_deadline(heartbeat: &Option<Heartbeat>) {
+    match heartbeat {
+        Some(heartbeat) => tokio::time::sleep_until(heartbeat.deadline().into()).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Connection state of a [`ClientWsTransport`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Not connected; either never opened, closed, or out of reconnect attempts
+    Disconnected,
+    /// Establishing the initial connection
+    Connecting,
+    /// Connected and ready to send
+    Connected,
+    /// Connection lost; trying to re-establish it
+    Reconnecting {
+        /// Number of the current attempt, starting at 1
+        attempt: u32,
+    },
+}
+
+/// What happens to messages sent while a [`ClientWsTransport`] is reconnecting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutgoingPolicy {
+    /// Hold messages and send them once the connection is restored
+    Queue {
+        /// Maximum number of queued messages; further sends fail
+        capacity: usize,
+    },
+    /// Fail sends with `TransportErrorCode::ConnectionClosed`
+    Fail,
+}
+
+/// Reconnection settings for [`ClientWsTransport`]
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    /// Attempts per outage before giving up; `None` retries forever
+    pub max_attempts: Option<u32>,
+    /// Delay before the first attempt
+    pub initial_delay: Duration,
+    /// Upper bound for the delay between attempts
+    pub max_delay: Duration,
+    /// Factor applied to the delay after each failed attempt
+    pub multiplier: f64,
+    /// Fraction of each delay that is randomized, between 0.0 and 1.0
+    pub jitter: f64,
+    /// What to do with messages sent while reconnecting
+    pub outgoing: OutgoingPolicy,
+    /// Whether to replay the MCP `initialize` handshake and resource subscriptions
+    /// on the new connection before any queued message is sent
+    pub restore_session: bool,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: Some(10),
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: 0.2,
+            outgoing: OutgoingPolicy::Queue { capacity: 1000 },
+            restore_session: true,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Returns the delay before reconnect attempt `attempt` (starting at 1)
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let base = self.initial_delay.as_secs_f64() * self.multiplier.powi(exponent);
+        let base = base.min(self.max_delay.as_secs_f64());
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let factor = 1.0 - jitter + 2.0 * jitter * rand::random::<f64>();
+        Duration::from_secs_f64((base * factor).min(self.max_delay.as_secs_f64()))
     }
 }
 
+/// Callback run after the transport reconnects, before queued messages are sent
+pub type ReconnectHook = Arc<dyn Fn(ReconnectHandle) -> BoxFuture<'static, Result<()>> + Send + Sync>;
+
+/// Id of the first request sent by the transport itself while restoring a session
+///
+/// Kept well above the ids used by sessions so responses can be told apart.
+const INTERNAL_REQUEST_ID_BASE: RequestId = 1 << 52;
+
+/// How long a request sent while restoring a session may take
+const INTERNAL_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Client state replayed on a new connection
+#[derive(Default)]
+struct SessionReplay {
+    /// Parameters of the last successful `initialize` request
+    initialize: Option<serde_json::Value>,
+    /// Whether `notifications/initialized` was sent
+    initialized: bool,
+    /// URIs of active resource subscriptions
+    subscriptions: BTreeSet<String>,
+}
+
+/// A request sent by the client that has not been answered yet
+struct PendingRequest {
+    method: String,
+    params: Option<serde_json::Value>,
+}
+
+struct ClientWsInner {
+    url: String,
+    headers: HashMap<String, String>,
+    subprotocols: Vec<String>,
+    /// Subprotocol selected by the server on the current connection
+    subprotocol: std::sync::Mutex<Option<String>>,
+    /// Codec selected by the subprotocol of the current connection
+    codec: std::sync::Mutex<Arc<dyn Codec>>,
+    reconnect: Option<ReconnectPolicy>,
+    hook: Option<ReconnectHook>,
+    heartbeat: Option<HeartbeatConfig>,
+    metrics: ConnectionMetrics,
+    /// Why the connection ended, reported once by `receive`
+    last_error: std::sync::Mutex<Option<TransportError>>,
+    state: watch::Sender<ConnectionState>,
+    receive_queue: QueueConfig,
+    /// Messages waiting for `receive`; replaced on every `open`
+    incoming: std::sync::Mutex<Option<MessageQueue>>,
+    ws_write: Mutex<Option<WsSink>>,
+    /// Messages sent while reconnecting; also serializes sends with the queue flush
+    queue: Mutex<VecDeque<Message>>,
+    pending: std::sync::Mutex<HashMap<RequestId, PendingRequest>>,
+    replay: std::sync::Mutex<SessionReplay>,
+    internal: std::sync::Mutex<HashMap<RequestId, oneshot::Sender<JsonRpcResponse>>>,
+    next_internal_id: AtomicU64,
+    closed: AtomicBool,
+    /// Task reading the connection and reconnecting; replaced on every `open`
+    task: std::sync::Mutex<Option<tokio::task::JoinHandle<()>>>,
+}
+
 #[derive(Clone)]
 /// WebSocket transport implementation for the client side
+///
+/// With a [`ReconnectPolicy`] the transport re-establishes lost connections with
+/// exponential backoff. Requests in flight when the connection drops fail with an
+/// error response. Once reconnected, the transport replays the client's `initialize`
+/// handshake and resource subscriptions, runs the optional [`ReconnectHook`], and then
+/// sends any messages queued in the meantime. Progress is reported through
+/// [`ClientWsTransport::connection_state`].
 pub struct ClientWsTransport {
-    ws_tx: Arc<Mutex<Option<MessageSender>>>,
-    ws_rx: Arc<Mutex<Option<MessageReceiver>>>,
-    url: String,
-    headers: HashMap<String, String>,
-    ws_write: Arc<Mutex<Option<WsSink>>>,
+    inner: Arc<ClientWsInner>,
 }
 
 impl std::fmt::Debug for ClientWsTransport {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ClientWsTransport")
-            .field("url", &self.url)
-            .field("headers", &self.headers)
-            .field("ws_tx", &"<MessageSender>")
-            .field("ws_rx", &"<MessageReceiver>")
-            .field("ws_write", &"<WsSink>")
+            .field("url", &self.inner.url)
+            .field("headers", &self.inner.headers)
+            .field("reconnect", &self.inner.reconnect)
+            .field("state", &*self.inner.state.borrow())
             .finish()
     }
 }
@@ -100,12 +516,38 @@ impl ClientWsTransport {
     pub fn builder(url: String) -> ClientWsTransportBuilder {
         ClientWsTransportBuilder::new(url)
     }
+
+    /// Returns a receiver that observes connection state changes
+    pub fn connection_state(&self) -> watch::Receiver<ConnectionState> {
+        self.inner.state.subscribe()
+    }
+
+    /// Returns the liveness metrics of the connection
+    pub fn metrics(&self) -> ConnectionMetrics {
+        self.inner.metrics.clone()
+    }
+
+    /// Returns the subprotocol the server selected, if any
+    pub fn subprotocol(&self) -> Option<String> {
+        self.inner.subprotocol.lock().unwrap().clone()
+    }
+
+    /// Returns the codec in use on the current connection
+    pub fn codec(&self) -> Arc<dyn Codec> {
+        self.inner.codec.lock().unwrap().clone()
+    }
 }
 
 /// Builder for configuring and creating a client WebSocket transport
 pub struct ClientWsTransportBuilder {
     url: String,
     headers: HashMap<String, String>,
+    subprotocols: Option<Vec<String>>,
+    codec: Option<Arc<dyn Codec>>,
+    reconnect: Option<ReconnectPolicy>,
+    hook: Option<ReconnectHook>,
+    heartbeat: Option<HeartbeatConfig>,
+    receive_queue: QueueConfig,
 }
 
 impl ClientWsTransportBuilder {
@@ -117,6 +559,12 @@ impl ClientWsTransportBuilder {
         Self {
             url,
             headers: HashMap::new(),
+            subprotocols: None,
+            codec: None,
+            reconnect: None,
+            hook: None,
+            heartbeat: None,
+            receive_queue: QueueConfig::default(),
         }
     }
 
@@ -130,194 +578,175 @@ impl ClientWsTransportBuilder {
         self
     }
 
-    /// Build the client WebSocket transport with the configured options
-    pub fn build(self) -> ClientWsTransport {
-        ClientWsTransport {
-            ws_tx: Arc::new(Mutex::new(None)),
-            ws_rx: Arc::new(Mutex::new(None)),
-            url: self.url,
-            headers: self.headers,
-            ws_write: Arc::new(Mutex::new(None)),
-        }
+    /// Set the subprotocols offered in `Sec-WebSocket-Protocol`
+    ///
+    /// By default nothing is offered, which works with every server. A preferred
+    /// non-JSON codec (see [`with_codec`](Self::with_codec)) offers its subprotocol
+    /// followed by [`MCP_SUBPROTOCOL`]. When any are offered, the server must select
+    /// one of them or the handshake fails, so only offer [`MCP_SUBPROTOCOL`] to servers
+    /// that negotiate it.
+    pub fn with_subprotocols<I, S>(mut self, subprotocols: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.subprotocols = Some(subprotocols.into_iter().map(Into::into).collect());
+        self
     }
-}
 
-#[async_trait]
-impl Transport for ServerWsTransport {
-    async fn receive(&self) -> Result<Option<Message>> {
-        let mut rx_guard = self.rx.lock().await;
-        if let Some(rx) = rx_guard.as_mut() {
-            match rx.recv().await {
-                Ok(message) => {
-                    debug!("Server received WebSocket message: {:?}", message);
-                    Ok(Some(message))
-                },
-                Err(broadcast::error::RecvError::Closed) => {
-                    debug!("Server WebSocket channel closed");
-                    // Channel is closed, clear our reference to it
-                    *rx_guard = None;
-                    Ok(None)
-                },
-                Err(broadcast::error::RecvError::Lagged(n)) => {
-                    // We lagged behind, log a warning but continue
-                    debug!("Server WebSocket channel lagged behind by {} messages", n);
-                    // Try to receive again with a new subscription
-                    if let Some(tx) = self.tx.lock().await.as_ref() {
-                        *rx_guard = Some(tx.subscribe());
-                        // Return None this time, the next call will get a message
-                        Ok(None)
-                    } else {
-                        // No sender available, channel is effectively closed
-                        *rx_guard = None;
-                        Ok(None)
-                    }
-                }
-            }
-        } else {
-            // No receiver available
-            debug!("Server WebSocket receive called but no receiver is available");
-            Ok(None)
-        }
+    /// Prefer `codec` for messages on the connection
+    ///
+    /// A non-JSON codec's subprotocol is offered ahead of [`MCP_SUBPROTOCOL`]; servers
+    /// that do not support it fall back to JSON. Such servers must negotiate
+    /// [`MCP_SUBPROTOCOL`], or the handshake fails. The codec actually used is reported by
+    /// [`ClientWsTransport::codec`].
+    pub fn with_codec(mut self, codec: Arc<dyn Codec>) -> Self {
+        self.codec = Some(codec);
+        self
     }
 
-    async fn send(&self, message: &Message) -> Result<()> {
-        let mut session_guard = self.session.lock().await;
-        if let Some(session) = session_guard.as_mut() {
-            // Serialize the message to JSON
-            let json = serde_json::to_string(message)
-                .map_err(|e| TransportError::new(
-                    TransportErrorCode::MessageSendFailed,
-                    format!("Failed to serialize message: {}", e)
-                ))?;
-
-            debug!("Server sending WebSocket message: {}", json);
-
-            // Send the message
-            match session.text(json).await {
-                Ok(_) => {
-                    debug!("Server successfully sent WebSocket message");
-                    Ok(())
-                },
-                Err(e) => {
-                    // If sending fails, the session is likely closed
-                    debug!("Server failed to send WebSocket message: {}", e);
-                    // Clear the session reference
-                    *session_guard = None;
-                    Err(TransportError::new(
-                        TransportErrorCode::MessageSendFailed,
-                        format!("Failed to send message: {}", e)
-                    ))
-                }
-            }
-        } else {
-            debug!("Server attempted to send WebSocket message with no active session");
-            Err(TransportError::new(
-                TransportErrorCode::ConnectionClosed,
-                "No active WebSocket session"
-            ))
-        }
+    /// Reconnect automatically when the connection is lost
+    ///
+    /// # Arguments
+    /// * `policy` - Backoff, attempt limit and queueing behaviour
+    pub fn with_reconnect(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect = Some(policy);
+        self
     }
 
-    async fn open(&self) -> Result<()> {
-        Ok(())
+    /// Run `hook` after every successful reconnect
+    ///
+    /// The hook runs after the session has been restored and before queued messages
+    /// are sent; it can issue requests on the new connection through the
+    /// [`ReconnectHandle`]. An error closes the connection and triggers another attempt.
+    pub fn with_reconnect_hook<F, Fut>(mut self, hook: F) -> Self
+    where
+        F: Fn(ReconnectHandle) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.hook = Some(Arc::new(move |handle| Box::pin(hook(handle))));
+        self
     }
 
-    async fn close(&self) -> Result<()> {
-        debug!("Closing server WebSocket connection");
+    /// Ping the server periodically and drop the connection if it stops answering
+    ///
+    /// A dead connection is reconnected according to the [`ReconnectPolicy`];
+    /// without one, `receive` fails with `TransportErrorCode::ConnectionTimeout`.
+    pub fn with_heartbeat(mut self, heartbeat: HeartbeatConfig) -> Self {
+        self.heartbeat = Some(heartbeat);
+        self
+    }
 
-        // Take the session to ensure we don't leave dangling references
-        let mut session_guard = self.session.lock().await;
-        if let Some(session) = session_guard.take() {
-            // Create a normal closure frame
-            let close_frame = actix_ws::CloseReason {
-                code: actix_ws::CloseCode::Normal,
-                description: Some("Server initiated close".to_string()),
-            };
+    /// Set the size and overflow policy of the queue of received messages
+    ///
+    /// By default up to 1000 messages are queued, after which the connection is not
+    /// read from until `receive` catches up.
+    pub fn with_receive_queue(mut self, config: QueueConfig) -> Self {
+        self.receive_queue = config;
+        self
+    }
 
-            // Send the close frame and ignore errors if the connection is already closed
-            match session.close(Some(close_frame)).await {
-                Ok(_) => {
-                    debug!("Server WebSocket connection closed successfully");
-                },
-                Err(e) => {
-                    debug!("Error closing server WebSocket connection (may already be closed): {}", e);
-                }
-            }
-        } else {
-            debug!("Server WebSocket connection already closed");
+    /// Build the client WebSocket transport with the configured options
+    pub fn build(self) -> ClientWsTransport {
+        let subprotocols = self.subprotocols.unwrap_or_else(|| match &self.codec {
+            Some(codec) if codec.subprotocol() != MCP_SUBPROTOCOL => vec![
+                codec.subprotocol().to_string(),
+                MCP_SUBPROTOCOL.to_string(),
+            ],
+            _ => Vec::new(),
+        });
+        ClientWsTransport {
+            inner: Arc::new(ClientWsInner {
+                url: self.url,
+                headers: self.headers,
+                subprotocols,
+                subprotocol: std::sync::Mutex::new(None),
+                codec: std::sync::Mutex::new(default_codec()),
+                reconnect: self.reconnect,
+                hook: self.hook,
+                heartbeat: self.heartbeat,
+                metrics: ConnectionMetrics::default(),
+                last_error: std::sync::Mutex::new(None),
+                state: watch::Sender::new(ConnectionState::Disconnected),
+                receive_queue: self.receive_queue,
+                incoming: std::sync::Mutex::new(None),
+                ws_write: Mutex::new(None),
+                queue: Mutex::new(VecDeque::new()),
+                pending: std::sync::Mutex::new(HashMap::new()),
+                replay: std::sync::Mutex::new(SessionReplay::default()),
+                internal: std::sync::Mutex::new(HashMap::new()),
+                next_internal_id: AtomicU64::new(INTERNAL_REQUEST_ID_BASE),
+                closed: AtomicBool::new(false),
+                task: std::sync::Mutex::new(None),
+            }),
         }
-
-        // Clear the broadcast channel references
-        *self.rx.lock().await = None;
-        *self.tx.lock().await = None;
-
-        Ok(())
     }
 }
 
-#[async_trait]
-impl Transport for ClientWsTransport {
-    async fn receive(&self) -> Result<Option<Message>> {
-        // Check if we have a valid receiver
-        let mut rx_guard = self.ws_rx.lock().await;
-        if let Some(rx) = rx_guard.as_mut() {
-            match rx.recv().await {
-                Ok(message) => {
-                    debug!("WebSocket received message: {:?}", message);
-                    Ok(Some(message))
-                },
-                Err(broadcast::error::RecvError::Closed) => {
-                    debug!("WebSocket channel closed");
-                    // Channel is closed, clear our reference to it
-                    *rx_guard = None;
-                    Ok(None)
-                },
-                Err(broadcast::error::RecvError::Lagged(n)) => {
-                    // We lagged behind, log a warning but continue
-                    debug!("WebSocket channel lagged behind by {} messages", n);
-                    // Try to receive again with a new subscription
-                    if let Some(tx) = self.ws_tx.lock().await.as_ref() {
-                        *rx_guard = Some(tx.subscribe());
-                        // Return None this time, the next call will get a message
-                        Ok(None)
-                    } else {
-                        // No sender available, channel is effectively closed
-                        *rx_guard = None;
-                        Ok(None)
-                    }
-                }
+/// Access to a freshly re-established connection from a [`ReconnectHook`]
+#[derive(Clone)]
+pub struct ReconnectHandle {
+    inner: Arc<ClientWsInner>,
+}
+
+impl ReconnectHandle {
+    /// Sends a request on the new connection and waits for its result
+    ///
+    /// The response is not delivered to the transport's `receive`.
+    pub async fn request(
+        &self,
+        method: &str,
+        params: Option<serde_json::Value>,
+    ) -> Result<serde_json::Value> {
+        let id = self.inner.next_internal_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        self.inner.internal.lock().unwrap().insert(id, tx);
+        let request = JsonRpcMessage::Request(JsonRpcRequest {
+            id,
+            method: method.to_string(),
+            params,
+            jsonrpc: JsonRpcVersion::default(),
+        });
+        if let Err(e) = self.inner.write(&request).await {
+            self.inner.internal.lock().unwrap().remove(&id);
+            return Err(e);
+        }
+        let response = match tokio::time::timeout(INTERNAL_REQUEST_TIMEOUT, rx).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(_)) => return Err(TransportError::new(
+                TransportErrorCode::ConnectionClosed,
+                format!("Connection lost while waiting for `{}`", method)
+            )),
+            Err(_) => {
+                self.inner.internal.lock().unwrap().remove(&id);
+                return Err(TransportError::new(
+                    TransportErrorCode::Timeout,
+                    format!("`{}` timed out", method)
+                ));
             }
-        } else {
-            // No receiver available
-            debug!("WebSocket receive called but no receiver is available");
-            Ok(None)
+        };
+        match response.error {
+            Some(error) => Err(TransportError::new(
+                TransportErrorCode::ProtocolError,
+                format!("`{}` failed: {} ({})", method, error.message, error.code)
+            )),
+            None => Ok(response.result.unwrap_or_default()),
         }
     }
 
-    async fn send(&self, message: &Message) -> Result<()> {
-        let mut ws_write = self.ws_write.lock().await;
-        if let Some(ws_write) = ws_write.as_mut() {
-            let json = serde_json::to_string(message)?;
-            ws_write
-                .send(TungsteniteMessage::Text(json.into()))
-                .await
-                .map_err(|e| TransportError::new(TransportErrorCode::SendError, e.to_string()))?;
-            Ok(())
-        } else {
-            Err(TransportError::new(
-                TransportErrorCode::SendError,
-                "No active WebSocket connection",
-            ))
-        }
+    /// Sends a notification on the new connection
+    pub async fn notify(&self, method: &str, params: Option<serde_json::Value>) -> Result<()> {
+        self.inner.write(&JsonRpcMessage::Notification(JsonRpcNotification {
+            method: method.to_string(),
+            params,
+            jsonrpc: JsonRpcVersion::default(),
+        })).await
     }
+}
 
-    async fn open(&self) -> Result<()> {
-        // Check if we're already open
-        if self.ws_write.lock().await.is_some() {
-            debug!("WebSocket connection already open");
-            return Ok(());
-        }
-
+impl ClientWsInner {
+    /// Opens a new WebSocket connection to the configured URL
+    async fn connect(&self) -> Result<WsStream> {
         debug!("Opening WebSocket connection to {}", self.url);
 
         // Prepare the request with headers
@@ -342,10 +771,17 @@ impl Transport for ClientWsTransport {
             );
         }
 
+        if !self.subprotocols.is_empty() {
+            let protocols = HeaderValue::from_str(&self.subprotocols.join(", ")).map_err(|e| {
+                TransportError::new(TransportErrorCode::OpenError, format!("Invalid subprotocol: {}", e))
+            })?;
+            request.headers_mut().insert("Sec-WebSocket-Protocol", protocols);
+        }
+
         // Connect to the WebSocket server with timeout
         let connect_future = tokio_tungstenite::connect_async(request);
         let connect_result = tokio::time::timeout(
-            std::time::Duration::from_secs(30), // 30 second timeout
+            Duration::from_secs(30), // 30 second timeout
             connect_future
         ).await;
 
@@ -365,86 +801,412 @@ impl Transport for ClientWsTransport {
                 format!("WebSocket connection failed: {}", e)
             ))?;
 
-        // Log successful connection
-        debug!("WebSocket connection established with status: {}", response.status());
+        let subprotocol = response.headers()
+            .get("Sec-WebSocket-Protocol")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        debug!(
+            "WebSocket connection established with status: {} (subprotocol: {:?})",
+            response.status(),
+            subprotocol
+        );
+        let codec = subprotocol.as_deref().and_then(codec_for_subprotocol).unwrap_or_else(default_codec);
+        *self.codec.lock().unwrap() = codec;
+        *self.subprotocol.lock().unwrap() = subprotocol;
+        Ok(ws_stream)
+    }
 
-        // Split the WebSocket stream
-        let (write, mut read) = ws_stream.split();
-
-        // Create broadcast channel for message distribution
-        // Increase buffer size to 1000 to handle more messages
-        let (tx, rx) = broadcast::channel(1000);
-
-        // Store the sender, receiver, and write half
-        *self.ws_tx.lock().await = Some(tx.clone());
-        *self.ws_rx.lock().await = Some(rx);
-        *self.ws_write.lock().await = Some(write);
-
-        // Spawn a task to handle incoming messages
-        let tx = tx.clone();
-        let url = self.url.clone(); // Clone URL for the task
-        tokio::spawn(async move {
-            debug!("Starting WebSocket message handler for {}", url);
-
-            while let Some(msg) = read.next().await {
-                match msg {
-                    Ok(TungsteniteMessage::Text(text)) => {
-                        match serde_json::from_str::<Message>(&text) {
-                            Ok(message) => {
-                                debug!("Received WebSocket message: {:?}", message);
-                                if tx.send(message).is_err() {
-                                    debug!("All receivers dropped, stopping message handling");
-                                    break;
-                                }
-                            },
-                            Err(e) => {
-                                debug!("Failed to parse WebSocket message: {}", e);
-                                debug!("Message content: {}", text);
-                                // Continue processing other messages
+    /// Writes a message to the current connection and records it
+    async fn write(&self, message: &Message) -> Result<()> {
+        let mut ws_write = self.ws_write.lock().await;
+        let Some(ws_write) = ws_write.as_mut() else {
+            return Err(TransportError::new(
+                TransportErrorCode::SendError,
+                "No active WebSocket connection",
+            ));
+        };
+        let codec = self.codec.lock().unwrap().clone();
+        let frame = match encode_frame(codec.as_ref(), message)? {
+            Frame::Text(text) => TungsteniteMessage::Text(text.into()),
+            Frame::Binary(bytes) => TungsteniteMessage::Binary(bytes.into()),
+        };
+        // The response can be read before `send` returns, so the request is recorded first
+        let request = match message {
+            JsonRpcMessage::Request(request) if request.id < INTERNAL_REQUEST_ID_BASE => {
+                self.pending.lock().unwrap().insert(request.id, PendingRequest {
+                    method: request.method.clone(),
+                    params: request.params.clone(),
+                });
+                Some(request.id)
+            }
+            _ => None,
+        };
+        if let Err(e) = ws_write.send(frame).await {
+            if let Some(id) = request {
+                self.pending.lock().unwrap().remove(&id);
+            }
+            return Err(TransportError::new(TransportErrorCode::SendError, e.to_string()));
+        }
+        if let JsonRpcMessage::Notification(notification) = message
+            && notification.method == "notifications/initialized"
+        {
+            self.replay.lock().unwrap().initialized = true;
+        }
+        Ok(())
+    }
+
+    /// Routes an incoming message; returns it if it should be delivered to `receive`
+    fn track_incoming(&self, message: Message) -> Option<Message> {
+        let JsonRpcMessage::Response(response) = message else {
+            return Some(message);
+        };
+        if let Some(tx) = self.internal.lock().unwrap().remove(&response.id) {
+            let _ = tx.send(response);
+            return None;
+        }
+        let request = self.pending.lock().unwrap().remove(&response.id);
+        if let Some(request) = request
+            && response.error.is_none()
+        {
+            let mut replay = self.replay.lock().unwrap();
+            let uri = || request.params.as_ref()
+                .and_then(|p| p.get("uri"))
+                .and_then(|uri| uri.as_str())
+                .map(str::to_string);
+            match request.method.as_str() {
+                "initialize" => replay.initialize = request.params.clone(),
+                "resources/subscribe" => replay.subscriptions.extend(uri()),
+                "resources/unsubscribe" => {
+                    if let Some(uri) = uri() {
+                        replay.subscriptions.remove(&uri);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Some(JsonRpcMessage::Response(response))
+    }
+
+    /// Answers every request in flight with an error; their responses are lost
+    async fn fail_pending(&self, queue: &MessageQueue) {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        for id in pending.into_keys() {
+            let _ = queue.push(JsonRpcMessage::Response(JsonRpcResponse {
+                id,
+                result: None,
+                error: Some(JsonRpcError {
+                    code: INTERNAL_ERROR,
+                    message: "WebSocket connection lost before a response was received".to_string(),
+                    data: None,
+                }),
+                jsonrpc: JsonRpcVersion::default(),
+            })).await;
+        }
+        // Dropping the senders fails requests made by the reconnect hook
+        self.internal.lock().unwrap().clear();
+    }
+
+    /// Reads from one connection until it ends, sending heartbeats if configured
+    ///
+    /// Returns the reason the connection ended if it was not closed normally.
+    async fn read_connection(&self, mut read: WsSource, queue: &MessageQueue) -> Option<TransportError> {
+        let mut heartbeat = self.heartbeat.map(|config| Heartbeat::new(config, self.metrics.clone()));
+        loop {
+            let msg = tokio::select! {
+                // Read first: after waiting for room in the queue, a pong may be buffered
+                biased;
+                msg = read.next() => msg,
+                _ = heartbeat_deadline(&heartbeat) => {
+                    let Some(heartbeat) = heartbeat.as_mut() else { continue };
+                    match heartbeat.poll() {
+                        HeartbeatAction::Ping(payload) => {
+                            if let Some(write) = self.ws_write.lock().await.as_mut()
+                                && let Err(e) = write.send(TungsteniteMessage::Ping(payload.into())).await
+                            {
+                                debug!("Failed to send WebSocket ping: {}", e);
                             }
+                            continue;
                         }
-                    },
-                    Ok(TungsteniteMessage::Binary(data)) => {
-                        debug!("Received binary WebSocket message of {} bytes", data.len());
-                        // We don't handle binary messages currently
-                    },
-                    Ok(TungsteniteMessage::Ping(_)) => {
-                        debug!("Received WebSocket ping");
-                        // The WebSocket library automatically responds with pong
-                    },
-                    Ok(TungsteniteMessage::Pong(_)) => {
-                        // Ignore pong messages
-                    },
-                    Ok(TungsteniteMessage::Close(frame)) => {
-                        if let Some(frame) = frame {
-                            info!("WebSocket connection closed by server: {} - {}",
-                                  frame.code, frame.reason);
-                        } else {
-                            info!("WebSocket connection closed by server");
+                        HeartbeatAction::TimedOut => {
+                            warn!("WebSocket server {} stopped responding to pings", self.url);
+                            return Some(heartbeat.timeout_error());
                         }
-                        break;
-                    },
-                    Ok(TungsteniteMessage::Frame(_)) => {
-                        // Raw frames are not expected in normal operation
-                        debug!("Received raw WebSocket frame");
-                    },
-                    Err(e) => {
-                        debug!("Error reading from WebSocket: {}", e);
-                        break;
                     }
                 }
+            };
+            let msg = msg?;
+            self.metrics.record_seen();
+            match msg {
+                Ok(frame @ (TungsteniteMessage::Text(_) | TungsteniteMessage::Binary(_))) => {
+                    let is_text = frame.is_text();
+                    let payload = frame.into_data();
+                    let codec = self.codec.lock().unwrap().clone();
+                    match decode_frame(codec.as_ref(), &payload, is_text) {
+                        Ok(message) => {
+                            debug!("Received WebSocket message: {:?}", message);
+                            if let Some(message) = self.track_incoming(message)
+                                && let Err(e) = queue.push(message).await
+                                && queue.is_closed()
+                            {
+                                debug!("Receive queue closed, stopping message handling: {}", e);
+                                return None;
+                            }
+                        },
+                        Err(e) => {
+                            debug!("Failed to parse WebSocket message: {}", e);
+                            debug!("Message content: {}", String::from_utf8_lossy(&payload));
+                            // Continue processing other messages
+                        }
+                    }
+                },
+                Ok(TungsteniteMessage::Ping(_)) => {
+                    debug!("Received WebSocket ping");
+                    // The WebSocket library automatically responds with pong
+                },
+                Ok(TungsteniteMessage::Pong(payload)) => {
+                    if let Some(heartbeat) = heartbeat.as_mut() {
+                        heartbeat.pong(&payload);
+                    }
+                },
+                Ok(TungsteniteMessage::Close(frame)) => {
+                    if let Some(frame) = frame {
+                        info!("WebSocket connection closed by server: {} - {}",
+                              frame.code, frame.reason);
+                    } else {
+                        info!("WebSocket connection closed by server");
+                    }
+                    return None;
+                },
+                Ok(TungsteniteMessage::Frame(_)) => {
+                    // Raw frames are not expected in normal operation
+                    debug!("Received raw WebSocket frame");
+                },
+                Err(e) => {
+                    debug!("Error reading from WebSocket: {}", e);
+                    return Some(TransportError::new(
+                        TransportErrorCode::ReceiveError,
+                        format!("Error reading from WebSocket: {}", e)
+                    ));
+                }
             }
+        }
+    }
 
-            debug!("WebSocket message handler for {} terminated", url);
-        });
+    /// Tries to re-establish the connection; returns the new read half on success
+    async fn reconnect(self: &Arc<Self>, policy: &ReconnectPolicy) -> Option<WsSource> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            if policy.max_attempts.is_some_and(|max| attempt > max) {
+                warn!("Giving up reconnecting to {} after {} attempts", self.url, attempt - 1);
+                return None;
+            }
+            self.state.send_replace(ConnectionState::Reconnecting { attempt });
+            tokio::time::sleep(policy.delay(attempt)).await;
+            if self.closed.load(Ordering::Relaxed) {
+                return None;
+            }
+            match self.connect().await {
+                Ok(stream) => {
+                    info!("Reconnected to {} after {} attempt(s)", self.url, attempt);
+                    let (write, read) = stream.split();
+                    *self.ws_write.lock().await = Some(write);
+                    // Restoring needs responses from the new connection, so it runs
+                    // alongside the read loop
+                    let inner = self.clone();
+                    let restore_session = policy.restore_session;
+                    tokio::spawn(async move { inner.restore(restore_session).await });
+                    return Some(read);
+                }
+                Err(e) => warn!("Reconnect attempt {} to {} failed: {}", attempt, self.url, e),
+            }
+        }
+    }
+
+    /// Replays the session on a new connection, then sends queued messages
+    async fn restore(self: Arc<Self>, restore_session: bool) {
+        let handle = ReconnectHandle { inner: self.clone() };
+        let result = async {
+            if restore_session {
+                self.replay_session(&handle).await?;
+            }
+            if let Some(hook) = &self.hook {
+                hook(handle.clone()).await?;
+            }
+            Ok::<_, TransportError>(())
+        }.await;
+        if let Err(e) = result {
+            error!("Failed to restore WebSocket session: {}", e);
+            self.drop_connection().await;
+            return;
+        }
+
+        let mut queue = self.queue.lock().await;
+        while let Some(message) = queue.pop_front() {
+            match self.write(&message).await {
+                Ok(()) => {}
+                Err(e) if e.code() == Some(TransportErrorCode::SendError) => {
+                    error!("Failed to send queued message after reconnecting: {}", e);
+                    // Keep the message for the next connection
+                    queue.push_front(message);
+                    drop(queue);
+                    self.drop_connection().await;
+                    return;
+                }
+                // The message can never be encoded, so retrying it would not help
+                Err(e) => error!("Dropping queued message that cannot be sent: {}", e),
+            }
+        }
+        self.state.send_replace(ConnectionState::Connected);
+    }
+
+    /// Closes the current connection so the read loop reconnects
+    async fn drop_connection(&self) {
+        if let Some(write) = self.ws_write.lock().await.as_mut() {
+            let _ = write.close().await;
+        }
+    }
+
+    /// Re-sends `initialize`, `notifications/initialized` and resource subscriptions
+    async fn replay_session(&self, handle: &ReconnectHandle) -> Result<()> {
+        let (initialize, initialized, subscriptions) = {
+            let replay = self.replay.lock().unwrap();
+            (replay.initialize.clone(), replay.initialized, replay.subscriptions.clone())
+        };
+        let Some(params) = initialize else {
+            return Ok(());
+        };
+        handle.request("initialize", Some(params)).await?;
+        if initialized {
+            handle.notify("notifications/initialized", None).await?;
+        }
+        for uri in subscriptions {
+            handle.request("resources/subscribe", Some(serde_json::json!({ "uri": uri }))).await?;
+        }
+        debug!("Restored MCP session on {}", self.url);
+        Ok(())
+    }
+
+    /// Drives the connection, reconnecting according to the policy
+    async fn run(self: Arc<Self>, mut read: WsSource, queue: MessageQueue) {
+        debug!("Starting WebSocket message handler for {}", self.url);
+        loop {
+            let error = self.read_connection(read, &queue).await;
+            *self.ws_write.lock().await = None;
+            *self.last_error.lock().unwrap() = error;
+            self.fail_pending(&queue).await;
+            if self.closed.load(Ordering::Relaxed) {
+                break;
+            }
+            let Some(policy) = &self.reconnect else {
+                break;
+            };
+            match self.reconnect(policy).await {
+                Some(new_read) => read = new_read,
+                None => break,
+            }
+        }
+        debug!("WebSocket message handler for {} terminated", self.url);
+        self.state.send_replace(ConnectionState::Disconnected);
+        // Lets `receive` report the end of the stream once the queue is drained
+        queue.close();
+    }
+}
+
+#[async_trait]
+impl Transport for ClientWsTransport {
+    async fn receive(&self) -> Result<Option<Message>> {
+        let Some(queue) = self.inner.incoming.lock().unwrap().clone() else {
+            debug!("WebSocket receive called but the transport is not open");
+            return Ok(None);
+        };
+        match queue.pop().await? {
+            Some(message) => {
+                debug!("WebSocket received message: {:?}", message);
+                Ok(Some(message))
+            }
+            None => {
+                debug!("WebSocket receive queue closed");
+                match self.inner.last_error.lock().unwrap().take() {
+                    Some(error) => Err(error),
+                    None => Ok(None),
+                }
+            }
+        }
+    }
+
+    async fn send(&self, message: &Message) -> Result<()> {
+        // Holding the queue lock keeps sends ordered behind a flush of queued messages
+        let mut queue = self.inner.queue.lock().await;
+        let state = *self.inner.state.borrow();
+        match (state, &self.inner.reconnect) {
+            (ConnectionState::Connected, _) => self.inner.write(message).await,
+            (ConnectionState::Reconnecting { .. }, Some(policy)) => match policy.outgoing {
+                OutgoingPolicy::Queue { capacity } if queue.len() < capacity => {
+                    queue.push_back(message.clone());
+                    Ok(())
+                }
+                OutgoingPolicy::Queue { capacity } => Err(TransportError::new(
+                    TransportErrorCode::SendError,
+                    format!("Reconnect queue is full ({} messages)", capacity)
+                )),
+                OutgoingPolicy::Fail => Err(TransportError::new(
+                    TransportErrorCode::ConnectionClosed,
+                    "WebSocket connection lost; reconnecting"
+                )),
+            },
+            _ => Err(TransportError::new(
+                TransportErrorCode::SendError,
+                "No active WebSocket connection",
+            )),
+        }
+    }
+
+    async fn open(&self) -> Result<()> {
+        // Check if we're already open
+        if *self.inner.state.borrow() != ConnectionState::Disconnected {
+            debug!("WebSocket connection already open");
+            return Ok(());
+        }
+        self.inner.closed.store(false, Ordering::Relaxed);
+        self.inner.state.send_replace(ConnectionState::Connecting);
+
+        let ws_stream = match self.inner.connect().await {
+            Ok(ws_stream) => ws_stream,
+            Err(e) => {
+                self.inner.state.send_replace(ConnectionState::Disconnected);
+                return Err(e);
+            }
+        };
+
+        // Split the WebSocket stream
+        let (write, read) = ws_stream.split();
+
+        // Queue for incoming messages, with backpressure on the connection
+        let queue = MessageQueue::new(self.inner.receive_queue);
+
+        // Store the queue and write half
+        *self.inner.incoming.lock().unwrap() = Some(queue.clone());
+        *self.inner.ws_write.lock().await = Some(write);
+        self.inner.state.send_replace(ConnectionState::Connected);
+
+        // Spawn a task to handle incoming messages and reconnects
+        let task = tokio::spawn(self.inner.clone().run(read, queue));
+        *self.inner.task.lock().unwrap() = Some(task);
 
         debug!("WebSocket connection setup complete");
         Ok(())
     }
 
     async fn close(&self) -> Result<()> {
+        self.inner.closed.store(true, Ordering::Relaxed);
+        self.inner.queue.lock().await.clear();
+        // A later `open` starts a new session, which must not replay this one
+        *self.inner.replay.lock().unwrap() = SessionReplay::default();
+        self.inner.pending.lock().unwrap().clear();
+
         // Take the write half of the WebSocket to ensure we don't leave dangling references
-        if let Some(mut write) = self.ws_write.lock().await.take() {
+        if let Some(mut write) = self.inner.ws_write.lock().await.take() {
             // Send a close frame with normal closure status
             let close_frame = tokio_tungstenite::tungstenite::protocol::CloseFrame {
                 code: tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode::Normal,
@@ -462,53 +1224,140 @@ impl Transport for ClientWsTransport {
             }
         }
 
-        // Clear the broadcast channels to prevent memory leaks
-        *self.ws_tx.lock().await = None;
-        *self.ws_rx.lock().await = None;
+        // Stop the connection task, so it cannot reconnect once a later `open` resets
+        // `closed`, and wake any pending `receive`
+        if let Some(task) = self.inner.task.lock().unwrap().take() {
+            task.abort();
+        }
+        if let Some(queue) = self.inner.incoming.lock().unwrap().take() {
+            queue.close();
+        }
+        self.inner.state.send_replace(ConnectionState::Disconnected);
 
         Ok(())
     }
 }
 
+/// Pick the codec for a WebSocket upgrade request
+///
+/// Returns the codec of the first subprotocol in `Sec-WebSocket-Protocol` that is
+/// supported, or `None` if the client offers none.
+pub fn negotiate_codec(req: &actix_web::HttpRequest) -> Option<Arc<dyn Codec>> {
+    req.headers()
+        .get_all(actix_web::http::header::SEC_WEBSOCKET_PROTOCOL)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .find_map(|protocol| codec_for_subprotocol(protocol.trim()))
+}
+
+/// Upgrade an HTTP request to a WebSocket connection
+///
+/// Works like `actix_ws::handle`, and additionally selects the subprotocol chosen by
+/// [`negotiate_codec`], such as [`MCP_SUBPROTOCOL`]. Clients that offer no
+/// supported subprotocol are accepted without one.
+///
+/// # Arguments
+/// * `req` - The upgrade request
+/// * `body` - The request payload, which becomes the incoming message stream
+pub fn handle_ws_upgrade(
+    req: &actix_web::HttpRequest,
+    body: actix_web::web::Payload,
+) -> std::result::Result<(actix_web::HttpResponse, Session, actix_ws::MessageStream), actix_web::Error> {
+    let (mut response, session, stream) = actix_ws::handle(req, body)?;
+    if let Some(codec) = negotiate_codec(req) {
+        response.headers_mut().insert(
+            actix_web::http::header::SEC_WEBSOCKET_PROTOCOL,
+            actix_web::http::header::HeaderValue::from_static(codec.subprotocol()),
+        );
+    }
+    Ok((response, session, stream))
+}
+
 /// Handle a WebSocket connection, managing message flow between client and server
 ///
 /// This function sets up bidirectional communication between a WebSocket connection
-/// and broadcast channels for message passing.
+/// and message queues. Messages from the client are pushed to `incoming` under its
+/// overflow policy; with the default one the client is not read from while the queue
+/// is full, so it is slowed down rather than messages lost. `incoming` is closed when
+/// the connection ends.
 ///
 /// # Arguments
 /// * `session` - The WebSocket session
 /// * `stream` - Stream of incoming WebSocket messages
-/// * `tx` - Channel sender for outgoing messages
-/// * `rx` - Channel receiver for incoming messages
+/// * `incoming` - Queue for messages from the client
+/// * `outgoing` - Queue of messages to send to the client
 ///
 /// # Returns
 /// * `Result<()>` - Ok if the connection was handled successfully, Err otherwise
 pub async fn handle_ws_connection(
+    session: Session,
+    stream: actix_ws::MessageStream,
+    incoming: MessageQueue,
+    outgoing: MessageQueue,
+) -> Result<()> {
+    handle_ws_connection_with(session, stream, incoming, outgoing, WsConnectionConfig::default()).await
+}
+
+/// Options for [`handle_ws_connection_with`]
+#[derive(Debug, Clone)]
+pub struct WsConnectionConfig {
+    /// Ping settings; `None` disables heartbeats
+    pub heartbeat: Option<HeartbeatConfig>,
+    /// Metrics updated by the connection handler
+    pub metrics: ConnectionMetrics,
+    /// Codec for binary frames and outgoing messages, usually from [`negotiate_codec`]
+    pub codec: Arc<dyn Codec>,
+}
+
+impl Default for WsConnectionConfig {
+    fn default() -> Self {
+        Self {
+            heartbeat: None,
+            metrics: ConnectionMetrics::default(),
+            codec: default_codec(),
+        }
+    }
+}
+
+/// Handle a WebSocket connection with the specified options
+///
+/// Like [`handle_ws_connection`], but with a heartbeat the client is pinged
+/// periodically; if it stops answering, the connection is closed and
+/// `TransportErrorCode::ConnectionTimeout` is returned.
+pub async fn handle_ws_connection_with(
     mut session: Session,
     mut stream: actix_ws::MessageStream,
-    tx: broadcast::Sender<Message>,
-    mut rx: broadcast::Receiver<Message>,
+    incoming: MessageQueue,
+    outgoing: MessageQueue,
+    config: WsConnectionConfig,
 ) -> Result<()> {
     debug!("Starting WebSocket connection handler");
+    let metrics = config.metrics.clone();
+    let heartbeat = Arc::new(std::sync::Mutex::new(
+        config.heartbeat.map(|heartbeat| Heartbeat::new(heartbeat, metrics.clone()))
+    ));
+    let ping_session = session.clone();
+    let send_codec = config.codec.clone();
+    let recv_codec = config.codec;
 
-    // Send messages from rx to the WebSocket
+    // Send messages from the outgoing queue to the WebSocket
     let mut send_task = actix_web::rt::spawn(async move {
         debug!("Starting WebSocket send task");
 
-        while let Ok(message) = rx.recv().await {
+        while let Ok(Some(message)) = outgoing.pop().await {
             debug!("Sending message to WebSocket: {:?}", message);
 
-            match serde_json::to_string(&message) {
-                Ok(json) => {
-                    if let Err(e) = session.text(json).await {
-                        debug!("Error sending message to WebSocket: {}", e);
-                        break;
-                    }
-                },
+            let sent = match encode_frame(send_codec.as_ref(), &message) {
+                Ok(Frame::Text(text)) => session.text(text).await,
+                Ok(Frame::Binary(bytes)) => session.binary(bytes).await,
                 Err(e) => {
-                    debug!("Error serializing message to JSON: {}", e);
+                    debug!("Error serializing message: {}", e);
                     continue;
                 }
+            };
+            if let Err(e) = sent {
+                debug!("Error sending message to WebSocket: {}", e);
+                break;
             }
         }
 
@@ -516,39 +1365,60 @@ pub async fn handle_ws_connection(
         Ok::<_, anyhow::Error>(())
     });
 
-    // Receive messages from the WebSocket and send them to tx
+    // Ping the client and watch for pongs
+    let pong_heartbeat = heartbeat.clone();
+    let mut heartbeat_task = actix_web::rt::spawn(async move {
+        let mut session = ping_session;
+        loop {
+            let Some(deadline) = heartbeat.lock().unwrap().as_ref().map(Heartbeat::deadline) else {
+                return Ok::<_, TransportError>(());
+            };
+            tokio::time::sleep_until(deadline.into()).await;
+            let action = match heartbeat.lock().unwrap().as_mut() {
+                Some(heartbeat) if Instant::now() >= heartbeat.deadline() => heartbeat.poll(),
+                // A pong moved the deadline while we slept
+                _ => continue,
+            };
+            match action {
+                HeartbeatAction::Ping(payload) => {
+                    if let Err(e) = session.ping(&payload).await {
+                        debug!("Error sending ping to WebSocket: {}", e);
+                        return Ok(());
+                    }
+                }
+                HeartbeatAction::TimedOut => {
+                    let error = heartbeat.lock().unwrap().as_ref().map(Heartbeat::timeout_error);
+                    warn!("WebSocket client stopped responding to pings");
+                    let _ = session.close(Some(actix_ws::CloseReason {
+                        code: actix_ws::CloseCode::Away,
+                        description: Some("Ping timeout".to_string()),
+                    })).await;
+                    return Err(error.expect("heartbeat is configured"));
+                }
+            }
+        }
+    });
+
+    // Receive messages from the WebSocket and queue them
+    let queue = incoming.clone();
     let mut recv_task = actix_web::rt::spawn(async move {
         debug!("Starting WebSocket receive task");
 
         while let Some(msg_result) = stream.next().await {
-            match msg_result {
-                Ok(WsMessage::Text(text)) => {
-                    debug!("Received text message from WebSocket: {}", text);
-
-                    match serde_json::from_str::<Message>(&text) {
-                        Ok(message) => {
-                            debug!("Parsed message: {:?}", message);
-                            if tx.send(message).is_err() {
-                                debug!("Error sending message to channel (no receivers)");
-                                break;
-                            }
-                        },
-                        Err(e) => {
-                            debug!("Error parsing message from WebSocket: {}", e);
-                            // Continue processing other messages
-                        }
-                    }
-                },
-                Ok(WsMessage::Binary(bytes)) => {
-                    debug!("Received binary message from WebSocket ({} bytes)", bytes.len());
-                    // We don't handle binary messages currently
-                },
+            metrics.record_seen();
+            let (payload, is_text) = match msg_result {
+                Ok(WsMessage::Text(text)) => (text.into_bytes(), true),
+                Ok(WsMessage::Binary(bytes)) => (bytes, false),
                 Ok(WsMessage::Ping(_)) => {
                     debug!("Received ping from WebSocket");
                     // Handled automatically by actix-ws
+                    continue;
                 },
-                Ok(WsMessage::Pong(_)) => {
-                    // Ignore pong messages
+                Ok(WsMessage::Pong(payload)) => {
+                    if let Some(heartbeat) = pong_heartbeat.lock().unwrap().as_mut() {
+                        heartbeat.pong(&payload);
+                    }
+                    continue;
                 },
                 Ok(WsMessage::Close(reason)) => {
                     if let Some(reason) = reason {
@@ -561,14 +1431,36 @@ pub async fn handle_ws_connection(
                 Ok(WsMessage::Continuation(_)) => {
                     debug!("Received continuation frame from WebSocket");
                     // We don't handle continuation frames explicitly
+                    continue;
                 },
                 Ok(WsMessage::Nop) => {
                     // No operation, ignore
+                    continue;
                 },
                 Err(e) => {
                     debug!("Error receiving message from WebSocket: {}", e);
                     break;
                 }
+            };
+            debug!("Received {} byte message from WebSocket", payload.len());
+
+            match decode_frame(recv_codec.as_ref(), &payload, is_text) {
+                Ok(message) => {
+                    debug!("Parsed message: {:?}", message);
+                    match queue.push(message).await {
+                        Ok(()) => {}
+                        Err(e) if e.code() == Some(TransportErrorCode::ConnectionClosed) => {
+                            debug!("Receive queue closed, ending WebSocket connection");
+                            break;
+                        }
+                        // Rejected under `OverflowPolicy::Error`; `receive` reports it
+                        Err(e) => debug!("Error queueing message from WebSocket: {}", e),
+                    }
+                },
+                Err(e) => {
+                    debug!("Error parsing message from WebSocket: {}", e);
+                    // Continue processing other messages
+                }
             }
         }
 
@@ -618,11 +1510,21 @@ pub async fn handle_ws_connection(
                 ))
             },
         },
+        res = (&mut heartbeat_task), if config.heartbeat.is_some() => match res {
+            Ok(result) => result,
+            Err(e) => Err(TransportError::new(
+                TransportErrorCode::InternalError,
+                format!("Heartbeat task join error: {}", e)
+            )),
+        },
     };
 
-    // Cancel the other task if one completes
+    // Cancel the other tasks if one completes
     send_task.abort();
     recv_task.abort();
+    heartbeat_task.abort();
+    // Messages already queued can still be received, then the transport sees the end
+    incoming.close();
 
     debug!("WebSocket connection handler completed");
     result
@@ -0,0 +1,129 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use jsoncall::{RequestContextAs, Response, Result, bail_public};
+use mcp_daemon::{
+    schema::{
+        CallToolRequestParams, CallToolResult, ListResourcesRequestParams, ListResourcesResult,
+        Resource,
+    },
+    server::{Server, SessionData},
+    testing::{CheckOutcome, ConformanceReport, ConformanceSuite},
+};
+use serde_json::json;
+
+/// Server that relies entirely on the default request handlers
+struct DefaultOnlyServer;
+
+impl Server for DefaultOnlyServer {}
+
+/// Server with two pages of resources and a tool that takes a while to finish
+struct PagedServer;
+
+impl Server for PagedServer {
+    fn resources_list(
+        self: Arc<Self>,
+        p: ListResourcesRequestParams,
+        cx: RequestContextAs<ListResourcesResult>,
+        _data: Arc<SessionData>,
+    ) -> Result<Response> {
+        let (name, next_cursor) = match p.cursor.as_deref() {
+            None => ("first", Some("page-2".to_string())),
+            Some("page-2") => ("second", None),
+            Some(cursor) => bail_public!(_, "unknown cursor `{cursor}`"),
+        };
+        cx.handle(Ok(ListResourcesResult {
+            resources: vec![Resource {
+                annotations: None,
+                description: None,
+                mime_type: None,
+                name: name.to_string(),
+                uri: format!("memory:///{name}"),
+            }],
+            next_cursor,
+            meta: Default::default(),
+        }))
+    }
+
+    fn tools_call(
+        self: Arc<Self>,
+        _p: CallToolRequestParams,
+        cx: RequestContextAs<CallToolResult>,
+        _data: Arc<SessionData>,
+    ) -> Result<Response> {
+        cx.handle_async(async move {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            Ok(CallToolResult::from(()))
+        })
+    }
+}
+
+/// Server whose resource list never ends
+struct BrokenServer;
+
+impl Server for BrokenServer {
+    fn resources_list(
+        self: Arc<Self>,
+        _p: ListResourcesRequestParams,
+        cx: RequestContextAs<ListResourcesResult>,
+        _data: Arc<SessionData>,
+    ) -> Result<Response> {
+        cx.handle(Ok(ListResourcesResult {
+            resources: Vec::new(),
+            next_cursor: Some("again".to_string()),
+            meta: Default::default(),
+        }))
+    }
+}
+
+fn outcome<'a>(report: &'a ConformanceReport, name: &str) -> &'a CheckOutcome {
+    &report
+        .checks
+        .iter()
+        .find(|c| c.name == name)
+        .unwrap_or_else(|| panic!("check `{name}` missing from report:\n{report}"))
+        .outcome
+}
+
+#[tokio::test]
+async fn test_default_server_conforms() {
+    let report = ConformanceSuite::new().run(DefaultOnlyServer).await;
+    report.assert_passed();
+    assert_eq!(
+        outcome(&report, "request before initialize is rejected"),
+        &CheckOutcome::Passed
+    );
+    assert!(matches!(
+        outcome(&report, "cancellation is honored"),
+        CheckOutcome::Skipped(_)
+    ));
+}
+
+#[tokio::test]
+async fn test_pagination_and_cancellation() {
+    let report = ConformanceSuite::new()
+        .with_cancellation_probe("tools/call", json!({ "name": "slow" }))
+        .run(PagedServer)
+        .await;
+    report.assert_passed();
+    assert_eq!(
+        outcome(&report, "resources/list results and pagination"),
+        &CheckOutcome::Passed
+    );
+    assert_eq!(
+        outcome(&report, "cancellation is honored"),
+        &CheckOutcome::Passed
+    );
+}
+
+#[tokio::test]
+async fn test_broken_server_fails() {
+    let report = ConformanceSuite::new().run(BrokenServer).await;
+    assert!(!report.passed());
+    let CheckOutcome::Failed(reason) = outcome(&report, "resources/list results and pagination")
+    else {
+        panic!("pagination loop was not detected:\n{report}");
+    };
+    assert!(reason.contains("returned twice"), "{reason}");
+    assert!(report.to_string().contains("1 failed"));
+}
@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::StreamExt;
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Full, StreamBody};
+use hyper::body::{Frame, Incoming};
+use hyper::server::conn::http2;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use mcp_daemon::transport::{
+    ClientHttp2Transport, ClientTlsConfig, JsonRpcMessage, JsonRpcNotification, JsonRpcRequest,
+    JsonRpcResponse, JsonRpcVersion, Message, Transport, TransportErrorCode,
+};
+use serde_json::json;
+use tokio::net::TcpListener;
+use tokio::sync::{Mutex, Notify, mpsc};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+type Body = BoxBody<Bytes, Infallible>;
+
+/// Plain HTTP/2 server mounted under `/mcp`
+struct TestServer {
+    url: url::Url,
+    /// Server-initiated messages, written to the event stream
+    events: mpsc::UnboundedSender<Message>,
+    /// Number of accepted TCP connections
+    connections: Arc<AtomicUsize>,
+    /// Lets a pending `slow` request complete; the client answering a server-initiated
+    /// request does the same
+    release: Arc<Notify>,
+}
+
+fn full(body: impl Into<Bytes>) -> Body {
+    Full::new(body.into()).boxed()
+}
+
+/// Answers requests with their `id` and `method`, and acknowledges everything else
+/// with an empty body
+///
+/// A `slow` request is only answered once `release` is notified, which also happens
+/// when the client posts a response.
+async fn post_message(req: Request<Incoming>, release: Arc<Notify>) -> Response<Body> {
+    let body = req.into_body().collect().await.unwrap().to_bytes();
+    match serde_json::from_slice::<Message>(&body).unwrap() {
+        JsonRpcMessage::Request(request) => {
+            if request.method == "slow" {
+                release.notified().await;
+            }
+            let response = JsonRpcMessage::Response(JsonRpcResponse {
+                id: request.id,
+                result: Some(json!({ "method": request.method })),
+                error: None,
+                jsonrpc: JsonRpcVersion::default(),
+            });
+            Response::new(full(serde_json::to_vec(&response).unwrap()))
+        }
+        message => {
+            if matches!(message, JsonRpcMessage::Response(_)) {
+                release.notify_one();
+            }
+            Response::builder()
+                .status(StatusCode::ACCEPTED)
+                .body(full(Bytes::new()))
+                .unwrap()
+        }
+    }
+}
+
+async fn serve() -> TestServer {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = url::Url::parse(&format!("http://{}/mcp", listener.local_addr().unwrap())).unwrap();
+    let (events, rx) = mpsc::unbounded_channel::<Message>();
+    let rx = Arc::new(Mutex::new(Some(rx)));
+    let connections = Arc::new(AtomicUsize::new(0));
+    let release = Arc::new(Notify::new());
+
+    let accepted = connections.clone();
+    let server_release = release.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            accepted.fetch_add(1, Ordering::SeqCst);
+            let rx = rx.clone();
+            let release = server_release.clone();
+            let service = service_fn(move |req: Request<Incoming>| {
+                let rx = rx.clone();
+                let release = release.clone();
+                async move {
+                    let response = match (req.method(), req.uri().path()) {
+                        (&Method::POST, "/mcp/message") => post_message(req, release).await,
+                        (&Method::GET, "/mcp/events") => match rx.lock().await.take() {
+                            Some(rx) => {
+                                let frames = UnboundedReceiverStream::new(rx).map(|message| {
+                                    let data = serde_json::to_string(&message).unwrap();
+                                    Ok(Frame::data(Bytes::from(format!("data: {}\n\n", data))))
+                                });
+                                Response::new(BodyExt::boxed(StreamBody::new(frames)))
+                            }
+                            None => Response::builder()
+                                .status(StatusCode::CONFLICT)
+                                .body(full(Bytes::new()))
+                                .unwrap(),
+                        },
+                        _ => Response::builder()
+                            .status(StatusCode::NOT_FOUND)
+                            .body(full("Not found"))
+                            .unwrap(),
+                    };
+                    Ok::<_, Infallible>(response)
+                }
+            });
+            tokio::spawn(async move {
+                let _ = http2::Builder::new(TokioExecutor::new())
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await;
+            });
+        }
+    });
+
+    TestServer { url, events, connections, release }
+}
+
+fn client(url: url::Url) -> ClientHttp2Transport {
+    ClientHttp2Transport::new(url, HashMap::new(), ClientTlsConfig::None)
+}
+
+fn request(id: u64) -> Message {
+    request_with_method(id, &format!("test/{}", id))
+}
+
+fn request_with_method(id: u64, method: &str) -> Message {
+    JsonRpcMessage::Request(JsonRpcRequest {
+        id,
+        method: method.to_string(),
+        params: None,
+        jsonrpc: JsonRpcVersion::default(),
+    })
+}
+
+fn notification(method: &str) -> Message {
+    JsonRpcMessage::Notification(JsonRpcNotification {
+        method: method.to_string(),
+        params: None,
+        jsonrpc: JsonRpcVersion::default(),
+    })
+}
+
+async fn receive(transport: &ClientHttp2Transport) -> Message {
+    tokio::time::timeout(Duration::from_secs(5), transport.receive())
+        .await
+        .expect("timed out waiting for a message")
+        .unwrap()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_response_body_is_received() {
+    let server = serve().await;
+    let transport = client(server.url.clone());
+    transport.open().await.unwrap();
+
+    transport.send(&notification("notifications/initialized")).await.unwrap();
+    transport.send(&request(7)).await.unwrap();
+    match receive(&transport).await {
+        JsonRpcMessage::Response(response) => {
+            assert_eq!(response.id, 7);
+            assert_eq!(response.result, Some(json!({ "method": "test/7" })));
+        }
+        other => panic!("unexpected message: {:?}", other),
+    }
+
+    transport.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_server_initiated_messages_are_received() {
+    let server = serve().await;
+    let transport = client(server.url.clone());
+    transport.open().await.unwrap();
+
+    server.events.send(notification("notifications/tools/list_changed")).unwrap();
+    match receive(&transport).await {
+        JsonRpcMessage::Notification(notification) => {
+            assert_eq!(notification.method, "notifications/tools/list_changed");
+        }
+        other => panic!("unexpected message: {:?}", other),
+    }
+
+    transport.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_requests_share_one_connection() {
+    let server = serve().await;
+    let transport = client(server.url.clone());
+    transport.open().await.unwrap();
+
+    let sends = (1..=10).map(|id| {
+        let transport = transport.clone();
+        async move { transport.send(&request(id)).await }
+    });
+    for result in futures::future::join_all(sends).await {
+        result.unwrap();
+    }
+    let mut ids = Vec::new();
+    for _ in 0..10 {
+        if let JsonRpcMessage::Response(response) = receive(&transport).await {
+            ids.push(response.id);
+        }
+    }
+    ids.sort();
+    assert_eq!(ids, (1..=10).collect::<Vec<_>>());
+
+    // The event stream rides on the same connection as the POSTs
+    server.events.send(notification("notifications/message")).unwrap();
+    receive(&transport).await;
+    assert_eq!(server.connections.load(Ordering::SeqCst), 1);
+
+    transport.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_url_path_is_preserved() {
+    let server = serve().await;
+    let mut url = server.url.clone();
+    url.set_path("/other");
+    let transport = client(url);
+    transport.open().await.unwrap();
+
+    let err = transport.send(&notification("notifications/initialized")).await.unwrap_err();
+    assert_eq!(err.code(), Some(TransportErrorCode::MessageSendFailed));
+    assert!(err.to_string().contains("404"));
+
+    // A failed request is answered with an error response
+    transport.send(&request(1)).await.unwrap();
+    match receive(&transport).await {
+        JsonRpcMessage::Response(response) => {
+            assert_eq!(response.id, 1);
+            assert!(response.error.unwrap().message.contains("404"));
+        }
+        other => panic!("unexpected message: {:?}", other),
+    }
+
+    transport.close().await.unwrap();
+}
+
+async fn send(transport: &ClientHttp2Transport, message: &Message) {
+    tokio::time::timeout(Duration::from_secs(5), transport.send(message))
+        .await
+        .expect("send waited for the server")
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_concurrent_requests() {
+    let server = serve().await;
+    let transport = client(server.url.clone());
+    transport.open().await.unwrap();
+
+    // The slow request does not hold up the one sent after it
+    send(&transport, &request_with_method(1, "slow")).await;
+    send(&transport, &request(2)).await;
+    match receive(&transport).await {
+        JsonRpcMessage::Response(response) => assert_eq!(response.id, 2),
+        other => panic!("unexpected message: {:?}", other),
+    }
+
+    server.release.notify_one();
+    match receive(&transport).await {
+        JsonRpcMessage::Response(response) => {
+            assert_eq!(response.id, 1);
+            assert_eq!(response.result, Some(json!({ "method": "slow" })));
+        }
+        other => panic!("unexpected message: {:?}", other),
+    }
+
+    transport.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_server_request_answered_while_request_in_flight() {
+    let server = serve().await;
+    let transport = client(server.url.clone());
+    transport.open().await.unwrap();
+
+    // The server answers the slow request only after the client has answered its own
+    // request, like a tool call that needs sampling
+    send(&transport, &request_with_method(1, "slow")).await;
+    server.events.send(request_with_method(100, "sampling/createMessage")).unwrap();
+    match receive(&transport).await {
+        JsonRpcMessage::Request(request) => assert_eq!(request.method, "sampling/createMessage"),
+        other => panic!("unexpected message: {:?}", other),
+    }
+    let answer = JsonRpcMessage::Response(JsonRpcResponse {
+        id: 100,
+        result: Some(json!({})),
+        error: None,
+        jsonrpc: JsonRpcVersion::default(),
+    });
+    send(&transport, &answer).await;
+
+    match receive(&transport).await {
+        JsonRpcMessage::Response(response) => assert_eq!(response.id, 1),
+        other => panic!("unexpected message: {:?}", other),
+    }
+
+    transport.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_url_scheme_must_match_the_tls_configuration() {
+    let cases = [
+        ("https://127.0.0.1:1/mcp", ClientTlsConfig::None),
+        ("http://127.0.0.1:1/mcp", ClientTlsConfig::Default),
+    ];
+    for (url, tls_config) in cases {
+        let transport = ClientHttp2Transport::new(url::Url::parse(url).unwrap(), HashMap::new(), tls_config);
+        let error = transport.open().await.unwrap_err();
+        assert_eq!(error.code(), Some(TransportErrorCode::ConfigurationError), "{}", url);
+        assert!(!transport.is_open());
+    }
+}
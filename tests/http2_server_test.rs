@@ -0,0 +1,525 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use jsoncall::{RequestContextAs, Response};
+use mcp_daemon::{
+    client::ClientBuilder,
+    schema::{CallToolRequestParams, CallToolResult},
+    server::{Server, SessionData},
+    transport::{
+        ClientHttp2Transport, ClientTlsConfig, Http2ServerConfig, JsonRpcMessage,
+        JsonRpcNotification, JsonRpcRequest, JsonRpcResponse, JsonRpcVersion, Message,
+        MonitoringConfig, SESSION_ID_HEADER, ServerHandle, ShutdownConfig, Transport,
+        TransportError, TransportErrorCode, serve_http2, start_http2_server,
+    },
+};
+use serde_json::json;
+
+/// Server relying on the default handlers
+struct PingServer;
+
+impl Server for PingServer {}
+
+/// Server whose tool never finishes
+struct StuckToolServer;
+
+impl Server for StuckToolServer {
+    fn tools_call(
+        self: Arc<Self>,
+        _p: CallToolRequestParams,
+        cx: RequestContextAs<CallToolResult>,
+        _data: Arc<SessionData>,
+    ) -> jsoncall::Result<Response> {
+        cx.handle_async(async move {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(CallToolResult::from(()))
+        })
+    }
+}
+
+fn config() -> Http2ServerConfig {
+    Http2ServerConfig {
+        addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+        ..Default::default()
+    }
+}
+
+fn client(handle: &ServerHandle) -> ClientHttp2Transport {
+    client_with_headers(handle, HashMap::new())
+}
+
+fn client_with_headers(handle: &ServerHandle, headers: HashMap<String, String>) -> ClientHttp2Transport {
+    let url = url::Url::parse(&format!("http://{}", handle.local_addr())).unwrap();
+    ClientHttp2Transport::new(url, headers, ClientTlsConfig::None)
+}
+
+fn request(id: u64, method: &str) -> Message {
+    JsonRpcMessage::Request(JsonRpcRequest {
+        id,
+        method: method.to_string(),
+        params: None,
+        jsonrpc: JsonRpcVersion::default(),
+    })
+}
+
+/// Sends a request to `/message` over a fresh connection and returns the response
+/// head and body
+async fn raw_request(
+    handle: &ServerHandle,
+    method: &str,
+    headers: &[(&str, &str)],
+    body: &str,
+) -> (hyper::http::response::Parts, String) {
+    raw_request_to(handle, method, "/message", headers, body).await
+}
+
+/// Sends a request to `path` over a fresh connection and returns the response head
+/// and body
+async fn raw_request_to(
+    handle: &ServerHandle,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: &str,
+) -> (hyper::http::response::Parts, String) {
+    let stream = tokio::net::TcpStream::connect(handle.local_addr()).await.unwrap();
+    let (mut sender, connection) = hyper::client::conn::http2::handshake(
+        hyper_util::rt::TokioExecutor::new(),
+        hyper_util::rt::TokioIo::new(stream),
+    )
+    .await
+    .unwrap();
+    tokio::spawn(connection);
+
+    let mut request = hyper::Request::builder()
+        .method(method)
+        .uri(format!("http://{}{}", handle.local_addr(), path));
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    let request = request
+        .body(http_body_util::Full::new(bytes::Bytes::from(body.to_string())))
+        .unwrap();
+    let (parts, body) = sender.send_request(request).await.unwrap().into_parts();
+    let body = http_body_util::BodyExt::collect(body).await.unwrap().to_bytes();
+    (parts, String::from_utf8_lossy(&body).into_owned())
+}
+
+fn header<'a>(parts: &'a hyper::http::response::Parts, name: &str) -> Option<&'a str> {
+    parts.headers.get(name).map(|value| value.to_str().unwrap())
+}
+
+async fn receive(transport: &ClientHttp2Transport) -> Message {
+    tokio::time::timeout(Duration::from_secs(5), transport.receive())
+        .await
+        .expect("timed out waiting for a message")
+        .unwrap()
+        .unwrap()
+}
+
+/// Receives the error response to request `id`
+async fn receive_error(transport: &ClientHttp2Transport, id: u64) -> String {
+    let JsonRpcMessage::Response(response) = receive(transport).await else {
+        panic!("expected a response");
+    };
+    assert_eq!(response.id, id);
+    response.error.expect("expected an error response").message
+}
+
+/// Answers each request with its method, later requests first
+async fn echo_method(message: Message) -> mcp_daemon::transport::Result<Option<Message>> {
+    let JsonRpcMessage::Request(request) = message else {
+        return Ok(None);
+    };
+    if request.method == "fail" {
+        return Err(TransportError::new(TransportErrorCode::InternalError, "handler failed"));
+    }
+    tokio::time::sleep(Duration::from_millis(50 - request.id * 5)).await;
+    Ok(Some(JsonRpcMessage::Response(JsonRpcResponse {
+        id: request.id,
+        result: Some(json!({ "method": request.method })),
+        error: None,
+        jsonrpc: JsonRpcVersion::default(),
+    })))
+}
+
+/// Answers `quick` requests after a moment and `slow` ones after a minute
+async fn tool_calls(message: Message) -> mcp_daemon::transport::Result<Option<Message>> {
+    let JsonRpcMessage::Request(request) = message else {
+        return Ok(None);
+    };
+    let delay = if request.method == "slow" { 60_000 } else { 100 };
+    tokio::time::sleep(Duration::from_millis(delay)).await;
+    Ok(Some(JsonRpcMessage::Response(JsonRpcResponse {
+        id: request.id,
+        result: Some(json!({})),
+        error: None,
+        jsonrpc: JsonRpcVersion::default(),
+    })))
+}
+
+#[tokio::test]
+async fn test_responses_go_to_the_requesting_session() {
+    let handle = start_http2_server(config(), echo_method).await.unwrap();
+
+    let run = |name: &'static str| {
+        let transport = client(&handle);
+        async move {
+            transport.open().await.unwrap();
+            // Both clients use the same request IDs
+            let sends = (1..=5).map(|id| {
+                let transport = transport.clone();
+                async move { transport.send(&request(id, name)).await }
+            });
+            for result in futures::future::join_all(sends).await {
+                result.unwrap();
+            }
+            let mut ids = Vec::new();
+            for _ in 1..=5 {
+                let JsonRpcMessage::Response(response) = receive(&transport).await else {
+                    panic!("expected a response");
+                };
+                assert_eq!(response.result, Some(json!({ "method": name })));
+                ids.push(response.id);
+            }
+            ids.sort();
+            assert_eq!(ids, vec![1, 2, 3, 4, 5]);
+            transport.close().await.unwrap();
+        }
+    };
+    tokio::join!(run("first"), run("second"));
+
+    handle.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_handler_failure_is_a_json_rpc_error() {
+    let handle = start_http2_server(config(), echo_method).await.unwrap();
+    let transport = client(&handle);
+    transport.open().await.unwrap();
+
+    transport.send(&request(1, "fail")).await.unwrap();
+    let JsonRpcMessage::Response(response) = receive(&transport).await else {
+        panic!("expected a response");
+    };
+    assert_eq!(response.id, 1);
+    assert!(response.error.unwrap().message.contains("handler failed"));
+
+    transport.close().await.unwrap();
+    handle.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_server_messages_reach_the_session_event_stream() {
+    let handle = start_http2_server(config(), echo_method).await.unwrap();
+    let transport = client(&handle);
+    transport.open().await.unwrap();
+    transport.send(&request(1, "ping")).await.unwrap();
+    receive(&transport).await;
+
+    let session_id = transport.session_id().unwrap();
+    let session = handle
+        .sessions()
+        .into_iter()
+        .find(|session| session.id() == session_id)
+        .unwrap();
+    session
+        .send(&JsonRpcMessage::Notification(JsonRpcNotification {
+            method: "notifications/tools/list_changed".to_string(),
+            params: None,
+            jsonrpc: JsonRpcVersion::default(),
+        }))
+        .await
+        .unwrap();
+    let JsonRpcMessage::Notification(notification) = receive(&transport).await else {
+        panic!("expected a notification");
+    };
+    assert_eq!(notification.method, "notifications/tools/list_changed");
+
+    transport.close().await.unwrap();
+    handle.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_unknown_session_is_rejected() {
+    let handle = start_http2_server(config(), echo_method).await.unwrap();
+    let headers = HashMap::from([(SESSION_ID_HEADER.to_string(), "unknown".to_string())]);
+    let transport = client_with_headers(&handle, headers);
+    transport.open().await.unwrap();
+
+    transport.send(&request(1, "ping")).await.unwrap();
+    let error = receive_error(&transport, 1).await;
+    assert!(error.contains("404"), "{}", error);
+
+    transport.close().await.unwrap();
+    handle.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_each_connection_gets_its_own_server() {
+    let built = Arc::new(AtomicUsize::new(0));
+    let counter = built.clone();
+    let handle = serve_http2(config(), move || {
+        counter.fetch_add(1, Ordering::SeqCst);
+        PingServer
+    })
+    .await
+    .unwrap();
+
+    let first = ClientBuilder::new()
+        .build_with_transport(client(&handle))
+        .await
+        .unwrap();
+    let second = ClientBuilder::new()
+        .build_with_transport(client(&handle))
+        .await
+        .unwrap();
+    assert!(first.ping().await.is_ok());
+    assert!(second.ping().await.is_ok());
+    assert_eq!(built.load(Ordering::SeqCst), 2);
+    assert_eq!(handle.sessions().len(), 2);
+
+    handle.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_shutdown_finishes_requests_in_time_and_cancels_the_rest() {
+    let handle = start_http2_server(config(), tool_calls).await.unwrap();
+    let addr = handle.local_addr();
+    let transport = client(&handle);
+    transport.open().await.unwrap();
+
+    // Give both POSTs time to reach the server
+    let sends: Vec<_> = [(1, "quick"), (2, "slow")]
+        .into_iter()
+        .map(|(id, method)| {
+            let transport = transport.clone();
+            tokio::spawn(async move { transport.send(&request(id, method)).await })
+        })
+        .collect();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let notification = JsonRpcMessage::Notification(JsonRpcNotification {
+        method: "notifications/tools/list_changed".to_string(),
+        params: None,
+        jsonrpc: JsonRpcVersion::default(),
+    });
+    let started = std::time::Instant::now();
+    let shutdown = tokio::spawn(handle.shutdown(ShutdownConfig {
+        grace_period: Duration::from_millis(500),
+        notification: Some(notification.clone()),
+    }));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    // Requests arriving during the grace period are refused
+    transport.send(&request(3, "quick")).await.unwrap();
+
+    let mut messages = Vec::new();
+    for _ in 0..4 {
+        messages.push(receive(&transport).await);
+    }
+    assert!(messages.contains(&notification), "{:?}", messages);
+    let response = |id| {
+        messages.iter().find_map(|message| match message {
+            JsonRpcMessage::Response(response) if response.id == id => Some(response),
+            _ => None,
+        })
+    };
+    assert!(response(1).unwrap().result.is_some());
+    assert_eq!(response(3).unwrap().error.as_ref().unwrap().message, "Server is shutting down");
+    // The request cut off by the deadline is answered with an error too
+    assert_eq!(response(2).unwrap().error.as_ref().unwrap().message, "Server is shutting down");
+
+    for send in sends {
+        send.await.unwrap().unwrap();
+    }
+    tokio::time::timeout(Duration::from_secs(10), shutdown)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(started.elapsed() < Duration::from_secs(5), "{:?}", started.elapsed());
+    assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+}
+
+#[tokio::test]
+async fn test_shutdown_fails_the_client_calls_it_cancels() {
+    let handle = serve_http2(config(), || StuckToolServer).await.unwrap();
+    let client = ClientBuilder::new()
+        .build_with_transport(client(&handle))
+        .await
+        .unwrap();
+
+    let call = tokio::spawn(async move {
+        client
+            .tools_call(CallToolRequestParams {
+                name: "stuck".to_string(),
+                arguments: Default::default(),
+            })
+            .await
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    handle
+        .shutdown(ShutdownConfig {
+            grace_period: Duration::from_millis(100),
+            notification: None,
+        })
+        .await
+        .unwrap();
+
+    let result = tokio::time::timeout(Duration::from_secs(5), call)
+        .await
+        .expect("the cancelled call should not hang")
+        .unwrap();
+    let error = result.unwrap_err();
+    assert!(error.to_string().contains("Server is shutting down"), "{}", error);
+}
+
+#[tokio::test]
+async fn test_disallowed_origins_are_rejected_before_a_session_exists() {
+    let handle = start_http2_server(config(), echo_method).await.unwrap();
+    let headers = HashMap::from([("Origin".to_string(), "http://attacker.example".to_string())]);
+    let transport = client_with_headers(&handle, headers);
+    transport.open().await.unwrap();
+
+    transport.send(&request(1, "ping")).await.unwrap();
+    let error = receive_error(&transport, 1).await;
+    assert!(error.contains("403"), "{}", error);
+    assert!(handle.sessions().is_empty());
+    transport.close().await.unwrap();
+
+    let headers = HashMap::from([("Origin".to_string(), "https://localhost:3000".to_string())]);
+    let transport = client_with_headers(&handle, headers);
+    transport.open().await.unwrap();
+    transport.send(&request(1, "ping")).await.unwrap();
+    receive(&transport).await;
+    assert_eq!(handle.sessions().len(), 1);
+
+    transport.close().await.unwrap();
+    handle.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_default_cors_does_not_allow_credentialed_reads_from_any_origin() {
+    // Allowing every origin drops credentials even when they are asked for
+    for allow_credentials in [false, true] {
+        let mut config = config();
+        config.origin_policy = None;
+        config.cors_config.as_mut().unwrap().allow_credentials |= allow_credentials;
+        let handle = start_http2_server(config, echo_method).await.unwrap();
+        let ping = r#"{"jsonrpc":"2.0","id":1,"method":"ping"}"#;
+
+        let origin = [("origin", "https://attacker.example")];
+        let (preflight, _) = raw_request(&handle, "OPTIONS", &origin, "").await;
+        let (response, _) = raw_request(&handle, "POST", &origin, ping).await;
+        for parts in [preflight, response] {
+            assert_eq!(header(&parts, "access-control-allow-origin"), Some("*"));
+            assert_eq!(header(&parts, "access-control-allow-credentials"), None);
+        }
+
+        handle.stop().await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_cors_echoes_allowed_origins() {
+    let mut config = config();
+    config.origin_policy = None;
+    let cors = config.cors_config.as_mut().unwrap();
+    cors.allowed_origins = "https://app.example.com, https://*.tools.example.com".to_string();
+    cors.allow_credentials = true;
+    let handle = start_http2_server(config, echo_method).await.unwrap();
+    let ping = r#"{"jsonrpc":"2.0","id":1,"method":"ping"}"#;
+
+    for origin in ["https://app.example.com", "https://a.tools.example.com"] {
+        let (preflight, _) = raw_request(
+            &handle,
+            "OPTIONS",
+            &[
+                ("origin", origin),
+                ("access-control-request-method", "POST"),
+                ("access-control-request-headers", "content-type, mcp-session-id"),
+            ],
+            "",
+        )
+        .await;
+        assert_eq!(preflight.status, 204);
+        assert_eq!(header(&preflight, "access-control-allow-origin"), Some(origin));
+        assert_eq!(header(&preflight, "vary"), Some("Origin"));
+        assert_eq!(header(&preflight, "access-control-allow-credentials"), Some("true"));
+        assert_eq!(header(&preflight, "access-control-allow-headers"), Some("content-type, mcp-session-id"));
+
+        let (response, _) = raw_request(&handle, "POST", &[("origin", origin)], ping).await;
+        assert_eq!(response.status, 200);
+        assert_eq!(header(&response, "access-control-allow-origin"), Some(origin));
+        assert_eq!(header(&response, "access-control-expose-headers"), Some(SESSION_ID_HEADER));
+    }
+
+    // Other origins get no CORS headers, and requests without one need none
+    for origin in ["https://example.com", "https://tools.example.com", "null"] {
+        let (preflight, _) = raw_request(&handle, "OPTIONS", &[("origin", origin)], "").await;
+        assert_eq!(preflight.status, 400);
+        assert_eq!(header(&preflight, "access-control-allow-origin"), None);
+        let (response, _) = raw_request(&handle, "POST", &[("origin", origin)], ping).await;
+        assert_eq!(response.status, 400);
+    }
+    let (response, _) = raw_request(&handle, "POST", &[], ping).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(header(&response, "access-control-allow-origin"), None);
+
+    handle.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_monitoring_endpoints_are_off_by_default() {
+    let handle = start_http2_server(config(), echo_method).await.unwrap();
+    let headers = [("origin", "http://attacker.example")];
+    let (response, body) = raw_request_to(&handle, "GET", "/metrics", &headers, "").await;
+    assert_eq!(response.status, 403);
+    assert!(!body.contains("mcp_"), "{}", body);
+
+    handle.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_monitoring_endpoints_bypass_the_origin_check_and_report_metrics() {
+    let mut config = config();
+    config.monitoring = Some(MonitoringConfig {
+        bearer_token: Some("probe-token".to_string()),
+    });
+    let handle = start_http2_server(config, echo_method).await.unwrap();
+    let transport = client(&handle);
+    transport.open().await.unwrap();
+    transport.send(&request(1, "ping")).await.unwrap();
+    receive(&transport).await;
+
+    let probe = |path: &'static str, authorization: &'static str| {
+        let headers = [("origin", "http://attacker.example"), ("authorization", authorization)];
+        let handle = &handle;
+        async move { raw_request_to(handle, "GET", path, &headers, "").await }
+    };
+    assert_eq!(probe("/healthz", "Bearer wrong").await.0.status, 401);
+    assert_eq!(probe("/healthz", "Bearer probe-token").await.0.status, 200);
+    assert_eq!(probe("/readyz", "Bearer probe-token").await.0.status, 200);
+    let (response, metrics) = probe("/metrics", "Bearer probe-token").await;
+    assert_eq!(response.status, 200);
+    assert!(header(&response, "content-type").unwrap().starts_with("text/plain; version=0.0.4"));
+    for line in [
+        "mcp_sessions_active{transport=\"http2\"} 1",
+        "mcp_requests_total{method=\"ping\",outcome=\"success\"} 1",
+        "mcp_request_duration_seconds_count{method=\"ping\"} 1",
+    ] {
+        assert!(metrics.lines().any(|l| l == line), "missing {:?} in\n{}", line, metrics);
+    }
+    assert!(metrics.contains("mcp_received_bytes_total{transport=\"http2\"} "), "{}", metrics);
+
+    // MCP requests from that origin are still refused
+    let ping = r#"{"jsonrpc":"2.0","id":1,"method":"ping"}"#;
+    let (response, _) = raw_request(&handle, "POST", &[("origin", "http://attacker.example")], ping).await;
+    assert_eq!(response.status, 403);
+
+    transport.close().await.unwrap();
+    handle.stop().await.unwrap();
+}
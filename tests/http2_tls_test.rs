@@ -0,0 +1,189 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Incoming;
+use hyper::server::conn::http2;
+use hyper::service::service_fn;
+use hyper::{Request, Response};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use mcp_daemon::transport::{
+    ClientHttpTransport, Http2Builder, JsonRpcMessage, JsonRpcRequest, JsonRpcResponse,
+    JsonRpcVersion, Transport, TransportErrorCode,
+};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{RootCertStore, ServerConfig};
+
+const CERT: &str = "certs/localhost.example.crt";
+const KEY: &str = "certs/localhost.example.key";
+
+fn load_certs() -> Vec<CertificateDer<'static>> {
+    rustls_pemfile::certs(&mut BufReader::new(File::open(CERT).unwrap()))
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+fn load_key() -> PrivateKeyDer<'static> {
+    rustls_pemfile::private_key(&mut BufReader::new(File::open(KEY).unwrap()))
+        .unwrap()
+        .unwrap()
+}
+
+async fn echo(req: Request<Incoming>) -> Result<Response<Full<Bytes>>, hyper::Error> {
+    let body = req.collect().await?.to_bytes();
+    Ok(Response::new(Full::new(body)))
+}
+
+/// Starts an HTTP/2 echo server using the bundled certificate, optionally requiring
+/// clients to present a certificate signed by it
+async fn serve(require_client_cert: bool) -> SocketAddr {
+    let builder = ServerConfig::builder();
+    let builder = if require_client_cert {
+        let mut roots = RootCertStore::empty();
+        for cert in load_certs() {
+            roots.add(cert).unwrap();
+        }
+        builder.with_client_cert_verifier(
+            WebPkiClientVerifier::builder(Arc::new(roots)).build().unwrap(),
+        )
+    } else {
+        builder.with_no_client_auth()
+    };
+    let mut config = builder.with_single_cert(load_certs(), load_key()).unwrap();
+    config.alpn_protocols = vec![b"h2".to_vec()];
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let acceptor = acceptor.clone();
+            tokio::spawn(async move {
+                let Ok(stream) = acceptor.accept(stream).await else {
+                    return;
+                };
+                let _ = http2::Builder::new(TokioExecutor::new())
+                    .serve_connection(TokioIo::new(stream), service_fn(echo))
+                    .await;
+            });
+        }
+    });
+    addr
+}
+
+fn builder(addr: SocketAddr) -> Http2Builder {
+    Http2Builder::new()
+        .with_host(addr.ip().to_string())
+        .with_port(addr.port())
+}
+
+fn request() -> JsonRpcMessage {
+    JsonRpcMessage::Request(JsonRpcRequest {
+        id: 1,
+        method: "ping".to_string(),
+        params: None,
+        jsonrpc: JsonRpcVersion::default(),
+    })
+}
+
+/// Sends a request and returns the error it was answered with, if any
+async fn send(transport: &ClientHttpTransport) -> Result<(), String> {
+    transport.open().await.map_err(|e| e.to_string())?;
+    transport.send(&request()).await.map_err(|e| e.to_string())?;
+    let response = tokio::time::timeout(Duration::from_secs(5), transport.receive())
+        .await
+        .expect("timed out waiting for a response")
+        .map_err(|e| e.to_string())?;
+    transport.close().await.map_err(|e| e.to_string())?;
+    // The echo server answers with the request itself
+    match response {
+        Some(JsonRpcMessage::Response(JsonRpcResponse { error: Some(error), .. })) => {
+            Err(error.message)
+        }
+        Some(_) => Ok(()),
+        None => Err("transport closed".to_string()),
+    }
+}
+
+#[tokio::test]
+async fn test_custom_root_verifies_server() {
+    let addr = serve(false).await;
+    let transport = builder(addr)
+        .with_custom_tls(CERT.to_string(), true)
+        .build();
+    send(&transport).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_server_name_override() {
+    let addr = serve(false).await;
+    let transport = builder(addr)
+        .with_custom_tls(CERT.to_string(), true)
+        .with_sni("localhost".to_string())
+        .build();
+    send(&transport).await.unwrap();
+
+    // The certificate is checked against the override, not the address in the URL
+    let transport = builder(addr)
+        .with_custom_tls(CERT.to_string(), true)
+        .with_sni("mcp.example.com".to_string())
+        .build();
+    let err = send(&transport).await.unwrap_err();
+    assert!(err.contains("HTTP/2 request failed"), "{}", err);
+}
+
+#[tokio::test]
+async fn test_self_signed_server_is_not_trusted_by_default() {
+    let addr = serve(false).await;
+    let transport = builder(addr).with_tls(true).build();
+    // Fails on send, or on open when the platform has no root store at all
+    assert!(send(&transport).await.is_err());
+}
+
+#[tokio::test]
+async fn test_unverified_server_is_accepted_when_verification_is_disabled() {
+    let addr = serve(false).await;
+    let transport = builder(addr)
+        .with_custom_tls(String::new(), false)
+        .with_sni("mcp.example.com".to_string())
+        .build();
+    send(&transport).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_mutual_tls() {
+    let addr = serve(true).await;
+
+    let anonymous = builder(addr)
+        .with_custom_tls(CERT.to_string(), true)
+        .build();
+    assert!(send(&anonymous).await.is_err());
+
+    let authenticated = builder(addr)
+        .with_custom_tls(CERT.to_string(), true)
+        .with_client_cert(CERT.to_string(), KEY.to_string())
+        .build();
+    send(&authenticated).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_invalid_tls_config_fails_on_open() {
+    let transport = builder(SocketAddr::from(([127, 0, 0, 1], 1)))
+        .with_custom_tls("certs/missing.crt".to_string(), true)
+        .build();
+    let err = transport.open().await.unwrap_err();
+    assert_eq!(err.code(), Some(TransportErrorCode::ConfigurationError));
+
+    let transport = builder(SocketAddr::from(([127, 0, 0, 1], 1)))
+        .with_client_cert(CERT.to_string(), "certs/missing.key".to_string())
+        .build();
+    let err = transport.open().await.unwrap_err();
+    assert_eq!(err.code(), Some(TransportErrorCode::ConfigurationError));
+}
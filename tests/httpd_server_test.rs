@@ -0,0 +1,748 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use jsoncall::{RequestContextAs, Response};
+use mcp_daemon::schema::{CallToolRequestParams, CallToolResult};
+use mcp_daemon::server::{Server, SessionData};
+use mcp_daemon::start_http_server;
+use mcp_daemon::transport::httpd::{CorsConfig, HttpServerHandle, ServerConfig, SessionKind, SessionLimits};
+use mcp_daemon::transport::middleware::Claims;
+use mcp_daemon::transport::{Message, MonitoringConfig, ShutdownConfig, TransportErrorCode};
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+
+/// Server relying on the default handlers
+struct PingServer;
+
+impl Server for PingServer {}
+
+/// Server whose `slow` tool takes far longer than any test, and whose other tools
+/// finish quickly
+struct ToolServer {
+    /// Number of `slow` calls dropped before finishing
+    cancelled: Arc<AtomicUsize>,
+}
+
+/// Counts the call it is held by as cancelled unless it is disarmed
+struct CancelGuard(Option<Arc<AtomicUsize>>);
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        if let Some(cancelled) = self.0.take() {
+            cancelled.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+impl Server for ToolServer {
+    fn tools_call(
+        self: Arc<Self>,
+        p: CallToolRequestParams,
+        cx: RequestContextAs<CallToolResult>,
+        _data: Arc<SessionData>,
+    ) -> jsoncall::Result<Response> {
+        cx.handle_async(async move {
+            if p.name == "slow" {
+                let mut guard = CancelGuard(Some(self.cancelled.clone()));
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                guard.0 = None;
+            } else {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            Ok(CallToolResult::from(()))
+        })
+    }
+}
+
+async fn start(config: ServerConfig) -> HttpServerHandle {
+    start_http_server(config, None, |_transport| async {
+        Ok(Box::new(PingServer) as Box<dyn Server>)
+    })
+    .await
+    .unwrap()
+}
+
+fn local(port: u16) -> Vec<SocketAddr> {
+    vec![SocketAddr::from(([127, 0, 0, 1], port))]
+}
+
+/// Sends a GET for `path` with `headers` and returns the response up to the
+/// `endpoint` event, or whatever arrived before the connection closed
+async fn get(addr: SocketAddr, path: &str, headers: &[(&str, &str)]) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let mut request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\nAccept: text/event-stream\r\n", path);
+    for (name, value) in headers {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str("Connection: close\r\n\r\n");
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut received = Vec::new();
+    let mut buf = [0u8; 1024];
+    let read = async {
+        loop {
+            match stream.read(&mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => received.extend_from_slice(&buf[..n]),
+            }
+            if String::from_utf8_lossy(&received).contains("sessionId=") {
+                break;
+            }
+        }
+    };
+    let _ = tokio::time::timeout(Duration::from_secs(5), read).await;
+    String::from_utf8_lossy(&received).into_owned()
+}
+
+/// The URL advertised in the `endpoint` event
+fn endpoint(response: &str) -> &str {
+    let start = response.find("data: ").expect("no endpoint event") + "data: ".len();
+    response[start..].split_whitespace().next().unwrap()
+}
+
+/// Event stream of an SSE session
+struct SseClient {
+    /// URL to post messages to, from the `endpoint` event
+    endpoint: String,
+    /// `data` of the events after `endpoint`; closed when the stream ends
+    events: mpsc::UnboundedReceiver<String>,
+    task: JoinHandle<()>,
+}
+
+impl SseClient {
+    async fn connect(addr: SocketAddr) -> Result<Self, StatusCode> {
+        Self::connect_with_headers(addr, &[]).await
+    }
+
+    async fn connect_with_headers(addr: SocketAddr, headers: &[(&str, &str)]) -> Result<Self, StatusCode> {
+        let request = headers.iter().fold(
+            reqwest::Client::new().get(format!("http://{}/sse", addr)),
+            |request, (name, value)| request.header(*name, *value),
+        );
+        let response = request.send().await.unwrap();
+        if !response.status().is_success() {
+            return Err(response.status());
+        }
+
+        let (events_tx, mut events) = mpsc::unbounded_channel();
+        let task = tokio::spawn(async move {
+            let mut body = response.bytes_stream();
+            let mut buffer = String::new();
+            while let Some(Ok(chunk)) = body.next().await {
+                buffer.push_str(&String::from_utf8_lossy(&chunk));
+                while let Some(end) = buffer.find("\n\n") {
+                    let event: String = buffer.drain(..end + 2).collect();
+                    if let Some(data) = event.lines().find_map(|line| line.strip_prefix("data: ")) {
+                        let _ = events_tx.send(data.to_string());
+                    }
+                }
+            }
+        });
+        let endpoint = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        Ok(Self { endpoint, events, task })
+    }
+
+    async fn post(&self, message: Value) -> StatusCode {
+        reqwest::Client::new()
+            .post(&self.endpoint)
+            .json(&message)
+            .send()
+            .await
+            .unwrap()
+            .status()
+    }
+
+    /// Next event, or `None` once the server ended the stream
+    async fn next(&mut self) -> Option<Value> {
+        let data = tokio::time::timeout(Duration::from_secs(5), self.events.recv())
+            .await
+            .expect("timed out waiting for an event")?;
+        Some(serde_json::from_str(&data).unwrap())
+    }
+
+    fn disconnect(self) {
+        self.task.abort();
+    }
+}
+
+fn initialize(id: u64) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": "initialize",
+        "params": {
+            "protocolVersion": "2025-03-26",
+            "capabilities": {},
+            "clientInfo": { "name": "httpd-test", "version": "1.0.0" }
+        }
+    })
+}
+
+fn initialized() -> Value {
+    json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })
+}
+
+fn tools_call(id: u64, name: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": "tools/call",
+        "params": { "name": name }
+    })
+}
+
+fn tools_list_changed() -> Message {
+    serde_json::from_value(json!({
+        "jsonrpc": "2.0",
+        "method": "notifications/tools/list_changed"
+    }))
+    .unwrap()
+}
+
+/// Next text frame of `socket` as JSON
+async fn next_text<S>(socket: &mut S) -> Value
+where
+    S: futures::Stream<Item = Result<WsMessage, tokio_tungstenite::tungstenite::Error>> + Unpin,
+{
+    loop {
+        match tokio::time::timeout(Duration::from_secs(5), socket.next()).await.unwrap() {
+            Some(Ok(WsMessage::Text(text))) => break serde_json::from_str(&text).unwrap(),
+            Some(Ok(_)) => continue,
+            other => panic!("unexpected frame: {:?}", other),
+        }
+    }
+}
+
+fn limited(session_limits: SessionLimits) -> ServerConfig {
+    ServerConfig {
+        bind_addrs: local(0),
+        session_limits,
+        ..Default::default()
+    }
+}
+
+#[actix_web::test]
+async fn test_binds_every_address_with_its_actual_port() {
+    let handle = start(ServerConfig {
+        bind_addrs: vec!["127.0.0.1:0".parse().unwrap(), "[::1]:0".parse().unwrap()],
+        ..Default::default()
+    })
+    .await;
+
+    let addrs = handle.local_addrs().to_vec();
+    assert_eq!(addrs.len(), 2);
+    for addr in addrs {
+        assert_ne!(addr.port(), 0);
+        let response = get(addr, "/sse", &[]).await;
+        // Each listener advertises itself
+        assert!(
+            endpoint(&response).starts_with(&format!("http://{}/message?sessionId=", addr)),
+            "{}",
+            response
+        );
+    }
+
+    handle.stop().await.unwrap();
+}
+
+#[actix_web::test]
+async fn test_ipv4_and_ipv6_can_share_a_port() {
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let handle = start(ServerConfig {
+        bind_addrs: vec![
+            SocketAddr::from(([127, 0, 0, 1], port)),
+            format!("[::1]:{}", port).parse().unwrap(),
+        ],
+        ..Default::default()
+    })
+    .await;
+    assert_eq!(handle.local_addrs().len(), 2);
+    handle.stop().await.unwrap();
+}
+
+#[actix_web::test]
+async fn test_routes_are_mounted_under_the_path_prefix() {
+    let handle = start(ServerConfig {
+        bind_addrs: local(0),
+        path_prefix: "mcp/".to_string(),
+        ..Default::default()
+    })
+    .await;
+    let addr = handle.local_addrs()[0];
+
+    let response = get(addr, "/sse", &[]).await;
+    assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+
+    let response = get(addr, "/mcp/sse", &[]).await;
+    assert!(
+        endpoint(&response).starts_with(&format!("http://{}/mcp/message?sessionId=", addr)),
+        "{}",
+        response
+    );
+
+    handle.stop().await.unwrap();
+}
+
+#[actix_web::test]
+async fn test_public_url_is_advertised() {
+    let handle = start(ServerConfig {
+        bind_addrs: local(0),
+        public_url: Some("https://mcp.example.com/tools/".parse().unwrap()),
+        path_prefix: "/mcp".to_string(),
+        // The public URL takes precedence over forwarding headers
+        trust_forwarded_headers: true,
+        ..Default::default()
+    })
+    .await;
+    let addr = handle.local_addrs()[0];
+
+    let response = get(addr, "/mcp/sse", &[("X-Forwarded-Host", "other.example.com")]).await;
+    assert!(
+        endpoint(&response).starts_with("https://mcp.example.com/tools/mcp/message?sessionId="),
+        "{}",
+        response
+    );
+
+    handle.stop().await.unwrap();
+}
+
+#[actix_web::test]
+async fn test_forwarded_headers_are_only_used_when_trusted() {
+    let forwarded = [
+        ("X-Forwarded-Proto", "https"),
+        ("X-Forwarded-Host", "proxy.example.com, internal"),
+        ("X-Forwarded-Prefix", "/api/"),
+    ];
+
+    let handle = start(ServerConfig {
+        bind_addrs: local(0),
+        trust_forwarded_headers: true,
+        ..Default::default()
+    })
+    .await;
+    let response = get(handle.local_addrs()[0], "/sse", &forwarded).await;
+    assert!(
+        endpoint(&response).starts_with("https://proxy.example.com/api/message?sessionId="),
+        "{}",
+        response
+    );
+    handle.stop().await.unwrap();
+
+    let handle = start(ServerConfig {
+        bind_addrs: local(0),
+        ..Default::default()
+    })
+    .await;
+    let addr = handle.local_addrs()[0];
+    let response = get(addr, "/sse", &forwarded).await;
+    assert!(
+        endpoint(&response).starts_with(&format!("http://{}/message?sessionId=", addr)),
+        "{}",
+        response
+    );
+    handle.stop().await.unwrap();
+}
+
+#[actix_web::test]
+async fn test_sse_session_serves_requests() {
+    let handle = start(limited(SessionLimits::default())).await;
+    let mut client = SseClient::connect(handle.local_addrs()[0]).await.unwrap();
+
+    assert_eq!(client.post(initialize(1)).await, StatusCode::ACCEPTED);
+    let response = client.next().await.unwrap();
+    assert_eq!(response["id"], 1);
+    assert!(response["result"]["serverInfo"].is_object(), "{}", response);
+
+    client.disconnect();
+    handle.stop().await.unwrap();
+}
+
+#[actix_web::test]
+async fn test_websocket_session_serves_requests() {
+    let handle = start(limited(SessionLimits::default())).await;
+    let url = format!("ws://{}/ws", handle.local_addrs()[0]);
+    let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+
+    socket
+        .send(WsMessage::Text(initialize(1).to_string().into()))
+        .await
+        .unwrap();
+    // The first message back is the response, not the request echoed
+    let response = next_text(&mut socket).await;
+    assert_eq!(response["id"], 1);
+    assert!(response["result"].is_object(), "{}", response);
+
+    handle.stop().await.unwrap();
+}
+
+#[actix_web::test]
+async fn test_closed_sessions_free_their_slot() {
+    let handle = start(ServerConfig {
+        // Dead SSE clients are noticed on the next keep-alive
+        sse_keep_alive: Duration::from_millis(100),
+        ..limited(SessionLimits {
+            max_sessions: Some(1),
+            ..Default::default()
+        })
+    })
+    .await;
+    let addr = handle.local_addrs()[0];
+
+    let first = SseClient::connect(addr).await.unwrap();
+    assert_eq!(
+        SseClient::connect(addr).await.err(),
+        Some(StatusCode::SERVICE_UNAVAILABLE)
+    );
+    let url = format!("ws://{}/ws", addr);
+    assert!(tokio_tungstenite::connect_async(&url).await.is_err());
+
+    first.disconnect();
+    for _ in 0..100 {
+        if let Ok(client) = SseClient::connect(addr).await {
+            client.disconnect();
+            handle.stop().await.unwrap();
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("the session of the disconnected client was not removed");
+}
+
+#[actix_web::test]
+async fn test_sessions_per_ip_are_limited() {
+    let handle = start(limited(SessionLimits {
+        max_sessions_per_ip: Some(1),
+        ..Default::default()
+    }))
+    .await;
+    let addr = handle.local_addrs()[0];
+
+    let first = SseClient::connect(addr).await.unwrap();
+    assert_eq!(
+        SseClient::connect(addr).await.err(),
+        Some(StatusCode::TOO_MANY_REQUESTS)
+    );
+
+    first.disconnect();
+    handle.stop().await.unwrap();
+}
+
+#[actix_web::test]
+async fn test_sessions_per_ip_use_the_address_the_proxy_appended() {
+    let handle = start(ServerConfig {
+        trust_forwarded_headers: true,
+        ..limited(SessionLimits {
+            max_sessions_per_ip: Some(1),
+            ..Default::default()
+        })
+    })
+    .await;
+    let addr = handle.local_addrs()[0];
+    let connect = |forwarded_for: &'static str| async move {
+        SseClient::connect_with_headers(addr, &[("X-Forwarded-For", forwarded_for)]).await
+    };
+
+    let first = connect("203.0.113.1, 198.51.100.7").await.unwrap();
+    // Entries before the proxy's own are chosen by the client and cannot dodge the limit
+    assert_eq!(
+        connect("203.0.113.2, 198.51.100.7").await.err(),
+        Some(StatusCode::TOO_MANY_REQUESTS)
+    );
+    let other = connect("198.51.100.8").await.unwrap();
+
+    first.disconnect();
+    other.disconnect();
+    handle.stop().await.unwrap();
+}
+
+#[actix_web::test]
+async fn test_idle_sessions_expire() {
+    let handle = start(limited(SessionLimits {
+        idle_timeout: Some(Duration::from_secs(1)),
+        ..Default::default()
+    }))
+    .await;
+    let mut client = SseClient::connect(handle.local_addrs()[0]).await.unwrap();
+
+    // Activity keeps the session open, with margin for slow test machines
+    for id in 1..=3 {
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert_eq!(client.post(initialize(id)).await, StatusCode::ACCEPTED);
+        assert_eq!(client.next().await.unwrap()["id"], id);
+    }
+
+    // The server ends the event stream and forgets the session once it idles out
+    assert!(client.next().await.is_none());
+    assert_eq!(client.post(initialize(4)).await, StatusCode::NOT_FOUND);
+
+    handle.stop().await.unwrap();
+}
+
+#[actix_web::test]
+async fn test_messages_are_pushed_to_selected_sessions() {
+    let handle = start(limited(SessionLimits::default())).await;
+    let addr = handle.local_addrs()[0];
+    let registry = handle.sessions();
+
+    let mut sse = SseClient::connect(addr).await.unwrap();
+    assert_eq!(sse.post(initialize(1)).await, StatusCode::ACCEPTED);
+    assert_eq!(sse.next().await.unwrap()["id"], 1);
+    let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", addr))
+        .await
+        .unwrap();
+
+    let sessions = registry.list();
+    assert_eq!(sessions.len(), 2);
+    let sse_session = sessions.iter().find(|s| s.kind() == SessionKind::Sse).unwrap();
+    let ws_session = sessions.iter().find(|s| s.kind() == SessionKind::WebSocket).unwrap();
+    assert_eq!(sse_session.client_info().unwrap().name, "httpd-test");
+    assert!(ws_session.client_info().is_none());
+    assert_eq!(sse_session.peer_addr().unwrap().ip(), addr.ip());
+    assert!(sse_session.connected_at() <= std::time::SystemTime::now());
+
+    // Only initialized sessions
+    let sent = registry
+        .send_where(|session| session.client_info().is_some(), &tools_list_changed())
+        .await;
+    assert_eq!(sent.len(), 1);
+    assert!(sent[sse_session.id()].is_ok());
+    assert_eq!(sse.next().await.unwrap()["method"], "notifications/tools/list_changed");
+
+    // One session by ID
+    registry.send(ws_session.id(), &tools_list_changed()).await.unwrap();
+    assert_eq!(next_text(&mut ws).await["method"], "notifications/tools/list_changed");
+    let err = registry.send("unknown", &tools_list_changed()).await.unwrap_err();
+    assert_eq!(err.code(), Some(TransportErrorCode::SessionNotFound));
+
+    // Everyone
+    let sent = registry.send_all(&tools_list_changed()).await;
+    assert_eq!(sent.len(), 2);
+    assert!(sent.values().all(|result| result.is_ok()));
+    assert_eq!(sse.next().await.unwrap()["method"], "notifications/tools/list_changed");
+    assert_eq!(next_text(&mut ws).await["method"], "notifications/tools/list_changed");
+
+    sse.disconnect();
+    handle.stop().await.unwrap();
+}
+
+#[actix_web::test]
+async fn test_shutdown_finishes_requests_in_time_and_cancels_the_rest() {
+    let cancelled = Arc::new(AtomicUsize::new(0));
+    let server_cancelled = cancelled.clone();
+    let handle = start_http_server(limited(SessionLimits::default()), None, move |_transport| {
+        let cancelled = server_cancelled.clone();
+        async move { Ok(Box::new(ToolServer { cancelled }) as Box<dyn Server>) }
+    })
+    .await
+    .unwrap();
+    let addr = handle.local_addrs()[0];
+
+    let mut sse = SseClient::connect(addr).await.unwrap();
+    assert_eq!(sse.post(initialize(1)).await, StatusCode::ACCEPTED);
+    assert_eq!(sse.next().await.unwrap()["id"], 1);
+    assert_eq!(sse.post(initialized()).await, StatusCode::ACCEPTED);
+    let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", addr))
+        .await
+        .unwrap();
+
+    assert_eq!(sse.post(tools_call(2, "quick")).await, StatusCode::ACCEPTED);
+    assert_eq!(sse.post(tools_call(3, "slow")).await, StatusCode::ACCEPTED);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let shutdown = tokio::spawn(handle.shutdown(ShutdownConfig {
+        grace_period: Duration::from_millis(500),
+        notification: Some(tools_list_changed()),
+    }));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    // Requests arriving during the grace period are refused,
+    assert_eq!(sse.post(tools_call(4, "quick")).await, StatusCode::ACCEPTED);
+    // and so are new sessions
+    assert_eq!(
+        SseClient::connect(addr).await.err(),
+        Some(StatusCode::SERVICE_UNAVAILABLE)
+    );
+
+    let mut events = Vec::new();
+    while let Some(event) = sse.next().await {
+        events.push(event);
+    }
+    let find = |predicate: &dyn Fn(&Value) -> bool| {
+        events
+            .iter()
+            .find(|event| predicate(event))
+            .unwrap_or_else(|| panic!("missing event in {:?}", events))
+    };
+    find(&|e| e["method"] == "notifications/tools/list_changed");
+    assert!(find(&|e| e["id"] == 2)["result"].is_object());
+    assert_eq!(find(&|e| e["id"] == 4)["error"]["message"], "Server is shutting down");
+    assert_eq!(find(&|e| e["id"] == 3)["error"]["message"], "Server is shutting down");
+
+    // The WebSocket gets the notification, then is closed as going away
+    assert_eq!(next_text(&mut ws).await["method"], "notifications/tools/list_changed");
+    let close = loop {
+        match tokio::time::timeout(Duration::from_secs(5), ws.next()).await.unwrap() {
+            Some(Ok(WsMessage::Close(frame))) => break frame,
+            Some(Ok(_)) => continue,
+            other => panic!("unexpected frame: {:?}", other),
+        }
+    };
+    assert_eq!(close.unwrap().code, CloseCode::Away);
+
+    tokio::time::timeout(Duration::from_secs(10), shutdown)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(cancelled.load(Ordering::SeqCst), 1);
+    assert!(TcpStream::connect(addr).await.is_err());
+}
+
+#[actix_web::test]
+async fn test_disallowed_origins_are_rejected_before_a_session_exists() {
+    let handle = start(limited(SessionLimits::default())).await;
+    let addr = handle.local_addrs()[0];
+    let client = reqwest::Client::new();
+    let sse = |headers: &[(&'static str, &'static str)]| {
+        let mut request = client.get(format!("http://{}/sse", addr));
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        request.send()
+    };
+
+    let response = sse(&[("Origin", "http://attacker.example")]).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    // DNS rebinding: the page's origin and the host both name the attacker's domain
+    let response = sse(&[("Host", "attacker.example:8080")]).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let mut request = format!("ws://{}/ws", addr).into_client_request().unwrap();
+    request
+        .headers_mut()
+        .insert("Origin", "http://attacker.example".parse().unwrap());
+    assert!(tokio_tungstenite::connect_async(request).await.is_err());
+    assert!(handle.sessions().is_empty());
+
+    let response = sse(&[("Origin", "http://localhost:3000")]).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    drop(response);
+    handle.stop().await.unwrap();
+
+    // Without a policy, any origin is accepted
+    let handle = start(ServerConfig {
+        origin_policy: None,
+        ..limited(SessionLimits::default())
+    })
+    .await;
+    let addr = handle.local_addrs()[0];
+    let response = client
+        .get(format!("http://{}/sse", addr))
+        .header("Origin", "http://attacker.example")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    drop(response);
+    handle.stop().await.unwrap();
+}
+
+#[actix_web::test]
+async fn test_cors_allowing_any_origin_does_not_allow_credentials() {
+    let handle = start(ServerConfig {
+        origin_policy: None,
+        cors: Some(CorsConfig {
+            allowed_origin: "*".to_string(),
+            allow_credentials: true,
+            max_age: None,
+        }),
+        ..limited(SessionLimits::default())
+    })
+    .await;
+    let addr = handle.local_addrs()[0];
+
+    let response = get(addr, "/sse", &[("Origin", "https://attacker.example")]).await.to_ascii_lowercase();
+    assert!(response.contains("access-control-allow-origin: *\r\n"), "{}", response);
+    assert!(!response.contains("access-control-allow-credentials"), "{}", response);
+    assert!(!response.contains("attacker.example"), "{}", response);
+    handle.stop().await.unwrap();
+}
+
+#[actix_web::test]
+async fn test_monitoring_endpoints_bypass_jwt_and_report_metrics() {
+    let handle = start_http_server(
+        ServerConfig {
+            bind_addrs: local(0),
+            monitoring: Some(MonitoringConfig {
+                bearer_token: Some("probe-token".to_string()),
+            }),
+            ..Default::default()
+        },
+        Some("jwt-secret".to_string()),
+        |_transport| async { Ok(Box::new(PingServer) as Box<dyn Server>) },
+    )
+    .await
+    .unwrap();
+    let base = format!("http://{}", handle.local_addrs()[0]);
+    let client = reqwest::Client::new();
+    let probe = |path: &str, token: &str| {
+        client
+            .get(format!("{}{}", base, path))
+            // Orchestrators probe by address, which the origin check would refuse
+            .header("Host", "10.0.0.7:8080")
+            .bearer_auth(token)
+            .send()
+    };
+
+    assert_eq!(probe("/healthz", "jwt").await.unwrap().status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(probe("/healthz", "probe-token").await.unwrap().status(), StatusCode::OK);
+    assert_eq!(probe("/readyz", "probe-token").await.unwrap().status(), StatusCode::OK);
+    // The MCP endpoints still need a JWT
+    let sse = client.get(format!("{}/sse", base)).bearer_auth("probe-token").send().await.unwrap();
+    assert_eq!(sse.status(), StatusCode::UNAUTHORIZED);
+
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs() as usize;
+    let jwt = jsonwebtoken::encode(
+        &jsonwebtoken::Header::default(),
+        &Claims { exp: now + 60, iat: now },
+        &jsonwebtoken::EncodingKey::from_secret(b"jwt-secret"),
+    )
+    .unwrap();
+    let mut request = format!("ws://{}/ws", handle.local_addrs()[0]).into_client_request().unwrap();
+    request.headers_mut().insert("Authorization", format!("Bearer {}", jwt).parse().unwrap());
+    let (mut socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+    socket.send(WsMessage::Text(initialize(1).to_string().into())).await.unwrap();
+    assert_eq!(next_text(&mut socket).await["id"], 1);
+
+    let metrics = probe("/metrics", "probe-token").await.unwrap();
+    assert_eq!(metrics.status(), StatusCode::OK);
+    assert!(metrics.headers()["content-type"].to_str().unwrap().starts_with("text/plain; version=0.0.4"));
+    let metrics = metrics.text().await.unwrap();
+    for line in [
+        "mcp_sessions_active{transport=\"sse\"} 0",
+        "mcp_sessions_active{transport=\"websocket\"} 1",
+        "mcp_requests_total{method=\"initialize\",outcome=\"success\"} 1",
+        "mcp_request_duration_seconds_count{method=\"initialize\"} 1",
+    ] {
+        assert!(metrics.lines().any(|l| l == line), "missing {:?} in\n{}", line, metrics);
+    }
+    assert!(metrics.contains("mcp_received_bytes_total{transport=\"websocket\"} "), "{}", metrics);
+    assert!(metrics.contains("mcp_sent_bytes_total{transport=\"websocket\"} "), "{}", metrics);
+
+    handle.stop().await.unwrap();
+}
@@ -0,0 +1,217 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+use std::time::Duration;
+
+use mcp_daemon::run_http_server;
+use mcp_daemon::server::Server;
+use mcp_daemon::transport::TransportError;
+use mcp_daemon::transport::TransportErrorCode;
+use mcp_daemon::transport::httpd::{ServerConfig, TlsConfig};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+
+const CERT: &str = "certs/localhost.example.crt";
+const KEY: &str = "certs/localhost.example.key";
+
+/// Server relying on the default handlers
+struct PingServer;
+
+impl Server for PingServer {}
+
+fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+fn load_certs() -> Vec<CertificateDer<'static>> {
+    rustls_pemfile::certs(&mut BufReader::new(File::open(CERT).unwrap()))
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+fn load_key() -> PrivateKeyDer<'static> {
+    rustls_pemfile::private_key(&mut BufReader::new(File::open(KEY).unwrap()))
+        .unwrap()
+        .unwrap()
+}
+
+/// Starts `run_http_server` on a free port and waits until it accepts connections
+async fn start(tls: TlsConfig) -> u16 {
+    let port = free_port();
+    let config = ServerConfig {
+        port,
+        tls: Some(tls),
+        ..Default::default()
+    };
+    actix_web::rt::spawn(run_http_server(config, None, |_transport| async {
+        Ok(Box::new(PingServer) as Box<dyn Server>)
+    }));
+    for _ in 0..250 {
+        if TcpStream::connect(("127.0.0.1", port)).await.is_ok() {
+            return port;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("server did not start");
+}
+
+fn client_config(client_cert: bool) -> ClientConfig {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs() {
+        roots.add(cert).unwrap();
+    }
+    let builder = ClientConfig::builder().with_root_certificates(roots);
+    if client_cert {
+        builder.with_client_auth_cert(load_certs(), load_key()).unwrap()
+    } else {
+        builder.with_no_client_auth()
+    }
+}
+
+fn connector(client_cert: bool) -> TlsConnector {
+    TlsConnector::from(Arc::new(client_config(client_cert)))
+}
+
+/// Opens the SSE stream over TLS and returns what it has sent once the endpoint
+/// event arrives, or whatever was read before the connection failed
+async fn read_endpoint(port: u16, client_cert: bool) -> String {
+    let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    let name = ServerName::try_from("localhost").unwrap();
+    let Ok(mut stream) = connector(client_cert).connect(name, stream).await else {
+        return String::new();
+    };
+    let request = "GET /sse HTTP/1.1\r\nHost: localhost\r\nAccept: text/event-stream\r\n\r\n";
+    if stream.write_all(request.as_bytes()).await.is_err() {
+        return String::new();
+    }
+
+    let mut received = Vec::new();
+    let mut buf = [0u8; 1024];
+    let read = async {
+        loop {
+            match stream.read(&mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => received.extend_from_slice(&buf[..n]),
+            }
+            if String::from_utf8_lossy(&received).contains("sessionId=") {
+                break;
+            }
+        }
+    };
+    let _ = tokio::time::timeout(Duration::from_secs(5), read).await;
+    String::from_utf8_lossy(&received).into_owned()
+}
+
+#[actix_web::test]
+async fn test_sse_over_tls_advertises_https_endpoint() {
+    let port = start(TlsConfig::new(CERT, KEY)).await;
+    let received = read_endpoint(port, false).await;
+    assert!(received.starts_with("HTTP/1.1 200"), "{}", received);
+    assert!(
+        received.contains(&format!("https://127.0.0.1:{}/message?sessionId=", port)),
+        "{}",
+        received
+    );
+}
+
+#[actix_web::test]
+async fn test_sse_over_h2_passes_the_host_check() {
+    let port = start(TlsConfig::new(CERT, KEY)).await;
+    let mut config = client_config(false);
+    config.alpn_protocols = vec![b"h2".to_vec()];
+    let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    let stream = TlsConnector::from(Arc::new(config))
+        .connect(ServerName::try_from("localhost").unwrap(), stream)
+        .await
+        .unwrap();
+    assert_eq!(stream.get_ref().1.alpn_protocol(), Some(&b"h2"[..]));
+
+    let (mut sender, connection) = hyper::client::conn::http2::handshake(
+        hyper_util::rt::TokioExecutor::new(),
+        hyper_util::rt::TokioIo::new(stream),
+    )
+    .await
+    .unwrap();
+    tokio::spawn(connection);
+    // HTTP/2 sends the host as `:authority`, without a `Host` header
+    let request = hyper::Request::builder()
+        .uri(format!("https://localhost:{}/sse", port))
+        .header("Accept", "text/event-stream")
+        .body(http_body_util::Empty::<bytes::Bytes>::new())
+        .unwrap();
+    let response = sender.send_request(request).await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    let mut body = response.into_body();
+    let frame = tokio::time::timeout(Duration::from_secs(5), http_body_util::BodyExt::frame(&mut body))
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    let data = String::from_utf8_lossy(frame.data_ref().unwrap()).into_owned();
+    assert!(data.contains("/message?sessionId="), "{}", data);
+}
+
+#[actix_web::test]
+async fn test_plain_http_is_not_served() {
+    let port = start(TlsConfig::new(CERT, KEY)).await;
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    stream
+        .write_all(b"GET /sse HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+    let mut received = Vec::new();
+    let _ = tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut received)).await;
+    assert!(!String::from_utf8_lossy(&received).contains("sessionId="));
+}
+
+#[actix_web::test]
+async fn test_required_client_certificate() {
+    let port = start(TlsConfig::new(CERT, KEY).with_client_ca(CERT, true)).await;
+    assert!(!read_endpoint(port, false).await.contains("sessionId="));
+    assert!(read_endpoint(port, true).await.contains("sessionId="));
+}
+
+#[actix_web::test]
+async fn test_optional_client_certificate() {
+    let port = start(TlsConfig::new(CERT, KEY).with_client_ca(CERT, false)).await;
+    assert!(read_endpoint(port, false).await.contains("sessionId="));
+    assert!(read_endpoint(port, true).await.contains("sessionId="));
+}
+
+#[actix_web::test]
+async fn test_bad_certificate_fails_fast() {
+    let config = ServerConfig {
+        port: free_port(),
+        tls: Some(TlsConfig::new("certs/missing.crt", KEY)),
+        ..Default::default()
+    };
+    let err = run_http_server(config, None, |_transport| async {
+        Ok(Box::new(PingServer) as Box<dyn Server>)
+    })
+    .await
+    .unwrap_err();
+    let err = err.downcast_ref::<TransportError>().unwrap();
+    assert_eq!(err.code(), Some(TransportErrorCode::ConfigurationError));
+
+    // A key that does not belong to the certificate is rejected too
+    let config = ServerConfig {
+        port: free_port(),
+        tls: Some(TlsConfig::new(CERT, CERT)),
+        ..Default::default()
+    };
+    assert!(
+        run_http_server(config, None, |_transport| async {
+            Ok(Box::new(PingServer) as Box<dyn Server>)
+        })
+        .await
+        .is_err()
+    );
+}
@@ -11,9 +11,9 @@ use mcp_daemon::schema::{
     ListPromptsResult, GetPromptResult, ServerCapabilities,
 };
 
-/// Define standalone async handler functions
-/// 
-/// These don't capture any references so they can be moved freely
+// Define standalone async handler functions
+//
+// These don't capture any references so they can be moved freely
 
 /// Handles the prompts/list request.
 ///
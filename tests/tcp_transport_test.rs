@@ -0,0 +1,137 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use mcp_daemon::{
+    client::ClientBuilder,
+    schema::{Tool, ToolInputSchema},
+    server::Server,
+    testing::{Expectation, MockServer},
+    transport::{ClientTcpTransport, TcpServerConfig, TlsConfig, TransportErrorCode, serve_tcp},
+};
+use serde_json::json;
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{ClientConfig, DigitallySignedStruct, Error, SignatureScheme};
+
+/// Server relying on the default handlers
+struct PingServer;
+
+impl Server for PingServer {}
+
+/// Accepts any certificate; the bundled test certificate may have expired
+#[derive(Debug)]
+struct AcceptAnyCert;
+
+impl ServerCertVerifier for AcceptAnyCert {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        tokio_rustls::rustls::crypto::aws_lc_rs::default_provider()
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+fn localhost() -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], 0))
+}
+
+#[tokio::test]
+async fn test_sessions_per_connection() {
+    let server = MockServer::new();
+    server.expect(
+        Expectation::request("tools/list")
+            .respond_with(json!({ "tools": [Tool::new("echo", ToolInputSchema::new())] })),
+    );
+    let mock = server.clone();
+    let handle = serve_tcp(TcpServerConfig::new(localhost()), move || mock.clone())
+        .await
+        .unwrap();
+    let addr = handle.local_addr().to_string();
+
+    let first = ClientBuilder::new()
+        .build_with_transport(ClientTcpTransport::new(&addr))
+        .await
+        .unwrap();
+    let second = ClientBuilder::new()
+        .build_with_transport(ClientTcpTransport::new(&addr))
+        .await
+        .unwrap();
+    assert_eq!(first.tools_list(None).await.unwrap().tools[0].name, "echo");
+    assert_eq!(second.tools_list(None).await.unwrap().tools[0].name, "echo");
+    assert_eq!(server.received_requests().len(), 2);
+
+    handle.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_tls() {
+    let tls = TlsConfig::Manual {
+        cert_path: "certs/localhost.example.crt".to_string(),
+        key_path: "certs/localhost.example.key".to_string(),
+    };
+    let handle = serve_tcp(TcpServerConfig::new(localhost()).with_tls(tls), || PingServer)
+        .await
+        .unwrap();
+
+    let config = ClientConfig::builder()
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(AcceptAnyCert))
+        .with_no_client_auth();
+    let transport = ClientTcpTransport::new(handle.local_addr().to_string())
+        .with_tls(Arc::new(config), ServerName::try_from("localhost").unwrap());
+    let client = ClientBuilder::new()
+        .build_with_transport(transport)
+        .await
+        .unwrap();
+    assert!(client.ping().await.is_ok());
+
+    // A plain client cannot talk to a TLS server
+    let plain = ClientBuilder::new()
+        .build_with_transport(ClientTcpTransport::new(handle.local_addr().to_string()))
+        .await;
+    assert!(plain.is_err());
+
+    handle.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_missing_certificate_fails_fast() {
+    let tls = TlsConfig::Manual {
+        cert_path: "certs/missing.crt".to_string(),
+        key_path: "certs/missing.key".to_string(),
+    };
+    let err = serve_tcp(TcpServerConfig::new(localhost()).with_tls(tls), || PingServer)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(TransportErrorCode::ConfigurationError));
+}
@@ -0,0 +1,196 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use mcp_daemon::run_http_server;
+use mcp_daemon::server::Server;
+use mcp_daemon::transport::httpd::{ServerConfig, TlsConfig as HttpdTlsConfig};
+use mcp_daemon::transport::{Http2ServerConfig, TlsConfig, start_http2_server};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName};
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+
+const CERT: &str = "certs/localhost.example.crt";
+const KEY: &str = "certs/localhost.example.key";
+const ROTATED_CERT: &str = "certs/localhost.rotated.crt";
+const ROTATED_KEY: &str = "certs/localhost.rotated.key";
+
+/// Server relying on the default handlers
+struct PingServer;
+
+impl Server for PingServer {}
+
+/// Certificate and key files in a scratch directory, starting out as the bundled pair
+struct CertFiles {
+    dir: PathBuf,
+}
+
+impl CertFiles {
+    fn new() -> Self {
+        let dir = std::env::temp_dir().join(format!("mcp-tls-reload-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let files = Self { dir };
+        files.install(CERT, KEY);
+        files
+    }
+
+    fn cert_path(&self) -> String {
+        self.dir.join("server.crt").to_string_lossy().into_owned()
+    }
+
+    fn key_path(&self) -> String {
+        self.dir.join("server.key").to_string_lossy().into_owned()
+    }
+
+    /// Replaces the files the way certificate managers do, by renaming new files over them
+    fn install(&self, cert: &str, key: &str) {
+        for (source, target) in [(key, self.key_path()), (cert, self.cert_path())] {
+            let staged = self.dir.join("staged");
+            std::fs::copy(source, &staged).unwrap();
+            std::fs::rename(&staged, target).unwrap();
+        }
+    }
+}
+
+impl Drop for CertFiles {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn load_cert(path: impl AsRef<Path>) -> CertificateDer<'static> {
+    rustls_pemfile::certs(&mut BufReader::new(File::open(path).unwrap()))
+        .next()
+        .unwrap()
+        .unwrap()
+}
+
+/// Connector trusting both the bundled and the rotated certificate
+fn connector() -> TlsConnector {
+    let mut roots = RootCertStore::empty();
+    roots.add(load_cert(CERT)).unwrap();
+    roots.add(load_cert(ROTATED_CERT)).unwrap();
+    let mut config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    config.alpn_protocols = vec![b"h2".to_vec()];
+    TlsConnector::from(Arc::new(config))
+}
+
+/// Completes a TLS handshake with `addr` and returns the certificate it presented
+async fn served_cert(addr: SocketAddr) -> CertificateDer<'static> {
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let name = ServerName::try_from("localhost").unwrap();
+    let stream = connector().connect(name, stream).await.unwrap();
+    stream.get_ref().1.peer_certificates().unwrap()[0].clone()
+}
+
+/// Waits until new handshakes with `addr` present `expected`
+async fn wait_for_cert(addr: SocketAddr, expected: &CertificateDer<'static>) {
+    for _ in 0..100 {
+        if &served_cert(addr).await == expected {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("the rotated certificate was not picked up");
+}
+
+#[tokio::test]
+async fn test_http2_server_picks_up_rotated_certificate() {
+    let files = CertFiles::new();
+    let config = Http2ServerConfig {
+        addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+        tls_config: Some(TlsConfig::Manual {
+            cert_path: files.cert_path(),
+            key_path: files.key_path(),
+        }),
+        ..Default::default()
+    };
+    let handle = start_http2_server(config, |_message| async { Ok(None) })
+        .await
+        .unwrap();
+    let addr = handle.local_addr();
+    assert_eq!(served_cert(addr).await, load_cert(CERT));
+
+    // A connection made before the rotation stays usable after it
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let name = ServerName::try_from("localhost").unwrap();
+    let mut established = connector().connect(name, stream).await.unwrap();
+
+    files.install(ROTATED_CERT, ROTATED_KEY);
+    wait_for_cert(addr, &load_cert(ROTATED_CERT)).await;
+
+    established.write_all(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n").await.unwrap();
+    established.write_all(&[0, 0, 0, 4, 0, 0, 0, 0, 0]).await.unwrap();
+    let mut settings = [0u8; 9];
+    established.read_exact(&mut settings).await.unwrap();
+    assert_eq!(settings[3], 4, "expected the server's SETTINGS frame");
+
+    handle.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_invalid_replacement_keeps_serving_current_certificate() {
+    let files = CertFiles::new();
+    let config = Http2ServerConfig {
+        addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+        tls_config: Some(TlsConfig::Manual {
+            cert_path: files.cert_path(),
+            key_path: files.key_path(),
+        }),
+        ..Default::default()
+    };
+    let handle = start_http2_server(config, |_message| async { Ok(None) })
+        .await
+        .unwrap();
+    let addr = handle.local_addr();
+
+    // A rotated certificate without its key, then garbage, are both rejected
+    std::fs::copy(ROTATED_CERT, files.cert_path()).unwrap();
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    assert_eq!(served_cert(addr).await, load_cert(CERT));
+    std::fs::write(files.cert_path(), "not a certificate").unwrap();
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    assert_eq!(served_cert(addr).await, load_cert(CERT));
+
+    // The listener is still up and recovers once a valid pair is in place
+    files.install(ROTATED_CERT, ROTATED_KEY);
+    wait_for_cert(addr, &load_cert(ROTATED_CERT)).await;
+
+    handle.stop().await.unwrap();
+}
+
+#[actix_web::test]
+async fn test_httpd_picks_up_rotated_certificate() {
+    let files = CertFiles::new();
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let config = ServerConfig {
+        port,
+        tls: Some(HttpdTlsConfig::new(files.cert_path(), files.key_path())),
+        ..Default::default()
+    };
+    actix_web::rt::spawn(run_http_server(config, None, |_transport| async {
+        Ok(Box::new(PingServer) as Box<dyn Server>)
+    }));
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+    for _ in 0..250 {
+        if TcpStream::connect(addr).await.is_ok() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(served_cert(addr).await, load_cert(CERT));
+
+    files.install(ROTATED_CERT, ROTATED_KEY);
+    wait_for_cert(addr, &load_cert(ROTATED_CERT)).await;
+}
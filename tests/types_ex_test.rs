@@ -62,7 +62,7 @@ fn test_tag_serialization() {
     }
 
     // Test Tag serialization
-    let tag = Tag(TestTag::default());
+    let tag = Tag(TestTag);
     let json_value = serde_json::to_value(&tag).unwrap();
     assert_eq!(json_value, json!("test-tag"));
 }
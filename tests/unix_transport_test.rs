@@ -0,0 +1,132 @@
+#![cfg(unix)]
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+
+use mcp_daemon::{
+    client::ClientBuilder,
+    schema::{Tool, ToolInputSchema},
+    server::Server,
+    testing::{Expectation, MockServer},
+    transport::{ClientUnixTransport, TransportErrorCode, UnixServerConfig, serve_unix},
+};
+use serde_json::json;
+
+fn socket_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("mcp-{}-{}.sock", name, uuid::Uuid::new_v4()))
+}
+
+/// Server relying on the default handlers
+struct PingServer;
+
+impl Server for PingServer {}
+
+fn mock() -> MockServer {
+    let server = MockServer::new().allow_unexpected();
+    server.expect(
+        Expectation::request("tools/list")
+            .respond_with(json!({ "tools": [Tool::new("echo", ToolInputSchema::new())] })),
+    );
+    server
+}
+
+#[tokio::test]
+async fn test_sessions_per_connection() {
+    let path = socket_path("sessions");
+    let server = mock();
+    let handle = serve_unix(
+        UnixServerConfig::new(&path).with_permissions(0o600),
+        move || server.clone(),
+    )
+    .await
+    .unwrap();
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    let first = ClientBuilder::new()
+        .build_with_transport(ClientUnixTransport::new(&path))
+        .await
+        .unwrap();
+    let second = ClientBuilder::new()
+        .build_with_transport(ClientUnixTransport::new(&path))
+        .await
+        .unwrap();
+    assert_eq!(first.tools_list(None).await.unwrap().tools[0].name, "echo");
+    assert_eq!(second.tools_list(None).await.unwrap().tools[0].name, "echo");
+    assert!(first.ping().await.is_ok());
+
+    handle.stop().await.unwrap();
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn test_stale_socket_is_removed() {
+    let path = socket_path("stale");
+    // A listener that is dropped leaves its socket file behind
+    drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+    assert!(path.exists());
+
+    let handle = serve_unix(UnixServerConfig::new(&path), || PingServer)
+        .await
+        .unwrap();
+    let client = ClientBuilder::new()
+        .build_with_transport(ClientUnixTransport::new(&path))
+        .await
+        .unwrap();
+    assert!(client.ping().await.is_ok());
+
+    // A live socket is never removed
+    let err = serve_unix(UnixServerConfig::new(&path), || PingServer)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(TransportErrorCode::ConnectionFailed));
+
+    handle.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_stale_socket_kept_when_disabled() {
+    let path = socket_path("keep");
+    drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+
+    let err = serve_unix(
+        UnixServerConfig::new(&path).with_remove_stale(false),
+        || PingServer,
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(err.code(), Some(TransportErrorCode::ConfigurationError));
+    assert!(path.exists());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn test_permissions_applied_before_socket_is_reachable() {
+    let dir = std::env::temp_dir().join(format!("mcp-perms-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir(&dir).unwrap();
+    let path = dir.join("server.sock");
+    let handle = serve_unix(
+        UnixServerConfig::new(&path).with_permissions(0o600),
+        || PingServer,
+    )
+    .await
+    .unwrap();
+
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    // The private directory used for binding is gone, leaving only the socket
+    let entries: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .collect();
+    assert_eq!(entries, vec![std::ffi::OsString::from("server.sock")]);
+
+    let client = ClientBuilder::new()
+        .build_with_transport(ClientUnixTransport::new(&path))
+        .await
+        .unwrap();
+    assert!(client.ping().await.is_ok());
+
+    handle.stop().await.unwrap();
+    std::fs::remove_dir(&dir).unwrap();
+}
@@ -0,0 +1,371 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use mcp_daemon::{
+    client::ClientBuilder,
+    schema::SubscribeRequestParams,
+    transport::{
+        ClientWsTransport, ConnectionState, HeartbeatConfig, JsonRpcMessage, JsonRpcRequest,
+        JsonRpcVersion, MCP_SUBPROTOCOL, OutgoingPolicy, OverflowPolicy, QueueConfig,
+        ReconnectPolicy, Transport, TransportErrorCode,
+    },
+};
+use serde_json::{Value, json};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{Notify, watch};
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+
+/// Minimal MCP server over WebSocket that records the methods it receives per connection
+struct TestServer {
+    url: String,
+    /// Methods received, as (connection number, method)
+    received: Arc<Mutex<Vec<(usize, String)>>>,
+    /// Drops the current connection when notified
+    drop_connection: Arc<Notify>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+/// Completes the handshake, selecting the `mcp` subprotocol if the client offers it
+#[allow(clippy::result_large_err)]
+async fn accept(stream: TcpStream) -> WebSocketStream<TcpStream> {
+    tokio_tungstenite::accept_hdr_async(stream, |request: &Request, mut response: Response| {
+        let offered = request
+            .headers()
+            .get("Sec-WebSocket-Protocol")
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.split(',').any(|p| p.trim() == MCP_SUBPROTOCOL));
+        if offered {
+            response
+                .headers_mut()
+                .insert("Sec-WebSocket-Protocol", MCP_SUBPROTOCOL.parse().unwrap());
+        }
+        Ok(response)
+    })
+    .await
+    .unwrap()
+}
+
+impl TestServer {
+    async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let drop_connection = Arc::new(Notify::new());
+        let (task_received, task_drop) = (received.clone(), drop_connection.clone());
+        let task = tokio::spawn(async move {
+            let mut connection = 0;
+            while let Ok((stream, _)) = listener.accept().await {
+                connection += 1;
+                let mut ws = accept(stream).await;
+                loop {
+                    tokio::select! {
+                        _ = task_drop.notified() => break,
+                        frame = ws.next() => {
+                            let text = match frame {
+                                Some(Ok(WsMessage::Text(text))) => text,
+                                // Pings are answered by tungstenite while reading
+                                Some(Ok(WsMessage::Ping(_))) => continue,
+                                _ => break,
+                            };
+                            let message: Value = serde_json::from_str(&text).unwrap();
+                            let method = message["method"].as_str().unwrap_or_default().to_string();
+                            task_received.lock().unwrap().push((connection, method.clone()));
+                            if let Some(id) = message.get("id") {
+                                let result = match method.as_str() {
+                                    "initialize" => json!({
+                                        "protocolVersion": "2024-11-05",
+                                        "capabilities": {},
+                                        "serverInfo": { "name": "test", "version": "1.0" },
+                                    }),
+                                    _ => json!({}),
+                                };
+                                let response = json!({ "jsonrpc": "2.0", "id": id, "result": result });
+                                ws.send(WsMessage::Text(response.to_string().into())).await.unwrap();
+                            }
+                        }
+                    }
+                }
+            }
+        });
+        Self { url, received, drop_connection, task }
+    }
+
+    fn methods(&self, connection: usize) -> Vec<String> {
+        self.received
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(c, _)| *c == connection)
+            .map(|(_, m)| m.clone())
+            .collect()
+    }
+}
+
+fn fast_policy() -> ReconnectPolicy {
+    ReconnectPolicy {
+        initial_delay: Duration::from_millis(20),
+        max_delay: Duration::from_millis(100),
+        jitter: 0.0,
+        ..ReconnectPolicy::default()
+    }
+}
+
+async fn wait_for(
+    state: &mut watch::Receiver<ConnectionState>,
+    f: impl FnMut(&ConnectionState) -> bool,
+) {
+    tokio::time::timeout(Duration::from_secs(5), state.wait_for(f))
+        .await
+        .expect("timed out waiting for connection state")
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_reconnect_restores_session() {
+    let server = TestServer::start().await;
+    let transport = ClientWsTransport::builder(server.url.clone())
+        .with_reconnect(fast_policy())
+        .build();
+    let mut state = transport.connection_state();
+    let client = ClientBuilder::new()
+        .build_with_transport(transport)
+        .await
+        .unwrap();
+    client
+        .resources_subscribe(SubscribeRequestParams {
+            uri: "file:///watched".to_string(),
+        })
+        .await
+        .unwrap();
+
+    server.drop_connection.notify_one();
+    wait_for(&mut state, |s| matches!(s, ConnectionState::Reconnecting { .. })).await;
+    wait_for(&mut state, |s| *s == ConnectionState::Connected).await;
+
+    client.ping().await.unwrap();
+    assert_eq!(
+        server.methods(2),
+        [
+            "initialize",
+            "notifications/initialized",
+            "resources/subscribe",
+            "ping"
+        ]
+    );
+    server.task.abort();
+}
+
+#[tokio::test]
+async fn test_reopened_transport_does_not_replay_the_closed_session() {
+    let server = TestServer::start().await;
+    let transport = ClientWsTransport::builder(server.url.clone())
+        .with_reconnect(fast_policy())
+        .build();
+    let mut state = transport.connection_state();
+    let request = |id, method: &str| {
+        JsonRpcMessage::Request(JsonRpcRequest {
+            id,
+            method: method.to_string(),
+            params: Some(json!({})),
+            jsonrpc: JsonRpcVersion::default(),
+        })
+    };
+
+    transport.open().await.unwrap();
+    transport.send(&request(1, "initialize")).await.unwrap();
+    transport.receive().await.unwrap().unwrap();
+    transport.close().await.unwrap();
+
+    transport.open().await.unwrap();
+    transport.send(&request(2, "ping")).await.unwrap();
+    transport.receive().await.unwrap().unwrap();
+    server.drop_connection.notify_one();
+    wait_for(&mut state, |s| matches!(s, ConnectionState::Reconnecting { .. })).await;
+    wait_for(&mut state, |s| *s == ConnectionState::Connected).await;
+
+    transport.send(&request(3, "ping")).await.unwrap();
+    transport.receive().await.unwrap().unwrap();
+    assert_eq!(server.methods(3), ["ping"]);
+    transport.close().await.unwrap();
+    server.task.abort();
+}
+
+#[tokio::test]
+async fn test_gives_up_after_max_attempts() {
+    let server = TestServer::start().await;
+    let transport = ClientWsTransport::builder(server.url.clone())
+        .with_reconnect(ReconnectPolicy {
+            max_attempts: Some(2),
+            outgoing: OutgoingPolicy::Fail,
+            ..fast_policy()
+        })
+        .build();
+    let mut state = transport.connection_state();
+    let client = ClientBuilder::new()
+        .build_with_transport(transport)
+        .await
+        .unwrap();
+
+    // Stop the server entirely so every reconnect attempt fails
+    server.task.abort();
+    server.drop_connection.notify_one();
+    wait_for(&mut state, |s| *s == ConnectionState::Reconnecting { attempt: 2 }).await;
+    wait_for(&mut state, |s| *s == ConnectionState::Disconnected).await;
+    assert!(client.ping().await.is_err());
+}
+
+#[test]
+fn test_backoff_delay() {
+    let policy = ReconnectPolicy {
+        initial_delay: Duration::from_millis(100),
+        max_delay: Duration::from_secs(1),
+        jitter: 0.0,
+        ..ReconnectPolicy::default()
+    };
+    assert_eq!(policy.delay(1), Duration::from_millis(100));
+    assert_eq!(policy.delay(3), Duration::from_millis(400));
+    assert_eq!(policy.delay(10), Duration::from_secs(1));
+
+    let jittered = ReconnectPolicy { jitter: 0.5, ..policy };
+    for _ in 0..100 {
+        let delay = jittered.delay(2);
+        assert!(delay >= Duration::from_millis(100) && delay <= Duration::from_millis(300));
+    }
+}
+
+#[tokio::test]
+async fn test_heartbeat_measures_rtt() {
+    let server = TestServer::start().await;
+    let transport = ClientWsTransport::builder(server.url.clone())
+        .with_heartbeat(HeartbeatConfig {
+            interval: Duration::from_millis(20),
+            timeout: Duration::from_secs(1),
+        })
+        .build();
+    transport.open().await.unwrap();
+
+    let ping = JsonRpcMessage::Request(JsonRpcRequest {
+        id: 1,
+        method: "ping".to_string(),
+        params: None,
+        jsonrpc: JsonRpcVersion::default(),
+    });
+    transport.send(&ping).await.unwrap();
+    transport.receive().await.unwrap().unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let metrics = transport.metrics();
+    assert!(metrics.rtt().is_some());
+    assert!(metrics.last_seen().unwrap().elapsed() < Duration::from_secs(1));
+    transport.close().await.unwrap();
+    server.task.abort();
+}
+
+#[tokio::test]
+async fn test_heartbeat_detects_dead_server() {
+    // Accepts the handshake, then never reads again, so pings go unanswered
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let _ws = accept(stream).await;
+        std::future::pending::<()>().await;
+    });
+
+    let transport = ClientWsTransport::builder(url)
+        .with_heartbeat(HeartbeatConfig {
+            interval: Duration::from_millis(20),
+            timeout: Duration::from_millis(50),
+        })
+        .build();
+    transport.open().await.unwrap();
+    let err = tokio::time::timeout(Duration::from_secs(5), transport.receive())
+        .await
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(err.code(), Some(TransportErrorCode::ConnectionTimeout));
+    server.abort();
+}
+
+/// Accepts one connection and sends `count` numbered notifications right away
+async fn flooding_server(count: usize) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut ws = accept(stream).await;
+        for n in 0..count {
+            let notification = json!({ "jsonrpc": "2.0", "method": format!("test/{}", n) });
+            ws.send(WsMessage::Text(notification.to_string().into())).await.unwrap();
+        }
+        while ws.next().await.is_some() {}
+    });
+    url
+}
+
+fn method(message: JsonRpcMessage) -> String {
+    match message {
+        JsonRpcMessage::Notification(notification) => notification.method,
+        other => panic!("unexpected message: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_slow_consumer_loses_nothing() {
+    let url = flooding_server(200).await;
+    let transport = ClientWsTransport::builder(url)
+        .with_receive_queue(QueueConfig {
+            capacity: 4,
+            overflow: OverflowPolicy::Block,
+        })
+        .build();
+    transport.open().await.unwrap();
+
+    // Let the queue fill up before reading
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    for n in 0..200 {
+        let message = transport.receive().await.unwrap().unwrap();
+        assert_eq!(method(message), format!("test/{}", n));
+    }
+    transport.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_drop_oldest_keeps_latest_messages() {
+    let url = flooding_server(50).await;
+    let transport = ClientWsTransport::builder(url)
+        .with_receive_queue(QueueConfig {
+            capacity: 4,
+            overflow: OverflowPolicy::DropOldest,
+        })
+        .build();
+    transport.open().await.unwrap();
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    for n in 46..50 {
+        let message = transport.receive().await.unwrap().unwrap();
+        assert_eq!(method(message), format!("test/{}", n));
+    }
+    transport.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_subprotocol_offer() {
+    let server = TestServer::start().await;
+
+    // Servers that do not negotiate a subprotocol keep working by default
+    let transport = ClientWsTransport::builder(server.url.clone()).build();
+    transport.open().await.unwrap();
+    assert_eq!(transport.subprotocol(), None);
+    transport.close().await.unwrap();
+
+    let transport = ClientWsTransport::builder(server.url.clone())
+        .with_subprotocols([MCP_SUBPROTOCOL])
+        .build();
+    transport.open().await.unwrap();
+    assert_eq!(transport.subprotocol().as_deref(), Some(MCP_SUBPROTOCOL));
+    transport.close().await.unwrap();
+}
@@ -0,0 +1,257 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use actix_web::{App, HttpRequest, HttpResponse, HttpServer, web};
+use futures::StreamExt;
+use futures::SinkExt;
+use mcp_daemon::transport::{
+    ClientWsTransport, ConnectionMetrics, HeartbeatConfig, JsonRpcNotification, JsonRpcVersion,
+    MCP_SUBPROTOCOL, Message, MessageQueue, QueueConfig, ServerWsTransport, Transport,
+    TransportError, TransportErrorCode, WsConnectionConfig, default_codec,
+    handle_ws_connection_with, handle_ws_upgrade, negotiate_codec,
+};
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio::sync::mpsc;
+
+type Outcome = (ConnectionMetrics, Result<(), TransportError>);
+
+struct State {
+    outcomes: mpsc::UnboundedSender<Outcome>,
+    transports: mpsc::UnboundedSender<ServerWsTransport>,
+    heartbeat: HeartbeatConfig,
+    receive_queue: QueueConfig,
+}
+
+async fn ws(
+    req: HttpRequest,
+    body: web::Payload,
+    state: web::Data<Mutex<State>>,
+) -> actix_web::Result<HttpResponse> {
+    let codec = negotiate_codec(&req).unwrap_or_else(default_codec);
+    let (response, session, stream) = handle_ws_upgrade(&req, body)?;
+    let (outcomes, transports, heartbeat, receive_queue) = {
+        let state = state.lock().unwrap();
+        (state.outcomes.clone(), state.transports.clone(), state.heartbeat, state.receive_queue)
+    };
+    let (transport, incoming) = ServerWsTransport::new_with_queue(session.clone(), receive_queue);
+    let _ = transports.send(transport.with_codec(codec.clone()));
+    actix_web::rt::spawn(async move {
+        let config = WsConnectionConfig {
+            heartbeat: Some(heartbeat),
+            codec,
+            ..Default::default()
+        };
+        let metrics = config.metrics.clone();
+        // The transport writes to the client itself
+        let outgoing = MessageQueue::new(QueueConfig::default());
+        let result = handle_ws_connection_with(session, stream, incoming, outgoing, config).await;
+        let _ = outcomes.send((metrics, result));
+    });
+    Ok(response)
+}
+
+struct TestServer {
+    url: String,
+    outcomes: mpsc::UnboundedReceiver<Outcome>,
+    transports: mpsc::UnboundedReceiver<ServerWsTransport>,
+}
+
+impl TestServer {
+    /// Transport of the next connection the server accepted
+    async fn transport(&mut self) -> ServerWsTransport {
+        tokio::time::timeout(Duration::from_secs(5), self.transports.recv())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+}
+
+async fn start(heartbeat: HeartbeatConfig) -> TestServer {
+    start_with_queue(heartbeat, QueueConfig::default()).await
+}
+
+async fn start_with_queue(heartbeat: HeartbeatConfig, receive_queue: QueueConfig) -> TestServer {
+    let (outcomes, outcomes_rx) = mpsc::unbounded_channel();
+    let (transports, transports_rx) = mpsc::unbounded_channel();
+    let state = web::Data::new(Mutex::new(State {
+        outcomes,
+        transports,
+        heartbeat,
+        receive_queue,
+    }));
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(state.clone())
+            .route("/ws", web::get().to(ws))
+    })
+    .workers(1)
+    .bind(("127.0.0.1", 0))
+    .unwrap();
+    let url = format!("ws://{}/ws", server.addrs()[0]);
+    actix_web::rt::spawn(server.run());
+    TestServer {
+        url,
+        outcomes: outcomes_rx,
+        transports: transports_rx,
+    }
+}
+
+async fn receive(transport: &ServerWsTransport) -> Message {
+    tokio::time::timeout(Duration::from_secs(5), transport.receive())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap()
+}
+
+fn notification(method: &str) -> Message {
+    Message::Notification(JsonRpcNotification {
+        method: method.to_string(),
+        params: None,
+        jsonrpc: JsonRpcVersion::default(),
+    })
+}
+
+#[actix_web::test]
+async fn test_unresponsive_client_times_out() {
+    let TestServer { url, mut outcomes, .. } = start(HeartbeatConfig {
+        interval: Duration::from_millis(20),
+        timeout: Duration::from_millis(50),
+    })
+    .await;
+
+    // Never read from the connection, so pings are not answered
+    let (_ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+    let (_, result) = tokio::time::timeout(Duration::from_secs(5), outcomes.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        result.unwrap_err().code(),
+        Some(TransportErrorCode::ConnectionTimeout)
+    );
+}
+
+#[actix_web::test]
+async fn test_responsive_client_reports_rtt() {
+    let TestServer { url, mut outcomes, .. } = start(HeartbeatConfig {
+        interval: Duration::from_millis(20),
+        timeout: Duration::from_secs(1),
+    })
+    .await;
+
+    let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+    // Reading answers pings; stop after a few
+    for _ in 0..3 {
+        ws.next().await.unwrap().unwrap();
+    }
+    ws.close(None).await.unwrap();
+
+    let (metrics, result) = tokio::time::timeout(Duration::from_secs(5), outcomes.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(result.is_ok());
+    assert!(metrics.rtt().is_some());
+    assert!(metrics.last_seen().is_some());
+}
+
+#[actix_web::test]
+async fn test_negotiates_mcp_subprotocol() {
+    let server = start(HeartbeatConfig::default()).await;
+
+    let transport = ClientWsTransport::builder(server.url.clone())
+        .with_subprotocols([MCP_SUBPROTOCOL])
+        .build();
+    transport.open().await.unwrap();
+    assert_eq!(transport.subprotocol().as_deref(), Some(MCP_SUBPROTOCOL));
+    transport.close().await.unwrap();
+
+    // Clients that do not ask for a subprotocol are still accepted
+    let (_ws, response) = tokio_tungstenite::connect_async(server.url).await.unwrap();
+    assert!(response.headers().get("Sec-WebSocket-Protocol").is_none());
+}
+
+#[actix_web::test]
+async fn test_binary_frames_are_decoded_as_json() {
+    let mut server = start(HeartbeatConfig::default()).await;
+
+    let mut request = server.url.as_str().into_client_request().unwrap();
+    request
+        .headers_mut()
+        .insert("Sec-WebSocket-Protocol", "mcp".parse().unwrap());
+    let (mut ws, response) = tokio_tungstenite::connect_async(request).await.unwrap();
+    assert_eq!(response.headers()["Sec-WebSocket-Protocol"], "mcp");
+
+    let json = r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#;
+    ws.send(WsMessage::Binary(json.as_bytes().to_vec().into()))
+        .await
+        .unwrap();
+    match receive(&server.transport().await).await {
+        Message::Notification(notification) => {
+            assert_eq!(notification.method, "notifications/initialized")
+        }
+        other => panic!("unexpected message: {:?}", other),
+    }
+}
+
+#[cfg(feature = "msgpack")]
+#[actix_web::test]
+async fn test_negotiates_msgpack_codec() {
+    use mcp_daemon::transport::MessagePackCodec;
+    use std::sync::Arc;
+
+    let mut server = start(HeartbeatConfig::default()).await;
+
+    let transport = ClientWsTransport::builder(server.url.clone())
+        .with_codec(Arc::new(MessagePackCodec))
+        .build();
+    transport.open().await.unwrap();
+    assert_eq!(transport.subprotocol().as_deref(), Some("mcp.msgpack"));
+    assert_eq!(transport.codec().name(), "msgpack");
+
+    let notification = notification("notifications/initialized");
+    transport.send(&notification).await.unwrap();
+    assert_eq!(receive(&server.transport().await).await, notification);
+    transport.close().await.unwrap();
+}
+
+#[actix_web::test]
+async fn test_bursts_wait_for_a_slow_consumer_instead_of_closing_the_session() {
+    let mut server = start_with_queue(
+        HeartbeatConfig::default(),
+        QueueConfig {
+            capacity: 4,
+            ..Default::default()
+        },
+    )
+    .await;
+    let (mut ws, _) = tokio_tungstenite::connect_async(server.url.clone()).await.unwrap();
+    let transport = server.transport().await;
+
+    // Far more frames than the queue holds, while nothing is received
+    let burst = 300;
+    for i in 0..burst {
+        let json = serde_json::to_string(&notification(&format!("burst/{}", i))).unwrap();
+        ws.send(WsMessage::Text(json.into())).await.unwrap();
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    for i in 0..burst {
+        match receive(&transport).await {
+            Message::Notification(n) => assert_eq!(n.method, format!("burst/{}", i)),
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+
+    // The session is still up in both directions
+    transport.send(&notification("after/burst")).await.unwrap();
+    let frame = tokio::time::timeout(Duration::from_secs(5), ws.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(frame.into_text().unwrap().contains("after/burst"));
+    assert!(server.outcomes.try_recv().is_err());
+}